@@ -1,55 +1,266 @@
 use std::{
-    fs::File,
+    fmt::Write,
+    fs::{self, File},
     io::{BufReader, Read},
+    path::Path,
+    time::{Duration, Instant},
 };
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use llvm_compiler::{lexer::AsciiLexer, parse::parser::Parser, token::Token};
+use criterion::{
+    black_box, criterion_group, criterion_main, measurement::WallTime, BenchmarkGroup, BenchmarkId,
+    Criterion, Throughput,
+};
+use llvm_compiler::{
+    lexer::AsciiLexer,
+    parse::{parser::Parser, tree::ParseTree},
+    token::Token,
+};
 
-pub fn criterion_benchmark(c: &mut Criterion) {
-    c.bench_function("lexing", |b| {
-        const READER_CAPACITY: usize = 100_000_000;
-        let file = File::open("./examples/parse_bench.src").unwrap();
-        let mut reader = BufReader::with_capacity(READER_CAPACITY, file);
+const READER_CAPACITY: usize = 100_000_000;
 
-        let mut lexer = AsciiLexer::new();
-        let mut buf = String::new();
+/// The number of lines in the synthetic input, which can be overridden with `SYNTHETIC_LINES`.
+const SYNTHETIC_LINES: usize = 1_000_000;
 
-        // Read in as much as we can at once
-        let bytes_read = reader.read_to_string(&mut buf).unwrap(); // TODO: There is some edge
-                                                                   // case behaviour here when
-                                                                   // a file is too long to
-                                                                   // store in memory. This is
-                                                                   // currently unhandled.
+/// How many samples criterion measures for each input, which is its default.
+const SAMPLES: usize = 100;
 
-        b.iter(|| {
-            let tokens: Vec<Token<'_>> = lexer.tokenize(&buf);
-            black_box(tokens);
-            buf.clear();
-        });
-    });
+/// How many samples are measured for the synthetic input, where a single sample can take whole seconds.
+const SYNTHETIC_SAMPLES: usize = 10;
+
+/// A named source to benchmark.
+struct Input {
+    name: String,
+    source: String,
+    lines: u64,
+    /// How many samples criterion measures, after warming up
+    samples: usize,
 }
 
-pub fn parsing_benchmark(c: &mut Criterion) {
-    const READER_CAPACITY: usize = 100_000_000;
-    let file = File::open("./examples/parse_bench.src").unwrap();
+impl Input {
+    fn new(name: String, source: String) -> Self {
+        let lines = source.lines().count() as u64;
+        Input {
+            name,
+            source,
+            lines,
+            samples: SAMPLES,
+        }
+    }
+}
+
+/// Every `.src` file in `examples/`, in name order.
+fn examples() -> Vec<Input> {
+    let mut paths: Vec<_> = fs::read_dir("./examples")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "src"))
+        .collect();
+    paths.sort();
+
+    paths.iter().map(|path| read(path)).collect()
+}
+
+fn read(path: &Path) -> Input {
+    let file = File::open(path).unwrap();
     let mut reader = BufReader::with_capacity(READER_CAPACITY, file);
 
-    let mut lexer = AsciiLexer::new();
     let mut buf = String::new();
-    let _ = reader.read_to_string(&mut buf).unwrap();
 
-    let tokens: Vec<Token<'_>> = lexer.tokenize(&buf);
+    // Read in as much as we can at once
+    let _bytes_read = reader.read_to_string(&mut buf).unwrap(); // TODO: There is some edge
+                                                                // case behaviour here when
+                                                                // a file is too long to
+                                                                // store in memory. This is
+                                                                // currently unhandled.
 
-    c.bench_function("parsing", |b| {
-        b.iter(|| {
-            let parser = Parser::new(0, &tokens);
-            let parsed = parser.parse().unwrap();
+    let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+    Input::new(name, buf)
+}
 
-            black_box(parsed);
+/// Generates `lines` lines of valid source, mixing declarations, functions, conditionals and loops.
+///
+/// The output is the same for the same number of lines, so results can be compared between runs.
+pub fn synthetic(lines: usize) -> String {
+    let mut source = String::with_capacity(lines * 32);
+    let mut line = 0;
+    let mut index = 0;
+
+    while line < lines {
+        let remaining = lines - line;
+        match index % 4 {
+            // A 7 line function
+            0 if remaining >= 7 => {
+                let _ = write!(
+                    source,
+                    "fn f{index}(a: i32, b: Vec<i32>): i32 {{\n    \
+                        var c: i32 = a * {index} + (b - 1);\n    \
+                        if c < a {{\n        \
+                            c = f{index}(c - 1, b);\n    \
+                        }}\n    \
+                        c\n\
+                    }}\n"
+                );
+                line += 7;
+            }
+            // A 3 line loop
+            1 if remaining >= 3 => {
+                let _ = write!(
+                    source,
+                    "while x{index} > 0 {{\n    x{index} -= -y * {index};\n}}\n"
+                );
+                line += 3;
+            }
+            2 => {
+                let _ = writeln!(
+                    source,
+                    "var value{index} = (first + {index}) * second << third;"
+                );
+                line += 1;
+            }
+            _ => {
+                let _ = writeln!(source, "call{index}(a, b + {index}, &c);");
+                line += 1;
+            }
+        }
+        index += 1;
+    }
+
+    source
+}
+
+fn synthetic_input() -> Input {
+    let lines = std::env::var("SYNTHETIC_LINES")
+        .ok()
+        .and_then(|lines| lines.parse().ok())
+        .unwrap_or(SYNTHETIC_LINES);
+
+    Input {
+        samples: SYNTHETIC_SAMPLES,
+        ..Input::new(format!("synthetic_{lines}"), synthetic(lines))
+    }
+}
+
+/// Benchmarks `routine` over `input`, reporting bytes per second through criterion, and lines per second once it's done.
+///
+/// `routine` is given the number of iterations to run, and returns the time they took. Lines per second only counts
+/// the samples criterion measures, which are the last `input.samples` calls, as the calls before them are warm-up.
+fn bench_input(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    stage: &str,
+    input: &Input,
+    mut routine: impl FnMut(u64) -> Duration,
+) {
+    group.throughput(Throughput::Bytes(input.source.len() as u64));
+    group.sample_size(input.samples);
+
+    let mut samples: Vec<(u64, Duration)> = Vec::new();
+    group.bench_function(BenchmarkId::new(stage, &input.name), |b| {
+        b.iter_custom(|iters| {
+            let time = routine(iters);
+            samples.push((iters, time));
+            time
         });
     });
+
+    let measured = &samples[samples.len().saturating_sub(input.samples)..];
+    let elapsed: Duration = measured.iter().map(|&(_, time)| time).sum();
+    let iterations: u64 = measured.iter().map(|&(iters, _)| iters).sum();
+
+    if iterations > 0 && input.lines > 0 {
+        let per_iteration = elapsed.as_secs_f64() / iterations as f64;
+        println!(
+            "{:<48} {:>14.0} lines/s {:>10.1} ns/line",
+            format!("{stage}/{}", input.name),
+            input.lines as f64 / per_iteration,
+            per_iteration * 1e9 / input.lines as f64,
+        );
+    }
+}
+
+fn lex(group: &mut BenchmarkGroup<'_, WallTime>, input: &Input) {
+    let mut lexer = AsciiLexer::new();
+
+    bench_input(group, "lex", input, |iters| {
+        let start = Instant::now();
+        for _ in 0..iters {
+            let tokens: Vec<Token<'_>> = lexer.tokenize(&input.source);
+            black_box(tokens);
+        }
+        start.elapsed()
+    });
+}
+
+fn parse(group: &mut BenchmarkGroup<'_, WallTime>, input: &Input) {
+    let mut lexer = AsciiLexer::new();
+    let tokens: Vec<Token<'_>> = lexer.tokenize(&input.source);
+
+    bench_input(group, "parse", input, |iters| {
+        let start = Instant::now();
+        for _ in 0..iters {
+            let parsed = Parser::new(0, &tokens).parse_recovering();
+            black_box(parsed);
+        }
+        start.elapsed()
+    });
+}
+
+/// Lexes, parses and builds the parse tree, to compare against the 100 ns/line budget.
+fn end_to_end(group: &mut BenchmarkGroup<'_, WallTime>, input: &Input) {
+    bench_input(group, "lex_parse_tree", input, |iters| {
+        let start = Instant::now();
+        for _ in 0..iters {
+            let mut lexer = AsciiLexer::new();
+            let tokens: Vec<Token<'_>> = lexer.tokenize(&input.source);
+
+            let (parsed, _) = Parser::new(0, &tokens).parse_recovering();
+            let tree = ParseTree::new(&tokens, &parsed);
+
+            black_box(tree);
+        }
+        start.elapsed()
+    });
+}
+
+pub fn lexing_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lexing");
+    for input in examples() {
+        lex(&mut group, &input);
+    }
+    group.finish();
+}
+
+pub fn parsing_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parsing");
+    for input in examples() {
+        parse(&mut group, &input);
+    }
+    group.finish();
+}
+
+pub fn end_to_end_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("end_to_end");
+    for input in examples() {
+        end_to_end(&mut group, &input);
+    }
+    group.finish();
+}
+
+/// Runs every stage over a single large input, where a run can take whole seconds.
+pub fn synthetic_benchmark(c: &mut Criterion) {
+    let input = synthetic_input();
+
+    let mut group = c.benchmark_group("synthetic");
+    lex(&mut group, &input);
+    parse(&mut group, &input);
+    end_to_end(&mut group, &input);
+    group.finish();
 }
 
-criterion_group!(benches, criterion_benchmark, parsing_benchmark);
+criterion_group!(
+    benches,
+    lexing_benchmark,
+    parsing_benchmark,
+    end_to_end_benchmark,
+    synthetic_benchmark
+);
 criterion_main!(benches);
@@ -0,0 +1,224 @@
+; ModuleID = 'arithmetic.src'
+source_filename = "arithmetic.src"
+
+@small = internal global i8 zeroinitializer
+@.str = private unnamed_addr constant [5 x i8] c"true\00"
+@.str.1 = private unnamed_addr constant [6 x i8] c"false\00"
+@.str.2 = private unnamed_addr constant [54 x i8] c"(%lld, %lld, %lld, %lld, %llu, %lld, %lld, %lld, %s)\0A\00"
+
+define internal i64 @sign(i64 %x) {
+entry:
+  %x.addr = alloca i64
+  store i64 %x, ptr %x.addr
+  %t0 = load i64, ptr %x.addr
+  %t1 = icmp slt i64 %t0, 0
+  br i1 %t1, label %if.then, label %if.else
+if.then:
+  br label %if.end
+if.else:
+  %t2 = load i64, ptr %x.addr
+  %t3 = icmp eq i64 %t2, 0
+  br i1 %t3, label %if.then.1, label %if.else.1
+if.then.1:
+  br label %if.end.1
+if.else.1:
+  br label %if.end.1
+if.end.1:
+  %t4 = phi i64 [ 0, %if.then.1 ], [ 1, %if.else.1 ]
+  br label %if.end
+if.end:
+  %t5 = phi i64 [ -1, %if.then ], [ %t4, %if.end.1 ]
+  ret i64 %t5
+}
+
+define internal i8 @mask(i8 %bits) {
+entry:
+  %bits.addr = alloca i8
+  store i8 %bits, ptr %bits.addr
+  %t0 = load i8, ptr %bits.addr
+  %t1 = icmp uge i8 %t0, 8
+  br i1 %t1, label %trap, label %ok
+ok:
+  %t2 = lshr i8 255, %t0
+  %t3 = xor i8 %t2, -1
+  %t4 = load i8, ptr %bits.addr
+  %t5 = icmp uge i8 %t4, 8
+  br i1 %t5, label %trap, label %ok.1
+ok.1:
+  %t6 = shl i8 1, %t4
+  %t7 = lshr i8 %t6, %t4
+  %t8 = icmp ne i8 %t7, 1
+  br i1 %t8, label %trap, label %ok.2
+ok.2:
+  %t9 = call { i8, i1 } @llvm.uadd.with.overflow.i8(i8 %t3, i8 %t6)
+  %t10 = extractvalue { i8, i1 } %t9, 0
+  %t11 = extractvalue { i8, i1 } %t9, 1
+  br i1 %t11, label %trap, label %ok.3
+ok.3:
+  ret i8 %t10
+trap:
+  call void @llvm.trap()
+  unreachable
+}
+
+define i32 @main() {
+entry:
+  store i8 -2, ptr @small
+  %t0 = call i32 @pow.i32(i32 2, i32 10)
+  %t1 = insertvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } poison, i32 %t0, 0
+  %t2 = load i8, ptr @small
+  %t3 = call i8 @pow.i8(i8 %t2, i8 7)
+  %t4 = insertvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t1, i8 %t3, 1
+  %t5 = call i32 @pow.i32(i32 3, i32 -1)
+  %t6 = insertvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t4, i32 %t5, 2
+  %t7 = call i32 @pow.i32(i32 -1, i32 -3)
+  %t8 = insertvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t6, i32 %t7, 3
+  %t9 = call i8 @mask(i8 3)
+  %t10 = insertvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t8, i8 %t9, 4
+  %t11 = icmp uge i32 2, 32
+  br i1 %t11, label %trap, label %ok
+ok:
+  %t12 = ashr i32 -128, 2
+  %t13 = insertvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t10, i32 %t12, 5
+  %t14 = icmp eq i32 -7, 0
+  br i1 %t14, label %trap, label %ok.1
+ok.1:
+  %t15 = icmp eq i32 1000, -2147483648
+  %t16 = icmp eq i32 -7, -1
+  %t17 = and i1 %t15, %t16
+  br i1 %t17, label %trap, label %ok.2
+ok.2:
+  %t18 = sdiv i32 1000, -7
+  %t19 = insertvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t13, i32 %t18, 6
+  %t20 = call i64 @sign(i64 -5)
+  %t21 = call i64 @sign(i64 0)
+  %t22 = call { i64, i1 } @llvm.smul.with.overflow.i64(i64 %t21, i64 10)
+  %t23 = extractvalue { i64, i1 } %t22, 0
+  %t24 = extractvalue { i64, i1 } %t22, 1
+  br i1 %t24, label %trap, label %ok.3
+ok.3:
+  %t25 = call { i64, i1 } @llvm.sadd.with.overflow.i64(i64 %t20, i64 %t23)
+  %t26 = extractvalue { i64, i1 } %t25, 0
+  %t27 = extractvalue { i64, i1 } %t25, 1
+  br i1 %t27, label %trap, label %ok.4
+ok.4:
+  %t28 = insertvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t19, i64 %t26, 7
+  %t29 = xor i1 true, true
+  %t30 = insertvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t28, i1 %t29, 8
+  %t31 = extractvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t30, 0
+  %t32 = sext i32 %t31 to i64
+  %t33 = extractvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t30, 1
+  %t34 = sext i8 %t33 to i64
+  %t35 = extractvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t30, 2
+  %t36 = sext i32 %t35 to i64
+  %t37 = extractvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t30, 3
+  %t38 = sext i32 %t37 to i64
+  %t39 = extractvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t30, 4
+  %t40 = zext i8 %t39 to i64
+  %t41 = extractvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t30, 5
+  %t42 = sext i32 %t41 to i64
+  %t43 = extractvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t30, 6
+  %t44 = sext i32 %t43 to i64
+  %t45 = extractvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t30, 7
+  %t46 = extractvalue { i32, i8, i32, i32, i8, i32, i32, i64, i1 } %t30, 8
+  %t47 = select i1 %t46, ptr @.str, ptr @.str.1
+  call i32 (ptr, ...) @printf(ptr @.str.2, i64 %t32, i64 %t34, i64 %t36, i64 %t38, i64 %t40, i64 %t42, i64 %t44, i64 %t45, ptr %t47)
+  ret i32 0
+trap:
+  call void @llvm.trap()
+  unreachable
+}
+
+define internal i32 @pow.i32(i32 %base, i32 %exponent) {
+entry:
+  %negative = icmp slt i32 %exponent, 0
+  br i1 %negative, label %reciprocal, label %loop
+reciprocal:
+  %zero = icmp eq i32 %base, 0
+  br i1 %zero, label %trap, label %truncate
+truncate:
+  %one = icmp eq i32 %base, 1
+  %minus_one = icmp eq i32 %base, -1
+  %odd = trunc i32 %exponent to i1
+  %sign = select i1 %odd, i32 -1, i32 1
+  %unit = select i1 %minus_one, i32 %sign, i32 0
+  %inverse = select i1 %one, i32 1, i32 %unit
+  ret i32 %inverse
+loop:
+  %result = phi i32 [ 1, %entry ], [ %result.shifted, %square ]
+  %factor = phi i32 [ %base, %entry ], [ %squared, %square ]
+  %remaining = phi i32 [ %exponent, %entry ], [ %halved, %square ]
+  %bit = trunc i32 %remaining to i1
+  br i1 %bit, label %multiply, label %shift
+multiply:
+  %product = call { i32, i1 } @llvm.smul.with.overflow.i32(i32 %result, i32 %factor)
+  %multiplied = extractvalue { i32, i1 } %product, 0
+  %multiply.overflow = extractvalue { i32, i1 } %product, 1
+  br i1 %multiply.overflow, label %trap, label %shift
+shift:
+  %result.shifted = phi i32 [ %result, %loop ], [ %multiplied, %multiply ]
+  %halved = lshr i32 %remaining, 1
+  %done = icmp eq i32 %halved, 0
+  br i1 %done, label %exit, label %square
+square:
+  %squares = call { i32, i1 } @llvm.smul.with.overflow.i32(i32 %factor, i32 %factor)
+  %squared = extractvalue { i32, i1 } %squares, 0
+  %square.overflow = extractvalue { i32, i1 } %squares, 1
+  br i1 %square.overflow, label %trap, label %loop
+exit:
+  ret i32 %result.shifted
+trap:
+  call void @llvm.trap()
+  unreachable
+}
+
+define internal i8 @pow.i8(i8 %base, i8 %exponent) {
+entry:
+  %negative = icmp slt i8 %exponent, 0
+  br i1 %negative, label %reciprocal, label %loop
+reciprocal:
+  %zero = icmp eq i8 %base, 0
+  br i1 %zero, label %trap, label %truncate
+truncate:
+  %one = icmp eq i8 %base, 1
+  %minus_one = icmp eq i8 %base, -1
+  %odd = trunc i8 %exponent to i1
+  %sign = select i1 %odd, i8 -1, i8 1
+  %unit = select i1 %minus_one, i8 %sign, i8 0
+  %inverse = select i1 %one, i8 1, i8 %unit
+  ret i8 %inverse
+loop:
+  %result = phi i8 [ 1, %entry ], [ %result.shifted, %square ]
+  %factor = phi i8 [ %base, %entry ], [ %squared, %square ]
+  %remaining = phi i8 [ %exponent, %entry ], [ %halved, %square ]
+  %bit = trunc i8 %remaining to i1
+  br i1 %bit, label %multiply, label %shift
+multiply:
+  %product = call { i8, i1 } @llvm.smul.with.overflow.i8(i8 %result, i8 %factor)
+  %multiplied = extractvalue { i8, i1 } %product, 0
+  %multiply.overflow = extractvalue { i8, i1 } %product, 1
+  br i1 %multiply.overflow, label %trap, label %shift
+shift:
+  %result.shifted = phi i8 [ %result, %loop ], [ %multiplied, %multiply ]
+  %halved = lshr i8 %remaining, 1
+  %done = icmp eq i8 %halved, 0
+  br i1 %done, label %exit, label %square
+square:
+  %squares = call { i8, i1 } @llvm.smul.with.overflow.i8(i8 %factor, i8 %factor)
+  %squared = extractvalue { i8, i1 } %squares, 0
+  %square.overflow = extractvalue { i8, i1 } %squares, 1
+  br i1 %square.overflow, label %trap, label %loop
+exit:
+  ret i8 %result.shifted
+trap:
+  call void @llvm.trap()
+  unreachable
+}
+
+declare i32 @printf(ptr, ...)
+declare void @llvm.trap()
+declare { i32, i1 } @llvm.smul.with.overflow.i32(i32, i32)
+declare { i64, i1 } @llvm.sadd.with.overflow.i64(i64, i64)
+declare { i64, i1 } @llvm.smul.with.overflow.i64(i64, i64)
+declare { i8, i1 } @llvm.smul.with.overflow.i8(i8, i8)
+declare { i8, i1 } @llvm.uadd.with.overflow.i8(i8, i8)
@@ -0,0 +1,54 @@
+; ModuleID = 'fib.src'
+source_filename = "fib.src"
+
+@.str = private unnamed_addr constant [6 x i8] c"%lld\0A\00"
+
+define internal i64 @fib(i64 %x) {
+entry:
+  %x.addr = alloca i64
+  store i64 %x, ptr %x.addr
+  %t0 = load i64, ptr %x.addr
+  %t1 = icmp slt i64 %t0, 3
+  br i1 %t1, label %if.then, label %if.else
+if.then:
+  br label %if.end
+if.else:
+  %t2 = load i64, ptr %x.addr
+  %t3 = call { i64, i1 } @llvm.ssub.with.overflow.i64(i64 %t2, i64 1)
+  %t4 = extractvalue { i64, i1 } %t3, 0
+  %t5 = extractvalue { i64, i1 } %t3, 1
+  br i1 %t5, label %trap, label %ok
+ok:
+  %t6 = call i64 @fib(i64 %t4)
+  %t7 = load i64, ptr %x.addr
+  %t8 = call { i64, i1 } @llvm.ssub.with.overflow.i64(i64 %t7, i64 2)
+  %t9 = extractvalue { i64, i1 } %t8, 0
+  %t10 = extractvalue { i64, i1 } %t8, 1
+  br i1 %t10, label %trap, label %ok.1
+ok.1:
+  %t11 = call i64 @fib(i64 %t9)
+  %t12 = call { i64, i1 } @llvm.sadd.with.overflow.i64(i64 %t6, i64 %t11)
+  %t13 = extractvalue { i64, i1 } %t12, 0
+  %t14 = extractvalue { i64, i1 } %t12, 1
+  br i1 %t14, label %trap, label %ok.2
+ok.2:
+  br label %if.end
+if.end:
+  %t15 = phi i64 [ 1, %if.then ], [ %t13, %ok.2 ]
+  ret i64 %t15
+trap:
+  call void @llvm.trap()
+  unreachable
+}
+
+define i32 @main() {
+entry:
+  %t0 = call i64 @fib(i64 20)
+  call i32 (ptr, ...) @printf(ptr @.str, i64 %t0)
+  ret i32 0
+}
+
+declare i32 @printf(ptr, ...)
+declare void @llvm.trap()
+declare { i64, i1 } @llvm.sadd.with.overflow.i64(i64, i64)
+declare { i64, i1 } @llvm.ssub.with.overflow.i64(i64, i64)
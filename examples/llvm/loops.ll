@@ -0,0 +1,189 @@
+; ModuleID = 'loops.src'
+source_filename = "loops.src"
+
+@total = internal global i32 zeroinitializer
+@i = internal global i32 zeroinitializer
+@a = internal global i32 zeroinitializer
+@b = internal global i32 zeroinitializer
+@.str = private unnamed_addr constant [5 x i8] c"true\00"
+@.str.1 = private unnamed_addr constant [6 x i8] c"false\00"
+@.str.2 = private unnamed_addr constant [30 x i8] c"(%lld, %lld, %lld, %llu, %s)\0A\00"
+
+define internal void @swap(ptr %a, ptr %b) {
+entry:
+  %a.addr = alloca ptr
+  %b.addr = alloca ptr
+  %t.addr = alloca i32
+  store ptr %a, ptr %a.addr
+  store ptr %b, ptr %b.addr
+  %t0 = load ptr, ptr %a.addr
+  %t1 = load i32, ptr %t0
+  store i32 %t1, ptr %t.addr
+  %t2 = load ptr, ptr %a.addr
+  %t3 = load ptr, ptr %b.addr
+  %t4 = load i32, ptr %t3
+  store i32 %t4, ptr %t2
+  %t5 = load ptr, ptr %b.addr
+  %t6 = load i32, ptr %t.addr
+  store i32 %t6, ptr %t5
+  ret void
+}
+
+define internal i64 @collatz(i64 %n) {
+entry:
+  %n.addr = alloca i64
+  %steps.addr = alloca i64
+  %x.addr = alloca i64
+  store i64 %n, ptr %n.addr
+  store i64 0, ptr %steps.addr
+  %t0 = load i64, ptr %n.addr
+  store i64 %t0, ptr %x.addr
+  br label %while.cond
+while.cond:
+  %t1 = load i64, ptr %x.addr
+  %t2 = icmp ne i64 %t1, 1
+  br i1 %t2, label %while.body, label %while.end
+while.body:
+  %t3 = load i64, ptr %x.addr
+  %t4 = icmp eq i64 2, 0
+  br i1 %t4, label %trap, label %ok
+ok:
+  %t5 = udiv i64 %t3, 2
+  %t6 = call { i64, i1 } @llvm.umul.with.overflow.i64(i64 %t5, i64 2)
+  %t7 = extractvalue { i64, i1 } %t6, 0
+  %t8 = extractvalue { i64, i1 } %t6, 1
+  br i1 %t8, label %trap, label %ok.1
+ok.1:
+  %t9 = load i64, ptr %x.addr
+  %t10 = icmp eq i64 %t7, %t9
+  br i1 %t10, label %if.then, label %if.else
+if.then:
+  %t11 = load i64, ptr %x.addr
+  %t12 = icmp eq i64 2, 0
+  br i1 %t12, label %trap, label %ok.2
+ok.2:
+  %t13 = udiv i64 %t11, 2
+  store i64 %t13, ptr %x.addr
+  br label %if.end
+if.else:
+  %t14 = load i64, ptr %x.addr
+  %t15 = call { i64, i1 } @llvm.umul.with.overflow.i64(i64 3, i64 %t14)
+  %t16 = extractvalue { i64, i1 } %t15, 0
+  %t17 = extractvalue { i64, i1 } %t15, 1
+  br i1 %t17, label %trap, label %ok.3
+ok.3:
+  %t18 = call { i64, i1 } @llvm.uadd.with.overflow.i64(i64 %t16, i64 1)
+  %t19 = extractvalue { i64, i1 } %t18, 0
+  %t20 = extractvalue { i64, i1 } %t18, 1
+  br i1 %t20, label %trap, label %ok.4
+ok.4:
+  store i64 %t19, ptr %x.addr
+  br label %if.end
+if.end:
+  %t21 = load i64, ptr %steps.addr
+  %t22 = call { i64, i1 } @llvm.uadd.with.overflow.i64(i64 %t21, i64 1)
+  %t23 = extractvalue { i64, i1 } %t22, 0
+  %t24 = extractvalue { i64, i1 } %t22, 1
+  br i1 %t24, label %trap, label %ok.5
+ok.5:
+  store i64 %t23, ptr %steps.addr
+  br label %while.cond
+while.end:
+  %t25 = load i64, ptr %steps.addr
+  ret i64 %t25
+trap:
+  call void @llvm.trap()
+  unreachable
+}
+
+define internal { i32, i32, i32, i64, i1 } @main.1() {
+entry:
+  %t0 = load i32, ptr @total
+  %t1 = insertvalue { i32, i32, i32, i64, i1 } poison, i32 %t0, 0
+  %t2 = load i32, ptr @a
+  %t3 = insertvalue { i32, i32, i32, i64, i1 } %t1, i32 %t2, 1
+  %t4 = load i32, ptr @b
+  %t5 = insertvalue { i32, i32, i32, i64, i1 } %t3, i32 %t4, 2
+  %t6 = call i64 @collatz(i64 27)
+  %t7 = insertvalue { i32, i32, i32, i64, i1 } %t5, i64 %t6, 3
+  %t8 = load i32, ptr @a
+  %t9 = load i32, ptr @b
+  %t10 = icmp sgt i32 %t8, %t9
+  %t11 = insertvalue { i32, i32, i32, i64, i1 } %t7, i1 %t10, 4
+  ret { i32, i32, i32, i64, i1 } %t11
+}
+
+define i32 @main() {
+entry:
+  store i32 0, ptr @total
+  store i32 1, ptr @i
+  br label %while.cond
+while.cond:
+  %t0 = load i32, ptr @i
+  %t1 = icmp sle i32 %t0, 10
+  br i1 %t1, label %while.body, label %while.end
+while.body:
+  %t2 = load i32, ptr @i
+  %t3 = icmp sgt i32 %t2, 5
+  br i1 %t3, label %if.then, label %if.else
+if.then:
+  %t4 = load i32, ptr @i
+  %t5 = call { i32, i1 } @llvm.smul.with.overflow.i32(i32 %t4, i32 2)
+  %t6 = extractvalue { i32, i1 } %t5, 0
+  %t7 = extractvalue { i32, i1 } %t5, 1
+  br i1 %t7, label %trap, label %ok
+ok:
+  %t8 = load i32, ptr @total
+  %t9 = call { i32, i1 } @llvm.sadd.with.overflow.i32(i32 %t8, i32 %t6)
+  %t10 = extractvalue { i32, i1 } %t9, 0
+  %t11 = extractvalue { i32, i1 } %t9, 1
+  br i1 %t11, label %trap, label %ok.1
+ok.1:
+  store i32 %t10, ptr @total
+  br label %if.end
+if.else:
+  %t12 = load i32, ptr @total
+  %t13 = call { i32, i1 } @llvm.ssub.with.overflow.i32(i32 %t12, i32 1)
+  %t14 = extractvalue { i32, i1 } %t13, 0
+  %t15 = extractvalue { i32, i1 } %t13, 1
+  br i1 %t15, label %trap, label %ok.2
+ok.2:
+  store i32 %t14, ptr @total
+  br label %if.end
+if.end:
+  %t16 = load i32, ptr @i
+  %t17 = call { i32, i1 } @llvm.sadd.with.overflow.i32(i32 %t16, i32 1)
+  %t18 = extractvalue { i32, i1 } %t17, 0
+  %t19 = extractvalue { i32, i1 } %t17, 1
+  br i1 %t19, label %trap, label %ok.3
+ok.3:
+  store i32 %t18, ptr @i
+  br label %while.cond
+while.end:
+  store i32 3, ptr @a
+  store i32 4, ptr @b
+  call void @swap(ptr @a, ptr @b)
+  %t20 = call { i32, i32, i32, i64, i1 } @main.1()
+  %t21 = extractvalue { i32, i32, i32, i64, i1 } %t20, 0
+  %t22 = sext i32 %t21 to i64
+  %t23 = extractvalue { i32, i32, i32, i64, i1 } %t20, 1
+  %t24 = sext i32 %t23 to i64
+  %t25 = extractvalue { i32, i32, i32, i64, i1 } %t20, 2
+  %t26 = sext i32 %t25 to i64
+  %t27 = extractvalue { i32, i32, i32, i64, i1 } %t20, 3
+  %t28 = extractvalue { i32, i32, i32, i64, i1 } %t20, 4
+  %t29 = select i1 %t28, ptr @.str, ptr @.str.1
+  call i32 (ptr, ...) @printf(ptr @.str.2, i64 %t22, i64 %t24, i64 %t26, i64 %t27, ptr %t29)
+  ret i32 0
+trap:
+  call void @llvm.trap()
+  unreachable
+}
+
+declare i32 @printf(ptr, ...)
+declare void @llvm.trap()
+declare { i32, i1 } @llvm.sadd.with.overflow.i32(i32, i32)
+declare { i32, i1 } @llvm.smul.with.overflow.i32(i32, i32)
+declare { i32, i1 } @llvm.ssub.with.overflow.i32(i32, i32)
+declare { i64, i1 } @llvm.uadd.with.overflow.i64(i64, i64)
+declare { i64, i1 } @llvm.umul.with.overflow.i64(i64, i64)
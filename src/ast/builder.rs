@@ -0,0 +1,599 @@
+use log::trace;
+use miette::{miette, LabeledSpan, SourceSpan};
+
+use crate::ast::{
+    join, BinaryOp, Block, Expr, ExprKind, Function, Ident, If, Item, ItemKind, Module, Param,
+    Stmt, StmtKind, TypeExpr, TypeKind, UnaryOp,
+};
+use crate::token::Token;
+
+/// Builds a [`Module`] from the reverse Polish output of [`crate::parse::parser::Parser`].
+///
+/// Declarations are read front to back, since their shape is fixed by their introducing `fn` or `var`. Expressions and
+/// types are folded onto a stack of [`Fragment`]s using the arity of each token.
+pub struct Builder<'p, 't> {
+    index: usize,
+    tokens: &'p [Token<'t>],
+}
+
+/// A partially built node on the fold stack.
+#[derive(Debug)]
+enum Fragment<'t> {
+    Expr(Expr<'t>),
+    Type(TypeExpr<'t>),
+    /// Comma separated expressions inside of brackets, which become a tuple
+    List(Vec<Expr<'t>>, SourceSpan),
+    /// An open bracket, waiting for its closing bracket
+    Open(Token<'t>),
+}
+
+impl<'p, 't> Builder<'p, 't> {
+    pub fn new(tokens: &'p [Token<'t>]) -> Self {
+        Builder { index: 0, tokens }
+    }
+
+    pub fn build(mut self) -> miette::Result<Module<'t>> {
+        trace!("Start building");
+
+        let mut stmts = Vec::new();
+        while self.peek().is_some() {
+            match self.statement()? {
+                Ok(stmt) => stmts.push(stmt),
+                // The last expression in a file doesn't need a terminating `;`
+                Err(expr) => {
+                    let span = expr.span;
+                    stmts.push(Stmt {
+                        kind: StmtKind::Expr(expr),
+                        span,
+                    });
+                }
+            }
+        }
+
+        Ok(Module { stmts })
+    }
+
+    fn peek(&self) -> Option<Token<'t>> {
+        self.tokens.get(self.index).copied()
+    }
+
+    fn next(&mut self) -> Option<Token<'t>> {
+        let token = self.peek();
+        self.index += 1;
+        token
+    }
+
+    /// Consumes the next token, when `predicate` returns `true` for it.
+    fn expect(
+        &mut self,
+        predicate: impl FnOnce(&Token<'t>) -> bool,
+        expected: &str,
+    ) -> miette::Result<Token<'t>> {
+        match self.peek() {
+            Some(token) if predicate(&token) => {
+                self.index += 1;
+                Ok(token)
+            }
+            Some(token) => Err(malformed(token.span(), expected)),
+            None => {
+                let end = self
+                    .tokens
+                    .last()
+                    .map_or(0, |token| token.loc() + token.as_str().len());
+                Err(malformed(SourceSpan::from((end, 0)), expected))
+            }
+        }
+    }
+
+    /// Builds a statement, or returns `Err` containing an expression which wasn't terminated by a `;`.
+    fn statement(&mut self) -> miette::Result<Result<Stmt<'t>, Expr<'t>>> {
+        let stmt = match self.peek() {
+            Some(Token::FunctionDeclaration(_)) => {
+                let item = self.function()?;
+                let span = item.span;
+                Stmt {
+                    kind: StmtKind::Item(item),
+                    span,
+                }
+            }
+            Some(Token::VariableDeclaration(_) | Token::Const(_)) => self.variable()?,
+            Some(Token::If(_)) => self.if_statement()?,
+            Some(Token::While(_)) => {
+                let while_token = self.next().unwrap();
+                let condition = self.fold(|_| false)?.into_expr()?;
+                let body = self.block()?;
+                let span = join(while_token.span(), body.span);
+                Stmt {
+                    kind: StmtKind::While { condition, body },
+                    span,
+                }
+            }
+            Some(Token::OpenCurly(_)) => {
+                let block = self.block()?;
+                let span = block.span;
+                Stmt {
+                    kind: StmtKind::Block(block),
+                    span,
+                }
+            }
+            // Recovered errors keep the `;` they were skipped up to, if there was one
+            Some(error @ Token::Error(_)) => {
+                self.index += 1;
+                let span = match self.peek() {
+                    Some(semicolon @ Token::SemiColon(_)) => {
+                        self.index += 1;
+                        join(error.span(), semicolon.span())
+                    }
+                    _ => error.span(),
+                };
+                Stmt {
+                    kind: StmtKind::Error,
+                    span,
+                }
+            }
+            _ => {
+                let expr = self.fold(|_| false)?.into_expr()?;
+
+                match self.peek() {
+                    Some(semicolon @ Token::SemiColon(_)) => {
+                        self.index += 1;
+                        let span = join(expr.span, semicolon.span());
+                        Stmt {
+                            kind: StmtKind::Expr(expr),
+                            span,
+                        }
+                    }
+                    _ => return Ok(Err(expr)),
+                }
+            }
+        };
+
+        Ok(Ok(stmt))
+    }
+
+    fn block(&mut self) -> miette::Result<Block<'t>> {
+        let open_curly = self.expect(|token| matches!(token, Token::OpenCurly(_)), "`{`")?;
+
+        let mut stmts = Vec::new();
+        let mut tail = None;
+        while !matches!(self.peek(), Some(Token::CloseCurly(_)) | None) {
+            match self.statement()? {
+                Ok(stmt) => stmts.push(stmt),
+                Err(expr) => tail = Some(Box::new(expr)),
+            }
+        }
+
+        let close_curly = self.expect(|token| matches!(token, Token::CloseCurly(_)), "`}`")?;
+
+        Ok(Block {
+            stmts,
+            tail,
+            span: join(open_curly.span(), close_curly.span()),
+        })
+    }
+
+    /// Builds `if condition { ... } else ...`, where the `else` is followed by another `if`, or a block
+    fn if_statement(&mut self) -> miette::Result<Stmt<'t>> {
+        let if_token = self.next().unwrap();
+        let condition = self.fold(|_| false)?.into_expr()?;
+        let then_block = self.block()?;
+        let mut span = join(if_token.span(), then_block.span);
+
+        let mut else_branch = None;
+        if let Some(Token::Else(_)) = self.peek() {
+            self.index += 1;
+
+            let branch = match self.peek() {
+                Some(Token::If(_)) => self.if_statement()?,
+                _ => {
+                    let block = self.block()?;
+                    Stmt {
+                        span: block.span,
+                        kind: StmtKind::Block(block),
+                    }
+                }
+            };
+            span = join(span, branch.span);
+            else_branch = Some(Box::new(branch));
+        }
+
+        Ok(Stmt {
+            kind: StmtKind::If(If {
+                condition,
+                then_block,
+                else_branch,
+            }),
+            span,
+        })
+    }
+
+    /// Builds `fn name ( a A : b B : , ) R : { ... }`
+    fn function(&mut self) -> miette::Result<Item<'t>> {
+        let fn_token = self.next().unwrap();
+        let name = self.ident("function name")?;
+
+        self.expect(|token| matches!(token, Token::OpenBracket(_)), "`(`")?;
+        let mut params = Vec::new();
+        while !matches!(self.peek(), Some(Token::CloseBracket(_)) | None) {
+            let name = self.ident("parameter name")?;
+            let ty = self
+                .fold(|token| matches!(token, Token::Colon(_)))?
+                .into_type()?;
+            self.expect(|token| matches!(token, Token::Colon(_)), "`:`")?;
+
+            // Every parameter after the first is followed by the comma joining it to the list
+            if let Some(Token::Comma(_)) = self.peek() {
+                self.index += 1;
+            }
+
+            let span = join(name.span, ty.span);
+            params.push(Param { name, ty, span });
+        }
+        self.expect(|token| matches!(token, Token::CloseBracket(_)), "`)`")?;
+
+        let return_type = match self.peek() {
+            Some(Token::OpenCurly(_)) => None,
+            _ => {
+                let ty = self
+                    .fold(|token| matches!(token, Token::Colon(_) | Token::Arrow(_)))?
+                    .into_type()?;
+                self.expect(
+                    |token| matches!(token, Token::Colon(_) | Token::Arrow(_)),
+                    "`:` or `->`",
+                )?;
+                Some(ty)
+            }
+        };
+
+        let body = self.block()?;
+        let span = join(fn_token.span(), body.span);
+
+        Ok(Item {
+            kind: ItemKind::Function(Function {
+                name,
+                params,
+                return_type,
+                body,
+            }),
+            span,
+        })
+    }
+
+    /// Builds `var name T : value = ;`, where both the type and value are optional, or the same beginning with
+    /// `const`, where the value isn't
+    fn variable(&mut self) -> miette::Result<Stmt<'t>> {
+        let var_token = self.next().unwrap();
+        let name = self.ident("variable name")?;
+
+        let mut ty = None;
+        let mut value = None;
+        if !matches!(self.peek(), Some(Token::SemiColon(_))) {
+            let fragment =
+                self.fold(|token| matches!(token, Token::Colon(_) | Token::Equals(_)))?;
+
+            match self.next() {
+                Some(Token::Colon(_)) => {
+                    ty = Some(fragment.into_type()?);
+
+                    if !matches!(self.peek(), Some(Token::SemiColon(_))) {
+                        let expr = self
+                            .fold(|token| matches!(token, Token::Equals(_)))?
+                            .into_expr()?;
+                        self.expect(|token| matches!(token, Token::Equals(_)), "`=`")?;
+                        value = Some(expr);
+                    }
+                }
+                Some(Token::Equals(_)) => {
+                    value = Some(fragment.into_expr()?);
+                }
+                _ => {
+                    self.index -= 1;
+                    return Err(malformed(fragment.span(), "`:` or `=` after variable"));
+                }
+            }
+        }
+
+        let semicolon = self.expect(|token| matches!(token, Token::SemiColon(_)), "`;`")?;
+        let span = join(var_token.span(), semicolon.span());
+
+        let kind = match (var_token, value) {
+            (Token::Const(_), Some(value)) => StmtKind::Const { name, ty, value },
+            (Token::Const(_), None) => return Err(malformed(span, "a constant's value")),
+            (_, value) => StmtKind::Variable { name, ty, value },
+        };
+        Ok(Stmt { kind, span })
+    }
+
+    fn ident(&mut self, expected: &str) -> miette::Result<Ident<'t>> {
+        let token = self.expect(|token| matches!(token, Token::Identifier(_)), expected)?;
+
+        Ok(Ident {
+            name: token.inner().slice,
+            span: token.span(),
+        })
+    }
+
+    /// Folds tokens into a single fragment, stopping before a token which can't be part of an expression or type, or
+    /// before a token `stop` returns `true` for once there is a single fragment.
+    fn fold(&mut self, stop: impl Fn(&Token<'t>) -> bool) -> miette::Result<Fragment<'t>> {
+        let mut stack: Vec<Fragment<'t>> = Vec::new();
+
+        while let Some(token) = self.peek() {
+            let ends_statement = matches!(
+                token,
+                Token::SemiColon(_)
+                    | Token::OpenCurly(_)
+                    | Token::CloseCurly(_)
+                    | Token::FunctionDeclaration(_)
+                    | Token::VariableDeclaration(_)
+                    | Token::Const(_)
+                    | Token::If(_)
+                    | Token::Else(_)
+                    | Token::While(_)
+                    | Token::Error(_)
+            );
+            if ends_statement || (stack.len() == 1 && stop(&token)) {
+                break;
+            }
+            self.index += 1;
+
+            let fragment = match token {
+                Token::Identifier(inner) => Fragment::Expr(Expr {
+                    kind: match inner.slice {
+                        "true" => ExprKind::Boolean(true),
+                        "false" => ExprKind::Boolean(false),
+                        name => ExprKind::Identifier(Ident {
+                            name,
+                            span: token.span(),
+                        }),
+                    },
+                    span: token.span(),
+                }),
+                Token::NumericLiteral(inner) => Fragment::Expr(Expr {
+                    kind: ExprKind::Integer(inner.slice),
+                    span: token.span(),
+                }),
+
+                Token::OpenBracket(_) => Fragment::Open(token),
+                Token::CloseBracket(_) => {
+                    let inner = pop(&mut stack, token)?;
+                    let Some(Fragment::Open(open_bracket)) = stack.pop() else {
+                        return Err(malformed(token.span(), "`(` before `)`"));
+                    };
+                    let span = join(open_bracket.span(), token.span());
+
+                    match inner {
+                        Fragment::List(elements, _) => Fragment::Expr(Expr {
+                            kind: ExprKind::Tuple(elements),
+                            span,
+                        }),
+                        fragment => {
+                            let mut expr = fragment.into_expr()?;
+                            expr.span = span;
+                            Fragment::Expr(expr)
+                        }
+                    }
+                }
+                Token::Comma(_) => {
+                    let right = pop(&mut stack, token)?.into_expr()?;
+                    let (mut elements, span) = match pop(&mut stack, token)? {
+                        Fragment::List(elements, span) => (elements, span),
+                        fragment => {
+                            let expr = fragment.into_expr()?;
+                            let span = expr.span;
+                            (vec![expr], span)
+                        }
+                    };
+
+                    let span = join(span, right.span);
+                    elements.push(right);
+                    Fragment::List(elements, span)
+                }
+
+                Token::Call(_, arity) => {
+                    let arguments = pop_many(&mut stack, token, arity)?
+                        .into_iter()
+                        .map(Fragment::into_expr)
+                        .collect::<miette::Result<Vec<_>>>()?;
+                    let callee = pop(&mut stack, token)?.into_expr()?;
+
+                    Fragment::Expr(Expr {
+                        span: join(callee.span, token.span()),
+                        kind: ExprKind::Call(Box::new(callee), arguments),
+                    })
+                }
+
+                Token::Generic(_, arity) => {
+                    let arguments = pop_types(&mut stack, token, arity)?;
+                    let base = pop(&mut stack, token)?.into_type()?;
+
+                    Fragment::Type(TypeExpr {
+                        span: join(base.span, token.span()),
+                        kind: TypeKind::Generic(Box::new(base), arguments),
+                    })
+                }
+                Token::Tuple(_, arity) => {
+                    let elements = pop_types(&mut stack, token, arity)?;
+                    let span = elements
+                        .first()
+                        .map_or(token.span(), |first| join(first.span, token.span()));
+
+                    Fragment::Type(TypeExpr {
+                        kind: TypeKind::Tuple(elements),
+                        span,
+                    })
+                }
+                Token::FunctionType(_, arity) => {
+                    let return_type = pop(&mut stack, token)?.into_type()?;
+                    let params = pop_types(&mut stack, token, arity)?;
+
+                    Fragment::Type(TypeExpr {
+                        span: join(token.span(), return_type.span),
+                        kind: TypeKind::Function(params, Box::new(return_type)),
+                    })
+                }
+
+                // `&` is a prefix operator in expressions, but also makes reference types
+                Token::Reference(_) => match pop(&mut stack, token)? {
+                    Fragment::Type(ty) => Fragment::Type(TypeExpr {
+                        span: join(token.span(), ty.span),
+                        kind: TypeKind::Reference(Box::new(ty)),
+                    }),
+                    fragment => unary(UnaryOp::Reference, token, fragment.into_expr()?),
+                },
+                Token::Negate(_) => unary(UnaryOp::Negate, token, pop_expr(&mut stack, token)?),
+                Token::UnaryPlus(_) => unary(UnaryOp::Plus, token, pop_expr(&mut stack, token)?),
+                Token::Not(_) => unary(UnaryOp::Not, token, pop_expr(&mut stack, token)?),
+                Token::Deref(_) => unary(UnaryOp::Deref, token, pop_expr(&mut stack, token)?),
+
+                _ => {
+                    let Some(op) = binary_op(&token) else {
+                        return Err(malformed(token.span(), "an expression or type"));
+                    };
+                    let right = pop_expr(&mut stack, token)?;
+                    let left = pop_expr(&mut stack, token)?;
+
+                    Fragment::Expr(Expr {
+                        span: join(left.span, right.span),
+                        kind: ExprKind::Binary(op, Box::new(left), Box::new(right)),
+                    })
+                }
+            };
+
+            stack.push(fragment);
+        }
+
+        if stack.len() != 1 {
+            let span = match (self.peek(), stack.last()) {
+                (_, Some(fragment)) => fragment.span(),
+                (Some(token), None) => token.span(),
+                (None, None) => SourceSpan::from((0, 0)),
+            };
+            return Err(malformed(span, "a single expression or type"));
+        }
+
+        Ok(stack.pop().unwrap())
+    }
+}
+
+impl<'t> Fragment<'t> {
+    fn span(&self) -> SourceSpan {
+        match self {
+            Fragment::Expr(expr) => expr.span,
+            Fragment::Type(ty) => ty.span,
+            Fragment::List(_, span) => *span,
+            Fragment::Open(token) => token.span(),
+        }
+    }
+
+    fn into_expr(self) -> miette::Result<Expr<'t>> {
+        match self {
+            Fragment::Expr(expr) => Ok(expr),
+            fragment => Err(malformed(fragment.span(), "an expression")),
+        }
+    }
+
+    /// Converts into a type, since names and references parse the same as expressions.
+    fn into_type(self) -> miette::Result<TypeExpr<'t>> {
+        match self {
+            Fragment::Type(ty) => Ok(ty),
+            Fragment::Expr(expr) => expr_into_type(expr),
+            fragment => Err(malformed(fragment.span(), "a type")),
+        }
+    }
+}
+
+fn expr_into_type(expr: Expr<'_>) -> miette::Result<TypeExpr<'_>> {
+    let kind = match expr.kind {
+        ExprKind::Identifier(ident) => TypeKind::Named(ident.name),
+        ExprKind::Unary(UnaryOp::Reference, inner) => {
+            TypeKind::Reference(Box::new(expr_into_type(*inner)?))
+        }
+        _ => return Err(malformed(expr.span, "a type")),
+    };
+
+    Ok(TypeExpr {
+        kind,
+        span: expr.span,
+    })
+}
+
+fn unary<'t>(op: UnaryOp, token: Token<'t>, operand: Expr<'t>) -> Fragment<'t> {
+    Fragment::Expr(Expr {
+        span: join(token.span(), operand.span),
+        kind: ExprKind::Unary(op, Box::new(operand)),
+    })
+}
+
+fn binary_op(token: &Token<'_>) -> Option<BinaryOp> {
+    let op = match token {
+        Token::Plus(_) => BinaryOp::Add,
+        Token::Minus(_) => BinaryOp::Subtract,
+        Token::Times(_) => BinaryOp::Multiply,
+        Token::Divide(_) => BinaryOp::Divide,
+        Token::Power(_) => BinaryOp::Power,
+        Token::ShiftLeft(_) => BinaryOp::ShiftLeft,
+        Token::ShiftRight(_) => BinaryOp::ShiftRight,
+        Token::GreaterThan(_) => BinaryOp::Greater,
+        Token::LessThan(_) => BinaryOp::Less,
+        Token::GreaterEquals(_) => BinaryOp::GreaterEqual,
+        Token::LessEquals(_) => BinaryOp::LessEqual,
+        Token::DoubleEquals(_) => BinaryOp::Equal,
+        Token::NotEquals(_) => BinaryOp::NotEqual,
+        Token::Equals(_) => BinaryOp::Assign,
+        Token::PlusEquals(_) => BinaryOp::AddAssign,
+        Token::MinusEquals(_) => BinaryOp::SubtractAssign,
+        Token::TimesEquals(_) => BinaryOp::MultiplyAssign,
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+/// Pops the operand of `token`, which mustn't be an open bracket.
+fn pop<'t>(stack: &mut Vec<Fragment<'t>>, token: Token<'t>) -> miette::Result<Fragment<'t>> {
+    match stack.pop() {
+        Some(Fragment::Open(_)) | None => Err(malformed(token.span(), "an operand")),
+        Some(fragment) => Ok(fragment),
+    }
+}
+
+fn pop_expr<'t>(stack: &mut Vec<Fragment<'t>>, token: Token<'t>) -> miette::Result<Expr<'t>> {
+    pop(stack, token)?.into_expr()
+}
+
+/// Pops `count` operands of `token`, in the order they were written.
+fn pop_many<'t>(
+    stack: &mut Vec<Fragment<'t>>,
+    token: Token<'t>,
+    count: u32,
+) -> miette::Result<Vec<Fragment<'t>>> {
+    let mut operands = (0..count)
+        .map(|_| pop(stack, token))
+        .collect::<miette::Result<Vec<_>>>()?;
+    operands.reverse();
+
+    Ok(operands)
+}
+
+fn pop_types<'t>(
+    stack: &mut Vec<Fragment<'t>>,
+    token: Token<'t>,
+    count: u32,
+) -> miette::Result<Vec<TypeExpr<'t>>> {
+    pop_many(stack, token, count)?
+        .into_iter()
+        .map(Fragment::into_type)
+        .collect()
+}
+
+/// The parser should never produce output which can't be built, so this is a bug rather than a user error.
+pub(crate) fn malformed(span: SourceSpan, expected: &str) -> miette::Report {
+    let label = LabeledSpan::new_with_span(Some(format!("expected {expected}")), span);
+
+    miette!(
+        code = "E0010",
+        labels = vec![label],
+        "Malformed parser output"
+    )
+}
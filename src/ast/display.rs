@@ -0,0 +1,200 @@
+//! Compact S-expression printing of the syntax tree, with types printed as they are written in source.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::ast::{
+    BinaryOp, Block, Expr, ExprKind, Function, If, Item, ItemKind, Module, Param, Stmt, StmtKind,
+    TypeExpr, TypeKind, UnaryOp,
+};
+
+/// Writes `items` separated by `separator`.
+fn write_separated<T: Display>(f: &mut Formatter, items: &[T], separator: &str) -> fmt::Result {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            write!(f, "{}", separator)?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+impl<'t> Display for Module<'t> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write_separated(f, &self.stmts, "\n")
+    }
+}
+
+impl<'t> Display for Item<'t> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match &self.kind {
+            ItemKind::Function(function) => write!(f, "{}", function),
+        }
+    }
+}
+
+impl<'t> Display for Function<'t> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "(fn {} (", self.name.name)?;
+        write_separated(f, &self.params, ", ")?;
+        write!(f, ")")?;
+        if let Some(return_type) = &self.return_type {
+            write!(f, " -> {}", return_type)?;
+        }
+        write!(f, " {})", self.body)
+    }
+}
+
+impl<'t> Display for Param<'t> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name.name, self.ty)
+    }
+}
+
+impl<'t> Display for Stmt<'t> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match &self.kind {
+            StmtKind::Item(item) => write!(f, "{}", item),
+            StmtKind::Variable { name, ty, value } => {
+                write!(f, "(var {}", name.name)?;
+                if let Some(ty) = ty {
+                    write!(f, ": {}", ty)?;
+                }
+                if let Some(value) = value {
+                    write!(f, " {}", value)?;
+                }
+                write!(f, ")")
+            }
+            StmtKind::Const { name, ty, value } => {
+                write!(f, "(const {}", name.name)?;
+                if let Some(ty) = ty {
+                    write!(f, ": {}", ty)?;
+                }
+                write!(f, " {})", value)
+            }
+            StmtKind::Expr(expr) => write!(f, "{};", expr),
+            StmtKind::Block(block) => write!(f, "{}", block),
+            StmtKind::If(if_stmt) => write!(f, "{}", if_stmt),
+            StmtKind::While { condition, body } => write!(f, "(while {} {})", condition, body),
+            StmtKind::Error => write!(f, "(error)"),
+        }
+    }
+}
+
+impl<'t> Display for If<'t> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "(if {} {}", self.condition, self.then_block)?;
+        if let Some(else_branch) = &self.else_branch {
+            write!(f, " {}", else_branch)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl<'t> Display for Block<'t> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.stmts.is_empty() && self.tail.is_none() {
+            return write!(f, "{{}}");
+        }
+
+        write!(f, "{{ ")?;
+        write_separated(f, &self.stmts, " ")?;
+        if let Some(tail) = &self.tail {
+            if !self.stmts.is_empty() {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", tail)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+impl<'t> Display for Expr<'t> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match &self.kind {
+            ExprKind::Identifier(ident) => write!(f, "{}", ident.name),
+            ExprKind::Integer(value) => write!(f, "{}", value),
+            ExprKind::Boolean(value) => write!(f, "{}", value),
+            ExprKind::Unary(op, operand) => write!(f, "({} {})", op, operand),
+            ExprKind::Binary(op, left, right) => write!(f, "({} {} {})", op, left, right),
+            ExprKind::Call(callee, arguments) => {
+                write!(f, "(call {}", callee)?;
+                for argument in arguments {
+                    write!(f, " {}", argument)?;
+                }
+                write!(f, ")")
+            }
+            ExprKind::Tuple(elements) => {
+                write!(f, "(tuple")?;
+                for element in elements {
+                    write!(f, " {}", element)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl Display for UnaryOp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = match self {
+            UnaryOp::Negate => "neg",
+            UnaryOp::Plus => "pos",
+            UnaryOp::Not => "!",
+            UnaryOp::Deref => "deref",
+            UnaryOp::Reference => "ref",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let symbol = match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Subtract => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Power => "**",
+            BinaryOp::ShiftLeft => "<<",
+            BinaryOp::ShiftRight => ">>",
+            BinaryOp::Greater => ">",
+            BinaryOp::Less => "<",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::Equal => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Assign => "=",
+            BinaryOp::AddAssign => "+=",
+            BinaryOp::SubtractAssign => "-=",
+            BinaryOp::MultiplyAssign => "*=",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl<'t> Display for TypeExpr<'t> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match &self.kind {
+            TypeKind::Named(name) => write!(f, "{}", name),
+            TypeKind::Generic(base, arguments) => {
+                write!(f, "{}<", base)?;
+                write_separated(f, arguments, ", ")?;
+                write!(f, ">")
+            }
+            TypeKind::Tuple(elements) => {
+                write!(f, "(")?;
+                write_separated(f, elements, ", ")?;
+                if elements.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            TypeKind::Reference(inner) => write!(f, "&{}", inner),
+            TypeKind::Function(params, return_type) => {
+                write!(f, "fn(")?;
+                write_separated(f, params, ", ")?;
+                write!(f, ") -> {}", return_type)
+            }
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Prints the syntax tree back out as source, in the canonical layout used by `fmt`.
+//!
+//! Brackets are only written where they're needed to keep the meaning of an expression, using the same binding powers
+//! as the parser.
+
+use crate::ast::{Block, Expr, ExprKind, Function, Ident, If, ItemKind, Module, Stmt, StmtKind};
+use crate::token::{Inner, Token};
+
+const INDENT: &str = "    ";
+
+/// Formats `module` as source, ending with a newline unless it's empty.
+pub fn format(module: &Module) -> String {
+    let mut formatter = Formatter {
+        out: String::new(),
+        depth: 0,
+    };
+
+    for (index, stmt) in module.stmts.iter().enumerate() {
+        // Functions are kept apart from their neighbours at the top level
+        if index > 0 && (is_function(stmt) || is_function(&module.stmts[index - 1])) {
+            formatter.out.push('\n');
+        }
+        formatter.stmt(stmt);
+    }
+
+    formatter.out
+}
+
+fn is_function(stmt: &Stmt) -> bool {
+    matches!(stmt.kind, StmtKind::Item(_))
+}
+
+/// How tightly `expr` binds to an operator on its left, if it starts with a bare operand.
+fn left_power(expr: &Expr) -> Option<u8> {
+    match &expr.kind {
+        ExprKind::Binary(op, _, _) => Some(Token::from(0, &op.to_string()).precedence().0),
+        _ => None,
+    }
+}
+
+/// How tightly `expr` binds to an operator on its right, if it ends with a bare operand.
+fn right_power(expr: &Expr) -> Option<u8> {
+    match &expr.kind {
+        ExprKind::Binary(op, _, _) => Some(Token::from(0, &op.to_string()).precedence().1),
+        ExprKind::Unary(..) => Some(prefix_power()),
+        _ => None,
+    }
+}
+
+fn prefix_power() -> u8 {
+    Token::Negate(Inner::new(0, "-")).precedence().1
+}
+
+struct Formatter {
+    out: String,
+    depth: usize,
+}
+
+impl Formatter {
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        self.indent();
+        self.stmt_inline(stmt);
+        self.out.push('\n');
+    }
+
+    /// Writes a statement from the current position, without indenting it or ending the line.
+    fn stmt_inline(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Item(item) => match &item.kind {
+                ItemKind::Function(function) => self.function(function),
+            },
+            StmtKind::Variable { name, ty, value } => {
+                self.out.push_str("var ");
+                self.out.push_str(name.name);
+                if let Some(ty) = ty {
+                    self.out.push_str(&format!(": {ty}"));
+                }
+                if let Some(value) = value {
+                    self.out.push_str(" = ");
+                    self.expr(value);
+                }
+                self.out.push(';');
+            }
+            StmtKind::Const { name, ty, value } => {
+                self.out.push_str("const ");
+                self.out.push_str(name.name);
+                if let Some(ty) = ty {
+                    self.out.push_str(&format!(": {ty}"));
+                }
+                self.out.push_str(" = ");
+                self.expr(value);
+                self.out.push(';');
+            }
+            StmtKind::Expr(expr) => {
+                self.expr(expr);
+                self.out.push(';');
+            }
+            StmtKind::Block(block) => self.block(block),
+            StmtKind::If(if_stmt) => self.if_stmt(if_stmt),
+            StmtKind::While { condition, body } => {
+                self.out.push_str("while ");
+                self.expr(condition);
+                self.out.push(' ');
+                self.block(body);
+            }
+            // Never formatted, as `fmt` refuses source with errors
+            StmtKind::Error => {}
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        self.out.push_str("fn ");
+        self.out.push_str(function.name.name);
+        self.out.push('(');
+        for (index, param) in function.params.iter().enumerate() {
+            if index > 0 {
+                self.out.push_str(", ");
+            }
+            self.out
+                .push_str(&format!("{}: {}", param.name.name, param.ty));
+        }
+        self.out.push(')');
+        if let Some(return_type) = &function.return_type {
+            self.out.push_str(&format!(": {return_type}"));
+        }
+        self.out.push(' ');
+        self.block(&function.body);
+    }
+
+    fn if_stmt(&mut self, if_stmt: &If) {
+        self.out.push_str("if ");
+        self.expr(&if_stmt.condition);
+        self.out.push(' ');
+        self.block(&if_stmt.then_block);
+        if let Some(else_branch) = &if_stmt.else_branch {
+            self.out.push_str(" else ");
+            self.stmt_inline(else_branch);
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        if block.stmts.is_empty() && block.tail.is_none() {
+            self.out.push_str("{}");
+            return;
+        }
+
+        self.out.push_str("{\n");
+        self.depth += 1;
+        for stmt in &block.stmts {
+            self.stmt(stmt);
+        }
+        if let Some(tail) = &block.tail {
+            self.indent();
+            self.expr(tail);
+            self.out.push('\n');
+        }
+        self.depth -= 1;
+        self.indent();
+        self.out.push('}');
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Identifier(Ident { name, .. }) | ExprKind::Integer(name) => {
+                self.out.push_str(name)
+            }
+            ExprKind::Boolean(value) => self.out.push_str(if *value { "true" } else { "false" }),
+            ExprKind::Unary(op, operand) => {
+                self.out.push_str(op.symbol());
+
+                // Only an operator binding more tightly than the prefix can be left unbracketed
+                let bare = left_power(operand).is_none_or(|left| left > prefix_power());
+                self.operand(operand, bare);
+            }
+            ExprKind::Binary(op, left, right) => {
+                let (op_left, op_right) = Token::from(0, &op.to_string()).precedence();
+
+                // An operand is only left unbracketed when it binds more tightly than this operator
+                let bare_left = right_power(left).is_none_or(|power| power > op_left);
+                let bare_right = left_power(right).is_none_or(|power| power > op_right);
+
+                self.operand(left, bare_left);
+                self.out.push_str(&format!(" {op} "));
+                self.operand(right, bare_right);
+            }
+            ExprKind::Call(callee, arguments) => {
+                let bare = matches!(callee.kind, ExprKind::Identifier(_) | ExprKind::Call(..));
+                self.operand(callee, bare);
+                self.out.push('(');
+                self.list(arguments);
+                self.out.push(')');
+            }
+            ExprKind::Tuple(elements) => {
+                self.out.push('(');
+                self.list(elements);
+                self.out.push(')');
+            }
+        }
+    }
+
+    fn operand(&mut self, expr: &Expr, bare: bool) {
+        if bare {
+            self.expr(expr);
+        } else {
+            self.out.push('(');
+            self.expr(expr);
+            self.out.push(')');
+        }
+    }
+
+    fn list(&mut self, exprs: &[Expr]) {
+        for (index, expr) in exprs.iter().enumerate() {
+            if index > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr);
+        }
+    }
+}
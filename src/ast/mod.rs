@@ -0,0 +1,186 @@
+//! A typed syntax tree, folded from the parser's reverse Polish output by [`builder::Builder`].
+//!
+//! Names and literals borrow from the source buffer, and every node carries the span of source it was parsed from.
+
+use miette::SourceSpan;
+
+pub mod builder;
+mod display;
+pub mod format;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module<'t> {
+    pub stmts: Vec<Stmt<'t>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ident<'t> {
+    pub name: &'t str,
+    pub span: SourceSpan,
+}
+
+// --- Items ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item<'t> {
+    pub kind: ItemKind<'t>,
+    pub span: SourceSpan,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemKind<'t> {
+    Function(Function<'t>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function<'t> {
+    pub name: Ident<'t>,
+    pub params: Vec<Param<'t>>,
+    pub return_type: Option<TypeExpr<'t>>,
+    pub body: Block<'t>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param<'t> {
+    pub name: Ident<'t>,
+    pub ty: TypeExpr<'t>,
+    pub span: SourceSpan,
+}
+
+// --- Statements ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt<'t> {
+    pub kind: StmtKind<'t>,
+    pub span: SourceSpan,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind<'t> {
+    Item(Item<'t>),
+    Variable {
+        name: Ident<'t>,
+        ty: Option<TypeExpr<'t>>,
+        value: Option<Expr<'t>>,
+    },
+    /// A name for a value known at compile time, eg. `const SIZE: i32 = 16;`
+    Const {
+        name: Ident<'t>,
+        ty: Option<TypeExpr<'t>>,
+        value: Expr<'t>,
+    },
+    Expr(Expr<'t>),
+    Block(Block<'t>),
+    If(If<'t>),
+    While {
+        condition: Expr<'t>,
+        body: Block<'t>,
+    },
+    /// A statement which failed to parse, and was skipped
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block<'t> {
+    pub stmts: Vec<Stmt<'t>>,
+    /// The final expression, when it isn't followed by a `;`
+    pub tail: Option<Box<Expr<'t>>>,
+    pub span: SourceSpan,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct If<'t> {
+    pub condition: Expr<'t>,
+    pub then_block: Block<'t>,
+    /// Either a [`StmtKind::Block`], or a [`StmtKind::If`] for `else if`
+    pub else_branch: Option<Box<Stmt<'t>>>,
+}
+
+// --- Expressions ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr<'t> {
+    pub kind: ExprKind<'t>,
+    pub span: SourceSpan,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind<'t> {
+    /// A name, with the span of the name itself, as the expression's span also covers any brackets around it
+    Identifier(Ident<'t>),
+    Integer(&'t str),
+    /// `true` or `false`, which are written as names but can't be declared
+    Boolean(bool),
+    Unary(UnaryOp, Box<Expr<'t>>),
+    Binary(BinaryOp, Box<Expr<'t>>, Box<Expr<'t>>),
+    Call(Box<Expr<'t>>, Vec<Expr<'t>>),
+    Tuple(Vec<Expr<'t>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Negate,
+    Plus,
+    Not,
+    Deref,
+    Reference,
+}
+
+impl UnaryOp {
+    /// The operator as it's written in source.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOp::Negate => "-",
+            UnaryOp::Plus => "+",
+            UnaryOp::Not => "!",
+            UnaryOp::Deref => "*",
+            UnaryOp::Reference => "&",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    ShiftLeft,
+    ShiftRight,
+    Greater,
+    Less,
+    GreaterEqual,
+    LessEqual,
+    Equal,
+    NotEqual,
+    Assign,
+    AddAssign,
+    SubtractAssign,
+    MultiplyAssign,
+}
+
+// --- Types ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeExpr<'t> {
+    pub kind: TypeKind<'t>,
+    pub span: SourceSpan,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeKind<'t> {
+    Named(&'t str),
+    Generic(Box<TypeExpr<'t>>, Vec<TypeExpr<'t>>),
+    Tuple(Vec<TypeExpr<'t>>),
+    Reference(Box<TypeExpr<'t>>),
+    Function(Vec<TypeExpr<'t>>, Box<TypeExpr<'t>>),
+}
+
+/// Returns the smallest span covering both `a` and `b`.
+pub fn join(a: SourceSpan, b: SourceSpan) -> SourceSpan {
+    let start = a.offset().min(b.offset());
+    let end = (a.offset() + a.len()).max(b.offset() + b.len());
+
+    SourceSpan::from((start, end - start))
+}
@@ -0,0 +1,531 @@
+//! Compiles a checked module to bytecode.
+//!
+//! Every expression leaves its value on the stack, and statements whose value isn't needed pop it again. The value
+//! of a block is its tail, or the value of the `if` it ends with, as it is for the interpreter.
+
+use std::collections::HashMap;
+
+use miette::SourceSpan;
+
+use crate::ast::{
+    BinaryOp, Block, Expr, ExprKind, Function, Ident, If, ItemKind, Module, Stmt, StmtKind, UnaryOp,
+};
+use crate::bytecode::{Chunk, Instruction, IntType, Program};
+use crate::sema::check::Typing;
+use crate::sema::consteval::{self, Constants, LiteralOutOfRange};
+use crate::sema::resolve::Resolution;
+use crate::sema::symbols::{SymbolId, SymbolKind};
+use crate::sema::types::Type;
+
+/// Compiles `module`, named `name` and parsed from `source`, to a program which runs it, with every constant
+/// expression replaced by its value.
+pub fn compile<'t>(
+    module: &Module<'t>,
+    resolution: &Resolution<'t>,
+    typing: &Typing,
+    constants: &Constants,
+    name: &str,
+    source: &str,
+) -> Result<Program, miette::Report> {
+    let mut compiler = Compiler {
+        resolution,
+        typing,
+        constants,
+        indices: HashMap::new(),
+        functions: Vec::new(),
+        globals: HashMap::new(),
+        slots: HashMap::new(),
+        chunk: Chunk::new("<module>", 0),
+    };
+    compiler.declare_functions(&module.stmts);
+
+    let end = SourceSpan::from((source.len(), 0));
+    compiler.stmts(&module.stmts, None, end)?;
+
+    // The program's result is the value `main` returns instead, if it's declared
+    let main = module.stmts.iter().find_map(|stmt| match &stmt.kind {
+        StmtKind::Item(item) => {
+            let ItemKind::Function(function) = &item.kind;
+            (function.name.name == "main" && function.params.is_empty()).then_some(function)
+        }
+        _ => None,
+    });
+    if let Some(main) = main.and_then(|main| resolution.declaration(main.name.span)) {
+        compiler.emit(Instruction::Pop, end);
+        compiler.emit(Instruction::Function(compiler.indices[&main]), end);
+        compiler.emit(Instruction::Call(0), end);
+    }
+    compiler.emit(Instruction::Return, end);
+
+    // The module's variables are its frame's slots, which functions reach as globals
+    compiler.globals = std::mem::take(&mut compiler.slots);
+    let mut chunks = vec![std::mem::replace(&mut compiler.chunk, Chunk::new("", 0))];
+
+    for function in compiler.functions.clone() {
+        chunks.push(compiler.function(function)?);
+    }
+
+    Ok(Program {
+        functions: chunks,
+        name: name.to_owned(),
+        source: source.to_owned(),
+    })
+}
+
+struct Compiler<'m, 'r, 't> {
+    resolution: &'r Resolution<'t>,
+    typing: &'r Typing,
+    constants: &'r Constants,
+    /// The index of each function in the program
+    indices: HashMap<SymbolId, u32>,
+    /// Every function declared in the module, in the order they're indexed, after the module's own
+    functions: Vec<&'m Function<'t>>,
+    /// The slots of the module's frame
+    globals: HashMap<SymbolId, u32>,
+    /// The slots of the frame being compiled
+    slots: HashMap<SymbolId, u32>,
+    chunk: Chunk,
+}
+
+impl<'m, 'r, 't> Compiler<'m, 'r, 't> {
+    /// Indexes every function declared in `stmts`, including those nested inside of other statements.
+    fn declare_functions(&mut self, stmts: &'m [Stmt<'t>]) {
+        for stmt in stmts {
+            match &stmt.kind {
+                StmtKind::Item(item) => {
+                    let ItemKind::Function(function) = &item.kind;
+                    if let Some(id) = self.resolution.declaration(function.name.span) {
+                        // The module's statements are the first function
+                        self.indices.insert(id, self.functions.len() as u32 + 1);
+                        self.functions.push(function);
+                    }
+                    self.declare_functions(&function.body.stmts);
+                }
+                StmtKind::Block(block) | StmtKind::While { body: block, .. } => {
+                    self.declare_functions(&block.stmts)
+                }
+                StmtKind::If(if_stmt) => {
+                    self.declare_functions(&if_stmt.then_block.stmts);
+                    if let Some(else_branch) = &if_stmt.else_branch {
+                        self.declare_functions(std::slice::from_ref(else_branch));
+                    }
+                }
+                StmtKind::Variable { .. }
+                | StmtKind::Const { .. }
+                | StmtKind::Expr(_)
+                | StmtKind::Error => {}
+            }
+        }
+    }
+
+    fn function(&mut self, function: &Function<'t>) -> Result<Chunk, miette::Report> {
+        self.chunk = Chunk::new(function.name.name, function.params.len() as u32);
+        self.slots.clear();
+        for (slot, param) in function.params.iter().enumerate() {
+            if let Some(id) = self.resolution.declaration(param.name.span) {
+                self.slots.insert(id, slot as u32);
+            }
+        }
+
+        self.block(&function.body, true)?;
+        self.emit(Instruction::Return, function.body.span);
+
+        Ok(std::mem::replace(&mut self.chunk, Chunk::new("", 0)))
+    }
+
+    fn emit(&mut self, instruction: Instruction, span: SourceSpan) -> u32 {
+        self.chunk.push(instruction, span)
+    }
+
+    /// Points the jump at `index` to the next instruction to be emitted.
+    fn patch(&mut self, index: u32) {
+        let target = self.chunk.code.len() as u32;
+        match &mut self.chunk.code[index as usize] {
+            Instruction::Jump(to) | Instruction::JumpIfFalse(to) => *to = target,
+            _ => unreachable!("patched an instruction which isn't a jump"),
+        }
+    }
+
+    /// The slot of the variable declared by the name at `span`, which is given one if it hasn't been yet.
+    fn declare(&mut self, span: SourceSpan) -> Option<u32> {
+        let id = self.resolution.declaration(span)?;
+        let next = self.chunk.slots;
+        let slot = *self.slots.entry(id).or_insert(next);
+        self.chunk.slots = self.chunk.slots.max(slot + 1);
+        Some(slot)
+    }
+
+    /// Compiles a sequence of statements sharing a scope, leaving the value of the last one, or of `tail`, on the
+    /// stack.
+    ///
+    /// `end` is the span of the end of the sequence, where an empty sequence's unit value comes from.
+    fn stmts(
+        &mut self,
+        stmts: &[Stmt<'t>],
+        tail: Option<&Expr<'t>>,
+        end: SourceSpan,
+    ) -> Result<(), miette::Report> {
+        let Some((last, rest)) = stmts.split_last() else {
+            return match tail {
+                Some(tail) => self.expr(tail),
+                None => {
+                    self.emit(Instruction::Unit, end);
+                    Ok(())
+                }
+            };
+        };
+
+        for stmt in rest {
+            self.stmt(stmt, false)?;
+        }
+        match tail {
+            Some(tail) => {
+                self.stmt(last, false)?;
+                self.expr(tail)
+            }
+            None => self.stmt(last, true),
+        }
+    }
+
+    fn block(&mut self, block: &Block<'t>, keep: bool) -> Result<(), miette::Report> {
+        let end = SourceSpan::from((block.span.offset() + block.span.len(), 0));
+        let ends_with_if = matches!(
+            block.stmts.last(),
+            Some(Stmt {
+                kind: StmtKind::If(_),
+                ..
+            })
+        );
+
+        if block.tail.is_some() || ends_with_if {
+            self.stmts(&block.stmts, block.tail.as_deref(), end)?;
+        } else {
+            for stmt in &block.stmts {
+                self.stmt(stmt, false)?;
+            }
+            self.emit(Instruction::Unit, end);
+        }
+
+        if !keep {
+            self.emit(Instruction::Pop, end);
+        }
+        Ok(())
+    }
+
+    /// Compiles a statement, leaving its value on the stack if `keep` is set.
+    fn stmt(&mut self, stmt: &Stmt<'t>, keep: bool) -> Result<(), miette::Report> {
+        match &stmt.kind {
+            // Functions are compiled separately
+            StmtKind::Item(_) | StmtKind::Error => {
+                if keep {
+                    self.emit(Instruction::Unit, stmt.span);
+                }
+                Ok(())
+            }
+            StmtKind::Variable { name, value, .. } => {
+                let slot = self.declare(name.span);
+                match (value, slot) {
+                    (Some(value), Some(slot)) => {
+                        self.expr(value)?;
+                        self.emit(Instruction::Store(slot), stmt.span);
+                    }
+                    (None, Some(slot)) => {
+                        self.emit(Instruction::Clear(slot), stmt.span);
+                    }
+                    (Some(value), None) => {
+                        self.expr(value)?;
+                        self.emit(Instruction::Pop, stmt.span);
+                    }
+                    (None, None) => {}
+                }
+                if keep {
+                    self.emit(Instruction::Unit, stmt.span);
+                }
+                Ok(())
+            }
+            StmtKind::Const { name, value, .. } => {
+                self.expr(value)?;
+                match self.declare(name.span) {
+                    Some(slot) => self.emit(Instruction::Store(slot), stmt.span),
+                    None => self.emit(Instruction::Pop, stmt.span),
+                };
+                if keep {
+                    self.emit(Instruction::Unit, stmt.span);
+                }
+                Ok(())
+            }
+            StmtKind::Expr(expr) => {
+                self.expr(expr)?;
+                if !keep {
+                    self.emit(Instruction::Pop, stmt.span);
+                }
+                Ok(())
+            }
+            StmtKind::Block(block) => self.block(block, keep),
+            StmtKind::If(if_stmt) => self.if_stmt(if_stmt, stmt.span, keep),
+            StmtKind::While { condition, body } => {
+                let start = self.chunk.code.len() as u32;
+                self.expr(condition)?;
+                let exit = self.emit(Instruction::JumpIfFalse(0), condition.span);
+                self.block(body, false)?;
+                self.emit(Instruction::Jump(start), body.span);
+                self.patch(exit);
+
+                if keep {
+                    self.emit(Instruction::Unit, stmt.span);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn if_stmt(
+        &mut self,
+        if_stmt: &If<'t>,
+        span: SourceSpan,
+        keep: bool,
+    ) -> Result<(), miette::Report> {
+        self.expr(&if_stmt.condition)?;
+        let otherwise = self.emit(Instruction::JumpIfFalse(0), if_stmt.condition.span);
+        self.block(&if_stmt.then_block, keep)?;
+        let end = self.emit(Instruction::Jump(0), span);
+
+        self.patch(otherwise);
+        match &if_stmt.else_branch {
+            Some(else_branch) => self.stmt(else_branch, keep)?,
+            None if keep => {
+                self.emit(Instruction::Unit, span);
+            }
+            None => {}
+        }
+        self.patch(end);
+        Ok(())
+    }
+
+    /// The integer type the checker gave `expr`.
+    fn int_type(&self, expr: &Expr) -> IntType {
+        // Floats can't be written yet, so arithmetic on anything other than an integer can never run
+        self.typing
+            .expr(expr)
+            .and_then(IntType::new)
+            .unwrap_or(IntType {
+                signed: true,
+                bits: 64,
+            })
+    }
+
+    fn expr(&mut self, expr: &Expr<'t>) -> Result<(), miette::Report> {
+        if let Some(value) = self.constants.expr(expr) {
+            let instruction = match value {
+                consteval::Value::Int(value) => Instruction::Int(value),
+                consteval::Value::Bool(value) => Instruction::Bool(value),
+            };
+            self.emit(instruction, expr.span);
+            return Ok(());
+        }
+
+        match &expr.kind {
+            ExprKind::Integer(literal) => self.literal(literal, false, expr)?,
+            ExprKind::Boolean(value) => {
+                self.emit(Instruction::Bool(*value), expr.span);
+            }
+            ExprKind::Identifier(_) => {
+                let instruction = self.load(expr)?;
+                self.emit(instruction, expr.span);
+            }
+            ExprKind::Unary(op, operand) => {
+                // `-128` is in range for an `i8`, even though `128` isn't
+                if let (UnaryOp::Negate, ExprKind::Integer(literal)) = (op, &operand.kind) {
+                    return self.literal(literal, true, expr);
+                }
+
+                let instruction = match op {
+                    UnaryOp::Reference => return self.place(operand),
+                    UnaryOp::Deref => Instruction::Deref,
+                    UnaryOp::Negate => Instruction::Negate(self.int_type(expr)),
+                    UnaryOp::Not if self.typing.expr(expr) == Some(&Type::Bool) => Instruction::Not,
+                    UnaryOp::Not => Instruction::Complement(self.int_type(expr)),
+                    // Already the value of the operand
+                    UnaryOp::Plus => return self.expr(operand),
+                };
+                self.expr(operand)?;
+                self.emit(instruction, expr.span);
+            }
+            ExprKind::Binary(op, left, right) => self.binary(*op, left, right, expr)?,
+            ExprKind::Call(callee, arguments) => {
+                self.expr(callee)?;
+                for argument in arguments {
+                    self.expr(argument)?;
+                }
+                self.emit(Instruction::Call(arguments.len() as u32), expr.span);
+            }
+            ExprKind::Tuple(elements) => {
+                for element in elements {
+                    self.expr(element)?;
+                }
+                self.emit(Instruction::Tuple(elements.len() as u32), expr.span);
+            }
+        }
+        Ok(())
+    }
+
+    /// The instruction which pushes the value of the name `expr`.
+    fn load(&self, expr: &Expr<'t>) -> Result<Instruction, miette::Report> {
+        let ExprKind::Identifier(Ident { name, .. }) = expr.kind else {
+            unreachable!("only names can be loaded")
+        };
+        let Some(id) = self.resolution.binding(expr) else {
+            unreachable!("`{name}` wasn't resolved, which should have been reported")
+        };
+
+        if self.resolution.symbols.symbol(id).kind == SymbolKind::Function {
+            return Ok(Instruction::Function(self.indices[&id]));
+        }
+        if let Some(&slot) = self.slots.get(&id) {
+            return Ok(Instruction::Load(slot));
+        }
+        match self.globals.get(&id) {
+            Some(&slot) => Ok(Instruction::LoadGlobal(slot)),
+            None => unreachable!(
+                "`{name}` belongs to an enclosing function, which should have been reported"
+            ),
+        }
+    }
+
+    /// Pushes a reference to what `expr` refers to, storing any value which isn't a variable as a temporary.
+    fn place(&mut self, expr: &Expr<'t>) -> Result<(), miette::Report> {
+        match &expr.kind {
+            ExprKind::Identifier(_) => {
+                let instruction = match self.load(expr)? {
+                    Instruction::Load(slot) => Instruction::Reference(slot),
+                    Instruction::LoadGlobal(slot) => Instruction::ReferenceGlobal(slot),
+                    function => {
+                        self.emit(function, expr.span);
+                        Instruction::ReferenceTemporary
+                    }
+                };
+                self.emit(instruction, expr.span);
+            }
+            ExprKind::Unary(UnaryOp::Deref, operand) => self.expr(operand)?,
+            _ => {
+                self.expr(expr)?;
+                self.emit(Instruction::ReferenceTemporary, expr.span);
+            }
+        }
+        Ok(())
+    }
+
+    fn binary(
+        &mut self,
+        op: BinaryOp,
+        left: &Expr<'t>,
+        right: &Expr<'t>,
+        expr: &Expr<'t>,
+    ) -> Result<(), miette::Report> {
+        let ty = self.int_type(left);
+        let instruction = match op {
+            BinaryOp::Add | BinaryOp::AddAssign => Instruction::Add(ty),
+            BinaryOp::Subtract | BinaryOp::SubtractAssign => Instruction::Subtract(ty),
+            BinaryOp::Multiply | BinaryOp::MultiplyAssign => Instruction::Multiply(ty),
+            BinaryOp::Divide => Instruction::Divide(ty),
+            BinaryOp::Power => Instruction::Power(ty),
+            BinaryOp::ShiftLeft => Instruction::ShiftLeft(ty),
+            BinaryOp::ShiftRight => Instruction::ShiftRight(ty),
+            BinaryOp::Greater => Instruction::Greater,
+            BinaryOp::Less => Instruction::Less,
+            BinaryOp::GreaterEqual => Instruction::GreaterEqual,
+            BinaryOp::LessEqual => Instruction::LessEqual,
+            BinaryOp::Equal => Instruction::Equal,
+            BinaryOp::NotEqual => Instruction::NotEqual,
+            BinaryOp::Assign => {
+                self.assign(left, right, None, expr.span)?;
+                return Ok(());
+            }
+        };
+
+        match op {
+            BinaryOp::AddAssign | BinaryOp::SubtractAssign | BinaryOp::MultiplyAssign => {
+                self.assign(left, right, Some(instruction), expr.span)
+            }
+            _ => {
+                self.expr(left)?;
+                self.expr(right)?;
+                self.emit(instruction, expr.span);
+                Ok(())
+            }
+        }
+    }
+
+    /// Assigns `value` to `target`, first applying `operator` to the current value of `target` and `value` for a
+    /// compound assignment, and pushes unit.
+    fn assign(
+        &mut self,
+        target: &Expr<'t>,
+        value: &Expr<'t>,
+        operator: Option<Instruction>,
+        span: SourceSpan,
+    ) -> Result<(), miette::Report> {
+        let variable = match target.kind {
+            ExprKind::Identifier(_) => match self.load(target)? {
+                Instruction::Load(slot) => Some(Instruction::Store(slot)),
+                Instruction::LoadGlobal(slot) => Some(Instruction::StoreGlobal(slot)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        match variable {
+            Some(store) => {
+                if let Some(operator) = operator {
+                    self.expr(target)?;
+                    self.expr(value)?;
+                    self.emit(operator, span);
+                } else {
+                    self.expr(value)?;
+                }
+                self.emit(store, span);
+            }
+            None => {
+                self.place(target)?;
+                if let Some(operator) = operator {
+                    self.emit(Instruction::Dup, span);
+                    self.emit(Instruction::Deref, target.span);
+                    self.expr(value)?;
+                    self.emit(operator, span);
+                } else {
+                    self.expr(value)?;
+                }
+                self.emit(Instruction::StoreDeref, span);
+            }
+        }
+
+        self.emit(Instruction::Unit, span);
+        Ok(())
+    }
+
+    fn literal(
+        &mut self,
+        literal: &str,
+        negative: bool,
+        expr: &Expr<'t>,
+    ) -> Result<(), miette::Report> {
+        let ty = self.typing.expr(expr).cloned().unwrap_or(Type::Error);
+        let value = literal
+            .parse::<i128>()
+            .ok()
+            .map(|value| if negative { -value } else { value })
+            .filter(|&value| ty.fits(value));
+
+        match value {
+            Some(value) => {
+                self.emit(Instruction::Int(value), expr.span);
+                Ok(())
+            }
+            None => Err(LiteralOutOfRange {
+                ty,
+                span: expr.span,
+            }
+            .into()),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Human readable listings of bytecode, written by `--emit=bytecode`.
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::bytecode::{Chunk, Instruction, IntType, Program};
+
+impl Display for IntType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.ty())
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Instruction::Int(value) => write!(f, "int {}", value),
+            Instruction::Bool(value) => write!(f, "bool {}", value),
+            Instruction::Unit => write!(f, "unit"),
+            Instruction::Function(index) => write!(f, "function {}", index),
+            Instruction::Tuple(count) => write!(f, "tuple {}", count),
+            Instruction::Load(slot) => write!(f, "load {}", slot),
+            Instruction::Store(slot) => write!(f, "store {}", slot),
+            Instruction::Clear(slot) => write!(f, "clear {}", slot),
+            Instruction::LoadGlobal(slot) => write!(f, "load_global {}", slot),
+            Instruction::StoreGlobal(slot) => write!(f, "store_global {}", slot),
+            Instruction::Reference(slot) => write!(f, "ref {}", slot),
+            Instruction::ReferenceGlobal(slot) => write!(f, "ref_global {}", slot),
+            Instruction::ReferenceTemporary => write!(f, "ref_temporary"),
+            Instruction::Deref => write!(f, "deref"),
+            Instruction::StoreDeref => write!(f, "store_deref"),
+            Instruction::Pop => write!(f, "pop"),
+            Instruction::Dup => write!(f, "dup"),
+            Instruction::Add(ty) => write!(f, "add {}", ty),
+            Instruction::Subtract(ty) => write!(f, "sub {}", ty),
+            Instruction::Multiply(ty) => write!(f, "mul {}", ty),
+            Instruction::Divide(ty) => write!(f, "div {}", ty),
+            Instruction::Power(ty) => write!(f, "pow {}", ty),
+            Instruction::ShiftLeft(ty) => write!(f, "shl {}", ty),
+            Instruction::ShiftRight(ty) => write!(f, "shr {}", ty),
+            Instruction::Negate(ty) => write!(f, "neg {}", ty),
+            Instruction::Complement(ty) => write!(f, "complement {}", ty),
+            Instruction::Not => write!(f, "not"),
+            Instruction::Greater => write!(f, "gt"),
+            Instruction::Less => write!(f, "lt"),
+            Instruction::GreaterEqual => write!(f, "ge"),
+            Instruction::LessEqual => write!(f, "le"),
+            Instruction::Equal => write!(f, "eq"),
+            Instruction::NotEqual => write!(f, "ne"),
+            Instruction::Jump(target) => write!(f, "jump {}", target),
+            Instruction::JumpIfFalse(target) => write!(f, "jump_if_false {}", target),
+            Instruction::Call(arguments) => write!(f, "call {}", arguments),
+            Instruction::Return => write!(f, "return"),
+        }
+    }
+}
+
+impl Display for Chunk {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} param{}, {} slot{}",
+            self.name,
+            self.params,
+            if self.params == 1 { "" } else { "s" },
+            self.slots,
+            if self.slots == 1 { "" } else { "s" },
+        )?;
+        for (index, instruction) in self.code.iter().enumerate() {
+            writeln!(f, "  {:04}  {}", index, instruction)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lists each function after the index `function` instructions refer to it by.
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (index, chunk) in self.functions.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{} {}", index, chunk)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,316 @@
+//! The `.dobc` file format, which stores a compiled [`Program`] to be run later.
+//!
+//! A file starts with the magic bytes `DOBC` and a format version, which is bumped whenever the encoding changes so
+//! that files written by another version of the compiler are rejected rather than misread. Integers are little
+//! endian, strings are a `u32` length followed by UTF-8, and each instruction is an opcode byte followed by its
+//! operand and then the span of source it was compiled from.
+//!
+//! Files are checked as they're read, so that a program read from a file never refers to a function, slot or
+//! instruction which doesn't exist.
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::bytecode::{Chunk, Instruction, IntType, Program};
+
+/// The first bytes of every bytecode file.
+pub const MAGIC: &[u8; 4] = b"DOBC";
+
+/// The version of the format written by [`Program::to_bytes`], which is the only version that can be read.
+pub const VERSION: u16 = 1;
+
+/// The extension of bytecode files.
+pub const EXTENSION: &str = "dobc";
+
+/// A file which doesn't start with [`MAGIC`]
+#[derive(Error, Diagnostic, Debug)]
+#[error("Not a bytecode file")]
+#[diagnostic(code(E0034), help("bytecode files are written by `build --output`"))]
+pub struct NotBytecode;
+
+/// A file written in a version of the format other than [`VERSION`]
+#[derive(Error, Diagnostic, Debug)]
+#[error(
+    "Bytecode format version {version} isn't supported, only version {} is",
+    VERSION
+)]
+#[diagnostic(code(E0035), help("the file has to be compiled again"))]
+pub struct UnsupportedVersion {
+    pub version: u16,
+}
+
+/// A file which ends early, has bytes left over, or refers to something which doesn't exist
+#[derive(Error, Diagnostic, Debug)]
+#[error("The bytecode file is corrupt")]
+#[diagnostic(code(E0036), help("the file has to be compiled again"))]
+pub struct Corrupt;
+
+impl Program {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer { bytes: Vec::new() };
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.u16(VERSION);
+        writer.string(&self.name);
+        writer.string(&self.source);
+
+        writer.u32(self.functions.len() as u32);
+        for chunk in &self.functions {
+            writer.string(&chunk.name);
+            writer.u32(chunk.params);
+            writer.u32(chunk.slots);
+            writer.u32(chunk.code.len() as u32);
+            for (instruction, span) in chunk.code.iter().zip(&chunk.spans) {
+                writer.instruction(*instruction);
+                writer.u32(span.offset() as u32);
+                writer.u32(span.len() as u32);
+            }
+        }
+
+        writer.bytes
+    }
+
+    /// Reads a program written by [`Program::to_bytes`], reporting a file which isn't bytecode, was written by
+    /// another version of the compiler, or is corrupt.
+    pub fn from_bytes(bytes: &[u8]) -> miette::Result<Program> {
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(NotBytecode.into());
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(UnsupportedVersion { version }.into());
+        }
+
+        let name = reader.string()?;
+        let source = reader.string()?;
+        let mut functions = Vec::new();
+        for _ in 0..reader.u32()? {
+            let mut chunk = Chunk::new(&reader.string()?, reader.u32()?);
+            chunk.slots = reader.u32()?;
+            for _ in 0..reader.u32()? {
+                let instruction = reader.instruction()?;
+                let span = SourceSpan::from((reader.u32()? as usize, reader.u32()? as usize));
+                chunk.push(instruction, span);
+            }
+            functions.push(chunk);
+        }
+        if reader.position != bytes.len() {
+            return Err(corrupt());
+        }
+
+        let program = Program {
+            functions,
+            name,
+            source,
+        };
+        program.validate()?;
+        Ok(program)
+    }
+
+    /// Checks that every index in the program refers to something which exists.
+    fn validate(&self) -> miette::Result<()> {
+        let globals = self.functions.first().ok_or_else(corrupt)?.slots;
+
+        for chunk in &self.functions {
+            // Every path through a function has to end by returning
+            if chunk.params > chunk.slots || chunk.code.last() != Some(&Instruction::Return) {
+                return Err(corrupt());
+            }
+
+            for (instruction, span) in chunk.code.iter().zip(&chunk.spans) {
+                let valid = match *instruction {
+                    Instruction::Function(index) => (index as usize) < self.functions.len(),
+                    Instruction::Load(slot)
+                    | Instruction::Store(slot)
+                    | Instruction::Clear(slot)
+                    | Instruction::Reference(slot) => slot < chunk.slots,
+                    Instruction::LoadGlobal(slot)
+                    | Instruction::StoreGlobal(slot)
+                    | Instruction::ReferenceGlobal(slot) => slot < globals,
+                    Instruction::Jump(target) | Instruction::JumpIfFalse(target) => {
+                        (target as usize) < chunk.code.len()
+                    }
+                    _ => true,
+                };
+                if !valid || span.offset() + span.len() > self.source.len() {
+                    return Err(corrupt());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn corrupt() -> miette::Report {
+    Corrupt.into()
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    fn instruction(&mut self, instruction: Instruction) {
+        let (opcode, operand) = match instruction {
+            Instruction::Int(value) => (0, Operand::Int(value)),
+            Instruction::Bool(value) => (1, Operand::U32(value.into())),
+            Instruction::Unit => (2, Operand::None),
+            Instruction::Function(index) => (3, Operand::U32(index)),
+            Instruction::Tuple(count) => (4, Operand::U32(count)),
+            Instruction::Load(slot) => (5, Operand::U32(slot)),
+            Instruction::Store(slot) => (6, Operand::U32(slot)),
+            Instruction::Clear(slot) => (7, Operand::U32(slot)),
+            Instruction::LoadGlobal(slot) => (8, Operand::U32(slot)),
+            Instruction::StoreGlobal(slot) => (9, Operand::U32(slot)),
+            Instruction::Reference(slot) => (10, Operand::U32(slot)),
+            Instruction::ReferenceGlobal(slot) => (11, Operand::U32(slot)),
+            Instruction::ReferenceTemporary => (12, Operand::None),
+            Instruction::Deref => (13, Operand::None),
+            Instruction::StoreDeref => (14, Operand::None),
+            Instruction::Pop => (15, Operand::None),
+            Instruction::Dup => (16, Operand::None),
+            Instruction::Add(ty) => (17, Operand::Type(ty)),
+            Instruction::Subtract(ty) => (18, Operand::Type(ty)),
+            Instruction::Multiply(ty) => (19, Operand::Type(ty)),
+            Instruction::Divide(ty) => (20, Operand::Type(ty)),
+            Instruction::Power(ty) => (21, Operand::Type(ty)),
+            Instruction::ShiftLeft(ty) => (22, Operand::Type(ty)),
+            Instruction::ShiftRight(ty) => (23, Operand::Type(ty)),
+            Instruction::Negate(ty) => (24, Operand::Type(ty)),
+            Instruction::Complement(ty) => (25, Operand::Type(ty)),
+            Instruction::Not => (26, Operand::None),
+            Instruction::Greater => (27, Operand::None),
+            Instruction::Less => (28, Operand::None),
+            Instruction::GreaterEqual => (29, Operand::None),
+            Instruction::LessEqual => (30, Operand::None),
+            Instruction::Equal => (31, Operand::None),
+            Instruction::NotEqual => (32, Operand::None),
+            Instruction::Jump(target) => (33, Operand::U32(target)),
+            Instruction::JumpIfFalse(target) => (34, Operand::U32(target)),
+            Instruction::Call(arguments) => (35, Operand::U32(arguments)),
+            Instruction::Return => (36, Operand::None),
+        };
+
+        self.bytes.push(opcode);
+        match operand {
+            Operand::None => {}
+            Operand::U32(value) => self.u32(value),
+            Operand::Int(value) => self.bytes.extend_from_slice(&value.to_le_bytes()),
+            Operand::Type(ty) => self.bytes.push(ty.bits | if ty.signed { 0x80 } else { 0 }),
+        }
+    }
+}
+
+/// The operand following an instruction's opcode.
+enum Operand {
+    None,
+    U32(u32),
+    Int(i128),
+    Type(IntType),
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    position: usize,
+}
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, count: usize) -> miette::Result<&'b [u8]> {
+        let end = self.position.checked_add(count).ok_or_else(corrupt)?;
+        let bytes = self.bytes.get(self.position..end).ok_or_else(corrupt)?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> miette::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> miette::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> miette::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> miette::Result<String> {
+        let length = self.u32()? as usize;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| corrupt())
+    }
+
+    fn int_type(&mut self) -> miette::Result<IntType> {
+        let byte = self.u8()?;
+        let ty = IntType {
+            signed: byte & 0x80 != 0,
+            bits: byte & 0x7f,
+        };
+        match ty.bits {
+            8 | 16 | 32 | 64 => Ok(ty),
+            _ => Err(corrupt()),
+        }
+    }
+
+    fn instruction(&mut self) -> miette::Result<Instruction> {
+        let instruction = match self.u8()? {
+            0 => Instruction::Int(i128::from_le_bytes(self.take(16)?.try_into().unwrap())),
+            1 => match self.u32()? {
+                0 => Instruction::Bool(false),
+                1 => Instruction::Bool(true),
+                _ => return Err(corrupt()),
+            },
+            2 => Instruction::Unit,
+            3 => Instruction::Function(self.u32()?),
+            4 => Instruction::Tuple(self.u32()?),
+            5 => Instruction::Load(self.u32()?),
+            6 => Instruction::Store(self.u32()?),
+            7 => Instruction::Clear(self.u32()?),
+            8 => Instruction::LoadGlobal(self.u32()?),
+            9 => Instruction::StoreGlobal(self.u32()?),
+            10 => Instruction::Reference(self.u32()?),
+            11 => Instruction::ReferenceGlobal(self.u32()?),
+            12 => Instruction::ReferenceTemporary,
+            13 => Instruction::Deref,
+            14 => Instruction::StoreDeref,
+            15 => Instruction::Pop,
+            16 => Instruction::Dup,
+            17 => Instruction::Add(self.int_type()?),
+            18 => Instruction::Subtract(self.int_type()?),
+            19 => Instruction::Multiply(self.int_type()?),
+            20 => Instruction::Divide(self.int_type()?),
+            21 => Instruction::Power(self.int_type()?),
+            22 => Instruction::ShiftLeft(self.int_type()?),
+            23 => Instruction::ShiftRight(self.int_type()?),
+            24 => Instruction::Negate(self.int_type()?),
+            25 => Instruction::Complement(self.int_type()?),
+            26 => Instruction::Not,
+            27 => Instruction::Greater,
+            28 => Instruction::Less,
+            29 => Instruction::GreaterEqual,
+            30 => Instruction::LessEqual,
+            31 => Instruction::Equal,
+            32 => Instruction::NotEqual,
+            33 => Instruction::Jump(self.u32()?),
+            34 => Instruction::JumpIfFalse(self.u32()?),
+            35 => Instruction::Call(self.u32()?),
+            36 => Instruction::Return,
+            _ => return Err(corrupt()),
+        };
+        Ok(instruction)
+    }
+}
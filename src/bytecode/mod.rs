@@ -0,0 +1,148 @@
+//! A compact stack based bytecode, compiled from a checked module by [`compile::compile`] and run by [`vm::Vm`].
+//!
+//! Each function is compiled to a [`Chunk`] of instructions, which take their operands from the top of the stack and
+//! push their result in their place. Variables live in numbered slots of the frame of the call they belong to, and
+//! variables declared at the top of the module are the slots of the first frame, which runs the module's statements.
+//!
+//! A [`Program`] can be written to a `.dobc` file with [`Program::to_bytes`] and read back with
+//! [`Program::from_bytes`], so that it can be run later without compiling it again. The program's source is kept
+//! with it, so that errors while running it can still point at the code which caused them.
+
+use miette::SourceSpan;
+
+use crate::sema::types::Type;
+
+pub mod compile;
+mod disassemble;
+pub mod file;
+pub mod vm;
+
+/// An integer type, as the arithmetic instructions which check for overflow need to know it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntType {
+    pub signed: bool,
+    pub bits: u8,
+}
+
+impl IntType {
+    pub fn new(ty: &Type) -> Option<Self> {
+        match *ty {
+            Type::Int { signed, bits } => Some(IntType { signed, bits }),
+            _ => None,
+        }
+    }
+
+    pub fn ty(&self) -> Type {
+        Type::Int {
+            signed: self.signed,
+            bits: self.bits,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// Pushes an integer
+    Int(i128),
+    Bool(bool),
+    /// Pushes the unit value `()`
+    Unit,
+    /// Pushes a function, indexing [`Program::functions`]
+    Function(u32),
+    /// Pops the given number of values, and pushes them as a tuple
+    Tuple(u32),
+
+    /// Pushes the value of a slot in the current frame
+    Load(u32),
+    /// Pops a value into a slot in the current frame
+    Store(u32),
+    /// Leaves a slot in the current frame without a value, as a variable declared without one is
+    Clear(u32),
+    /// Pushes the value of a slot in the module's frame
+    LoadGlobal(u32),
+    /// Pops a value into a slot in the module's frame
+    StoreGlobal(u32),
+    /// Pushes a reference to a slot in the current frame
+    Reference(u32),
+    /// Pushes a reference to a slot in the module's frame
+    ReferenceGlobal(u32),
+    /// Pops a value, and pushes a reference to a copy of it which lives as long as the program
+    ReferenceTemporary,
+    /// Pops a reference, and pushes the value it refers to
+    Deref,
+    /// Pops a value and then a reference, and stores the value where the reference refers to
+    StoreDeref,
+
+    Pop,
+    Dup,
+
+    Add(IntType),
+    Subtract(IntType),
+    Multiply(IntType),
+    Divide(IntType),
+    Power(IntType),
+    ShiftLeft(IntType),
+    ShiftRight(IntType),
+    Negate(IntType),
+    /// Flips every bit of an integer
+    Complement(IntType),
+    /// Negates a `bool`
+    Not,
+
+    Greater,
+    Less,
+    GreaterEqual,
+    LessEqual,
+    Equal,
+    NotEqual,
+
+    /// Continues from the instruction at the given index
+    Jump(u32),
+    /// Pops a `bool`, and continues from the instruction at the given index if it's `false`
+    JumpIfFalse(u32),
+    /// Pops the given number of arguments and then a function, and calls it
+    Call(u32),
+    /// Returns the value on top of the stack from the current call
+    Return,
+}
+
+/// A compiled function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub name: String,
+    /// The parameters are the first slots of the frame
+    pub params: u32,
+    /// The number of slots in the frame, including parameters
+    pub slots: u32,
+    pub code: Vec<Instruction>,
+    /// The source each instruction was compiled from
+    pub spans: Vec<SourceSpan>,
+}
+
+impl Chunk {
+    pub fn new(name: &str, params: u32) -> Self {
+        Chunk {
+            name: name.to_owned(),
+            params,
+            slots: params,
+            code: Vec::new(),
+            spans: Vec::new(),
+        }
+    }
+
+    /// Appends `instruction`, returning its index.
+    pub fn push(&mut self, instruction: Instruction, span: SourceSpan) -> u32 {
+        self.code.push(instruction);
+        self.spans.push(span);
+        (self.code.len() - 1) as u32
+    }
+}
+
+/// A compiled module, whose first function runs the module's statements.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub functions: Vec<Chunk>,
+    /// The name of the file the program was compiled from
+    pub name: String,
+    pub source: String,
+}
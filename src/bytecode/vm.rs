@@ -0,0 +1,370 @@
+//! The virtual machine which runs bytecode.
+//!
+//! Calls don't recurse in the host, so the depth a program can reach is only bounded by the recursion limit. Errors
+//! are the same as the interpreter's, pointing at the source each instruction was compiled from.
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::ast::BinaryOp;
+use crate::bytecode::{Instruction, IntType, Program};
+use crate::interpret::{
+    self, DanglingReference, RecursionLimit, Uninitialised, DEFAULT_RECURSION_LIMIT,
+};
+
+/// A value on the stack or in a slot, the same as the interpreter's, but with functions indexing
+/// [`Program::functions`].
+pub type Value = interpret::Value<u32, Place>;
+
+/// An instruction given operands it can't use, or which would leave the function
+#[derive(Error, Diagnostic, Debug)]
+#[error("Malformed bytecode")]
+#[diagnostic(code(E0037))]
+pub struct Malformed;
+
+/// Where a reference points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Place {
+    /// Indexes the slots of every frame, laid end to end, belonging to the frame at `frame` while it has the same
+    /// generation
+    Slot {
+        frame: usize,
+        generation: usize,
+        slot: usize,
+    },
+    /// A value which isn't stored in a slot, eg. `&(1 + 2)`
+    Temporary(usize),
+}
+
+/// A call in progress.
+struct Frame {
+    function: usize,
+    /// The next instruction to run
+    ip: usize,
+    /// Where the frame's slots start
+    base: usize,
+    /// Tells this call apart from earlier calls which used the same frame, so references to their slots are caught
+    generation: usize,
+}
+
+pub struct Vm<'p> {
+    program: &'p Program,
+    stack: Vec<Value>,
+    /// The slots of every frame, where a slot which hasn't been given a value is `None`
+    slots: Vec<Option<Value>>,
+    frames: Vec<Frame>,
+    temporaries: Vec<Value>,
+    /// How many frames have been entered
+    generations: usize,
+    recursion_limit: usize,
+}
+
+impl<'p> Vm<'p> {
+    pub fn new(program: &'p Program) -> Self {
+        Vm {
+            program,
+            stack: Vec::new(),
+            slots: Vec::new(),
+            frames: Vec::new(),
+            temporaries: Vec::new(),
+            generations: 0,
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+        }
+    }
+
+    /// Sets how many calls can be in progress at once.
+    pub fn with_recursion_limit(mut self, limit: usize) -> Self {
+        self.recursion_limit = limit;
+        self
+    }
+
+    /// Runs the program, returning its result, or the error which stopped it.
+    pub fn run(&mut self) -> Result<Value, miette::Report> {
+        self.enter(0, Vec::new());
+
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let chunk = &self.program.functions[frame.function];
+            let (Some(&instruction), Some(&span)) =
+                (chunk.code.get(frame.ip), chunk.spans.get(frame.ip))
+            else {
+                return Err(malformed());
+            };
+            frame.ip += 1;
+
+            match instruction {
+                Instruction::Int(value) => self.stack.push(Value::Int(value)),
+                Instruction::Bool(value) => self.stack.push(Value::Bool(value)),
+                Instruction::Unit => self.stack.push(Value::Tuple(Vec::new())),
+                Instruction::Function(index) => self.stack.push(Value::Function(index)),
+                Instruction::Tuple(count) => {
+                    let elements = self.pop_many(count as usize)?;
+                    self.stack.push(Value::Tuple(elements));
+                }
+
+                Instruction::Load(slot) => self.load(self.local(slot), span)?,
+                Instruction::LoadGlobal(slot) => self.load(self.global(slot), span)?,
+                Instruction::Store(slot) => {
+                    let value = self.pop()?;
+                    self.store(self.local(slot), value, span)?;
+                }
+                Instruction::StoreGlobal(slot) => {
+                    let value = self.pop()?;
+                    self.store(self.global(slot), value, span)?;
+                }
+                Instruction::Clear(slot) => {
+                    let base = self.frames.last().unwrap().base;
+                    self.slots[base + slot as usize] = None;
+                }
+                Instruction::Reference(slot) => {
+                    let place = self.local(slot);
+                    self.stack.push(Value::Reference(place));
+                }
+                Instruction::ReferenceGlobal(slot) => {
+                    let place = self.global(slot);
+                    self.stack.push(Value::Reference(place));
+                }
+                Instruction::ReferenceTemporary => {
+                    let value = self.pop()?;
+                    self.temporaries.push(value);
+                    let place = Place::Temporary(self.temporaries.len() - 1);
+                    self.stack.push(Value::Reference(place));
+                }
+                Instruction::Deref => {
+                    let place = self.pop_reference()?;
+                    self.load(place, span)?;
+                }
+                Instruction::StoreDeref => {
+                    let value = self.pop()?;
+                    let place = self.pop_reference()?;
+                    self.store(place, value, span)?;
+                }
+
+                Instruction::Pop => {
+                    self.pop()?;
+                }
+                Instruction::Dup => {
+                    let value = self.stack.last().ok_or_else(malformed)?.clone();
+                    self.stack.push(value);
+                }
+
+                Instruction::Add(ty) => self.arithmetic(BinaryOp::Add, ty, span)?,
+                Instruction::Subtract(ty) => self.arithmetic(BinaryOp::Subtract, ty, span)?,
+                Instruction::Multiply(ty) => self.arithmetic(BinaryOp::Multiply, ty, span)?,
+                Instruction::Divide(ty) => self.arithmetic(BinaryOp::Divide, ty, span)?,
+                Instruction::Power(ty) => self.arithmetic(BinaryOp::Power, ty, span)?,
+                Instruction::ShiftLeft(ty) => self.arithmetic(BinaryOp::ShiftLeft, ty, span)?,
+                Instruction::ShiftRight(ty) => self.arithmetic(BinaryOp::ShiftRight, ty, span)?,
+                Instruction::Negate(ty) => {
+                    let value = self.pop_int()?;
+                    let result = interpret::checked(value.checked_neg(), &ty.ty(), span)?;
+                    self.stack.push(Value::Int(result));
+                }
+                // Flips every bit within the width of the type, which is `-x - 1` when signed
+                Instruction::Complement(ty) => {
+                    let value = self.pop_int()?;
+                    let result = match ty.ty().range() {
+                        Some(range) if !ty.signed => range.end() - value,
+                        _ => -value - 1,
+                    };
+                    self.stack.push(Value::Int(result));
+                }
+                Instruction::Not => {
+                    let Value::Bool(value) = self.pop()? else {
+                        return Err(malformed());
+                    };
+                    self.stack.push(Value::Bool(!value));
+                }
+
+                Instruction::Equal | Instruction::NotEqual => {
+                    let right = self.pop()?;
+                    let left = self.pop()?;
+                    let equal = left == right;
+                    self.stack
+                        .push(Value::Bool(equal == (instruction == Instruction::Equal)));
+                }
+                Instruction::Greater
+                | Instruction::Less
+                | Instruction::GreaterEqual
+                | Instruction::LessEqual => {
+                    let right = self.pop_int()?;
+                    let left = self.pop_int()?;
+                    let result = match instruction {
+                        Instruction::Greater => left > right,
+                        Instruction::Less => left < right,
+                        Instruction::GreaterEqual => left >= right,
+                        _ => left <= right,
+                    };
+                    self.stack.push(Value::Bool(result));
+                }
+
+                Instruction::Jump(target) => self.frames.last_mut().unwrap().ip = target as usize,
+                Instruction::JumpIfFalse(target) => {
+                    let Value::Bool(condition) = self.pop()? else {
+                        return Err(malformed());
+                    };
+                    if !condition {
+                        self.frames.last_mut().unwrap().ip = target as usize;
+                    }
+                }
+                Instruction::Call(count) => {
+                    let arguments = self.pop_many(count as usize)?;
+                    let Value::Function(function) = self.pop()? else {
+                        return Err(malformed());
+                    };
+                    let function = function as usize;
+                    if arguments.len() != self.program.functions[function].params as usize {
+                        return Err(malformed());
+                    }
+
+                    // The first frame is the module's, not a call's
+                    if self.frames.len() > self.recursion_limit {
+                        return Err(RecursionLimit {
+                            limit: self.recursion_limit,
+                            span,
+                        }
+                        .into());
+                    }
+                    self.enter(function, arguments);
+                }
+                Instruction::Return => {
+                    let value = self.pop()?;
+                    let frame = self.frames.pop().unwrap();
+                    self.slots.truncate(frame.base);
+                    if self.frames.is_empty() {
+                        return Ok(value);
+                    }
+                    self.stack.push(value);
+                }
+            }
+        }
+    }
+
+    /// Pushes a frame calling `function`, with its parameters given `arguments`.
+    fn enter(&mut self, function: usize, arguments: Vec<Value>) {
+        let base = self.slots.len();
+        let slots = self.program.functions[function].slots as usize;
+        self.slots.extend(arguments.into_iter().map(Some));
+        self.slots.resize(base + slots, None);
+        self.frames.push(Frame {
+            function,
+            ip: 0,
+            base,
+            generation: self.generations,
+        });
+        self.generations += 1;
+    }
+
+    /// The place of `slot` in the current frame.
+    fn local(&self, slot: u32) -> Place {
+        let frame = self.frames.len() - 1;
+        Place::Slot {
+            frame,
+            generation: self.frames[frame].generation,
+            slot: self.frames[frame].base + slot as usize,
+        }
+    }
+
+    /// The place of `slot` in the module's frame, which is the first.
+    fn global(&self, slot: u32) -> Place {
+        Place::Slot {
+            frame: 0,
+            generation: self.frames[0].generation,
+            slot: slot as usize,
+        }
+    }
+
+    /// Reports `place` if it's a slot of a call which has returned, which a later call may have reused.
+    fn live(&self, place: Place, span: SourceSpan) -> Result<(), miette::Report> {
+        if let Place::Slot {
+            frame, generation, ..
+        } = place
+        {
+            let frame = self.frames.get(frame);
+            if frame.is_none_or(|frame| frame.generation != generation) {
+                return Err(DanglingReference { span }.into());
+            }
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, place: Place, span: SourceSpan) -> Result<(), miette::Report> {
+        self.live(place, span)?;
+        let value = match place {
+            Place::Slot { slot, .. } => self.slots.get(slot).cloned().flatten(),
+            Place::Temporary(index) => self.temporaries.get(index).cloned(),
+        };
+        let value = value.ok_or_else(|| {
+            let name = self
+                .program
+                .source
+                .get(span.offset()..span.offset() + span.len());
+            Uninitialised {
+                name: name.unwrap_or_default().to_owned(),
+                span,
+            }
+        })?;
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn store(
+        &mut self,
+        place: Place,
+        value: Value,
+        span: SourceSpan,
+    ) -> Result<(), miette::Report> {
+        self.live(place, span)?;
+        match place {
+            Place::Slot { slot, .. } => {
+                if let Some(slot) = self.slots.get_mut(slot) {
+                    *slot = Some(value);
+                }
+            }
+            Place::Temporary(index) => self.temporaries[index] = value,
+        }
+        Ok(())
+    }
+
+    fn arithmetic(
+        &mut self,
+        op: BinaryOp,
+        ty: IntType,
+        span: SourceSpan,
+    ) -> Result<(), miette::Report> {
+        let right = self.pop_int()?;
+        let left = self.pop_int()?;
+        let result = interpret::integer(op, left, right, &ty.ty(), span)?;
+        self.stack.push(Value::Int(result));
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Value, miette::Report> {
+        self.stack.pop().ok_or_else(malformed)
+    }
+
+    fn pop_many(&mut self, count: usize) -> Result<Vec<Value>, miette::Report> {
+        let start = self.stack.len().checked_sub(count).ok_or_else(malformed)?;
+        Ok(self.stack.split_off(start))
+    }
+
+    fn pop_int(&mut self) -> Result<i128, miette::Report> {
+        match self.pop()? {
+            Value::Int(value) => Ok(value),
+            _ => Err(malformed()),
+        }
+    }
+
+    fn pop_reference(&mut self) -> Result<Place, miette::Report> {
+        match self.pop()? {
+            Value::Reference(place) => Ok(place),
+            _ => Err(malformed()),
+        }
+    }
+}
+
+/// Bytecode compiled from a checked module never does anything invalid, so this is a bug rather than a user error,
+/// unless the bytecode was read from a file which was tampered with.
+fn malformed() -> miette::Report {
+    Malformed.into()
+}
@@ -0,0 +1,69 @@
+//! The registry of error codes, mapping each code given to a diagnostic to a long-form explanation.
+//!
+//! Explanations live in a markdown file named after their code, and are shown by `explain <code>`.
+
+pub struct ErrorCode {
+    pub code: &'static str,
+    /// A short, stable name for the error, eg. `unexpected_token`
+    pub name: &'static str,
+    pub explanation: &'static str,
+}
+
+macro_rules! registry {
+    ($($code:ident: $name:literal),* $(,)?) => {
+        pub const REGISTRY: &[ErrorCode] = &[
+            $(ErrorCode {
+                code: stringify!($code),
+                name: $name,
+                explanation: include_str!(concat!(stringify!($code), ".md")),
+            }),*
+        ];
+    };
+}
+
+registry! {
+    E0001: "unexpected_token",
+    E0002: "unmatched_delimiter",
+    E0003: "unclosed_delimiter",
+    E0004: "mismatched_delimiter",
+    E0005: "chained_comparison",
+    E0006: "missing_semicolon",
+    E0007: "unknown_keyword",
+    E0008: "missing_type_colon",
+    E0009: "assignment_in_condition",
+    E0010: "malformed_parser_output",
+    E0011: "undefined_name",
+    E0012: "duplicate_declaration",
+    E0013: "unknown_type",
+    E0014: "type_mismatch",
+    E0015: "invalid_operands",
+    E0016: "argument_count",
+    E0017: "not_callable",
+    E0018: "annotations_needed",
+    E0019: "overflow",
+    E0020: "not_constant",
+    E0021: "division_by_zero",
+    E0022: "arithmetic_overflow",
+    E0023: "uninitialised_variable",
+    E0024: "recursion_limit",
+    E0025: "captured_variable",
+    E0026: "literal_out_of_range",
+    E0027: "dangling_reference",
+    E0028: "unsupported_in_evaluation",
+    E0029: "unsupported_in_llvm",
+    E0030: "ir_syntax_error",
+    E0031: "invalid_ir",
+    E0032: "invalid_assignee",
+    E0033: "immutable_assignment",
+    E0034: "not_bytecode",
+    E0035: "bytecode_version",
+    E0036: "corrupt_bytecode",
+    E0037: "malformed_bytecode",
+}
+
+/// Finds the entry for `code`, ignoring case.
+pub fn lookup(code: &str) -> Option<&'static ErrorCode> {
+    REGISTRY
+        .iter()
+        .find(|entry| entry.code.eq_ignore_ascii_case(code))
+}
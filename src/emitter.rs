@@ -0,0 +1,416 @@
+//! Writes diagnostics out in a format chosen by the user, either for people to read or for tools to consume.
+
+use std::fmt::{self, Debug, Display, Formatter};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use miette::{
+    Diagnostic, GraphicalReportHandler, GraphicalTheme, LabeledSpan, NamedSource, Report, Severity,
+    SourceCode,
+};
+use serde_json::{json, Value};
+
+use crate::codes;
+use crate::suggestion::{suggestion, Applicability, Suggestion};
+
+pub const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+pub const SARIF_VERSION: &str = "2.1.0";
+
+/// How diagnostics are written out, as given by `--error-format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// Rendered with the source code and labels, for reading in a terminal
+    #[default]
+    Human,
+    /// A single `file:line:col: message` line per diagnostic, for editors and grep
+    Short,
+    /// One JSON object per line
+    Json,
+    /// A single SARIF 2.1.0 log, written once every diagnostic has been emitted
+    Sarif,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "human" => Ok(Format::Human),
+            "short" => Ok(Format::Short),
+            "json" => Ok(Format::Json),
+            "sarif" => Ok(Format::Sarif),
+            _ => Err(format!(
+                "Unknown error format `{format}`, expected one of human, short, json or sarif"
+            )),
+        }
+    }
+}
+
+/// The file a diagnostic was reported in.
+#[derive(Debug, Clone, Copy)]
+pub struct SourceFile<'s> {
+    pub name: &'s str,
+    pub source: &'s str,
+}
+
+impl SourceFile<'_> {
+    /// The 1-based line and column of `offset`, with the column counted in characters.
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let before = &self.source[..offset];
+
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        let column = before[line_start..].chars().count() + 1;
+        (line, column)
+    }
+
+    /// Like [`SourceFile::position`], but with the column counted in UTF-16 code units, as SARIF expects.
+    pub fn utf16_position(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let before = &self.source[..offset];
+
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        let column = before[line_start..].encode_utf16().count() + 1;
+        (line, column)
+    }
+
+    /// The number of UTF-16 code units before the byte `offset`.
+    pub fn utf16_offset(&self, offset: usize) -> usize {
+        self.source[..offset.min(self.source.len())]
+            .encode_utf16()
+            .count()
+    }
+}
+
+/// Writes each diagnostic given to [`Emitter::emit`] to `out`, in the chosen [`Format`].
+pub struct Emitter<W: Write> {
+    format: Format,
+    out: W,
+    /// How [`Format::Human`] diagnostics are drawn
+    theme: GraphicalTheme,
+    /// SARIF results, held until [`Emitter::finish`] as the log is a single document
+    results: Vec<Value>,
+}
+
+impl<W: Write> Emitter<W> {
+    pub fn new(format: Format, out: W) -> Self {
+        Emitter {
+            format,
+            out,
+            theme: GraphicalTheme::default(),
+            results: Vec::new(),
+        }
+    }
+
+    /// Forces human readable diagnostics to be drawn with or without colour, rather than detecting whether the terminal
+    /// supports it.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.theme = if color {
+            GraphicalTheme::unicode()
+        } else {
+            GraphicalTheme::unicode_nocolor()
+        };
+        self
+    }
+
+    pub fn emit(&mut self, file: &SourceFile, report: &Report) -> io::Result<()> {
+        match self.format {
+            Format::Human => self.human(file, report),
+            Format::Short => self.short(file, report),
+            Format::Json => {
+                let object = json_diagnostic(file, report);
+                writeln!(self.out, "{object}")
+            }
+            Format::Sarif => {
+                self.results.push(sarif_result(file, report));
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes anything held back until every diagnostic was emitted, and flushes the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.format == Format::Sarif {
+            let results = std::mem::take(&mut self.results);
+            writeln!(self.out, "{}", sarif_log(results))?;
+        }
+
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn human(&mut self, file: &SourceFile, report: &Report) -> io::Result<()> {
+        let diagnostic = WithSource::new(report.as_ref(), file);
+
+        let mut rendered = String::new();
+        GraphicalReportHandler::new_themed(self.theme.clone())
+            .render_report(&mut rendered, &diagnostic)
+            .map_err(io::Error::other)?;
+        writeln!(self.out, "{rendered}")
+    }
+
+    fn short(&mut self, file: &SourceFile, report: &Report) -> io::Result<()> {
+        let (line, column) = primary_label(report.as_ref())
+            .map(|label| file.position(label.offset()))
+            .unwrap_or((1, 1));
+
+        let severity = severity(report.as_ref());
+        match report.code() {
+            Some(code) => writeln!(
+                self.out,
+                "{}:{line}:{column}: {severity}[{code}]: {report}",
+                file.name
+            ),
+            None => writeln!(
+                self.out,
+                "{}:{line}:{column}: {severity}: {report}",
+                file.name
+            ),
+        }
+    }
+}
+
+/// Gives a diagnostic the source code of `file` to render, unless it already has its own.
+struct WithSource<'d> {
+    diagnostic: &'d dyn Diagnostic,
+    source: NamedSource<String>,
+}
+
+impl<'d> WithSource<'d> {
+    fn new(diagnostic: &'d dyn Diagnostic, file: &SourceFile) -> Self {
+        WithSource {
+            diagnostic,
+            source: NamedSource::new(file.name, file.source.to_owned()),
+        }
+    }
+}
+
+impl Display for WithSource<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self.diagnostic, f)
+    }
+}
+
+impl Debug for WithSource<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Debug::fmt(self.diagnostic, f)
+    }
+}
+
+impl std::error::Error for WithSource<'_> {}
+
+impl Diagnostic for WithSource<'_> {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.diagnostic.code()
+    }
+
+    fn severity(&self) -> Option<Severity> {
+        self.diagnostic.severity()
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.diagnostic.help()
+    }
+
+    fn url<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.diagnostic.url()
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.diagnostic.source_code().or(Some(&self.source))
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        self.diagnostic.labels()
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        self.diagnostic.related()
+    }
+}
+
+fn severity(diagnostic: &dyn Diagnostic) -> &'static str {
+    match diagnostic.severity().unwrap_or(Severity::Error) {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Advice => "advice",
+    }
+}
+
+fn labels(diagnostic: &dyn Diagnostic) -> Vec<LabeledSpan> {
+    diagnostic
+        .labels()
+        .map(|labels| labels.collect())
+        .unwrap_or_default()
+}
+
+/// The label marked primary, or the first label if none are.
+fn primary_label(diagnostic: &dyn Diagnostic) -> Option<LabeledSpan> {
+    let labels = labels(diagnostic);
+    labels
+        .iter()
+        .find(|label| label.primary())
+        .or(labels.first())
+        .cloned()
+}
+
+fn applicability(suggestion: &Suggestion) -> &'static str {
+    match suggestion.applicability {
+        Applicability::MachineApplicable => "machine-applicable",
+        Applicability::MaybeIncorrect => "maybe-incorrect",
+    }
+}
+
+/// A diagnostic as a single JSON object, with byte offsets and 1-based positions for each span.
+pub fn json_diagnostic(file: &SourceFile, report: &Report) -> Value {
+    let diagnostic: &dyn Diagnostic = report.as_ref();
+    let primary = primary_label(diagnostic);
+    let (line, column) = primary
+        .as_ref()
+        .map(|label| file.position(label.offset()))
+        .unwrap_or((1, 1));
+
+    let labels: Vec<Value> = labels(diagnostic)
+        .iter()
+        .map(|label| {
+            let (line, column) = file.position(label.offset());
+            json!({
+                "start": label.offset(),
+                "end": label.offset() + label.len(),
+                "line": line,
+                "column": column,
+                "message": label.label(),
+            })
+        })
+        .collect();
+
+    let suggestions: Vec<Value> = suggestion(report)
+        .map(|suggestion| {
+            json!({
+                "start": suggestion.span.offset(),
+                "end": suggestion.span.offset() + suggestion.span.len(),
+                "replacement": suggestion.replacement,
+                "message": suggestion.message,
+                "applicability": applicability(suggestion),
+            })
+        })
+        .into_iter()
+        .collect();
+
+    json!({
+        "file": file.name,
+        "code": diagnostic.code().map(|code| code.to_string()),
+        "severity": severity(diagnostic),
+        "message": report.to_string(),
+        "help": diagnostic.help().map(|help| help.to_string()),
+        "line": line,
+        "column": column,
+        "labels": labels,
+        "suggestions": suggestions,
+    })
+}
+
+/// A SARIF region covering `length` bytes from `offset`, with columns and offsets converted to UTF-16 code units.
+fn sarif_region(file: &SourceFile, offset: usize, length: usize) -> Value {
+    let (start_line, start_column) = file.utf16_position(offset);
+    let (end_line, end_column) = file.utf16_position(offset + length);
+    let start = file.utf16_offset(offset);
+    let end = file.utf16_offset(offset + length);
+    json!({
+        "startLine": start_line,
+        "startColumn": start_column,
+        "endLine": end_line,
+        "endColumn": end_column,
+        "charOffset": start,
+        "charLength": end - start,
+    })
+}
+
+fn sarif_location(file: &SourceFile, label: &LabeledSpan) -> Value {
+    let mut location = json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": file.name },
+            "region": sarif_region(file, label.offset(), label.len()),
+        }
+    });
+    if let Some(text) = label.label() {
+        location["message"] = json!({ "text": text });
+    }
+    location
+}
+
+/// A diagnostic as a SARIF `result`.
+pub fn sarif_result(file: &SourceFile, report: &Report) -> Value {
+    let diagnostic: &dyn Diagnostic = report.as_ref();
+    let level = match diagnostic.severity().unwrap_or(Severity::Error) {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Advice => "note",
+    };
+
+    let primary = primary_label(diagnostic);
+    let locations: Vec<Value> = primary
+        .iter()
+        .map(|label| sarif_location(file, label))
+        .collect();
+    let related: Vec<Value> = labels(diagnostic)
+        .iter()
+        .filter(|label| Some(*label) != primary.as_ref())
+        .map(|label| sarif_location(file, label))
+        .collect();
+
+    let mut result = json!({
+        "level": level,
+        "message": { "text": report.to_string() },
+        "locations": locations,
+        "relatedLocations": related,
+    });
+    if let Some(code) = diagnostic.code() {
+        result["ruleId"] = json!(code.to_string());
+    }
+    if let Some(suggestion) = suggestion(report) {
+        result["fixes"] = json!([{
+            "description": { "text": suggestion.message },
+            "artifactChanges": [{
+                "artifactLocation": { "uri": file.name },
+                "replacements": [{
+                    "deletedRegion": sarif_region(file, suggestion.span.offset(), suggestion.span.len()),
+                    "insertedContent": { "text": suggestion.replacement },
+                }],
+            }],
+        }]);
+    }
+    result
+}
+
+/// A complete SARIF log for a single run, with a rule for every registered error code.
+pub fn sarif_log(results: Vec<Value>) -> Value {
+    let rules: Vec<Value> = codes::REGISTRY
+        .iter()
+        .map(|entry| {
+            json!({
+                "id": entry.code,
+                "name": entry.name,
+                "fullDescription": { "text": entry.explanation },
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "columnKind": "utf16CodeUnits",
+            "results": results,
+        }],
+    })
+}
@@ -0,0 +1,668 @@
+//! A tree-walking interpreter, running a checked module without compiling it.
+//!
+//! Statements at the top of a module run in order, after which `main` is called if the module declares it. A
+//! program's result is the value `main` returns, or without one, the value of its last statement. Integers are
+//! computed with the type the checker gave them, so that overflow is reported where it happens rather than wrapping
+//! around.
+//!
+//! Every call has its own frame of variables. A name is looked up from the innermost frame outwards, so every function
+//! sees the variables declared at the top of the module, as well as its own. A reference to a variable remembers which
+//! call its frame belongs to, so using it once that call has returned is reported, rather than reaching a later call's
+//! frame in the same place.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::ast::{
+    BinaryOp, Block, Expr, ExprKind, Function, Ident, If, ItemKind, Module, Stmt, StmtKind, UnaryOp,
+};
+use crate::sema::check::Typing;
+use crate::sema::resolve::Resolution;
+use crate::sema::symbols::{SymbolId, SymbolKind};
+use crate::sema::types::Type;
+
+/// How many calls can be in progress at once before a program is stopped, unless it's configured otherwise.
+pub const DEFAULT_RECURSION_LIMIT: usize = 1000;
+
+/// A value computed at runtime.
+///
+/// The bytecode VM shares it, with its own representation of functions as `F`, and of where references point as `P`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<F = SymbolId, P = Place> {
+    /// Wide enough to hold any value of any integer type
+    Int(i128),
+    Bool(bool),
+    /// The unit value `()` is the empty tuple
+    Tuple(Vec<Value<F, P>>),
+    Function(F),
+    Reference(P),
+}
+
+impl<F, P> Value<F, P> {
+    pub fn unit() -> Self {
+        Value::Tuple(Vec::new())
+    }
+
+    pub fn is_unit(&self) -> bool {
+        matches!(self, Value::Tuple(elements) if elements.is_empty())
+    }
+}
+
+impl<F, P> Display for Value<F, P> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Tuple(elements) => {
+                write!(f, "(")?;
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                if elements.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Value::Function(_) => write!(f, "<function>"),
+            Value::Reference(_) => write!(f, "<reference>"),
+        }
+    }
+}
+
+/// Where a reference points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Place {
+    /// A variable in a frame, indexed from the outermost frame, which is only there while the frame has the same
+    /// generation
+    Variable {
+        frame: usize,
+        generation: usize,
+        symbol: SymbolId,
+    },
+    /// A value which isn't stored in a variable, eg. `&(1 + 2)`
+    Temporary(usize),
+}
+
+/// An integer divided by zero, eg. `a / 0`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Attempted to divide by zero")]
+#[diagnostic(code(E0021))]
+pub struct DivisionByZero {
+    #[label("divides by zero")]
+    pub span: SourceSpan,
+}
+
+/// Arithmetic whose result doesn't fit in its type, eg. `a + 1` when `a` is the largest `i32`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Arithmetic overflow")]
+#[diagnostic(code(E0022))]
+pub struct Overflow {
+    pub ty: Type,
+    #[label("overflows `{ty}`")]
+    pub span: SourceSpan,
+}
+
+/// A variable read before it was given a value, eg. `var a: i32; a + 1`
+#[derive(Error, Diagnostic, Debug)]
+#[error("`{name}` was used before it was given a value")]
+#[diagnostic(code(E0023))]
+pub struct Uninitialised {
+    pub name: String,
+    #[label("has no value yet")]
+    pub span: SourceSpan,
+}
+
+/// A reference used after the call whose variable it refers to has returned, eg. `fn f(): &i32 { var x = 5; &x } *f()`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Used a reference to a variable which no longer exists")]
+#[diagnostic(
+    code(E0027),
+    help("a variable only lives until the call it was declared in returns")
+)]
+pub struct DanglingReference {
+    #[label("refers to a variable of a call which has returned")]
+    pub span: SourceSpan,
+}
+
+/// More calls in progress at once than the recursion limit allows
+#[derive(Error, Diagnostic, Debug)]
+#[error("Reached the recursion limit of {limit} calls")]
+#[diagnostic(
+    code(E0024),
+    help("a larger limit can be set with `--recursion-limit`")
+)]
+pub struct RecursionLimit {
+    pub limit: usize,
+    #[label("one call too many")]
+    pub span: SourceSpan,
+}
+
+/// Runs `module`, returning its result, or the error which stopped it.
+pub fn run(
+    module: &Module,
+    resolution: &Resolution,
+    typing: &Typing,
+) -> Result<Value, miette::Report> {
+    Interpreter::new(resolution, typing).run(module)
+}
+
+pub struct Interpreter<'m, 't> {
+    resolution: &'m Resolution<'t>,
+    typing: &'m Typing,
+    functions: HashMap<SymbolId, &'m Function<'t>>,
+    /// Each call in progress, outermost first, where the first frame holds the module's variables
+    frames: Vec<Frame>,
+    /// How many frames have been pushed, which gives each frame a generation of its own
+    generations: usize,
+    temporaries: Vec<Value>,
+    recursion_limit: usize,
+}
+
+/// The variables of a call.
+#[derive(Debug, Default)]
+struct Frame {
+    generation: usize,
+    /// A variable which is declared but hasn't been given a value is `None`
+    variables: HashMap<SymbolId, Option<Value>>,
+}
+
+impl<'m, 't> Interpreter<'m, 't> {
+    pub fn new(resolution: &'m Resolution<'t>, typing: &'m Typing) -> Self {
+        Interpreter {
+            resolution,
+            typing,
+            functions: HashMap::new(),
+            frames: Vec::new(),
+            generations: 0,
+            temporaries: Vec::new(),
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+        }
+    }
+
+    /// Sets how many calls can be in progress at once.
+    pub fn with_recursion_limit(mut self, limit: usize) -> Self {
+        self.recursion_limit = limit;
+        self
+    }
+
+    /// Runs the statements of `module`, then `main` if it's declared, returning the program's result.
+    pub fn run(&mut self, module: &'m Module<'t>) -> Result<Value, miette::Report> {
+        self.declare_functions(&module.stmts);
+        self.frames = Vec::new();
+        self.push_frame(HashMap::new());
+
+        let mut result = Value::unit();
+        for stmt in &module.stmts {
+            result = self.stmt(stmt)?;
+        }
+
+        let main = module.stmts.iter().find_map(|stmt| match &stmt.kind {
+            StmtKind::Item(item) => {
+                let ItemKind::Function(function) = &item.kind;
+                (function.name.name == "main" && function.params.is_empty()).then_some(function)
+            }
+            _ => None,
+        });
+        if let Some(main) = main {
+            result = self.call(main, Vec::new(), main.name.span)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Records every function declared in `stmts`, including those nested inside of other statements, so that they
+    /// can be called by their symbol.
+    fn declare_functions(&mut self, stmts: &'m [Stmt<'t>]) {
+        for stmt in stmts {
+            match &stmt.kind {
+                StmtKind::Item(item) => {
+                    let ItemKind::Function(function) = &item.kind;
+                    if let Some(id) = self.resolution.declaration(function.name.span) {
+                        self.functions.insert(id, function);
+                    }
+                    self.declare_functions(&function.body.stmts);
+                }
+                StmtKind::Block(block) | StmtKind::While { body: block, .. } => {
+                    self.declare_functions(&block.stmts)
+                }
+                StmtKind::If(if_stmt) => {
+                    self.declare_functions(&if_stmt.then_block.stmts);
+                    if let Some(else_branch) = &if_stmt.else_branch {
+                        self.declare_functions(std::slice::from_ref(else_branch));
+                    }
+                }
+                StmtKind::Variable { .. }
+                | StmtKind::Const { .. }
+                | StmtKind::Expr(_)
+                | StmtKind::Error => {}
+            }
+        }
+    }
+
+    /// Runs a statement, returning its value, which is unit unless it's a block or `if`.
+    fn stmt(&mut self, stmt: &Stmt<'t>) -> Result<Value, miette::Report> {
+        match &stmt.kind {
+            // Functions were all declared before running anything
+            StmtKind::Item(_) => Ok(Value::unit()),
+            StmtKind::Variable { name, value, .. } => {
+                let value = value.as_ref().map(|value| self.expr(value)).transpose()?;
+                self.declare(name.span, value);
+                Ok(Value::unit())
+            }
+            StmtKind::Const { name, value, .. } => {
+                let value = self.expr(value)?;
+                self.declare(name.span, Some(value));
+                Ok(Value::unit())
+            }
+            StmtKind::Expr(expr) => self.expr(expr),
+            StmtKind::Block(block) => self.block(block),
+            StmtKind::If(if_stmt) => self.if_stmt(if_stmt),
+            StmtKind::While { condition, body } => {
+                while self.condition(condition)? {
+                    self.block(body)?;
+                }
+                Ok(Value::unit())
+            }
+            // Modules with errors aren't run
+            StmtKind::Error => Ok(Value::unit()),
+        }
+    }
+
+    /// Runs a block, returning its tail, or the value of the `if` it ends with.
+    fn block(&mut self, block: &Block<'t>) -> Result<Value, miette::Report> {
+        let mut last = Value::unit();
+        for stmt in &block.stmts {
+            last = self.stmt(stmt)?;
+        }
+
+        match &block.tail {
+            Some(tail) => self.expr(tail),
+            None if matches!(
+                block.stmts.last(),
+                Some(Stmt {
+                    kind: StmtKind::If(_),
+                    ..
+                })
+            ) =>
+            {
+                Ok(last)
+            }
+            None => Ok(Value::unit()),
+        }
+    }
+
+    fn if_stmt(&mut self, if_stmt: &If<'t>) -> Result<Value, miette::Report> {
+        if self.condition(&if_stmt.condition)? {
+            self.block(&if_stmt.then_block)
+        } else if let Some(else_branch) = &if_stmt.else_branch {
+            self.stmt(else_branch)
+        } else {
+            Ok(Value::unit())
+        }
+    }
+
+    fn condition(&mut self, condition: &Expr<'t>) -> Result<bool, miette::Report> {
+        match self.expr(condition)? {
+            Value::Bool(value) => Ok(value),
+            other => unreachable!(
+                "condition evaluated to `{other}`, which should have been a type error"
+            ),
+        }
+    }
+
+    /// Declares the variable named at `span` in the innermost frame.
+    fn declare(&mut self, span: SourceSpan, value: Option<Value>) {
+        if let Some(id) = self.resolution.declaration(span) {
+            self.frames.last_mut().unwrap().variables.insert(id, value);
+        }
+    }
+
+    fn push_frame(&mut self, variables: HashMap<SymbolId, Option<Value>>) {
+        self.frames.push(Frame {
+            generation: self.generations,
+            variables,
+        });
+        self.generations += 1;
+    }
+
+    /// The variable `symbol`, searching from the innermost frame outwards, or in the innermost frame if it hasn't
+    /// been declared yet.
+    fn variable(&self, symbol: SymbolId) -> Place {
+        let frame = self
+            .frames
+            .iter()
+            .rposition(|frame| frame.variables.contains_key(&symbol))
+            .unwrap_or(self.frames.len() - 1);
+        Place::Variable {
+            frame,
+            generation: self.frames[frame].generation,
+            symbol,
+        }
+    }
+
+    /// The variables of `frame`, reporting a reference used at `span` if the frame has been replaced since
+    /// `generation`.
+    fn frame(
+        &mut self,
+        frame: usize,
+        generation: usize,
+        span: SourceSpan,
+    ) -> Result<&mut HashMap<SymbolId, Option<Value>>, miette::Report> {
+        match self.frames.get_mut(frame) {
+            Some(frame) if frame.generation == generation => Ok(&mut frame.variables),
+            _ => Err(DanglingReference { span }.into()),
+        }
+    }
+
+    /// The value at `place`, which is `None` if it hasn't been given one yet.
+    fn read(&mut self, place: Place, span: SourceSpan) -> Result<Option<Value>, miette::Report> {
+        match place {
+            Place::Variable {
+                frame,
+                generation,
+                symbol,
+            } => Ok(self
+                .frame(frame, generation, span)?
+                .get(&symbol)
+                .cloned()
+                .flatten()),
+            Place::Temporary(index) => Ok(self.temporaries.get(index).cloned()),
+        }
+    }
+
+    fn write(
+        &mut self,
+        place: Place,
+        value: Value,
+        span: SourceSpan,
+    ) -> Result<(), miette::Report> {
+        match place {
+            Place::Variable {
+                frame,
+                generation,
+                symbol,
+            } => {
+                self.frame(frame, generation, span)?
+                    .insert(symbol, Some(value));
+            }
+            Place::Temporary(index) => self.temporaries[index] = value,
+        }
+        Ok(())
+    }
+
+    /// The place an expression which can be assigned to or referenced refers to, storing any other value as a
+    /// temporary.
+    fn place(&mut self, expr: &Expr<'t>) -> Result<Place, miette::Report> {
+        match &expr.kind {
+            ExprKind::Identifier(_) => {
+                if let Some(symbol) = self.resolution.binding(expr) {
+                    return Ok(self.variable(symbol));
+                }
+            }
+            ExprKind::Unary(UnaryOp::Deref, operand) => {
+                if let Value::Reference(place) = self.expr(operand)? {
+                    return Ok(place);
+                }
+            }
+            _ => {}
+        }
+
+        let value = self.expr(expr)?;
+        self.temporaries.push(value);
+        Ok(Place::Temporary(self.temporaries.len() - 1))
+    }
+
+    /// The type the checker gave `expr`.
+    fn ty(&self, expr: &Expr) -> Type {
+        self.typing.expr(expr).cloned().unwrap_or(Type::Error)
+    }
+
+    fn expr(&mut self, expr: &Expr<'t>) -> Result<Value, miette::Report> {
+        match &expr.kind {
+            ExprKind::Integer(literal) => self.literal(literal, false, expr),
+            ExprKind::Boolean(value) => Ok(Value::Bool(*value)),
+            ExprKind::Identifier(Ident { name, .. }) => {
+                let Some(symbol) = self.resolution.binding(expr) else {
+                    unreachable!("`{name}` wasn't resolved, which should have been reported")
+                };
+                if self.resolution.symbols.symbol(symbol).kind == SymbolKind::Function {
+                    return Ok(Value::Function(symbol));
+                }
+
+                let value = self.read(self.variable(symbol), expr.span)?;
+                value.ok_or_else(|| {
+                    Uninitialised {
+                        name: name.to_string(),
+                        span: expr.span,
+                    }
+                    .into()
+                })
+            }
+            ExprKind::Unary(op, operand) => {
+                // `-128` is in range for an `i8`, even though `128` isn't
+                if let (UnaryOp::Negate, ExprKind::Integer(literal)) = (op, &operand.kind) {
+                    return self.literal(literal, true, expr);
+                }
+
+                match op {
+                    UnaryOp::Reference => return Ok(Value::Reference(self.place(operand)?)),
+                    UnaryOp::Deref => {
+                        let Value::Reference(place) = self.expr(operand)? else {
+                            unreachable!("dereferenced a value which isn't a reference")
+                        };
+                        return self.read(place, expr.span)?.ok_or_else(|| {
+                            Uninitialised {
+                                name: operand.to_string(),
+                                span: expr.span,
+                            }
+                            .into()
+                        });
+                    }
+                    _ => {}
+                }
+
+                let ty = self.ty(expr);
+                let result = match (op, self.expr(operand)?) {
+                    (UnaryOp::Not, Value::Bool(value)) => return Ok(Value::Bool(!value)),
+                    (UnaryOp::Negate, Value::Int(value)) => value.checked_neg(),
+                    (UnaryOp::Plus, Value::Int(value)) => Some(value),
+                    // Flips every bit within the width of the type, which is `-x - 1` when signed
+                    (UnaryOp::Not, Value::Int(value)) => match ty {
+                        Type::Int { signed: false, .. } => {
+                            ty.range().map(|range| range.end() - value)
+                        }
+                        _ => Some(-value - 1),
+                    },
+                    (op, operand) => unreachable!(
+                        "applied `{}` to `{operand}`, which should have been a type error",
+                        op.symbol()
+                    ),
+                };
+                checked(result, &ty, expr.span).map(Value::Int)
+            }
+            ExprKind::Binary(op, left, right) => self.binary(*op, left, right, expr),
+            ExprKind::Call(callee, arguments) => {
+                let Value::Function(symbol) = self.expr(callee)? else {
+                    unreachable!("called a value which isn't a function")
+                };
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.expr(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let function = self.functions[&symbol];
+                self.call(function, arguments, expr.span)
+            }
+            ExprKind::Tuple(elements) => Ok(Value::Tuple(
+                elements
+                    .iter()
+                    .map(|element| self.expr(element))
+                    .collect::<Result<_, _>>()?,
+            )),
+        }
+    }
+
+    /// Calls `function` in a new frame, reporting the call at `span` if it's one more than the recursion limit.
+    fn call(
+        &mut self,
+        function: &Function<'t>,
+        arguments: Vec<Value>,
+        span: SourceSpan,
+    ) -> Result<Value, miette::Report> {
+        // The first frame is the module's, not a call's
+        if self.frames.len() > self.recursion_limit {
+            return Err(RecursionLimit {
+                limit: self.recursion_limit,
+                span,
+            }
+            .into());
+        }
+
+        let mut frame = HashMap::new();
+        for (param, argument) in function.params.iter().zip(arguments) {
+            if let Some(id) = self.resolution.declaration(param.name.span) {
+                frame.insert(id, Some(argument));
+            }
+        }
+
+        self.push_frame(frame);
+        let result = self.block(&function.body);
+        self.frames.pop();
+        result
+    }
+
+    fn binary(
+        &mut self,
+        op: BinaryOp,
+        left: &Expr<'t>,
+        right: &Expr<'t>,
+        expr: &Expr<'t>,
+    ) -> Result<Value, miette::Report> {
+        let compound = match op {
+            BinaryOp::Assign => {
+                let place = self.place(left)?;
+                let value = self.expr(right)?;
+                self.write(place, value, left.span)?;
+                return Ok(Value::unit());
+            }
+            BinaryOp::AddAssign => Some(BinaryOp::Add),
+            BinaryOp::SubtractAssign => Some(BinaryOp::Subtract),
+            BinaryOp::MultiplyAssign => Some(BinaryOp::Multiply),
+            _ => None,
+        };
+        if let Some(op) = compound {
+            let place = self.place(left)?;
+            let current = self.expr(left)?;
+            let value = self.expr(right)?;
+            let result = self.arithmetic(op, current, value, &self.ty(left), expr.span)?;
+            self.write(place, result, left.span)?;
+            return Ok(Value::unit());
+        }
+
+        let left = self.expr(left)?;
+        let right = self.expr(right)?;
+        self.arithmetic(op, left, right, &self.ty(expr), expr.span)
+    }
+
+    /// Applies an operator which isn't an assignment to two values, giving integer results the type `ty`.
+    fn arithmetic(
+        &self,
+        op: BinaryOp,
+        left: Value,
+        right: Value,
+        ty: &Type,
+        span: SourceSpan,
+    ) -> Result<Value, miette::Report> {
+        let (Value::Int(l), Value::Int(r)) = (&left, &right) else {
+            return match op {
+                BinaryOp::Equal => Ok(Value::Bool(left == right)),
+                BinaryOp::NotEqual => Ok(Value::Bool(left != right)),
+                _ => unreachable!("applied `{op:?}` to `{left}` and `{right}`, which should have been a type error"),
+            };
+        };
+        let (l, r) = (*l, *r);
+
+        let result = match op {
+            BinaryOp::Greater => l > r,
+            BinaryOp::Less => l < r,
+            BinaryOp::GreaterEqual => l >= r,
+            BinaryOp::LessEqual => l <= r,
+            BinaryOp::Equal => l == r,
+            BinaryOp::NotEqual => l != r,
+            _ => return integer(op, l, r, ty, span).map(Value::Int),
+        };
+        Ok(Value::Bool(result))
+    }
+
+    fn literal(
+        &self,
+        literal: &str,
+        negative: bool,
+        expr: &Expr<'t>,
+    ) -> Result<Value, miette::Report> {
+        let value = literal
+            .parse::<i128>()
+            .ok()
+            .map(|value| if negative { -value } else { value });
+        checked(value, &self.ty(expr), expr.span).map(Value::Int)
+    }
+}
+
+/// Applies an arithmetic operator to two integers of the type `ty`, reporting the result if it doesn't fit in `ty`.
+pub fn integer(
+    op: BinaryOp,
+    l: i128,
+    r: i128,
+    ty: &Type,
+    span: SourceSpan,
+) -> Result<i128, miette::Report> {
+    let result = match op {
+        BinaryOp::Add => l.checked_add(r),
+        BinaryOp::Subtract => l.checked_sub(r),
+        BinaryOp::Multiply => l.checked_mul(r),
+        BinaryOp::Divide if r == 0 => return Err(DivisionByZero { span }.into()),
+        BinaryOp::Divide => l.checked_div(r),
+        // A negative power is the reciprocal of a positive one, which truncates towards zero like division
+        BinaryOp::Power if r < 0 => match l {
+            0 => return Err(DivisionByZero { span }.into()),
+            1 => Some(1),
+            -1 => Some(if r % 2 == 0 { 1 } else { -1 }),
+            _ => Some(0),
+        },
+        BinaryOp::Power => u32::try_from(r).ok().and_then(|r| l.checked_pow(r)),
+        BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+            let bits = match ty {
+                Type::Int { bits, .. } => i128::from(*bits),
+                _ => 0,
+            };
+            if !(0..bits).contains(&r) {
+                None
+            } else if op == BinaryOp::ShiftLeft {
+                l.checked_mul(1 << r)
+            } else {
+                Some(l >> r)
+            }
+        }
+        _ => unreachable!("`{op:?}` isn't an arithmetic operator"),
+    };
+
+    checked(result, ty, span)
+}
+
+/// The result of integer arithmetic, reporting an overflow if it's `None` or doesn't fit in `ty`.
+pub fn checked(result: Option<i128>, ty: &Type, span: SourceSpan) -> Result<i128, miette::Report> {
+    match result {
+        Some(value) if ty.fits(value) => Ok(value),
+        _ => Err(Overflow {
+            ty: ty.clone(),
+            span,
+        }
+        .into()),
+    }
+}
@@ -0,0 +1,235 @@
+//! Builds functions one instruction at a time, giving each value the type its instruction produces.
+//!
+//! The builder doesn't check what it's given: an instruction whose operands have the wrong types produces a value of
+//! type `{error}`, and mistakes are left for [`verify`](crate::ir::verify::verify) to report.
+
+use crate::ir::{
+    BinaryOp, Block, BlockData, Comparison, Function, Instruction, InstructionKind, Terminator,
+    Value,
+};
+use crate::sema::types::Type;
+
+pub struct FunctionBuilder {
+    function: Function,
+    /// The block instructions are added to
+    current: Block,
+}
+
+impl FunctionBuilder {
+    /// Starts building a function, with an empty entry block to add instructions to.
+    pub fn new(name: &str, params: &[Type], return_type: Type) -> Self {
+        let mut function = Function {
+            name: name.to_owned(),
+            params: Vec::new(),
+            return_type,
+            blocks: vec![BlockData::default()],
+            values: Vec::new(),
+        };
+        for ty in params {
+            function.params.push(Value(function.values.len() as u32));
+            function.values.push(ty.clone());
+        }
+
+        FunctionBuilder {
+            function,
+            current: Block(0),
+        }
+    }
+
+    pub fn param(&self, index: usize) -> Value {
+        self.function.params[index]
+    }
+
+    pub fn ty(&self, value: Value) -> &Type {
+        self.function.ty(value)
+    }
+
+    pub fn create_block(&mut self) -> Block {
+        self.function.blocks.push(BlockData::default());
+        Block(self.function.blocks.len() as u32 - 1)
+    }
+
+    /// Adds instructions to the end of `block` from now on.
+    pub fn switch_to(&mut self, block: Block) {
+        self.current = block;
+    }
+
+    pub fn current_block(&self) -> Block {
+        self.current
+    }
+
+    /// Whether the current block has been ended by a terminator.
+    pub fn is_terminated(&self) -> bool {
+        self.function.block(self.current).terminator.is_some()
+    }
+
+    fn block_mut(&mut self, block: Block) -> &mut BlockData {
+        &mut self.function.blocks[block.0 as usize]
+    }
+
+    fn value(&mut self, ty: Type) -> Value {
+        self.function.values.push(ty);
+        Value(self.function.values.len() as u32 - 1)
+    }
+
+    /// Adds an instruction producing a value of type `ty` to the current block.
+    fn push(&mut self, kind: InstructionKind, ty: Type) -> Value {
+        let result = self.value(ty);
+        let current = self.current;
+        self.block_mut(current).instructions.push(Instruction {
+            result: Some(result),
+            kind,
+        });
+        result
+    }
+
+    pub fn int(&mut self, ty: Type, value: i128) -> Value {
+        self.push(InstructionKind::Int(value), ty)
+    }
+
+    pub fn bool(&mut self, value: bool) -> Value {
+        self.push(InstructionKind::Bool(value), Type::Bool)
+    }
+
+    pub fn unit(&mut self) -> Value {
+        self.tuple(&[])
+    }
+
+    pub fn binary(&mut self, op: BinaryOp, left: Value, right: Value) -> Value {
+        let ty = self.ty(left).clone();
+        self.push(InstructionKind::Binary(op, left, right), ty)
+    }
+
+    pub fn compare(&mut self, comparison: Comparison, left: Value, right: Value) -> Value {
+        self.push(
+            InstructionKind::Compare(comparison, left, right),
+            Type::Bool,
+        )
+    }
+
+    pub fn negate(&mut self, value: Value) -> Value {
+        let ty = self.ty(value).clone();
+        self.push(InstructionKind::Negate(value), ty)
+    }
+
+    pub fn not(&mut self, value: Value) -> Value {
+        let ty = self.ty(value).clone();
+        self.push(InstructionKind::Not(value), ty)
+    }
+
+    pub fn tuple(&mut self, elements: &[Value]) -> Value {
+        let ty = Type::Tuple(
+            elements
+                .iter()
+                .map(|&element| self.ty(element).clone())
+                .collect(),
+        );
+        self.push(InstructionKind::Tuple(elements.to_vec()), ty)
+    }
+
+    pub fn extract(&mut self, tuple: Value, index: u32) -> Value {
+        let ty = match self.ty(tuple) {
+            Type::Tuple(elements) => elements.get(index as usize).cloned(),
+            _ => None,
+        };
+        self.push(
+            InstructionKind::Extract(tuple, index),
+            ty.unwrap_or(Type::Error),
+        )
+    }
+
+    /// Allocates memory for a value of type `ty`, returning a reference to it.
+    ///
+    /// The memory is always allocated in the entry block, so that it's allocated once however many times the current
+    /// block runs, and its reference can be used from any block.
+    pub fn alloca(&mut self, ty: Type) -> Value {
+        let result = self.value(Type::Reference(Box::new(ty)));
+        self.block_mut(Block(0)).instructions.push(Instruction {
+            result: Some(result),
+            kind: InstructionKind::Alloca,
+        });
+        result
+    }
+
+    pub fn load(&mut self, pointer: Value) -> Value {
+        let ty = match self.ty(pointer) {
+            Type::Reference(inner) => (**inner).clone(),
+            _ => Type::Error,
+        };
+        self.push(InstructionKind::Load(pointer), ty)
+    }
+
+    pub fn store(&mut self, pointer: Value, value: Value) {
+        let current = self.current;
+        self.block_mut(current).instructions.push(Instruction {
+            result: None,
+            kind: InstructionKind::Store { pointer, value },
+        });
+    }
+
+    /// A reference to the global `name`, which has type `ty`.
+    pub fn global(&mut self, name: &str, ty: Type) -> Value {
+        self.push(
+            InstructionKind::Global(name.to_owned()),
+            Type::Reference(Box::new(ty)),
+        )
+    }
+
+    /// The function `name`, whose type is `ty`.
+    pub fn function(&mut self, name: &str, ty: Type) -> Value {
+        self.push(InstructionKind::Function(name.to_owned()), ty)
+    }
+
+    pub fn call(&mut self, callee: Value, arguments: &[Value]) -> Value {
+        let ty = match self.ty(callee) {
+            Type::Function(_, return_type) => (**return_type).clone(),
+            _ => Type::Error,
+        };
+        self.push(InstructionKind::Call(callee, arguments.to_vec()), ty)
+    }
+
+    /// Joins values of type `ty` from the blocks which branch to the current block, after any other phi instructions
+    /// at its start.
+    pub fn phi(&mut self, ty: Type, incoming: &[(Block, Value)]) -> Value {
+        let result = self.value(ty);
+        let current = self.current;
+        let instructions = &mut self.block_mut(current).instructions;
+        let position = instructions
+            .iter()
+            .position(|instruction| !matches!(instruction.kind, InstructionKind::Phi(_)))
+            .unwrap_or(instructions.len());
+        instructions.insert(
+            position,
+            Instruction {
+                result: Some(result),
+                kind: InstructionKind::Phi(incoming.to_vec()),
+            },
+        );
+        result
+    }
+
+    fn terminate(&mut self, terminator: Terminator) {
+        let current = self.current;
+        self.block_mut(current).terminator = Some(terminator);
+    }
+
+    pub fn jump(&mut self, target: Block) {
+        self.terminate(Terminator::Jump(target));
+    }
+
+    pub fn branch(&mut self, condition: Value, then: Block, otherwise: Block) {
+        self.terminate(Terminator::Branch {
+            condition,
+            then,
+            otherwise,
+        });
+    }
+
+    pub fn ret(&mut self, value: Value) {
+        self.terminate(Terminator::Return(value));
+    }
+
+    pub fn finish(self) -> Function {
+        self.function
+    }
+}
@@ -0,0 +1,537 @@
+//! Lowers a checked module to the IR, written by `--emit=ir`.
+//!
+//! The module's statements become the body of `@main`, which returns the program's result as `run` computes it, and
+//! each function declared in the module becomes a function of its own. As in the LLVM backend, variables live in
+//! memory allocated when their function is entered, or in globals when they're declared at the top of the module.
+
+use std::collections::{HashMap, HashSet};
+
+use miette::SourceSpan;
+
+use crate::ast::{
+    BinaryOp, Block, Expr, ExprKind, Function, Ident, If, ItemKind, Module, Stmt, StmtKind, UnaryOp,
+};
+use crate::ir::builder::FunctionBuilder;
+use crate::ir::{self, Comparison, Global, Value};
+use crate::llvm::unique;
+use crate::sema::check::Typing;
+use crate::sema::consteval::{self, Constants, LiteralOutOfRange};
+use crate::sema::resolve::Resolution;
+use crate::sema::symbols::{SymbolId, SymbolKind};
+use crate::sema::types::Type;
+
+/// Lowers `module`, which must have been checked without errors, with every constant expression replaced by its
+/// value.
+pub fn lower<'t>(
+    module: &Module<'t>,
+    resolution: &Resolution<'t>,
+    typing: &Typing,
+    constants: &Constants,
+) -> Result<ir::Module, miette::Report> {
+    let mut lowerer = Lowerer {
+        resolution,
+        typing,
+        constants,
+        // `main` is the program's entry point, which a function declared as `main` can't replace
+        symbols: HashSet::from(["main".to_owned()]),
+        names: HashMap::new(),
+        functions: Vec::new(),
+        globals: HashMap::new(),
+        output: ir::Module::default(),
+        module_level: true,
+        slots: HashMap::new(),
+        builder: FunctionBuilder::new("main", &[], Type::unit()),
+    };
+    lowerer.declare_functions(&module.stmts);
+
+    let value = lowerer.stmts(&module.stmts, None)?;
+    let mut value = lowerer.value(value);
+
+    // The program's result is the value `main` returns instead, if it's declared
+    let main = module.stmts.iter().find_map(|stmt| match &stmt.kind {
+        StmtKind::Item(item) => {
+            let ItemKind::Function(function) = &item.kind;
+            (function.name.name == "main" && function.params.is_empty()).then_some(function)
+        }
+        _ => None,
+    });
+    if let Some(id) = main.and_then(|main| resolution.declaration(main.name.span)) {
+        let ty = typing.symbol(id).cloned().unwrap_or(Type::Error);
+        let callee = lowerer.builder.function(&lowerer.names[&id], ty);
+        value = lowerer.builder.call(callee, &[]);
+    }
+    lowerer.builder.ret(value);
+    let return_type = lowerer.builder.ty(value).clone();
+    let mut main = std::mem::replace(
+        &mut lowerer.builder,
+        FunctionBuilder::new("main", &[], Type::unit()),
+    )
+    .finish();
+    // Only known once the statements are lowered
+    main.return_type = return_type;
+
+    lowerer.module_level = false;
+
+    for function in lowerer.functions.clone() {
+        let function = lowerer.function(function)?;
+        lowerer.output.functions.push(function);
+    }
+    lowerer.output.functions.push(main);
+
+    Ok(lowerer.output)
+}
+
+struct Lowerer<'m, 'r, 't> {
+    resolution: &'r Resolution<'t>,
+    typing: &'r Typing,
+    constants: &'r Constants,
+    /// Every global name, which must be unique within the module
+    symbols: HashSet<String>,
+    /// The global name of each function
+    names: HashMap<SymbolId, String>,
+    /// Every function declared in the module
+    functions: Vec<&'m Function<'t>>,
+    /// The global of each variable declared at the top of the module
+    globals: HashMap<SymbolId, String>,
+    output: ir::Module,
+    /// Whether the module's statements are being lowered, whose variables are globals
+    module_level: bool,
+    /// The memory allocated for each variable and parameter of the function being lowered
+    slots: HashMap<SymbolId, Value>,
+    builder: FunctionBuilder,
+}
+
+impl<'m, 'r, 't> Lowerer<'m, 'r, 't> {
+    /// Names every function declared in `stmts`, including those nested inside of other statements.
+    fn declare_functions(&mut self, stmts: &'m [Stmt<'t>]) {
+        for stmt in stmts {
+            match &stmt.kind {
+                StmtKind::Item(item) => {
+                    let ItemKind::Function(function) = &item.kind;
+                    if let Some(id) = self.resolution.declaration(function.name.span) {
+                        let name = unique(&mut self.symbols, function.name.name);
+                        self.names.insert(id, name);
+                        self.functions.push(function);
+                    }
+                    self.declare_functions(&function.body.stmts);
+                }
+                StmtKind::Block(block) | StmtKind::While { body: block, .. } => {
+                    self.declare_functions(&block.stmts)
+                }
+                StmtKind::If(if_stmt) => {
+                    self.declare_functions(&if_stmt.then_block.stmts);
+                    if let Some(else_branch) = &if_stmt.else_branch {
+                        self.declare_functions(std::slice::from_ref(else_branch));
+                    }
+                }
+                StmtKind::Variable { .. }
+                | StmtKind::Const { .. }
+                | StmtKind::Expr(_)
+                | StmtKind::Error => {}
+            }
+        }
+    }
+
+    fn function(&mut self, function: &Function<'t>) -> Result<ir::Function, miette::Report> {
+        let id = self.resolution.declaration(function.name.span);
+        let Some(Type::Function(params, return_type)) = id.and_then(|id| self.typing.symbol(id))
+        else {
+            unreachable!("`{}` wasn't given a type", function.name.name)
+        };
+
+        let name = &self.names[&id.unwrap()];
+        self.builder = FunctionBuilder::new(name, params, (**return_type).clone());
+        self.slots.clear();
+        for (index, (param, ty)) in function.params.iter().zip(params).enumerate() {
+            let value = self.builder.param(index);
+            if let Some(slot) = self.declare(param.name.span, ty) {
+                self.builder.store(slot, value);
+            }
+        }
+
+        let value = self.block(&function.body, true)?;
+        let value = self.value(value);
+        self.builder.ret(value);
+        let builder = std::mem::replace(
+            &mut self.builder,
+            FunctionBuilder::new("main", &[], Type::unit()),
+        );
+        Ok(builder.finish())
+    }
+
+    /// The type the checker gave `expr`.
+    fn ty(&self, expr: &Expr) -> Type {
+        self.typing.expr(expr).cloned().unwrap_or(Type::Error)
+    }
+
+    /// The memory holding the variable declared by the name at `span`, which is allocated if it hasn't been yet.
+    fn declare(&mut self, span: SourceSpan, ty: &Type) -> Option<Value> {
+        let id = self.resolution.declaration(span)?;
+        if let Some(slot) = self.slot(id) {
+            return Some(slot);
+        }
+
+        let name = self
+            .resolution
+            .symbols
+            .name(self.resolution.symbols.symbol(id).name);
+        if self.module_level {
+            let name = unique(&mut self.symbols, name);
+            self.output.globals.push(Global {
+                name: name.clone(),
+                ty: ty.clone(),
+            });
+            self.globals.insert(id, name);
+            return self.slot(id);
+        }
+
+        let slot = self.builder.alloca(ty.clone());
+        self.slots.insert(id, slot);
+        Some(slot)
+    }
+
+    /// A reference to the variable `id`, if it's a variable of the function being lowered or of the module.
+    fn slot(&mut self, id: SymbolId) -> Option<Value> {
+        if let Some(&slot) = self.slots.get(&id) {
+            return Some(slot);
+        }
+        let name = self.globals.get(&id)?;
+        let ty = self.output.global(name).unwrap().ty.clone();
+        Some(self.builder.global(name, ty))
+    }
+
+    /// The value of a statement, creating `()` if the statement's value is `None`.
+    fn value(&mut self, value: Option<Value>) -> Value {
+        match value {
+            Some(value) => value,
+            None => self.builder.unit(),
+        }
+    }
+
+    /// Lowers a sequence of statements sharing a scope, returning the value of the last one, or of `tail`.
+    ///
+    /// Statements return `None` for `()`, which isn't created unless something uses it.
+    fn stmts(
+        &mut self,
+        stmts: &[Stmt<'t>],
+        tail: Option<&Expr<'t>>,
+    ) -> Result<Option<Value>, miette::Report> {
+        let Some((last, rest)) = stmts.split_last() else {
+            return tail.map(|tail| self.expr(tail)).transpose();
+        };
+
+        for stmt in rest {
+            self.stmt(stmt, false)?;
+        }
+        match tail {
+            Some(tail) => {
+                self.stmt(last, false)?;
+                self.expr(tail).map(Some)
+            }
+            None => self.stmt(last, true),
+        }
+    }
+
+    /// Lowers a block, returning its value if `keep` is set.
+    fn block(&mut self, block: &Block<'t>, keep: bool) -> Result<Option<Value>, miette::Report> {
+        let ends_with_if = matches!(
+            block.stmts.last(),
+            Some(Stmt {
+                kind: StmtKind::If(_),
+                ..
+            })
+        );
+
+        if keep && (block.tail.is_some() || ends_with_if) {
+            return self.stmts(&block.stmts, block.tail.as_deref());
+        }
+        for stmt in &block.stmts {
+            self.stmt(stmt, false)?;
+        }
+        if let Some(tail) = &block.tail {
+            self.expr(tail)?;
+        }
+        Ok(None)
+    }
+
+    /// Lowers a statement, returning its value if `keep` is set.
+    fn stmt(&mut self, stmt: &Stmt<'t>, keep: bool) -> Result<Option<Value>, miette::Report> {
+        match &stmt.kind {
+            // Functions are lowered separately
+            StmtKind::Item(_) | StmtKind::Error => {}
+            StmtKind::Variable { name, value, .. } => {
+                let ty = self
+                    .resolution
+                    .declaration(name.span)
+                    .and_then(|id| self.typing.symbol(id))
+                    .cloned()
+                    .unwrap_or(Type::Error);
+                let slot = self.declare(name.span, &ty);
+                // A variable without a value is left as whatever its memory holds
+                if let Some(value) = value {
+                    let value = self.expr(value)?;
+                    if let Some(slot) = slot {
+                        self.builder.store(slot, value);
+                    }
+                }
+            }
+            StmtKind::Const { name, value, .. } => {
+                let value = self.expr(value)?;
+                let ty = self.builder.ty(value).clone();
+                if let Some(slot) = self.declare(name.span, &ty) {
+                    self.builder.store(slot, value);
+                }
+            }
+            StmtKind::Expr(expr) => {
+                let value = self.expr(expr)?;
+                if keep {
+                    return Ok(Some(value));
+                }
+            }
+            StmtKind::Block(block) => return self.block(block, keep),
+            StmtKind::If(if_stmt) => return self.if_stmt(if_stmt, keep),
+            StmtKind::While { condition, body } => {
+                let head = self.builder.create_block();
+                let then = self.builder.create_block();
+                let end = self.builder.create_block();
+
+                self.builder.jump(head);
+                self.builder.switch_to(head);
+                let condition = self.expr(condition)?;
+                self.builder.branch(condition, then, end);
+                self.builder.switch_to(then);
+                self.block(body, false)?;
+                self.builder.jump(head);
+                self.builder.switch_to(end);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Lowers an `if`, joining the values of its branches with a phi if `keep` is set.
+    fn if_stmt(&mut self, if_stmt: &If<'t>, keep: bool) -> Result<Option<Value>, miette::Report> {
+        let condition = self.expr(&if_stmt.condition)?;
+        let then = self.builder.create_block();
+        let otherwise = if_stmt
+            .else_branch
+            .as_ref()
+            .map(|_| self.builder.create_block());
+        let end = self.builder.create_block();
+
+        self.builder
+            .branch(condition, then, otherwise.unwrap_or(end));
+        self.builder.switch_to(then);
+        let then_value = self.block(&if_stmt.then_block, keep)?;
+        let then_end = self.builder.current_block();
+        self.builder.jump(end);
+
+        let Some((else_branch, otherwise)) = if_stmt.else_branch.as_ref().zip(otherwise) else {
+            self.builder.switch_to(end);
+            return Ok(None);
+        };
+        self.builder.switch_to(otherwise);
+        let else_value = self.stmt(else_branch, keep)?;
+        let else_end = self.builder.current_block();
+        self.builder.jump(end);
+        self.builder.switch_to(end);
+
+        let (Some(then_value), Some(else_value)) = (then_value, else_value) else {
+            return Ok(None);
+        };
+        let ty = self.builder.ty(then_value).clone();
+        if !keep || ty.is_unit() {
+            return Ok(None);
+        }
+        let value = self
+            .builder
+            .phi(ty, &[(then_end, then_value), (else_end, else_value)]);
+        Ok(Some(value))
+    }
+
+    fn expr(&mut self, expr: &Expr<'t>) -> Result<Value, miette::Report> {
+        match self.constants.expr(expr) {
+            Some(consteval::Value::Int(value)) => {
+                return Ok(self.builder.int(self.ty(expr), value))
+            }
+            Some(consteval::Value::Bool(value)) => return Ok(self.builder.bool(value)),
+            None => {}
+        }
+
+        let value = match &expr.kind {
+            ExprKind::Integer(literal) => self.literal(literal, false, expr)?,
+            ExprKind::Boolean(value) => self.builder.bool(*value),
+            ExprKind::Identifier(_) => self.load(expr)?,
+            ExprKind::Unary(op, operand) => {
+                // `-128` is in range for an `i8`, even though `128` isn't
+                if let (UnaryOp::Negate, ExprKind::Integer(literal)) = (op, &operand.kind) {
+                    self.literal(literal, true, expr)?
+                } else {
+                    self.unary(*op, operand)?
+                }
+            }
+            ExprKind::Binary(op, left, right) => self.binary(*op, left, right)?,
+            ExprKind::Call(callee, arguments) => {
+                let callee = self.expr(callee)?;
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.expr(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.builder.call(callee, &arguments)
+            }
+            ExprKind::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| self.expr(element))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.builder.tuple(&elements)
+            }
+        };
+        Ok(value)
+    }
+
+    /// The value of the name `expr`.
+    fn load(&mut self, expr: &Expr<'t>) -> Result<Value, miette::Report> {
+        let ExprKind::Identifier(Ident { name, .. }) = expr.kind else {
+            unreachable!("only names can be loaded")
+        };
+        let Some(id) = self.resolution.binding(expr) else {
+            unreachable!("`{name}` wasn't resolved, which should have been reported")
+        };
+
+        if self.resolution.symbols.symbol(id).kind == SymbolKind::Function {
+            let ty = self.ty(expr);
+            return Ok(self.builder.function(&self.names[&id], ty));
+        }
+        let slot = self.variable(expr)?;
+        Ok(self.builder.load(slot))
+    }
+
+    /// A reference to the variable `expr` names.
+    fn variable(&mut self, expr: &Expr<'t>) -> Result<Value, miette::Report> {
+        let ExprKind::Identifier(Ident { name, .. }) = expr.kind else {
+            unreachable!("only names can be loaded")
+        };
+        let Some(id) = self.resolution.binding(expr) else {
+            unreachable!("`{name}` wasn't resolved, which should have been reported")
+        };
+
+        let Some(slot) = self.slot(id) else {
+            unreachable!(
+                "`{name}` belongs to an enclosing function, which should have been reported"
+            )
+        };
+        Ok(slot)
+    }
+
+    /// A reference to what `expr` refers to, storing any value which isn't a variable in memory of its own.
+    fn place(&mut self, expr: &Expr<'t>) -> Result<Value, miette::Report> {
+        match &expr.kind {
+            ExprKind::Identifier(_) => {
+                let is_function = self.resolution.binding(expr).is_some_and(|id| {
+                    self.resolution.symbols.symbol(id).kind == SymbolKind::Function
+                });
+                if !is_function {
+                    return self.variable(expr);
+                }
+            }
+            ExprKind::Unary(UnaryOp::Deref, operand) => return self.expr(operand),
+            _ => {}
+        }
+
+        let value = self.expr(expr)?;
+        let slot = self.builder.alloca(self.builder.ty(value).clone());
+        self.builder.store(slot, value);
+        Ok(slot)
+    }
+
+    fn unary(&mut self, op: UnaryOp, operand: &Expr<'t>) -> Result<Value, miette::Report> {
+        let value = match op {
+            UnaryOp::Reference => self.place(operand)?,
+            UnaryOp::Plus => self.expr(operand)?,
+            UnaryOp::Deref => {
+                let pointer = self.expr(operand)?;
+                self.builder.load(pointer)
+            }
+            UnaryOp::Negate => {
+                let value = self.expr(operand)?;
+                self.builder.negate(value)
+            }
+            UnaryOp::Not => {
+                let value = self.expr(operand)?;
+                self.builder.not(value)
+            }
+        };
+        Ok(value)
+    }
+
+    fn binary(
+        &mut self,
+        op: BinaryOp,
+        left: &Expr<'t>,
+        right: &Expr<'t>,
+    ) -> Result<Value, miette::Report> {
+        let compound = match op {
+            BinaryOp::AddAssign => Some(ir::BinaryOp::Add),
+            BinaryOp::SubtractAssign => Some(ir::BinaryOp::Subtract),
+            BinaryOp::MultiplyAssign => Some(ir::BinaryOp::Multiply),
+            _ => None,
+        };
+        if op == BinaryOp::Assign || compound.is_some() {
+            let slot = self.place(left)?;
+            let mut value = self.expr(right)?;
+            if let Some(op) = compound {
+                let current = self.builder.load(slot);
+                value = self.builder.binary(op, current, value);
+            }
+            self.builder.store(slot, value);
+            return Ok(self.builder.unit());
+        }
+
+        let left = self.expr(left)?;
+        let right = self.expr(right)?;
+        let value = match op {
+            BinaryOp::Add => self.builder.binary(ir::BinaryOp::Add, left, right),
+            BinaryOp::Subtract => self.builder.binary(ir::BinaryOp::Subtract, left, right),
+            BinaryOp::Multiply => self.builder.binary(ir::BinaryOp::Multiply, left, right),
+            BinaryOp::Divide => self.builder.binary(ir::BinaryOp::Divide, left, right),
+            BinaryOp::Power => self.builder.binary(ir::BinaryOp::Power, left, right),
+            BinaryOp::ShiftLeft => self.builder.binary(ir::BinaryOp::ShiftLeft, left, right),
+            BinaryOp::ShiftRight => self.builder.binary(ir::BinaryOp::ShiftRight, left, right),
+            BinaryOp::Equal => self.builder.compare(Comparison::Equal, left, right),
+            BinaryOp::NotEqual => self.builder.compare(Comparison::NotEqual, left, right),
+            BinaryOp::Less => self.builder.compare(Comparison::Less, left, right),
+            BinaryOp::LessEqual => self.builder.compare(Comparison::LessEqual, left, right),
+            BinaryOp::Greater => self.builder.compare(Comparison::Greater, left, right),
+            BinaryOp::GreaterEqual => self.builder.compare(Comparison::GreaterEqual, left, right),
+            BinaryOp::Assign
+            | BinaryOp::AddAssign
+            | BinaryOp::SubtractAssign
+            | BinaryOp::MultiplyAssign => unreachable!("assignments are lowered above"),
+        };
+        Ok(value)
+    }
+
+    /// An integer literal as a constant of the type the checker gave `expr`.
+    fn literal(
+        &mut self,
+        literal: &str,
+        negative: bool,
+        expr: &Expr<'t>,
+    ) -> Result<Value, miette::Report> {
+        let ty = self.ty(expr);
+        let value = literal
+            .parse::<i128>()
+            .ok()
+            .map(|value| if negative { -value } else { value })
+            .filter(|&value| ty.fits(value));
+
+        match value {
+            Some(value) => Ok(self.builder.int(ty, value)),
+            None => Err(LiteralOutOfRange {
+                ty,
+                span: expr.span,
+            }
+            .into()),
+        }
+    }
+}
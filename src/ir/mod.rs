@@ -0,0 +1,217 @@
+//! A mid-level intermediate representation in SSA form, between the syntax tree and the backends, written by
+//! `--emit=ir`.
+//!
+//! A [`Function`] is a list of basic blocks, each a sequence of [`Instruction`]s ended by a [`Terminator`], where the
+//! first block is the entry block. Each instruction defines at most one [`Value`], whose type is fixed when it's
+//! defined and which is never assigned again, and values which depend on the path taken to a block are joined by phi
+//! instructions at its start. Variables live in memory allocated by `alloca`, or in the module's globals.
+//!
+//! Functions are built with [`builder::FunctionBuilder`] and checked with [`verify::verify`]. Modules are written as
+//! text by their [`Display`](std::fmt::Display) implementation and read back by [`parse::parse`], so tests can write
+//! IR directly.
+
+use crate::sema::types::Type;
+
+pub mod builder;
+pub mod lower;
+pub mod parse;
+mod print;
+pub mod verify;
+
+/// A value defined by a parameter or an instruction, indexing [`Function::values`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Value(pub u32);
+
+/// A basic block, indexing [`Function::blocks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Block(pub u32);
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Module {
+    pub globals: Vec<Global>,
+    pub functions: Vec<Function>,
+}
+
+impl Module {
+    pub fn global(&self, name: &str) -> Option<&Global> {
+        self.globals.iter().find(|global| global.name == name)
+    }
+
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+}
+
+/// A variable which lives as long as the program, and starts as zero.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub name: String,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Value>,
+    pub return_type: Type,
+    pub blocks: Vec<BlockData>,
+    /// The type of each value
+    pub values: Vec<Type>,
+}
+
+impl Function {
+    pub fn ty(&self, value: Value) -> &Type {
+        &self.values[value.0 as usize]
+    }
+
+    pub fn block(&self, block: Block) -> &BlockData {
+        &self.blocks[block.0 as usize]
+    }
+
+    /// The type of the function as a value, eg. `fn(i64) -> i64`.
+    pub fn signature(&self) -> Type {
+        let params = self.params.iter().map(|&param| self.ty(param).clone());
+        Type::Function(params.collect(), Box::new(self.return_type.clone()))
+    }
+
+    /// The blocks which can branch to each block.
+    pub fn predecessors(&self) -> Vec<Vec<Block>> {
+        let mut predecessors = vec![Vec::new(); self.blocks.len()];
+        for (index, block) in self.blocks.iter().enumerate() {
+            for successor in block.successors() {
+                if let Some(predecessors) = predecessors.get_mut(successor.0 as usize) {
+                    if !predecessors.contains(&Block(index as u32)) {
+                        predecessors.push(Block(index as u32));
+                    }
+                }
+            }
+        }
+        predecessors
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BlockData {
+    pub instructions: Vec<Instruction>,
+    /// Only `None` while the block is being built
+    pub terminator: Option<Terminator>,
+}
+
+impl BlockData {
+    pub fn successors(&self) -> Vec<Block> {
+        match self.terminator {
+            Some(Terminator::Jump(target)) => vec![target],
+            Some(Terminator::Branch {
+                then, otherwise, ..
+            }) => vec![then, otherwise],
+            Some(Terminator::Return(_)) | None => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    /// Only `None` for instructions which don't produce a value, which is only `store`
+    pub result: Option<Value>,
+    pub kind: InstructionKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstructionKind {
+    /// An integer of the result's type
+    Int(i128),
+    Bool(bool),
+    /// Integer arithmetic, which traps where `run` would report an error
+    Binary(BinaryOp, Value, Value),
+    Compare(Comparison, Value, Value),
+    /// Negates an integer, trapping if the result doesn't fit
+    Negate(Value),
+    /// Flips every bit of an integer, or negates a `bool`
+    Not(Value),
+    Tuple(Vec<Value>),
+    /// An element of a tuple
+    Extract(Value, u32),
+    /// Allocates memory in the function's frame for a value of the type the result refers to
+    Alloca,
+    Load(Value),
+    Store {
+        pointer: Value,
+        value: Value,
+    },
+    /// A reference to a global
+    Global(String),
+    /// A function of the module, as a value which can be called
+    Function(String),
+    Call(Value, Vec<Value>),
+    /// The value from whichever block was branched from
+    Phi(Vec<(Block, Value)>),
+}
+
+impl InstructionKind {
+    /// The values the instruction uses.
+    pub fn operands(&self) -> Vec<Value> {
+        match self {
+            InstructionKind::Int(_)
+            | InstructionKind::Bool(_)
+            | InstructionKind::Alloca
+            | InstructionKind::Global(_)
+            | InstructionKind::Function(_) => Vec::new(),
+            InstructionKind::Binary(_, left, right) | InstructionKind::Compare(_, left, right) => {
+                vec![*left, *right]
+            }
+            InstructionKind::Negate(value)
+            | InstructionKind::Not(value)
+            | InstructionKind::Extract(value, _)
+            | InstructionKind::Load(value) => vec![*value],
+            InstructionKind::Tuple(elements) => elements.clone(),
+            InstructionKind::Store { pointer, value } => vec![*pointer, *value],
+            InstructionKind::Call(callee, arguments) => std::iter::once(*callee)
+                .chain(arguments.iter().copied())
+                .collect(),
+            InstructionKind::Phi(incoming) => incoming.iter().map(|&(_, value)| value).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    ShiftLeft,
+    ShiftRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Terminator {
+    Jump(Block),
+    /// Continues from `then` if `condition` is true, and from `otherwise` if it isn't
+    Branch {
+        condition: Value,
+        then: Block,
+        otherwise: Block,
+    },
+    Return(Value),
+}
+
+impl Terminator {
+    pub fn operands(&self) -> Vec<Value> {
+        match *self {
+            Terminator::Jump(_) => Vec::new(),
+            Terminator::Branch { condition, .. } => vec![condition],
+            Terminator::Return(value) => vec![value],
+        }
+    }
+}
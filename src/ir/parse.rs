@@ -0,0 +1,656 @@
+//! Reads the textual form of the IR written by its [`Display`](std::fmt::Display) implementation.
+//!
+//! Values and blocks can be given any name, and can be used before they're defined, so IR can be written by hand:
+//!
+//! ```text
+//! fn @count(%n: i64) -> i64 {
+//! entry:
+//!     %zero: i64 = const 0
+//!     jump loop
+//! loop:
+//!     %i: i64 = phi [entry: %zero], [body: %next]
+//!     %done: bool = ge %i, %n
+//!     branch %done, exit, body
+//! body:
+//!     %one: i64 = const 1
+//!     %next: i64 = add %i, %one
+//!     jump loop
+//! exit:
+//!     return %i
+//! }
+//! ```
+//!
+//! The parser only checks the syntax. Whether the IR makes sense is left to [`verify`](crate::ir::verify::verify).
+
+use std::collections::HashMap;
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::ir::print::{global, is_name_char};
+use crate::ir::{
+    BinaryOp, Block, BlockData, Comparison, Function, Global, Instruction, InstructionKind, Module,
+    Terminator, Value,
+};
+use crate::sema::types::Type;
+
+/// Text which isn't IR as it's printed
+#[derive(Error, Diagnostic, Debug)]
+#[error("{message}")]
+#[diagnostic(code(E0030))]
+pub struct SyntaxError {
+    pub message: String,
+    #[label("here")]
+    pub span: SourceSpan,
+}
+
+/// Parses a module, reporting the first syntax error in `text`.
+pub fn parse(text: &str) -> Result<Module, miette::Report> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+    };
+    parser.module()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind<'t> {
+    /// A keyword, type or label
+    Word(&'t str),
+    /// A value's name, without its `%`
+    Value(&'t str),
+    /// A global's name, without its `@` or quotes
+    Global(&'t str),
+    Int(i128),
+    Punct(&'static str),
+    Newline,
+    End,
+}
+
+#[derive(Debug, Clone)]
+struct Token<'t> {
+    kind: Kind<'t>,
+    span: SourceSpan,
+}
+
+const PUNCTUATION: &[&str] = &["->", "(", ")", "[", "]", "{", "}", ",", ":", "=", "&"];
+
+fn tokenize(text: &str) -> Result<Vec<Token<'_>>, miette::Report> {
+    let mut tokens = Vec::new();
+    let bytes = text.as_bytes();
+    let mut start = 0;
+
+    // The end of the name starting at `from`
+    let name_end = |from: usize| {
+        text[from..]
+            .find(|char: char| !is_name_char(char))
+            .map_or(text.len(), |length| from + length)
+    };
+
+    while start < text.len() {
+        let char = text[start..].chars().next().unwrap();
+        let (kind, end) = match char {
+            '\n' => (Kind::Newline, start + 1),
+            ';' => {
+                start = text[start..]
+                    .find('\n')
+                    .map_or(text.len(), |end| start + end);
+                continue;
+            }
+            _ if char.is_whitespace() => {
+                start += char.len_utf8();
+                continue;
+            }
+            '%' => {
+                let end = name_end(start + 1);
+                (Kind::Value(&text[start + 1..end]), end)
+            }
+            '@' if bytes.get(start + 1) == Some(&b'"') => {
+                let Some(length) = text[start + 2..].find('"') else {
+                    return Err(error("Unterminated name", start, 1));
+                };
+                let end = start + 2 + length;
+                (Kind::Global(&text[start + 2..end]), end + 1)
+            }
+            '@' => {
+                let end = name_end(start + 1);
+                (Kind::Global(&text[start + 1..end]), end)
+            }
+            '-' if bytes.get(start + 1).is_some_and(u8::is_ascii_digit) => {
+                let end = name_end(start + 1);
+                (integer(text, start, end)?, end)
+            }
+            _ if char.is_ascii_digit() => {
+                let end = name_end(start);
+                (integer(text, start, end)?, end)
+            }
+            _ if is_name_char(char) => {
+                let end = name_end(start);
+                (Kind::Word(&text[start..end]), end)
+            }
+            _ => match PUNCTUATION
+                .iter()
+                .find(|punct| text[start..].starts_with(**punct))
+            {
+                Some(punct) => (Kind::Punct(punct), start + punct.len()),
+                None => {
+                    return Err(error(
+                        &format!("Unexpected character `{char}`"),
+                        start,
+                        char.len_utf8(),
+                    ))
+                }
+            },
+        };
+
+        tokens.push(Token {
+            kind,
+            span: SourceSpan::from((start, end - start)),
+        });
+        start = end;
+    }
+
+    tokens.push(Token {
+        kind: Kind::End,
+        span: SourceSpan::from((text.len(), 0)),
+    });
+    Ok(tokens)
+}
+
+fn integer(text: &str, start: usize, end: usize) -> Result<Kind<'_>, miette::Report> {
+    text[start..end]
+        .parse()
+        .map(Kind::Int)
+        .map_err(|_| error("Invalid integer", start, end - start))
+}
+
+fn error(message: &str, offset: usize, length: usize) -> miette::Report {
+    SyntaxError {
+        message: message.to_owned(),
+        span: SourceSpan::from((offset, length)),
+    }
+    .into()
+}
+
+/// The names given to the values and blocks of the function being parsed.
+#[derive(Default)]
+struct Scope<'t> {
+    values: HashMap<&'t str, Value>,
+    blocks: HashMap<&'t str, Block>,
+}
+
+struct Parser<'t> {
+    tokens: Vec<Token<'t>>,
+    position: usize,
+}
+
+impl<'t> Parser<'t> {
+    fn peek(&self) -> &Kind<'t> {
+        &self.tokens[self.position].kind
+    }
+
+    fn peek_at(&self, offset: usize) -> &Kind<'t> {
+        let index = (self.position + offset).min(self.tokens.len() - 1);
+        &self.tokens[index].kind
+    }
+
+    fn span(&self) -> SourceSpan {
+        self.tokens[self.position].span
+    }
+
+    fn next(&mut self) -> Token<'t> {
+        let token = self.tokens[self.position].clone();
+        if token.kind != Kind::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn unexpected(&self, expected: &str) -> miette::Report {
+        let span = self.span();
+        let found = match self.peek() {
+            Kind::Newline => "the end of the line".to_owned(),
+            Kind::End => "the end of the input".to_owned(),
+            Kind::Word(word) => format!("`{word}`"),
+            Kind::Value(name) => format!("`%{name}`"),
+            Kind::Global(name) => format!("`{}`", global(name)),
+            Kind::Int(value) => format!("`{value}`"),
+            Kind::Punct(punct) => format!("`{punct}`"),
+        };
+        error(
+            &format!("Expected {expected}, found {found}"),
+            span.offset(),
+            span.len(),
+        )
+    }
+
+    fn expect(&mut self, punct: &'static str) -> Result<(), miette::Report> {
+        if *self.peek() == Kind::Punct(punct) {
+            self.next();
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{punct}`")))
+        }
+    }
+
+    fn eat(&mut self, punct: &'static str) -> bool {
+        let matches = *self.peek() == Kind::Punct(punct);
+        if matches {
+            self.next();
+        }
+        matches
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), miette::Report> {
+        if *self.peek() == Kind::Word(word) {
+            self.next();
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{word}`")))
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), miette::Report> {
+        match self.peek() {
+            Kind::Newline => {
+                self.skip_newlines();
+                Ok(())
+            }
+            Kind::End => Ok(()),
+            _ => Err(self.unexpected("the end of the line")),
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while *self.peek() == Kind::Newline {
+            self.next();
+        }
+    }
+
+    fn global_name(&mut self) -> Result<String, miette::Report> {
+        match self.peek().clone() {
+            Kind::Global(name) => {
+                self.next();
+                Ok(name.to_owned())
+            }
+            _ => Err(self.unexpected("a name starting with `@`")),
+        }
+    }
+
+    fn integer(&mut self) -> Result<i128, miette::Report> {
+        match *self.peek() {
+            Kind::Int(value) => {
+                self.next();
+                Ok(value)
+            }
+            _ => Err(self.unexpected("an integer")),
+        }
+    }
+
+    fn module(&mut self) -> Result<Module, miette::Report> {
+        let mut module = Module::default();
+        self.skip_newlines();
+
+        loop {
+            match self.peek() {
+                Kind::Word("global") => {
+                    self.next();
+                    let name = self.global_name()?;
+                    self.expect(":")?;
+                    let ty = self.ty()?;
+                    module.globals.push(Global { name, ty });
+                }
+                Kind::Word("fn") => {
+                    let function = self.function()?;
+                    module.functions.push(function);
+                }
+                Kind::End => return Ok(module),
+                _ => return Err(self.unexpected("`global` or `fn`")),
+            }
+            self.end_of_line()?;
+        }
+    }
+
+    fn ty(&mut self) -> Result<Type, miette::Report> {
+        let span = self.span();
+        match self.next().kind {
+            Kind::Punct("&") => Ok(Type::Reference(Box::new(self.ty()?))),
+            Kind::Punct("(") => {
+                let mut elements = Vec::new();
+                while !self.eat(")") {
+                    elements.push(self.ty()?);
+                    if !self.eat(",") {
+                        self.expect(")")?;
+                        break;
+                    }
+                }
+                Ok(Type::Tuple(elements))
+            }
+            Kind::Word("fn") => {
+                self.expect("(")?;
+                let mut params = Vec::new();
+                while !self.eat(")") {
+                    params.push(self.ty()?);
+                    if !self.eat(",") {
+                        self.expect(")")?;
+                        break;
+                    }
+                }
+                self.expect("->")?;
+                Ok(Type::Function(params, Box::new(self.ty()?)))
+            }
+            Kind::Word(name) => Type::builtin(name)
+                .ok_or_else(|| error(&format!("Unknown type `{name}`"), span.offset(), span.len())),
+            _ => {
+                self.position -= 1;
+                Err(self.unexpected("a type"))
+            }
+        }
+    }
+
+    fn function(&mut self) -> Result<Function, miette::Report> {
+        self.expect_word("fn")?;
+        let name = self.global_name()?;
+        let mut scope = Scope::default();
+        let mut function = Function {
+            name,
+            params: Vec::new(),
+            return_type: Type::unit(),
+            blocks: Vec::new(),
+            values: Vec::new(),
+        };
+
+        self.expect("(")?;
+        while !self.eat(")") {
+            let param = self.define(&mut scope, &mut function)?;
+            self.expect(":")?;
+            function.values[param.0 as usize] = self.ty()?;
+            function.params.push(param);
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+        self.expect("->")?;
+        function.return_type = self.ty()?;
+        self.expect("{")?;
+        self.end_of_line()?;
+
+        self.declare(&mut scope, &mut function)?;
+        while *self.peek() != Kind::Punct("}") {
+            self.block(&scope, &mut function)?;
+        }
+        self.next();
+
+        Ok(function)
+    }
+
+    /// Gives a number to every value and block defined in the function's body, so they can be used before they're
+    /// defined, in the order they're defined.
+    fn declare(
+        &mut self,
+        scope: &mut Scope<'t>,
+        function: &mut Function,
+    ) -> Result<(), miette::Report> {
+        let start = self.position;
+        let mut line_start = true;
+
+        while !matches!(self.peek(), Kind::Punct("}") | Kind::End) || !line_start {
+            if line_start && *self.peek_at(1) == Kind::Punct(":") {
+                match self.peek().clone() {
+                    Kind::Value(_) => {
+                        self.define(scope, function)?;
+                    }
+                    Kind::Word(label) => {
+                        if scope.blocks.contains_key(label) {
+                            return Err(self.redefined(label));
+                        }
+                        scope
+                            .blocks
+                            .insert(label, Block(function.blocks.len() as u32));
+                        function.blocks.push(BlockData::default());
+                    }
+                    _ => {}
+                }
+            }
+            line_start = matches!(self.next().kind, Kind::Newline | Kind::End);
+            if *self.peek() == Kind::End {
+                break;
+            }
+        }
+
+        self.position = start;
+        Ok(())
+    }
+
+    /// Gives the next number to the value named by the current token.
+    fn define(
+        &mut self,
+        scope: &mut Scope<'t>,
+        function: &mut Function,
+    ) -> Result<Value, miette::Report> {
+        let Kind::Value(name) = *self.peek() else {
+            return Err(self.unexpected("a name starting with `%`"));
+        };
+        if scope.values.contains_key(name) {
+            return Err(self.redefined(&format!("%{name}")));
+        }
+        self.next();
+
+        let value = Value(function.values.len() as u32);
+        function.values.push(Type::Error);
+        scope.values.insert(name, value);
+        Ok(value)
+    }
+
+    fn redefined(&self, name: &str) -> miette::Report {
+        let span = self.span();
+        error(
+            &format!("`{name}` is defined more than once"),
+            span.offset(),
+            span.len(),
+        )
+    }
+
+    fn value(&mut self, scope: &Scope<'t>) -> Result<Value, miette::Report> {
+        let span = self.span();
+        let Kind::Value(name) = *self.peek() else {
+            return Err(self.unexpected("a value"));
+        };
+        self.next();
+        scope.values.get(name).copied().ok_or_else(|| {
+            error(
+                &format!("`%{name}` isn't defined"),
+                span.offset(),
+                span.len(),
+            )
+        })
+    }
+
+    fn values(&mut self, scope: &Scope<'t>, end: &Kind) -> Result<Vec<Value>, miette::Report> {
+        let mut values = Vec::new();
+        if self.peek() == end {
+            return Ok(values);
+        }
+        loop {
+            values.push(self.value(scope)?);
+            if !self.eat(",") {
+                return Ok(values);
+            }
+        }
+    }
+
+    fn label(&mut self, scope: &Scope<'t>) -> Result<Block, miette::Report> {
+        let span = self.span();
+        let Kind::Word(label) = *self.peek() else {
+            return Err(self.unexpected("a block"));
+        };
+        self.next();
+        scope.blocks.get(label).copied().ok_or_else(|| {
+            error(
+                &format!("There's no block called `{label}`"),
+                span.offset(),
+                span.len(),
+            )
+        })
+    }
+
+    fn block(&mut self, scope: &Scope<'t>, function: &mut Function) -> Result<(), miette::Report> {
+        let block = self.label(scope)?;
+        self.expect(":")?;
+        self.end_of_line()?;
+
+        let mut data = BlockData::default();
+        loop {
+            let line_is_label =
+                matches!(self.peek(), Kind::Word(_)) && *self.peek_at(1) == Kind::Punct(":");
+            if line_is_label || matches!(self.peek(), Kind::Punct("}") | Kind::End) {
+                break;
+            }
+
+            match self.peek() {
+                Kind::Word("jump") => {
+                    self.next();
+                    data.terminator = Some(Terminator::Jump(self.label(scope)?));
+                }
+                Kind::Word("branch") => {
+                    self.next();
+                    let condition = self.value(scope)?;
+                    self.expect(",")?;
+                    let then = self.label(scope)?;
+                    self.expect(",")?;
+                    let otherwise = self.label(scope)?;
+                    data.terminator = Some(Terminator::Branch {
+                        condition,
+                        then,
+                        otherwise,
+                    });
+                }
+                Kind::Word("return") => {
+                    self.next();
+                    data.terminator = Some(Terminator::Return(self.value(scope)?));
+                }
+                _ if data.terminator.is_some() => {
+                    return Err(self.unexpected("a block after the block's terminator"))
+                }
+                Kind::Word("store") => {
+                    self.next();
+                    let pointer = self.value(scope)?;
+                    self.expect(",")?;
+                    let value = self.value(scope)?;
+                    data.instructions.push(Instruction {
+                        result: None,
+                        kind: InstructionKind::Store { pointer, value },
+                    });
+                }
+                _ => {
+                    let result = self.value(scope)?;
+                    self.expect(":")?;
+                    function.values[result.0 as usize] = self.ty()?;
+                    self.expect("=")?;
+                    let kind = self.instruction(scope)?;
+                    data.instructions.push(Instruction {
+                        result: Some(result),
+                        kind,
+                    });
+                }
+            }
+            self.end_of_line()?;
+        }
+
+        function.blocks[block.0 as usize] = data;
+        Ok(())
+    }
+
+    fn instruction(&mut self, scope: &Scope<'t>) -> Result<InstructionKind, miette::Report> {
+        let span = self.span();
+        let Kind::Word(mnemonic) = self.next().kind else {
+            self.position -= 1;
+            return Err(self.unexpected("an instruction"));
+        };
+
+        let binary = match mnemonic {
+            "add" => Some(BinaryOp::Add),
+            "sub" => Some(BinaryOp::Subtract),
+            "mul" => Some(BinaryOp::Multiply),
+            "div" => Some(BinaryOp::Divide),
+            "pow" => Some(BinaryOp::Power),
+            "shl" => Some(BinaryOp::ShiftLeft),
+            "shr" => Some(BinaryOp::ShiftRight),
+            _ => None,
+        };
+        let comparison = match mnemonic {
+            "eq" => Some(Comparison::Equal),
+            "ne" => Some(Comparison::NotEqual),
+            "lt" => Some(Comparison::Less),
+            "le" => Some(Comparison::LessEqual),
+            "gt" => Some(Comparison::Greater),
+            "ge" => Some(Comparison::GreaterEqual),
+            _ => None,
+        };
+        if binary.is_some() || comparison.is_some() {
+            let left = self.value(scope)?;
+            self.expect(",")?;
+            let right = self.value(scope)?;
+            return Ok(match (binary, comparison) {
+                (Some(op), _) => InstructionKind::Binary(op, left, right),
+                (_, Some(comparison)) => InstructionKind::Compare(comparison, left, right),
+                _ => unreachable!(),
+            });
+        }
+
+        let kind = match mnemonic {
+            "const" => match self.peek() {
+                Kind::Word("true") | Kind::Word("false") => {
+                    InstructionKind::Bool(self.next().kind == Kind::Word("true"))
+                }
+                _ => InstructionKind::Int(self.integer()?),
+            },
+            "neg" => InstructionKind::Negate(self.value(scope)?),
+            "not" => InstructionKind::Not(self.value(scope)?),
+            "tuple" => InstructionKind::Tuple(self.values(scope, &Kind::Newline)?),
+            "extract" => {
+                let tuple = self.value(scope)?;
+                self.expect(",")?;
+                let span = self.span();
+                let index = u32::try_from(self.integer()?)
+                    .map_err(|_| error("Invalid index", span.offset(), span.len()))?;
+                InstructionKind::Extract(tuple, index)
+            }
+            "alloca" => InstructionKind::Alloca,
+            "load" => InstructionKind::Load(self.value(scope)?),
+            "global" => InstructionKind::Global(self.global_name()?),
+            "function" => InstructionKind::Function(self.global_name()?),
+            "call" => {
+                let callee = self.value(scope)?;
+                self.expect("(")?;
+                let arguments = self.values(scope, &Kind::Punct(")"))?;
+                self.expect(")")?;
+                InstructionKind::Call(callee, arguments)
+            }
+            "phi" => {
+                let mut incoming = Vec::new();
+                loop {
+                    self.expect("[")?;
+                    let block = self.label(scope)?;
+                    self.expect(":")?;
+                    incoming.push((block, self.value(scope)?));
+                    self.expect("]")?;
+                    if !self.eat(",") {
+                        break;
+                    }
+                }
+                InstructionKind::Phi(incoming)
+            }
+            _ => {
+                return Err(error(
+                    &format!("Unknown instruction `{mnemonic}`"),
+                    span.offset(),
+                    span.len(),
+                ))
+            }
+        };
+        Ok(kind)
+    }
+}
@@ -0,0 +1,196 @@
+//! The textual form of the IR, which [`parse`](crate::ir::parse::parse) reads back.
+//!
+//! Values are numbered in the order they're defined and blocks in the order they're listed, so a module printed after
+//! being parsed reads the same as the text it was parsed from, whichever order it was built in.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+use crate::ir::{
+    BinaryOp, Block, Comparison, Function, Instruction, InstructionKind, Module, Terminator, Value,
+};
+
+impl BinaryOp {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "add",
+            BinaryOp::Subtract => "sub",
+            BinaryOp::Multiply => "mul",
+            BinaryOp::Divide => "div",
+            BinaryOp::Power => "pow",
+            BinaryOp::ShiftLeft => "shl",
+            BinaryOp::ShiftRight => "shr",
+        }
+    }
+}
+
+impl Comparison {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Comparison::Equal => "eq",
+            Comparison::NotEqual => "ne",
+            Comparison::Less => "lt",
+            Comparison::LessEqual => "le",
+            Comparison::Greater => "gt",
+            Comparison::GreaterEqual => "ge",
+        }
+    }
+}
+
+impl Display for Block {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "b{}", self.0)
+    }
+}
+
+/// The number each value of a function is printed with.
+pub(crate) struct Names(HashMap<Value, usize>);
+
+impl Names {
+    pub(crate) fn new(function: &Function) -> Self {
+        let mut names = HashMap::new();
+        let results = function
+            .blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+            .filter_map(|instruction| instruction.result);
+        for value in function.params.iter().copied().chain(results) {
+            let next = names.len();
+            names.entry(value).or_insert(next);
+        }
+
+        Names(names)
+    }
+
+    /// The value as it's printed, which is numbered after every defined value if it isn't defined.
+    pub(crate) fn value(&self, value: Value) -> String {
+        match self.0.get(&value) {
+            Some(number) => format!("%{number}"),
+            None => format!("%{}", self.0.len() + value.0 as usize),
+        }
+    }
+
+    fn list(&self, values: &[Value]) -> String {
+        let values: Vec<String> = values.iter().map(|&value| self.value(value)).collect();
+        values.join(", ")
+    }
+
+    pub(crate) fn instruction(&self, function: &Function, instruction: &Instruction) -> String {
+        let operation = match &instruction.kind {
+            InstructionKind::Int(value) => format!("const {value}"),
+            InstructionKind::Bool(value) => format!("const {value}"),
+            InstructionKind::Binary(op, left, right) => {
+                format!("{} {}", op.mnemonic(), self.list(&[*left, *right]))
+            }
+            InstructionKind::Compare(comparison, left, right) => {
+                format!("{} {}", comparison.mnemonic(), self.list(&[*left, *right]))
+            }
+            InstructionKind::Negate(value) => format!("neg {}", self.value(*value)),
+            InstructionKind::Not(value) => format!("not {}", self.value(*value)),
+            InstructionKind::Tuple(elements) if elements.is_empty() => "tuple".into(),
+            InstructionKind::Tuple(elements) => format!("tuple {}", self.list(elements)),
+            InstructionKind::Extract(tuple, index) => {
+                format!("extract {}, {index}", self.value(*tuple))
+            }
+            InstructionKind::Alloca => "alloca".into(),
+            InstructionKind::Load(pointer) => format!("load {}", self.value(*pointer)),
+            InstructionKind::Store { pointer, value } => {
+                format!("store {}", self.list(&[*pointer, *value]))
+            }
+            InstructionKind::Global(name) => format!("global {}", global(name)),
+            InstructionKind::Function(name) => format!("function {}", global(name)),
+            InstructionKind::Call(callee, arguments) => {
+                format!("call {}({})", self.value(*callee), self.list(arguments))
+            }
+            InstructionKind::Phi(incoming) => {
+                let incoming: Vec<String> = incoming
+                    .iter()
+                    .map(|(block, value)| format!("[{block}: {}]", self.value(*value)))
+                    .collect();
+                format!("phi {}", incoming.join(", "))
+            }
+        };
+
+        match instruction.result {
+            Some(result) => format!(
+                "{}: {} = {operation}",
+                self.value(result),
+                function.ty(result)
+            ),
+            None => operation,
+        }
+    }
+
+    pub(crate) fn terminator(&self, terminator: &Terminator) -> String {
+        match terminator {
+            Terminator::Jump(target) => format!("jump {target}"),
+            Terminator::Branch {
+                condition,
+                then,
+                otherwise,
+            } => format!("branch {}, {then}, {otherwise}", self.value(*condition)),
+            Terminator::Return(value) => format!("return {}", self.value(*value)),
+        }
+    }
+}
+
+/// The name of a global or function, quoted unless it only has characters which can be written without quotes.
+pub(crate) fn global(name: &str) -> String {
+    let bare = !name.is_empty()
+        && name.chars().all(is_name_char)
+        && !name.starts_with(|char: char| char.is_ascii_digit());
+    if bare {
+        format!("@{name}")
+    } else {
+        format!("@\"{name}\"")
+    }
+}
+
+/// Whether `char` can be part of a name written without quotes.
+pub(crate) fn is_name_char(char: char) -> bool {
+    char.is_ascii_alphanumeric() || matches!(char, '_' | '.')
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let names = Names::new(self);
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|&param| format!("{}: {}", names.value(param), self.ty(param)))
+            .collect();
+        writeln!(
+            f,
+            "fn {}({}) -> {} {{",
+            global(&self.name),
+            params.join(", "),
+            self.return_type
+        )?;
+
+        for (index, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "{}:", Block(index as u32))?;
+            for instruction in &block.instructions {
+                writeln!(f, "    {}", names.instruction(self, instruction))?;
+            }
+            if let Some(terminator) = &block.terminator {
+                writeln!(f, "    {}", names.terminator(terminator))?;
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl Display for Module {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for global in &self.globals {
+            writeln!(f, "global {}: {}", self::global(&global.name), global.ty)?;
+        }
+        for (index, function) in self.functions.iter().enumerate() {
+            if index > 0 || !self.globals.is_empty() {
+                writeln!(f)?;
+            }
+            write!(f, "{function}")?;
+        }
+        Ok(())
+    }
+}
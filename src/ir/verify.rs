@@ -0,0 +1,612 @@
+//! Checks that a module is well formed: every block ends in a terminator, every value is defined once before it's
+//! used, and every instruction is given operands of the types it expects.
+//!
+//! A value can be used wherever its definition dominates the use, meaning every path from the entry block to the use
+//! passes through the definition. A phi instruction uses each of its values at the end of the block it comes from
+//! rather than in its own block, so a loop can join a value with the one computed by its last iteration.
+
+use std::collections::{HashMap, HashSet};
+
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::ir::print::{global, Names};
+use crate::ir::{
+    BinaryOp, Block, Comparison, Function, Instruction, InstructionKind, Module, Terminator, Value,
+};
+use crate::sema::types::Type;
+
+/// IR which parses, but breaks one of the rules the backends rely on
+#[derive(Error, Diagnostic, Debug)]
+#[error("Invalid IR in `{function}`: {message}")]
+#[diagnostic(code(E0031))]
+pub struct Invalid {
+    pub function: String,
+    pub message: String,
+}
+
+/// Reports the first problem found in `module`, naming values as they're printed.
+pub fn verify(module: &Module) -> Result<(), miette::Report> {
+    let mut names = HashSet::new();
+    for name in module.globals.iter().map(|global| &global.name) {
+        if !names.insert(name) {
+            return Err(invalid(
+                name,
+                "there's more than one global or function with this name".into(),
+            ));
+        }
+    }
+    for function in &module.functions {
+        if !names.insert(&function.name) {
+            return Err(invalid(
+                &function.name,
+                "there's more than one global or function with this name".into(),
+            ));
+        }
+    }
+    for global in &module.globals {
+        if !representable(&global.ty) {
+            return Err(invalid(
+                &global.name,
+                format!("globals can't have type {}", global.ty),
+            ));
+        }
+    }
+
+    for function in &module.functions {
+        let verifier = Verifier {
+            module,
+            function,
+            names: Names::new(function),
+            definitions: HashMap::new(),
+        };
+        verifier
+            .verify()
+            .map_err(|message| invalid(&function.name, message))?;
+    }
+    Ok(())
+}
+
+fn invalid(function: &str, message: String) -> miette::Report {
+    Invalid {
+        function: global(function),
+        message,
+    }
+    .into()
+}
+
+/// Whether values in the IR can have type `ty`.
+fn representable(ty: &Type) -> bool {
+    match ty {
+        Type::Int { .. } | Type::Bool => true,
+        Type::Tuple(elements) => elements.iter().all(representable),
+        Type::Reference(inner) => representable(inner),
+        Type::Function(params, return_type) => {
+            params.iter().all(representable) && representable(return_type)
+        }
+        _ => false,
+    }
+}
+
+/// Where a value is defined.
+#[derive(Debug, Clone, Copy)]
+enum Definition {
+    Param,
+    /// The index of the instruction defining it in a block
+    Instruction(Block, usize),
+}
+
+struct Verifier<'m> {
+    module: &'m Module,
+    function: &'m Function,
+    names: Names,
+    definitions: HashMap<Value, Definition>,
+}
+
+impl Verifier<'_> {
+    fn verify(mut self) -> Result<(), String> {
+        self.structure()?;
+
+        for (index, block) in self.function.blocks.iter().enumerate() {
+            let block_id = Block(index as u32);
+            for instruction in &block.instructions {
+                self.instruction(block_id, instruction)?;
+            }
+            self.terminator(block.terminator.as_ref().unwrap())?;
+        }
+
+        self.dominance()
+    }
+
+    fn value(&self, value: Value) -> String {
+        self.names.value(value)
+    }
+
+    fn ty(&self, value: Value) -> &Type {
+        self.function.ty(value)
+    }
+
+    /// Checks the blocks end in terminators branching to blocks which exist, and that values are defined once and
+    /// only used if they're defined.
+    fn structure(&mut self) -> Result<(), String> {
+        let function = self.function;
+        if function.blocks.is_empty() {
+            return Err("the function has no blocks".into());
+        }
+
+        let defined = function
+            .params
+            .iter()
+            .map(|&param| (param, Definition::Param))
+            .chain(
+                function
+                    .blocks
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(index, block)| {
+                        block.instructions.iter().enumerate().filter_map(
+                            move |(position, instruction)| {
+                                let definition =
+                                    Definition::Instruction(Block(index as u32), position);
+                                instruction.result.map(|result| (result, definition))
+                            },
+                        )
+                    }),
+            );
+        for (value, definition) in defined {
+            if value.0 as usize >= function.values.len() {
+                return Err(format!("{} has no type", self.value(value)));
+            }
+            if self.definitions.insert(value, definition).is_some() {
+                return Err(format!("{} is defined more than once", self.value(value)));
+            }
+            if !representable(self.ty(value)) {
+                return Err(format!(
+                    "{} has type {}, which values can't have",
+                    self.value(value),
+                    self.ty(value)
+                ));
+            }
+        }
+
+        for (index, block) in function.blocks.iter().enumerate() {
+            let block_id = Block(index as u32);
+            let Some(terminator) = &block.terminator else {
+                return Err(format!("{block_id} has no terminator"));
+            };
+            for target in block.successors() {
+                if target.0 as usize >= function.blocks.len() {
+                    return Err(format!(
+                        "{block_id} branches to {target}, which doesn't exist"
+                    ));
+                }
+                if target == Block(0) {
+                    return Err(format!(
+                        "{block_id} branches to the entry block, which can't be branched to"
+                    ));
+                }
+            }
+
+            let operands = block
+                .instructions
+                .iter()
+                .flat_map(|instruction| instruction.kind.operands())
+                .chain(terminator.operands());
+            for operand in operands {
+                if !self.definitions.contains_key(&operand) {
+                    return Err(format!("{} is used but never defined", self.value(operand)));
+                }
+            }
+
+            let first_other = block
+                .instructions
+                .iter()
+                .position(|instruction| !matches!(instruction.kind, InstructionKind::Phi(_)))
+                .unwrap_or(block.instructions.len());
+            if let Some(phi) = block.instructions[first_other..]
+                .iter()
+                .find(|instruction| matches!(instruction.kind, InstructionKind::Phi(_)))
+            {
+                return Err(format!(
+                    "the phi defining {} in {block_id} comes after an instruction which isn't a phi",
+                    self.result(phi)
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn result(&self, instruction: &Instruction) -> String {
+        match instruction.result {
+            Some(result) => self.value(result),
+            None => "nothing".into(),
+        }
+    }
+
+    /// An error saying `value` should have type `expected` rather than the type it has.
+    fn mismatch(&self, value: Value, expected: &Type, context: &str) -> String {
+        format!(
+            "{} has type {}, but {context} needs {expected}",
+            self.value(value),
+            self.ty(value)
+        )
+    }
+
+    fn expect(&self, value: Value, expected: &Type, context: &str) -> Result<(), String> {
+        if self.ty(value) == expected {
+            Ok(())
+        } else {
+            Err(self.mismatch(value, expected, context))
+        }
+    }
+
+    fn expect_integer(&self, value: Value, context: &str) -> Result<(), String> {
+        if matches!(self.ty(value), Type::Int { .. }) {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} has type {}, but {context} needs an integer",
+                self.value(value),
+                self.ty(value)
+            ))
+        }
+    }
+
+    fn instruction(&self, block: Block, instruction: &Instruction) -> Result<(), String> {
+        let (result, produces) = match (&instruction.kind, instruction.result) {
+            (InstructionKind::Store { .. }, None) => (None, Type::unit()),
+            (InstructionKind::Store { .. }, Some(result)) => {
+                return Err(format!("`store` can't define {}", self.value(result)))
+            }
+            (_, None) => {
+                return Err(format!(
+                    "an instruction in {block} doesn't define a value, which only `store` can do"
+                ))
+            }
+            (_, Some(result)) => (Some(result), self.ty(result).clone()),
+        };
+        let context = format!("the instruction defining {}", self.result(instruction));
+
+        let expected = match &instruction.kind {
+            InstructionKind::Int(value) => {
+                if !produces.fits(*value) {
+                    return Err(format!("{value} isn't a constant of type {produces}"));
+                }
+                produces.clone()
+            }
+            InstructionKind::Bool(_) => Type::Bool,
+            InstructionKind::Binary(op, left, right) => {
+                self.expect_integer(*left, &context)?;
+                if matches!(op, BinaryOp::ShiftLeft | BinaryOp::ShiftRight) {
+                    self.expect_integer(*right, &context)?;
+                } else {
+                    self.expect(*right, self.ty(*left), &context)?;
+                }
+                self.ty(*left).clone()
+            }
+            InstructionKind::Compare(comparison, left, right) => {
+                if !matches!(comparison, Comparison::Equal | Comparison::NotEqual) {
+                    self.expect_integer(*left, &context)?;
+                }
+                self.expect(*right, self.ty(*left), &context)?;
+                Type::Bool
+            }
+            InstructionKind::Negate(value) => {
+                if !matches!(self.ty(*value), Type::Int { signed: true, .. }) {
+                    return Err(format!(
+                        "{} has type {}, but {context} needs a signed integer",
+                        self.value(*value),
+                        self.ty(*value)
+                    ));
+                }
+                self.ty(*value).clone()
+            }
+            InstructionKind::Not(value) => {
+                if self.ty(*value) != &Type::Bool {
+                    self.expect_integer(*value, &context)?;
+                }
+                self.ty(*value).clone()
+            }
+            InstructionKind::Tuple(elements) => Type::Tuple(
+                elements
+                    .iter()
+                    .map(|&element| self.ty(element).clone())
+                    .collect(),
+            ),
+            InstructionKind::Extract(tuple, index) => match self.ty(*tuple) {
+                Type::Tuple(elements) if (*index as usize) < elements.len() => {
+                    elements[*index as usize].clone()
+                }
+                ty => {
+                    return Err(format!(
+                        "{} has type {ty}, which has no element {index}",
+                        self.value(*tuple)
+                    ))
+                }
+            },
+            InstructionKind::Alloca => match produces {
+                Type::Reference(_) => produces.clone(),
+                _ => return Err(format!("{context} should produce a reference")),
+            },
+            InstructionKind::Load(pointer) => match self.ty(*pointer) {
+                Type::Reference(inner) => (**inner).clone(),
+                ty => {
+                    return Err(format!(
+                        "{} has type {ty}, but {context} needs a reference",
+                        self.value(*pointer)
+                    ))
+                }
+            },
+            InstructionKind::Store { pointer, value } => {
+                let reference = Type::Reference(Box::new(self.ty(*value).clone()));
+                self.expect(*pointer, &reference, "`store`")?;
+                Type::unit()
+            }
+            InstructionKind::Global(name) => match self.module.global(name) {
+                Some(global) => Type::Reference(Box::new(global.ty.clone())),
+                None => return Err(format!("there's no global called {}", global(name))),
+            },
+            InstructionKind::Function(name) => match self.module.function(name) {
+                Some(function) => function.signature(),
+                None => return Err(format!("there's no function called {}", global(name))),
+            },
+            InstructionKind::Call(callee, arguments) => {
+                let Type::Function(params, return_type) = self.ty(*callee) else {
+                    return Err(format!(
+                        "{} has type {}, which can't be called",
+                        self.value(*callee),
+                        self.ty(*callee)
+                    ));
+                };
+                if params.len() != arguments.len() {
+                    return Err(format!(
+                        "{} takes {} arguments, but {context} gives it {}",
+                        self.value(*callee),
+                        params.len(),
+                        arguments.len()
+                    ));
+                }
+                for (&argument, param) in arguments.iter().zip(params) {
+                    self.expect(argument, param, &context)?;
+                }
+                (**return_type).clone()
+            }
+            InstructionKind::Phi(incoming) => {
+                for &(_, value) in incoming {
+                    self.expect(value, &produces, &context)?;
+                }
+                produces.clone()
+            }
+        };
+
+        match result {
+            Some(result) if produces != expected => Err(format!(
+                "{} has type {produces}, but its instruction produces {expected}",
+                self.value(result)
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn terminator(&self, terminator: &Terminator) -> Result<(), String> {
+        match *terminator {
+            Terminator::Jump(_) => Ok(()),
+            Terminator::Branch { condition, .. } => self.expect(condition, &Type::Bool, "`branch`"),
+            Terminator::Return(value) => self.expect(value, &self.function.return_type, "`return`"),
+        }
+    }
+
+    /// Checks each value's definition dominates its uses, and each phi has a value from every predecessor of its
+    /// block. Blocks which can't be reached aren't checked, since nothing they do can happen.
+    fn dominance(&self) -> Result<(), String> {
+        let dominators = Dominators::new(self.function);
+        let predecessors = self.function.predecessors();
+
+        for (index, block) in self.function.blocks.iter().enumerate() {
+            let block_id = Block(index as u32);
+            if !dominators.is_reachable(block_id) {
+                continue;
+            }
+
+            for (position, instruction) in block.instructions.iter().enumerate() {
+                if let InstructionKind::Phi(incoming) = &instruction.kind {
+                    self.phi(instruction, incoming, &predecessors[index])?;
+                    for &(from, value) in incoming {
+                        // Used at the end of the block it comes from
+                        let end = self.function.block(from).instructions.len();
+                        if dominators.is_reachable(from)
+                            && !self.dominates(&dominators, value, from, end)
+                        {
+                            return Err(format!(
+                                "{} comes from {from} in the phi defining {}, but isn't defined on every path to \
+                                 the end of {from}",
+                                self.value(value),
+                                self.result(instruction)
+                            ));
+                        }
+                    }
+                    continue;
+                }
+
+                for value in instruction.kind.operands() {
+                    self.check_use(&dominators, value, block_id, position)?;
+                }
+            }
+
+            let terminator = block.terminator.as_ref().unwrap();
+            for value in terminator.operands() {
+                self.check_use(&dominators, value, block_id, block.instructions.len())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the blocks a phi has values from are the predecessors of its block.
+    fn phi(
+        &self,
+        instruction: &Instruction,
+        incoming: &[(Block, Value)],
+        predecessors: &[Block],
+    ) -> Result<(), String> {
+        let mut from = HashSet::new();
+        for &(block, _) in incoming {
+            if !from.insert(block) {
+                return Err(format!(
+                    "the phi defining {} has more than one value from {block}",
+                    self.result(instruction)
+                ));
+            }
+            if !predecessors.contains(&block) {
+                return Err(format!(
+                    "the phi defining {} has a value from {block}, which doesn't branch to its block",
+                    self.result(instruction)
+                ));
+            }
+        }
+        if let Some(missing) = predecessors.iter().find(|block| !from.contains(block)) {
+            return Err(format!(
+                "the phi defining {} has no value from {missing}",
+                self.result(instruction)
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_use(
+        &self,
+        dominators: &Dominators,
+        value: Value,
+        block: Block,
+        position: usize,
+    ) -> Result<(), String> {
+        if self.dominates(dominators, value, block, position) {
+            return Ok(());
+        }
+        match self.definitions[&value] {
+            Definition::Instruction(defined, _) if defined == block => Err(format!(
+                "{} is used in {block} before it's defined",
+                self.value(value)
+            )),
+            _ => Err(format!(
+                "{} is used in {block}, but isn't defined on every path to it",
+                self.value(value)
+            )),
+        }
+    }
+
+    /// Whether `value` is defined on every path to the instruction at `position` in `block`.
+    fn dominates(
+        &self,
+        dominators: &Dominators,
+        value: Value,
+        block: Block,
+        position: usize,
+    ) -> bool {
+        match self.definitions[&value] {
+            Definition::Param => true,
+            Definition::Instruction(defined, index) if defined == block => index < position,
+            Definition::Instruction(defined, _) => dominators.dominates(defined, block),
+        }
+    }
+}
+
+/// The immediate dominator of each block reachable from the entry block, found by the algorithm in Cooper, Harvey and
+/// Kennedy's "A Simple, Fast Dominance Algorithm".
+struct Dominators {
+    /// Indexed by block, `None` for blocks which can't be reached
+    immediate: Vec<Option<Block>>,
+}
+
+impl Dominators {
+    fn new(function: &Function) -> Self {
+        // Blocks in reverse postorder, so that each block comes before its successors besides along back edges
+        let mut postorder = Vec::new();
+        let mut visited = vec![false; function.blocks.len()];
+        let mut stack = vec![(Block(0), 0)];
+        visited[0] = true;
+        while let Some((block, next)) = stack.pop() {
+            let successors = function.block(block).successors();
+            match successors.get(next) {
+                Some(&successor) => {
+                    stack.push((block, next + 1));
+                    if !visited[successor.0 as usize] {
+                        visited[successor.0 as usize] = true;
+                        stack.push((successor, 0));
+                    }
+                }
+                None => postorder.push(block),
+            }
+        }
+
+        let mut order = vec![usize::MAX; function.blocks.len()];
+        for (index, block) in postorder.iter().enumerate() {
+            order[block.0 as usize] = index;
+        }
+
+        let predecessors = function.predecessors();
+        let mut immediate = vec![None; function.blocks.len()];
+        immediate[0] = Some(Block(0));
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in postorder.iter().rev().skip(1) {
+                let mut processed = predecessors[block.0 as usize]
+                    .iter()
+                    .copied()
+                    .filter(|predecessor| immediate[predecessor.0 as usize].is_some());
+                let Some(first) = processed.next() else {
+                    continue;
+                };
+                let dominator = processed.fold(first, |left, right| {
+                    Self::intersect(&immediate, &order, left, right)
+                });
+                if immediate[block.0 as usize] != Some(dominator) {
+                    immediate[block.0 as usize] = Some(dominator);
+                    changed = true;
+                }
+            }
+        }
+
+        Dominators { immediate }
+    }
+
+    /// The closest block dominating both `left` and `right`.
+    fn intersect(
+        immediate: &[Option<Block>],
+        order: &[usize],
+        mut left: Block,
+        mut right: Block,
+    ) -> Block {
+        while left != right {
+            while order[left.0 as usize] < order[right.0 as usize] {
+                left = immediate[left.0 as usize].unwrap();
+            }
+            while order[right.0 as usize] < order[left.0 as usize] {
+                right = immediate[right.0 as usize].unwrap();
+            }
+        }
+        left
+    }
+
+    fn is_reachable(&self, block: Block) -> bool {
+        self.immediate[block.0 as usize].is_some()
+    }
+
+    /// Whether every path from the entry block to `block` passes through `dominator`.
+    fn dominates(&self, dominator: Block, mut block: Block) -> bool {
+        if !self.is_reachable(dominator) {
+            return false;
+        }
+        loop {
+            if block == dominator {
+                return true;
+            }
+            match self.immediate[block.0 as usize] {
+                Some(next) if next != block => block = next,
+                _ => return false,
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::token::{Inner, Token};
+use crate::token::Token;
 
 pub struct AsciiLexer;
 
@@ -10,7 +10,6 @@ impl<'a> AsciiLexer {
     pub fn tokenize(&mut self, buf: &'a String) -> Vec<Token<'a>> {
         let mut tokens = Vec::new();
 
-        let mut last_token = Token::Unknown(Inner::new(0, ""));
         let mut last_idx = 0;
 
         for (idx, byte) in buf.as_bytes().iter().enumerate() {
@@ -23,20 +22,7 @@ impl<'a> AsciiLexer {
                     // Get the str between both indices
                     let word: &str = unsafe { buf.get_unchecked(last_idx..idx) };
 
-                    let mut word_token = Token::from(last_idx, word);
-
-                    if let Token::Unknown(inner) = word_token {
-                        // Either a literal or an identifier.
-                        let first_char = unsafe { inner.slice.chars().next().unwrap_unchecked() };
-
-                        if first_char.is_numeric() {
-                            // Numeric literal. TODO: Try figure out what kind here?
-                            word_token = Token::NumericLiteral(inner);
-                        } else {
-                            word_token = Token::Identifier(inner);
-                        }
-                    }
-
+                    let word_token = word_token(last_idx, word);
                     tokens.push(word_token);
                 }
 
@@ -48,29 +34,47 @@ impl<'a> AsciiLexer {
                 match token {
                     Token::Space(_) => {
                         let last_token = tokens.last_mut();
-                        if let Some(last_token) = last_token {
+                        if let Some(_last_token) = last_token {
                             // last_token.inner_mut().spaced = true;
                         }
                     }
                     // Look behind to see if this is a two character boundary token
-                    Token::GreaterThan(_) => {
-                        if let Token::Minus(_) = last_token {
+                    Token::GreaterThan(_)
+                    | Token::LessThan(_)
+                    | Token::Equals(_)
+                    | Token::Times(_) => {
+                        // Only a boundary token directly before this one can be combined with it
+                        let previous = tokens.last().filter(|last| last.loc() + 1 == idx);
+
+                        let combines = matches!(
+                            (previous, token),
+                            (Some(Token::Minus(_)), Token::GreaterThan(_))
+                                | (Some(Token::Times(_)), Token::Times(_))
+                                | (Some(Token::LessThan(_)), Token::LessThan(_))
+                                | (Some(Token::GreaterThan(_)), Token::GreaterThan(_))
+                                | (
+                                    Some(
+                                        Token::Equals(_)
+                                            | Token::Not(_)
+                                            | Token::LessThan(_)
+                                            | Token::GreaterThan(_)
+                                            | Token::Plus(_)
+                                            | Token::Minus(_)
+                                            | Token::Times(_)
+                                    ),
+                                    Token::Equals(_)
+                                )
+                        );
+
+                        if combines {
                             tokens.pop();
-                            let token = Token::from(idx - 1, "->");
-
-                            last_token = token;
+                            let word: &str = unsafe { buf.get_unchecked(idx - 1..idx + 1) };
+                            tokens.push(Token::from(idx - 1, word));
+                        } else {
                             tokens.push(token);
                         }
                     }
-                    Token::Equals(_) => {
-                        if let Token::Equals(_) = last_token {
-                            // TODO: Comparison operators
-                        }
-                        last_token = token;
-                        tokens.push(token);
-                    }
                     _ => {
-                        last_token = token;
                         tokens.push(token);
                     }
                 }
@@ -79,10 +83,34 @@ impl<'a> AsciiLexer {
             }
         }
 
+        // The last word isn't followed by a boundary
+        if last_idx < buf.len() {
+            let word: &str = unsafe { buf.get_unchecked(last_idx..) };
+            tokens.push(word_token(last_idx, word));
+        }
+
         tokens
     }
 }
 
+fn word_token(loc: usize, word: &str) -> Token<'_> {
+    let token = Token::from(loc, word);
+
+    if let Token::Unknown(inner) = token {
+        // Either a literal or an identifier.
+        let first_char = unsafe { inner.slice.chars().next().unwrap_unchecked() };
+
+        if first_char.is_numeric() {
+            // Numeric literal. TODO: Try figure out what kind here?
+            return Token::NumericLiteral(inner);
+        } else {
+            return Token::Identifier(inner);
+        }
+    }
+
+    token
+}
+
 impl Default for AsciiLexer {
     fn default() -> Self {
         Self::new()
@@ -93,6 +121,7 @@ fn is_word_boundary(word: char) -> bool {
     matches!(
         word,
         '\n' | ' '
+            | '!'
             | '"'
             | '#'
             | '%'
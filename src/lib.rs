@@ -1,5 +1,15 @@
+pub mod ast;
+pub mod bytecode;
+pub mod codes;
+pub mod emitter;
+pub mod interpret;
+pub mod ir;
 pub mod lexer;
+pub mod llvm;
 pub mod parse;
+pub mod sema;
+pub mod suggestion;
+pub mod timing;
 pub mod token;
 
 pub fn parsed_to_str<I, D>(iterable: I) -> String
@@ -13,15 +23,40 @@ where
         None => return String::from("[]"),
         Some(x) => format!("{}", x),
     };
-    let body = iterator.fold(head, |a, v| format!("{} {}", a, v));
-    format!("{}", body)
+    iterator.fold(head, |a, v| format!("{} {}", a, v))
 }
 
 #[cfg(test)]
 mod tests {
     use log::trace;
 
-    use crate::{lexer::AsciiLexer, parse::parser::Parser, parsed_to_str, token::Token};
+    use crate::{
+        ast::{builder::Builder, format, ExprKind, Module, StmtKind},
+        bytecode::{compile::compile, file::VERSION, vm::Vm, Instruction, Program},
+        codes,
+        emitter::{Emitter, Format, SourceFile},
+        interpret::Interpreter,
+        ir::{self, builder::FunctionBuilder},
+        lexer::AsciiLexer,
+        llvm,
+        parse::{
+            error::{Found, UnexpectedToken},
+            evaluate::{evaluate, Environment},
+            parser::Parser,
+            tree::{NodeKind, ParseTree},
+        },
+        parsed_to_str,
+        sema::{
+            check::check_with,
+            consteval::{fold, Value},
+            resolve::{edit_distance, resolve},
+            symbols::{Mutability, SymbolKind, SymbolTable},
+            types::Type,
+        },
+        suggestion::{self, suggestion, Applicability},
+        timing::Timings,
+        token::Token,
+    };
 
     fn init_logging() {
         let _ = pretty_env_logger::env_logger::builder()
@@ -32,34 +67,43 @@ mod tests {
     fn lex<'buf>(buf: &'buf String) -> Vec<Token<'buf>> {
         let mut lexer = AsciiLexer::new();
         let tokens = lexer.tokenize(buf);
-        return tokens;
+        tokens
     }
 
     fn parse<'buf, 't>(tokens: &'t [Token<'buf>]) -> Vec<Token<'t>> {
-        let parser = Parser::new(0, &tokens);
+        let parser = Parser::new(0, tokens);
         let parsed = parser.parse().unwrap();
 
         trace!("Final parsed tokens:");
         trace!("{}", parsed_to_str(&parsed));
 
-        return parsed;
+        parsed
+    }
+
+    fn build<'t>(parsed: &[Token<'t>]) -> Module<'t> {
+        let module = Builder::new(parsed).build().unwrap();
+
+        trace!("Built module:");
+        trace!("{}", module);
+
+        module
     }
 
     fn matches_target<'buf, 't>(parsed: &[Token<'buf>], target: &'t String) -> bool {
         let mut lexer = AsciiLexer::new();
-        let target = lexer.tokenize(&target);
+        let target = lexer.tokenize(target);
 
         let matching = parsed
             .iter()
             .zip(&target)
             .all(|(a, b)| a.as_str() == b.as_str());
-        return matching;
+        matching
     }
 
     fn matches_length<'buf, 't>(tokens: &[Token<'buf>], target: &'t String) -> bool {
         let mut lexer = AsciiLexer::new();
-        let target = lexer.tokenize(&target);
-        return tokens.len() == target.len();
+        let target = lexer.tokenize(target);
+        tokens.len() == target.len()
     }
 
     #[test]
@@ -162,6 +206,7 @@ mod tests {
             .all(|(a, b)| a.as_str() == b.as_str());
         assert!(matching);
         assert!(tokens.len() == target.len());
+        assert_eq!(evaluate(&parsed, &mut Environment::new()).unwrap(), 6);
     }
 
     #[test]
@@ -188,6 +233,7 @@ mod tests {
             .all(|(a, b)| a.as_str() == b.as_str());
         assert!(matching);
         assert!(tokens.len() == target.len());
+        assert_eq!(evaluate(&parsed, &mut Environment::new()).unwrap(), 5);
     }
 
     #[test]
@@ -214,6 +260,7 @@ mod tests {
             .all(|(a, b)| a.as_str() == b.as_str());
         assert!(matching);
         assert!(tokens.len() == target.len());
+        assert_eq!(evaluate(&parsed, &mut Environment::new()).unwrap(), 7);
     }
 
     #[test]
@@ -228,6 +275,9 @@ mod tests {
 
         assert!(matches_length(&tokens, &target));
         assert!(matches_target(&parsed, &target));
+
+        let mut environment = Environment::from([("A", 2), ("B", 3), ("C", 4), ("D", 5), ("E", 6)]);
+        assert_eq!(evaluate(&parsed, &mut environment).unwrap(), 52);
     }
 
     #[test]
@@ -257,4 +307,2304 @@ mod tests {
         assert!(matches_length(&tokens, &target));
         assert!(matches_target(&parsed, &target));
     }
+
+    #[test]
+    fn parser_call_without_arguments() {
+        init_logging();
+
+        let input = String::from("f();");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(parsed_to_str(&parsed), "f call/0 ;");
+    }
+
+    #[test]
+    fn parser_call_arguments() {
+        init_logging();
+
+        let input = String::from("fib(x-1)+fib(x-2);");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(
+            parsed_to_str(&parsed),
+            "fib x 1 - call/1 fib x 2 - call/1 + ;"
+        );
+    }
+
+    #[test]
+    fn parser_nested_calls() {
+        init_logging();
+
+        let input = String::from("f(g(a, b * c), (d + e), h());");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(
+            parsed_to_str(&parsed),
+            "f g a b c * call/2 ( d e + ) h call/0 call/3 ;"
+        );
+    }
+
+    #[test]
+    fn parser_call_trailing_comma() {
+        init_logging();
+
+        let input = String::from("f(a, b,);");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(parsed_to_str(&parsed), "f a b call/2 ;");
+    }
+
+    #[test]
+    fn parser_call_missing_argument() {
+        init_logging();
+
+        let input = String::from("f(a,, b);");
+        let tokens = lex(&input);
+        let parser = Parser::new(0, &tokens);
+
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn parser_prefix_negation() {
+        init_logging();
+
+        let input = String::from("x - -1;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(parsed_to_str(&parsed), "x 1 neg - ;");
+        assert!(matches!(parsed[2], Token::Negate(_)));
+        assert!(matches!(parsed[3], Token::Minus(_)));
+    }
+
+    #[test]
+    fn parser_prefix_binds_tighter_than_binary() {
+        init_logging();
+
+        let input = String::from("-a * b + !c;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(parsed_to_str(&parsed), "a neg b * c ! + ;");
+    }
+
+    #[test]
+    fn parser_prefix_applies_after_call() {
+        init_logging();
+
+        let input = String::from("*p = -f(&x, +y);");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(
+            parsed_to_str(&parsed),
+            "p deref f x ref y pos call/2 neg = ;"
+        );
+    }
+
+    #[test]
+    fn parser_double_deref() {
+        init_logging();
+
+        let input = String::from("**q = a ** *b;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(parsed_to_str(&parsed), "q deref deref a b deref ** = ;");
+    }
+
+    #[test]
+    fn parser_missing_operand() {
+        init_logging();
+
+        let input = String::from("1 + ;");
+        let tokens = lex(&input);
+        let parser = Parser::new(0, &tokens);
+
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn parser_missing_operator() {
+        init_logging();
+
+        let input = String::from("a b;");
+        let tokens = lex(&input);
+        let parser = Parser::new(0, &tokens);
+
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn lexer_two_character_operators() {
+        let input = String::from("a->b==c!=d<=e>=f+=g-=h*=i**j - >k");
+        let tokens = lex(&input);
+
+        assert_eq!(
+            parsed_to_str(&tokens),
+            "a -> b == c != d <= e >= f += g -= h *= i ** j - > k"
+        );
+        assert!(matches!(tokens[1], Token::Arrow(_)));
+        assert!(matches!(tokens[17], Token::Power(_)));
+        assert!(matches!(tokens[20], Token::GreaterThan(_)));
+    }
+
+    #[test]
+    fn parser_assignment_chain() {
+        init_logging();
+
+        let input = String::from("a = b = c + d;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(parsed_to_str(&parsed), "a b c d + = = ;");
+
+        let mut environment = Environment::from([("c", 1), ("d", 2)]);
+        assert_eq!(evaluate(&parsed, &mut environment).unwrap(), 3);
+        assert_eq!((environment["a"], environment["b"]), (3, 3));
+    }
+
+    #[test]
+    fn parser_compound_assignment_chain() {
+        init_logging();
+
+        let input = String::from("a += b -= c *= d - e - f;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(parsed_to_str(&parsed), "a b c d e - f - *= -= += ;");
+
+        let mut environment =
+            Environment::from([("a", 1), ("b", 2), ("c", 3), ("d", 10), ("e", 4), ("f", 1)]);
+        evaluate(&parsed, &mut environment).unwrap();
+        assert_eq!(
+            (environment["a"], environment["b"], environment["c"]),
+            (-12, -13, 15)
+        );
+    }
+
+    #[test]
+    fn parser_power_is_right_associative() {
+        init_logging();
+
+        let input = String::from("-a ** b ** c * d;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(parsed_to_str(&parsed), "a b c ** ** neg d * ;");
+
+        let mut environment = Environment::from([("a", 2), ("b", 3), ("c", 2), ("d", 3)]);
+        assert_eq!(evaluate(&parsed, &mut environment).unwrap(), -1536);
+    }
+
+    #[test]
+    fn parser_comparison_with_arithmetic() {
+        init_logging();
+
+        let input = String::from("x = a + 1 > b * 2;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(parsed_to_str(&parsed), "x a 1 + b 2 * > = ;");
+
+        let mut environment = Environment::from([("a", 6), ("b", 3)]);
+        evaluate(&parsed, &mut environment).unwrap();
+        assert_eq!(environment["x"], 1);
+    }
+
+    #[test]
+    fn parser_chained_comparison() {
+        init_logging();
+
+        let input = String::from("a < b + 1 < c;");
+        let tokens = lex(&input);
+        let parser = Parser::new(0, &tokens);
+
+        assert!(parser.parse().is_err());
+
+        let input = String::from("(a < b) == c;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(parsed_to_str(&parsed), "( a b < ) c == ;");
+    }
+
+    #[test]
+    fn parser_nested_generic_types() {
+        init_logging();
+
+        let input = String::from("var m: Map<K, Vec<V>> = x;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(
+            parsed_to_str(&parsed),
+            "var m Map K Vec V generic/1 generic/2 : x = ;"
+        );
+    }
+
+    #[test]
+    fn parser_generic_type_before_equals() {
+        init_logging();
+
+        let input = String::from("var v: Vec<Vec<T>>= y;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(
+            parsed_to_str(&parsed),
+            "var v Vec Vec T generic/1 generic/1 : y = ;"
+        );
+    }
+
+    #[test]
+    fn parser_tuple_reference_and_function_types() {
+        init_logging();
+
+        let input = String::from("fn f(x: &T, y: (A, (B)), z: fn(A,) -> C, w: fn()): () {}");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(
+            parsed_to_str(&parsed),
+            "fn f ( x T ref : y A B tuple/2 : , z A C fn/1 : , w tuple/0 fn/0 : , ) tuple/0 : { }"
+        );
+    }
+
+    #[test]
+    fn parser_generics_in_expression_are_comparisons() {
+        init_logging();
+
+        let input = String::from("x = a << 1 >> b < c;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(parsed_to_str(&parsed), "x a 1 << b >> c < = ;");
+
+        let input = String::from("Vec < T > x;");
+        let tokens = lex(&input);
+        let parser = Parser::new(0, &tokens);
+
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn ast_operator_precedence() {
+        init_logging();
+
+        let input = String::from("a = -1 + 2 * f(x, (y)) ** 2;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+
+        assert_eq!(
+            module.to_string(),
+            "(= a (+ (neg 1) (* 2 (** (call f x y) 2))));"
+        );
+    }
+
+    #[test]
+    fn ast_declarations() {
+        init_logging();
+
+        let input = String::from(
+            "fn f(x: &T, y: Map<K, (A, B)>): fn(A) -> B { var z: T = x; var w; { z } y }",
+        );
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+
+        assert_eq!(
+            module.to_string(),
+            "(fn f (x: &T, y: Map<K, (A, B)>) -> fn(A) -> B { (var z: T x) (var w) { z } y })"
+        );
+    }
+
+    #[test]
+    fn ast_spans() {
+        init_logging();
+
+        let input = String::from("var a = fib(x - 1) + (2);");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+
+        let stmt = &module.stmts[0];
+        assert_eq!((stmt.span.offset(), stmt.span.len()), (0, 25));
+
+        let StmtKind::Variable {
+            value: Some(value), ..
+        } = &stmt.kind
+        else {
+            panic!("Expected variable declaration, found {}", stmt);
+        };
+        assert_eq!((value.span.offset(), value.span.len()), (8, 16));
+
+        let ExprKind::Binary(_, call, group) = &value.kind else {
+            panic!("Expected binary expression, found {}", value);
+        };
+        assert_eq!((call.span.offset(), call.span.len()), (8, 10));
+        assert_eq!((group.span.offset(), group.span.len()), (21, 3));
+    }
+
+    #[test]
+    fn parse_tree_subtree_sizes() {
+        init_logging();
+
+        let input = String::from("1 + (2 + 3);");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let tree = ParseTree::new(&tokens, &parsed);
+
+        // 1 ( 2 3 + ) + ;
+        let sizes: Vec<usize> = (0..tree.len())
+            .map(|node| tree.subtree_size(node))
+            .collect();
+        assert_eq!(sizes, [1, 1, 1, 1, 3, 5, 7, 8]);
+        assert_eq!(tree.roots(), [7]);
+        assert_eq!(tree.children(7), [6]);
+        assert_eq!(tree.children(6), [0, 5]);
+        assert_eq!(tree.children(5), [1, 4]);
+        assert_eq!(tree.kind(5), NodeKind::Group);
+
+        // Tokens are indexed in the lexer's output, rather than in postfix order
+        assert_eq!(tree.token(4), 4);
+        assert_eq!(tree.token(5), 6);
+        assert_eq!(tree.token(6), 1);
+    }
+
+    #[test]
+    fn parse_tree_statements_and_functions() {
+        init_logging();
+
+        let input = String::from("var a = f(b, c); fn g(x: T): R { x; { } x } a;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let tree = ParseTree::new(&tokens, &parsed);
+
+        let kinds: Vec<NodeKind> = tree
+            .roots()
+            .into_iter()
+            .map(|node| tree.kind(node))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                NodeKind::Statement,
+                NodeKind::FunctionDefinition,
+                NodeKind::Statement
+            ]
+        );
+
+        // var a f b c call/2 = ;
+        assert_eq!(tree.children(7), [0, 6]);
+        assert_eq!(tree.children(5), [2, 3, 4]);
+        assert_eq!(tree.kind(5), NodeKind::Call);
+
+        // fn g ( x T : ) R : { x ; { } x }
+        let function = tree.roots()[1];
+        let children: Vec<NodeKind> = tree
+            .children(function)
+            .into_iter()
+            .map(|node| tree.kind(node))
+            .collect();
+        assert_eq!(
+            children,
+            [
+                NodeKind::FunctionIntroducer,
+                NodeKind::Name,
+                NodeKind::InfixOperator,
+                NodeKind::BlockStart,
+                NodeKind::Statement,
+                NodeKind::Block,
+                NodeKind::Name
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_tree_navigation() {
+        init_logging();
+
+        let input = String::from("a * b + c;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let tree = ParseTree::new(&tokens, &parsed);
+
+        // a b * c + ;
+        assert_eq!(tree.parent(0), Some(2));
+        assert_eq!(tree.parent(2), Some(4));
+        assert_eq!(tree.parent(5), None);
+        assert_eq!(tree.next_sibling(0), Some(1));
+        assert_eq!(tree.next_sibling(2), Some(3));
+        assert_eq!(tree.next_sibling(3), None);
+        assert_eq!(tree.previous_sibling(3), Some(2));
+        assert_eq!(tree.previous_sibling(2), None);
+
+        // Every node's siblings agree with its parent's children
+        let input = String::from("a; f(b * c, -d) + e; fn g(x: i32) { x * 2; } { h; }");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let tree = ParseTree::new(&tokens, &parsed);
+        for node in 0..tree.len() {
+            let siblings = match tree.parent(node) {
+                Some(parent) => tree.children(parent),
+                None => tree.roots(),
+            };
+            let position = siblings
+                .iter()
+                .position(|&sibling| sibling == node)
+                .unwrap();
+            assert_eq!(tree.next_sibling(node), siblings.get(position + 1).copied());
+            let previous = position.checked_sub(1).map(|position| siblings[position]);
+            assert_eq!(tree.previous_sibling(node), previous);
+        }
+    }
+
+    #[test]
+    fn parser_recovers_from_errors() {
+        init_logging();
+
+        let input = String::from("var a = 1 +; var b = 2; fn f(x:) { a; } f(1,; { c = ; d }");
+        let tokens = lex(&input);
+        let (parsed, diagnostics) = Parser::new(0, &tokens).parse_recovering();
+
+        assert_eq!(diagnostics.len(), 4);
+        assert_eq!(
+            parsed_to_str(&parsed),
+            "error ; var b 2 = ; error error ; { error ; d }"
+        );
+
+        let module = build(&parsed);
+        assert_eq!(
+            module.to_string(),
+            "(error)\n(var b 2)\n(error)\n(error)\n{ (error) d }"
+        );
+        assert!(matches!(module.stmts[0].kind, StmtKind::Error));
+        assert_eq!(module.stmts[0].span, (0, 12).into());
+
+        let tree = ParseTree::new(&tokens, &parsed);
+        assert_eq!(tree.kind(0), NodeKind::Error);
+        assert_eq!(tree.kind(1), NodeKind::Statement);
+        assert_eq!(tree.subtree_size(1), 2);
+    }
+
+    #[test]
+    fn parser_reports_first_error() {
+        init_logging();
+
+        let input = String::from("var a = ; var b = ;");
+        let tokens = lex(&input);
+
+        let report = Parser::new(0, &tokens).parse().unwrap_err();
+        let label = report.labels().unwrap().next().unwrap();
+        assert_eq!(label.offset(), 8);
+    }
+
+    /// Returns the message and label offsets of each diagnostic from parsing `input`.
+    fn diagnostics(input: &str) -> Vec<(String, Vec<usize>)> {
+        let input = String::from(input);
+        let tokens = lex(&input);
+        let (_, diagnostics) = Parser::new(0, &tokens).parse_recovering();
+
+        diagnostics
+            .iter()
+            .map(|report| {
+                let mut offsets: Vec<usize> = report
+                    .labels()
+                    .into_iter()
+                    .flatten()
+                    .map(|label| label.offset())
+                    .collect();
+                offsets.sort();
+                (report.to_string(), offsets)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parser_unbalanced_brackets() {
+        init_logging();
+
+        assert_eq!(
+            diagnostics("1 + 2);"),
+            vec![("Unmatched closing delimiter `)`".to_string(), vec![5])]
+        );
+        assert_eq!(
+            diagnostics("(1 + 2;"),
+            vec![("Unclosed delimiter `(`".to_string(), vec![0, 6])]
+        );
+        assert_eq!(
+            diagnostics("f(a, (b);"),
+            vec![("Unclosed delimiter `(`".to_string(), vec![1, 8])]
+        );
+    }
+
+    #[test]
+    fn parser_unbalanced_curlies() {
+        init_logging();
+
+        assert_eq!(
+            diagnostics("{ (1 + 2 }"),
+            vec![("Mismatched closing delimiter `}`".to_string(), vec![2, 9])]
+        );
+        assert_eq!(
+            diagnostics("fn f() { a = 1); }"),
+            vec![("Unmatched closing delimiter `)`".to_string(), vec![7, 14])]
+        );
+        assert_eq!(
+            diagnostics("{ x; } }"),
+            vec![("Unmatched closing delimiter `}`".to_string(), vec![7])]
+        );
+        assert_eq!(
+            diagnostics("{ x;"),
+            vec![("Unclosed delimiter `{`".to_string(), vec![0, 4])]
+        );
+    }
+
+    #[test]
+    fn parser_conditionals_and_loops() {
+        init_logging();
+
+        let input =
+            String::from("if a < b { a } else if a == b { b; } else { c } while x { x -= 1; }");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+
+        assert_eq!(
+            parsed_to_str(&parsed),
+            "if a b < { a } else if a b == { b ; } else { c } while x { x 1 -= ; }"
+        );
+        assert_eq!(
+            build(&parsed).to_string(),
+            "(if (< a b) { a } (if (== a b) { b; } { c }))\n(while x { (-= x 1); })"
+        );
+
+        // The whole chain of branches is one statement
+        let tree = ParseTree::new(&tokens, &parsed);
+        assert_eq!(tree.roots(), vec![19, 27]);
+        assert_eq!(tree.kind(19), NodeKind::If);
+        assert_eq!(tree.subtree_size(19), 20);
+        assert_eq!(tree.kind(27), NodeKind::While);
+    }
+
+    #[test]
+    fn parser_ends_expressions_at_newlines() {
+        init_logging();
+
+        // An expression continues onto the next line after an operator, or inside brackets
+        let input = String::from("var x = 1 +\n2;\nf(x,\ny) *\n(x\n- 1);");
+        let tokens = lex(&input);
+        assert_eq!(
+            parsed_to_str(parse(&tokens)),
+            "var x 1 2 + = ; f x y call/2 ( x 1 - ) * ;"
+        );
+
+        // An operand on a new line after a complete expression starts another, so the `;` is missing
+        let input = String::from("a = 1\nb = 2;");
+        let tokens = lex(&input);
+        assert!(Parser::new(0, &tokens).parse().is_err());
+
+        // Keywords always end an expression
+        let input = String::from("x = 1 while x { } y = 2 if y { }");
+        let tokens = lex(&input);
+        assert!(Parser::new(0, &tokens).parse().is_err());
+    }
+    /// Parses `input`, returning the fixed source and the output of parsing it with every suggestion applied.
+    fn fix(input: &str) -> (String, String) {
+        let input = String::from(input);
+        let tokens = lex(&input);
+        let (parsed, diagnostics) = Parser::new(0, &tokens).parse_recovering();
+
+        assert!(diagnostics
+            .iter()
+            .all(|report| suggestion(report).is_some()));
+        let (fixed, _) = suggestion::apply(&input, diagnostics.iter().filter_map(suggestion));
+
+        (fixed, parsed_to_str(&parsed))
+    }
+
+    #[test]
+    fn parser_suggests_fixes() {
+        init_logging();
+
+        assert_eq!(
+            fix("var a = 1 var b = 2;"),
+            (
+                "var a = 1; var b = 2;".into(),
+                "var a 1 = ; var b 2 = ;".into()
+            )
+        );
+        assert_eq!(
+            fix("if a = b { }"),
+            ("if a == b { }".into(), "if a b = { }".into())
+        );
+
+        // The assignment is parsed as the comparison it was meant to be
+        let input = String::from("if a = b { }");
+        let tokens = lex(&input);
+        let (parsed, _) = Parser::new(0, &tokens).parse_recovering();
+        assert_eq!(build(&parsed).to_string(), "(if (== a b) {})");
+        assert_eq!(
+            fix("while ((a = b)) { }"),
+            (
+                "while ((a == b)) { }".into(),
+                "while ( ( a b = ) ) { }".into()
+            )
+        );
+        assert_eq!(
+            fix("if (a = b) { }"),
+            ("if (a == b) { }".into(), "if ( a b = ) { }".into())
+        );
+        assert_eq!(
+            fix("fun f(x T) { }"),
+            ("fn f(x: T) { }".into(), "fun f ( x T : ) { }".into())
+        );
+        assert_eq!(fix("var a T;"), ("var a: T;".into(), "var a T : ;".into()));
+    }
+
+    #[test]
+    fn parser_missing_semicolon_at_newline_needs_review() {
+        init_logging();
+
+        let input = String::from("a = 1\nb = 2;");
+        let tokens = lex(&input);
+        let (parsed, diagnostics) = Parser::new(0, &tokens).parse_recovering();
+
+        assert_eq!(parsed_to_str(&parsed), "a 1 = ; b 2 = ;");
+        assert_eq!(diagnostics.len(), 1);
+
+        let suggestion = suggestion(&diagnostics[0]).unwrap();
+        assert_eq!(suggestion.applicability, Applicability::MaybeIncorrect);
+        assert_eq!(suggestion.span, (5, 0).into());
+    }
+
+    #[test]
+    fn parser_unexpected_token_diagnostic() {
+        init_logging();
+
+        let input = String::from("fn f(a: T, b {}");
+        let tokens = lex(&input);
+        let report = Parser::new(0, &tokens)
+            .with_source("test.src", &input)
+            .parse()
+            .unwrap_err();
+
+        let unexpected = report.downcast_ref::<UnexpectedToken>().unwrap();
+        assert_eq!(unexpected.found, Found::Token("{".into()));
+        assert_eq!(unexpected.expected.to_string(), "expected `:`");
+        assert!(unexpected.help.is_some());
+
+        assert_eq!(report.to_string(), "Unexpected token `{`");
+        assert_eq!(
+            report.code().map(|code| code.to_string()),
+            Some("E0001".into())
+        );
+        let source = report.source_code().unwrap();
+        let contents = source.read_span(&unexpected.span, 0, 0).unwrap();
+        assert_eq!(contents.name(), Some("test.src"));
+
+        let labels: Vec<_> = report.labels().unwrap().collect();
+        assert_eq!(labels[0].offset(), 13);
+        assert_eq!(
+            labels[1].label(),
+            Some("while parsing this function signature")
+        );
+        assert_eq!((labels[1].offset(), labels[1].len()), (0, 12));
+    }
+
+    #[test]
+    fn parser_unexpected_end_of_input() {
+        init_logging();
+
+        let input = String::from("var x: Map<K, V");
+        let tokens = lex(&input);
+        let report = Parser::new(0, &tokens).parse().unwrap_err();
+
+        let unexpected = report.downcast_ref::<UnexpectedToken>().unwrap();
+        assert_eq!(unexpected.found, Found::EndOfInput);
+        assert_eq!(unexpected.expected.to_string(), "expected `,` or `>`");
+        assert_eq!(unexpected.span, (15, 0).into());
+    }
+
+    /// Messages of the `miette!` reports which aren't diagnostics of a program, and so have no code: usage errors, and
+    /// failures of the environment the compiler runs in.
+    const UNCODED_REPORTS: &[&str] = &[
+        "`--output` can only be used when building a single file",
+        "is too large to fit on the stack",
+        "No explanation for `{code}`",
+        "`--fix` can't write fixes back to stdin",
+        "Couldn't write `{path}`: {error}",
+        "Couldn't start a thread",
+        "The interpreter panicked while running the program",
+    ];
+
+    /// Finds every error code, written as `E` followed by four digits, in the source files under `dir`, along with the
+    /// line of each derived [`Diagnostic`], or `miette!` report not in [`UNCODED_REPORTS`], which isn't given a code.
+    fn codes_in(dir: &std::path::Path, codes: &mut Vec<String>, uncoded: &mut Vec<String>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                codes_in(&path, codes, uncoded);
+                continue;
+            }
+            if path.extension().is_none_or(|extension| extension != "rs") {
+                continue;
+            }
+
+            let source = std::fs::read_to_string(&path).unwrap();
+            let bytes = source.as_bytes();
+            for (index, _) in source.match_indices('E') {
+                let digits = bytes.get(index + 1..index + 5);
+                let is_code = digits.is_some_and(|digits| digits.iter().all(u8::is_ascii_digit))
+                    && !bytes.get(index + 5).is_some_and(u8::is_ascii_alphanumeric)
+                    && !index
+                        .checked_sub(1)
+                        .is_some_and(|previous| bytes[previous].is_ascii_alphanumeric());
+
+                if is_code {
+                    codes.push(source[index..index + 5].to_string());
+                }
+            }
+
+            // The attributes of a derived diagnostic run from its derive to the type it's on
+            let lines: Vec<_> = source.lines().collect();
+            for (number, line) in lines.iter().enumerate() {
+                if !(line.starts_with("#[derive(") && line.contains("Diagnostic")) {
+                    continue;
+                }
+                let attributes: String = lines[number + 1..]
+                    .iter()
+                    .take_while(|line| !line.contains("struct ") && !line.contains("enum "))
+                    .copied()
+                    .collect();
+                if !(attributes.contains("#[diagnostic(") && attributes.contains("code(")) {
+                    uncoded.push(format!("{}:{}", path.display(), number + 1));
+                }
+            }
+
+            // Split up so that this isn't found itself
+            for (index, _) in source.match_indices(concat!("miette", "!(")) {
+                let mut depth = 0;
+                let arguments: String = source[index..]
+                    .chars()
+                    .take_while(|&char| {
+                        depth += match char {
+                            '(' => 1,
+                            ')' => -1,
+                            _ => 0,
+                        };
+                        !(char == ')' && depth == 0)
+                    })
+                    .collect();
+
+                let allowed = UNCODED_REPORTS
+                    .iter()
+                    .any(|message| arguments.contains(message));
+                if !arguments.contains("code =") && !allowed {
+                    let line = source[..index].matches('\n').count() + 1;
+                    uncoded.push(format!("{}:{line}", path.display()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn error_codes_are_registered() {
+        let mut found = Vec::new();
+        let mut uncoded = Vec::new();
+        codes_in(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src"),
+            &mut found,
+            &mut uncoded,
+        );
+        assert!(!found.is_empty());
+        assert!(
+            uncoded.is_empty(),
+            "Diagnostics without a code: {uncoded:?}"
+        );
+
+        for code in found {
+            assert!(codes::lookup(&code).is_some(), "{code} has no explanation");
+        }
+        for entry in codes::REGISTRY {
+            assert!(!entry.explanation.is_empty(), "{} is empty", entry.code);
+        }
+    }
+
+    #[test]
+    fn diagnostics_have_registered_codes() {
+        init_logging();
+
+        let input = String::from(
+            "fun f(x T) { if a = b { (1 } a < b < c; (2; 1 + 2) } var y = 1 var z = ;",
+        );
+        let tokens = lex(&input);
+        let (_, diagnostics) = Parser::new(0, &tokens).parse_recovering();
+
+        assert_eq!(diagnostics.len(), 9);
+        for report in diagnostics {
+            let code = report.code().map(|code| code.to_string());
+            assert!(
+                code.as_deref().and_then(codes::lookup).is_some(),
+                "{report} has no registered code"
+            );
+        }
+    }
+
+    fn emit(format: Format, input: &str) -> String {
+        let input = String::from(input);
+        let tokens = lex(&input);
+        let (_, diagnostics) = Parser::new(0, &tokens).parse_recovering();
+
+        let file = SourceFile {
+            name: "test.src",
+            source: &input,
+        };
+        let mut emitter = Emitter::new(format, Vec::new());
+        for report in &diagnostics {
+            emitter.emit(&file, report).unwrap();
+        }
+        String::from_utf8(emitter.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn emit_short_diagnostics() {
+        init_logging();
+
+        let output = emit(Format::Short, "var a = 1;\nvar b = (2;\n");
+        assert_eq!(
+            output,
+            "test.src:2:9: error[E0003]: Unclosed delimiter `(`\n"
+        );
+    }
+
+    #[test]
+    fn emit_json_diagnostics() {
+        init_logging();
+
+        let output = emit(Format::Json, "var a = 1\nvar b = (2;");
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+
+        let missing = &lines[0];
+        assert_eq!(missing["file"], "test.src");
+        assert_eq!(missing["code"], "E0006");
+        assert_eq!(missing["severity"], "error");
+        assert_eq!(missing["labels"].as_array().unwrap().len(), 2);
+        let fix = &missing["suggestions"][0];
+        assert_eq!(fix["start"], 9);
+        assert_eq!(fix["end"], 9);
+        assert_eq!(fix["replacement"], ";");
+        assert_eq!(fix["applicability"], "machine-applicable");
+
+        let unclosed = &lines[1];
+        assert_eq!(unclosed["code"], "E0003");
+        assert_eq!(
+            (&unclosed["line"], &unclosed["column"]),
+            (&2.into(), &9.into())
+        );
+        assert!(unclosed["suggestions"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn emit_sarif_log() {
+        init_logging();
+
+        let output = emit(Format::Sarif, "var a = 1\nvar b = (2;");
+        let log: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+
+        let run = &log["runs"][0];
+        let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+        assert_eq!(rules.len(), codes::REGISTRY.len());
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["ruleId"], "E0006");
+        assert_eq!(results[0]["level"], "error");
+
+        let replacement = &results[0]["fixes"][0]["artifactChanges"][0]["replacements"][0];
+        assert_eq!(replacement["deletedRegion"]["startLine"], 1);
+        assert_eq!(replacement["deletedRegion"]["startColumn"], 10);
+        assert_eq!(replacement["insertedContent"]["text"], ";");
+
+        let region = &results[1]["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(
+            (&region["startLine"], &region["startColumn"]),
+            (&2.into(), &9.into())
+        );
+
+        // Columns and offsets are in UTF-16 code units, so the emoji counts as two
+        let output = emit(Format::Sarif, "var a = 1; \u{1F600} (2;");
+        let log: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(log["runs"][0]["columnKind"], "utf16CodeUnits");
+        let region = &log["runs"][0]["results"][0]["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(
+            (
+                &region["startColumn"],
+                &region["charOffset"],
+                &region["charLength"]
+            ),
+            (&15.into(), &14.into(), &1.into())
+        );
+    }
+
+    #[test]
+    fn format_round_trips() {
+        init_logging();
+
+        let input = String::from(
+            "var a=(1+2)*3; var b = -(a+1) ** 2; var c = (-a) ** 2; x = y = z;\nfn f(x: i32): i32 { if x < 1 { a - (b - c) } else if x == 2 { g((1, 2)); } else { f(x - 1) } } while a { a -= 1; }",
+        );
+        let target = "var a = (1 + 2) * 3;\nvar b = -(a + 1) ** 2;\nvar c = (-a) ** 2;\nx = y = z;\n\nfn f(x: i32): i32 {\n    if x < 1 {\n        a - (b - c)\n    } else if x == 2 {\n        g((1, 2));\n    } else {\n        f(x - 1)\n    }\n}\n\nwhile a {\n    a -= 1;\n}\n";
+
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+        let formatted = format::format(&module);
+        assert_eq!(formatted, target);
+
+        // Formatting keeps the meaning of the source, and formatted source stays as it is
+        let tokens = lex(&formatted);
+        let parsed = parse(&tokens);
+        let reformatted = build(&parsed);
+        assert_eq!(reformatted.to_string(), module.to_string());
+        assert_eq!(format::format(&reformatted), formatted);
+    }
+
+    #[test]
+    fn timings_report_each_pass() {
+        init_logging();
+
+        let input = String::from("var a = 1;\nvar b = a + 2;\n");
+        let mut timings = Timings::new("test.src", &input);
+        let tokens = timings.time("lex", || lex(&input));
+        timings.tokens = tokens.iter().filter(|token| !token.is_trivia()).count();
+        timings.time("parse", || parse(&tokens));
+        timings.time("consteval", || ());
+
+        assert_eq!(timings.lines, 2);
+        assert_eq!(timings.tokens, 12);
+        let names: Vec<&str> = timings.passes.iter().map(|pass| pass.name).collect();
+        assert_eq!(names, ["lex", "parse", "consteval"]);
+
+        let json = timings.to_json();
+        assert_eq!(json["tokens"], 12);
+        let passes = json["passes"].as_array().unwrap();
+        assert_eq!(passes.len(), 4);
+        assert_eq!(passes[3]["name"], "total");
+        assert_eq!(
+            passes[3]["nanos"].as_u64().unwrap(),
+            timings.total().as_nanos() as u64
+        );
+
+        // The columns line up however long the pass names are
+        let table = timings.to_string();
+        assert_eq!(table.lines().count(), 6);
+        assert!(table.lines().nth(1).unwrap().contains("ns/line"));
+        let time_end = "consteval".len() + 1 + 12;
+        for line in table.lines().skip(1) {
+            let chars: Vec<char> = line.chars().collect();
+            assert!(!chars[time_end - 1].is_whitespace(), "{table}");
+            assert_eq!(chars[time_end], ' ', "{table}");
+        }
+    }
+
+    #[test]
+    fn resolver_binds_names_to_declarations() {
+        init_logging();
+
+        let input = String::from(
+            "var a = 1; fn f(a: i32) { var b = a; { var a = b; a; } g(a); } fn g(x: i32) { f(x) } a;",
+        );
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+        let (resolution, diagnostics) = resolve(&module);
+        assert!(diagnostics.is_empty());
+
+        // Each use of a name, and the offset of the declaration it's bound to
+        let mut bindings: Vec<(usize, usize)> = resolution
+            .bindings
+            .iter()
+            .map(|(&offset, &id)| (offset, resolution.symbols.symbol(id).span.offset()))
+            .collect();
+        bindings.sort();
+        assert_eq!(
+            bindings,
+            [
+                (34, 16), // `var b = a` is the parameter
+                (47, 30), // `var a = b`
+                (50, 43), // `a;` is the innermost `a`
+                (55, 66), // `g` is declared after it's called
+                (57, 16), // `g(a)` is the parameter again
+                (78, 14), // `f` from inside `g`
+                (80, 68),
+                (85, 4),
+            ]
+        );
+
+        let f = resolution.symbols.symbol(resolution.bindings[&78]);
+        assert_eq!(resolution.symbols.name(f.name), "f");
+        assert_eq!(f.kind, SymbolKind::Function);
+        assert_eq!(f.ty.as_ref().unwrap().to_string(), "fn(i32) -> ()");
+    }
+
+    #[test]
+    fn resolver_binds_bracketed_names() {
+        init_logging();
+
+        let input = String::from("var a = 1; var b = (a); ((b)) * (a);");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+        let (resolution, diagnostics) = resolve(&module);
+        assert!(diagnostics.is_empty());
+
+        // Uses are keyed by the name itself, not the brackets which widen the expression's span
+        let mut bindings: Vec<(usize, usize)> = resolution
+            .bindings
+            .iter()
+            .map(|(&offset, &id)| (offset, resolution.symbols.symbol(id).span.offset()))
+            .collect();
+        bindings.sort();
+        assert_eq!(bindings, [(20, 4), (26, 15), (33, 4)]);
+
+        let StmtKind::Expr(product) = &module.stmts[2].kind else {
+            panic!("expected an expression statement");
+        };
+        let ExprKind::Binary(_, left, right) = &product.kind else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!((left.span.offset(), left.span.len()), (24, 5));
+        let name = |expr| {
+            resolution
+                .symbol(expr)
+                .map(|symbol| resolution.symbols.name(symbol.name))
+        };
+        assert_eq!(name(left), Some("b"));
+        assert_eq!(name(right), Some("a"));
+    }
+
+    #[test]
+    fn resolver_reports_undefined_names() {
+        init_logging();
+
+        let input =
+            String::from("var count = 1; fn f(x: i32) { var y = cuont + x; y + z; } var w = w;");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+        let (_, diagnostics) = resolve(&module);
+
+        let messages: Vec<String> = diagnostics
+            .iter()
+            .map(|report| report.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Cannot find `cuont` in this scope",
+                "Cannot find `z` in this scope",
+                "Cannot find `w` in this scope",
+            ]
+        );
+
+        let fix = suggestion(&diagnostics[0]).unwrap();
+        assert_eq!(fix.replacement, "count");
+        assert_eq!(fix.span, (38, 5).into());
+        assert_eq!(fix.applicability, Applicability::MaybeIncorrect);
+        assert!(suggestion(&diagnostics[1]).is_none());
+        assert!(diagnostics
+            .iter()
+            .all(|report| report.code().unwrap().to_string() == "E0011"));
+    }
+
+    #[test]
+    fn resolver_rejects_captured_variables() {
+        init_logging();
+
+        let input = String::from(
+            "var top = 1; fn outer(n: i32): i32 { var x = n; const C = 2; \
+             fn inner(): i32 { var own = top; { own + x + C + n } } \
+             fn sibling(): i32 { inner() + outer(0) } inner() + x }",
+        );
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+        let (_, diagnostics) = resolve(&module);
+
+        // The module's variables, the function's own, and functions around it can all be used
+        let reported: Vec<(String, usize, String)> = diagnostics
+            .iter()
+            .map(|report| {
+                let label = report.labels().unwrap().next().unwrap();
+                let code = report.code().unwrap().to_string();
+                (report.to_string(), label.offset(), code)
+            })
+            .collect();
+        let captured = |name: &str, offset| {
+            (
+                format!("Functions can't use `{name}` from the function they're declared in"),
+                offset,
+                "E0025".to_owned(),
+            )
+        };
+        assert_eq!(
+            reported,
+            [captured("x", 102), captured("C", 106), captured("n", 110)]
+        );
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("count", "cuont"), 1);
+        assert_eq!(edit_distance("ab", "ba"), 1);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+
+    #[test]
+    fn symbol_table_shadows_inner_scopes() {
+        let mut symbols = SymbolTable::new();
+        let span = (0, 1).into();
+
+        let outer = symbols
+            .declare("a", SymbolKind::Variable, span, Mutability::Mutable, None)
+            .unwrap();
+        let f = symbols
+            .declare("f", SymbolKind::Function, span, Mutability::Immutable, None)
+            .unwrap();
+
+        symbols.enter();
+        let inner = symbols
+            .declare(
+                "a",
+                SymbolKind::Parameter,
+                span,
+                Mutability::Immutable,
+                None,
+            )
+            .unwrap();
+        assert_eq!(symbols.lookup("a"), Some(inner));
+        assert_eq!(symbols.lookup("f"), Some(f));
+        assert_eq!(symbols.lookup("b"), None);
+
+        let mut visible: Vec<_> = symbols.visible().collect();
+        visible.sort();
+        assert_eq!(visible, [f, inner]);
+
+        // Variables can be declared again in the same scope, but parameters can't
+        let shadowed = symbols.declare("a", SymbolKind::Variable, span, Mutability::Mutable, None);
+        assert!(shadowed.is_ok());
+        let first = symbols
+            .declare(
+                "b",
+                SymbolKind::Parameter,
+                span,
+                Mutability::Immutable,
+                None,
+            )
+            .unwrap();
+        let duplicate = symbols.declare(
+            "b",
+            SymbolKind::Parameter,
+            span,
+            Mutability::Immutable,
+            None,
+        );
+        assert_eq!(duplicate, Err(first));
+
+        symbols.exit();
+        assert_eq!(symbols.lookup("a"), Some(outer));
+        assert_eq!(symbols.symbol(outer).mutability, Mutability::Mutable);
+    }
+
+    #[test]
+    fn resolver_reports_duplicate_declarations() {
+        init_logging();
+
+        let input =
+            String::from("fn f(a: i32, a: i32) {} fn g() {} fn f() { var x = 1; var x = x; }");
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+        let (_, diagnostics) = resolve(&module);
+
+        let reported: Vec<(String, Vec<usize>)> = diagnostics
+            .iter()
+            .map(|report| {
+                let offsets = report
+                    .labels()
+                    .into_iter()
+                    .flatten()
+                    .map(|label| label.offset())
+                    .collect();
+                (report.to_string(), offsets)
+            })
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                (
+                    "The function `f` is declared more than once".into(),
+                    vec![3, 37]
+                ),
+                (
+                    "The parameter `a` is declared more than once".into(),
+                    vec![5, 13]
+                ),
+            ]
+        );
+        assert_eq!(diagnostics[0].code().unwrap().to_string(), "E0012");
+    }
+
+    /// A diagnostic's message, and the offsets of its labels.
+    type Reported = (String, Vec<usize>);
+
+    /// Type checks `input`, returning each symbol with its type in declaration order, and each diagnostic.
+    fn type_check(input: &str, default_integer: &Type) -> (Vec<(String, String)>, Vec<Reported>) {
+        let input = String::from(input);
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+        let (resolution, diagnostics) = resolve(&module);
+        assert!(diagnostics.is_empty());
+        let (typing, diagnostics) = check_with(&module, &resolution, default_integer);
+
+        let symbols = resolution
+            .symbols
+            .symbols()
+            .iter()
+            .enumerate()
+            .map(|(id, symbol)| {
+                let ty = typing.symbol(id).unwrap().to_string();
+                (resolution.symbols.name(symbol.name).to_owned(), ty)
+            })
+            .collect();
+        let diagnostics = diagnostics
+            .iter()
+            .map(|report| {
+                let mut offsets: Vec<usize> = report
+                    .labels()
+                    .into_iter()
+                    .flatten()
+                    .map(|label| label.offset())
+                    .collect();
+                offsets.sort();
+                (report.to_string(), offsets)
+            })
+            .collect();
+
+        (symbols, diagnostics)
+    }
+
+    #[test]
+    fn type_checker_types_declarations() {
+        init_logging();
+
+        let (symbols, diagnostics) = type_check(
+            "fn add(a: i64, b: i64): i64 { a + b * 2 } var big = add(1, 2); var small: u8 = 1 + 2; \
+             var flag = (1 + small > 3) == true; var pair = (flag, -big); \
+             fn sign(x: i32): i32 { if x < 0 { -1 } else { 1 } }",
+            &Type::DEFAULT_INTEGER,
+        );
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        let symbols: Vec<(&str, &str)> = symbols
+            .iter()
+            .map(|(name, ty)| (name.as_str(), ty.as_str()))
+            .collect();
+        assert_eq!(
+            symbols,
+            [
+                ("add", "fn(i64, i64) -> i64"),
+                ("sign", "fn(i32) -> i32"),
+                ("a", "i64"),
+                ("b", "i64"),
+                ("big", "i64"),
+                ("small", "u8"),
+                ("flag", "bool"),
+                ("pair", "(bool, i64)"),
+                ("x", "i32"),
+            ]
+        );
+    }
+
+    #[test]
+    fn type_checker_reports_expected_and_found_types() {
+        init_logging();
+
+        let (_, diagnostics) = type_check(
+            "fn f(a: i32): bool { a } var b: bool = f(1, 2); var c: strng = true + 1; f(b); b();",
+            &Type::DEFAULT_INTEGER,
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                ("Mismatched types".into(), vec![14, 21]),
+                ("Expected 1 argument, found 2".into(), vec![3, 39]),
+                ("Cannot find type `strng`".into(), vec![55]),
+                (
+                    "Cannot apply `+` to `bool` and `{integer}`".into(),
+                    vec![63, 70]
+                ),
+                ("Mismatched types".into(), vec![8, 75]),
+                ("Cannot call a value of type `bool`".into(), vec![79]),
+            ]
+        );
+    }
+
+    #[test]
+    fn type_checker_rejects_invalid_assignees() {
+        init_logging();
+
+        let (_, diagnostics) = type_check(
+            "var a = 1; var p = &a; 1 = a; (a, a) = (1, 2); -a += 1; a = 2; *p *= 3; (a) = 4;",
+            &Type::DEFAULT_INTEGER,
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                ("Invalid left-hand side of `=`".into(), vec![23]),
+                ("Invalid left-hand side of `=`".into(), vec![30]),
+                ("Invalid left-hand side of `+=`".into(), vec![47]),
+            ]
+        );
+    }
+
+    #[test]
+    fn type_checker_rejects_changing_immutable_symbols() {
+        init_logging();
+
+        let (_, diagnostics) = type_check(
+            "const X = 1; X = 2; X += 1; var p = &X; fn f() {} f = f; X",
+            &Type::DEFAULT_INTEGER,
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                ("Cannot assign to the constant `X`".into(), vec![6, 13]),
+                ("Cannot assign to the constant `X`".into(), vec![6, 20]),
+                (
+                    "Cannot take a reference to the constant `X`".into(),
+                    vec![6, 37]
+                ),
+                ("Cannot assign to the function `f`".into(), vec![43, 50]),
+            ]
+        );
+
+        // Parameters are copied into a variable to be changed
+        let (_, diagnostics) = type_check(
+            "fn f(a: i32): i32 { a = 5; var p = &a; var b = a; b += 1; b }",
+            &Type::DEFAULT_INTEGER,
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                ("Cannot assign to the parameter `a`".into(), vec![5, 20]),
+                (
+                    "Cannot take a reference to the parameter `a`".into(),
+                    vec![5, 36]
+                ),
+            ]
+        );
+
+        // Variables can be changed, directly or through a reference
+        let (_, diagnostics) = type_check(
+            "var x = 1; x = 2; var p = &x; *p += 1;",
+            &Type::DEFAULT_INTEGER,
+        );
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+    }
+
+    #[test]
+    fn type_inference_unifies_local_variables() {
+        init_logging();
+
+        let (symbols, diagnostics) = type_check(
+            "fn f(a: i64): i64 { var x = 1 + 2; var y; y = x * a; var z = 3; var w = (z, true); y } \
+             var top = 1 << 2;",
+            &Type::builtin("u16").unwrap(),
+        );
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        let symbols: Vec<(&str, &str)> = symbols
+            .iter()
+            .map(|(name, ty)| (name.as_str(), ty.as_str()))
+            .collect();
+        assert_eq!(
+            symbols,
+            [
+                ("f", "fn(i64) -> i64"),
+                ("a", "i64"),
+                ("x", "i64"),
+                ("y", "i64"),
+                ("z", "u16"),
+                ("w", "(u16, bool)"),
+                ("top", "u16"),
+            ]
+        );
+
+        // Operators are checked once their operands' types have been inferred
+        let (_, diagnostics) = type_check(
+            "fn g() { var v; var s = v + v; v = true; var lonely; }",
+            &Type::DEFAULT_INTEGER,
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                ("Cannot apply `+` to `bool` and `bool`".into(), vec![24, 28]),
+                ("Type annotations needed for `lonely`".into(), vec![45]),
+            ]
+        );
+    }
+
+    #[test]
+    fn constant_evaluation_folds_and_reports_overflow() {
+        init_logging();
+
+        let input = String::from(
+            "const A: u8 = 2 * 100; const B = A + 50; fn f(x: i32): i32 { x * (3 + 4) } \
+             var c: u8 = 250 + 10 - 20; var v = 1; const D = v + 1; var e: u8 = 256;",
+        );
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+        let (resolution, _) = resolve(&module);
+        let (typing, diagnostics) = check_with(&module, &resolution, &Type::DEFAULT_INTEGER);
+        assert!(diagnostics.is_empty());
+        let (constants, diagnostics) = fold(&module, &resolution, &typing);
+
+        let constant = |name: &str| {
+            let id = resolution.symbols.lookup(name).unwrap();
+            constants.symbol(id)
+        };
+        assert_eq!(constant("A"), Some(Value::Int(200)));
+        assert_eq!(constant("B"), Some(Value::Int(250)));
+        assert_eq!(constant("D"), None);
+
+        // Constant parts of expressions which aren't constant are still folded
+        assert_eq!(constants.exprs.get(&(65, 7)), Some(&Value::Int(7)));
+
+        let reported: Vec<(String, usize, String)> = diagnostics
+            .iter()
+            .map(|report| {
+                let label = report.labels().unwrap().next().unwrap();
+                let code = report.code().unwrap().to_string();
+                (report.to_string(), label.offset(), code)
+            })
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                (
+                    "Arithmetic overflow evaluating a constant expression".into(),
+                    87,
+                    "E0019".into()
+                ),
+                (
+                    "The value of constant `D` isn't known at compile time".into(),
+                    123,
+                    "E0020".into()
+                ),
+                ("Literal out of range for `u8`".into(), 142, "E0026".into()),
+            ]
+        );
+    }
+
+    /// Runs `input` with `recursion_limit`, returning its result, or the message, offset and code of the error which
+    /// stopped it.
+    fn interpret(input: &str, recursion_limit: usize) -> Result<String, (String, usize, String)> {
+        let input = String::from(input);
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+        let (resolution, diagnostics) = resolve(&module);
+        assert!(diagnostics.is_empty());
+        let (typing, diagnostics) = check_with(&module, &resolution, &Type::DEFAULT_INTEGER);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        Interpreter::new(&resolution, &typing)
+            .with_recursion_limit(recursion_limit)
+            .run(&module)
+            .map(|value| value.to_string())
+            .map_err(|report| {
+                let label = report.labels().unwrap().next().unwrap();
+                let code = report.code().unwrap().to_string();
+                (report.to_string(), label.offset(), code)
+            })
+    }
+
+    #[test]
+    fn interpreter_runs_programs() {
+        init_logging();
+
+        let fib = "fn fib(x: i64): i64 { if x < 3 { 1 } else { fib(x - 1) + fib(x - 2) } }";
+        assert_eq!(interpret(&format!("{fib} fib(10)"), 100), Ok("55".into()));
+        assert_eq!(
+            interpret(&format!("{fib} fn main(): bool {{ fib(12) == 144 }}"), 100),
+            Ok("true".into())
+        );
+
+        let loops = "var total = 0; var i = 1; \
+                     while i <= 10 { if i > 5 { total += i * 2; } else { total -= 1; } i += 1; } \
+                     var r = &total; *r = *r / 3; (total, 2 ** 10, -128 >> 2)";
+        assert_eq!(interpret(loops, 100), Ok("(25, 1024, -32)".into()));
+
+        // Nested functions see the module's variables, and the functions declared around them
+        let nested = "var base = 10; fn outer(n: i32): i32 { fn inner(m: i32): i32 { m + base } \
+                      if n == 0 { inner(1) } else { outer(n - 1) + inner(n) } } outer(2)";
+        assert_eq!(interpret(nested, 100), Ok("34".into()));
+    }
+
+    #[test]
+    fn interpreter_reports_runtime_errors() {
+        init_logging();
+
+        assert_eq!(
+            interpret("var zero = 0; var a = 10 / zero;", 100),
+            Err(("Attempted to divide by zero".into(), 22, "E0021".into()))
+        );
+        assert_eq!(
+            interpret(
+                "fn double(x: u8): u8 { x * 2 } double(100) + double(200)",
+                100
+            ),
+            Err(("Arithmetic overflow".into(), 23, "E0022".into()))
+        );
+        assert_eq!(
+            interpret("var a: i32; a = a + 1;", 100),
+            Err((
+                "`a` was used before it was given a value".into(),
+                16,
+                "E0023".into()
+            ))
+        );
+        assert_eq!(
+            interpret(
+                "fn down(n: i64): i64 { if n == 0 { 0 } else { down(n - 1) } } down(10)",
+                11
+            ),
+            Ok("0".into())
+        );
+        assert_eq!(
+            interpret(
+                "fn down(n: i64): i64 { if n == 0 { 0 } else { down(n - 1) } } down(10)",
+                10
+            ),
+            Err((
+                "Reached the recursion limit of 10 calls".into(),
+                46,
+                "E0024".into()
+            ))
+        );
+
+        // A reference outliving its variable's call isn't mistaken for one to the frame now in the same place
+        let dangling =
+            "fn f(): &i32 { var x = 5; &x } fn g(): i32 { var x = 7; var r = f(); *r } g()";
+        assert_eq!(
+            interpret(dangling, 100),
+            Err((
+                "Used a reference to a variable which no longer exists".into(),
+                69,
+                "E0027".into()
+            ))
+        );
+        let dangling = "fn f(): &i32 { var x = 5; &x } fn g(): i32 { var x = 7; *f() = 3; x } g()";
+        assert_eq!(
+            interpret(dangling, 100),
+            Err((
+                "Used a reference to a variable which no longer exists".into(),
+                56,
+                "E0027".into()
+            ))
+        );
+    }
+
+    /// Evaluates the parser's output for `input` with `environment`.
+    fn evaluate_parsed(input: &str, environment: &[(&'static str, i64)]) -> miette::Result<i64> {
+        let input = String::from(input);
+        let tokens = lex(&input);
+        let parsed = Parser::new(0, &tokens).parse()?;
+        let mut environment = environment.iter().copied().collect();
+        evaluate(&parsed, &mut environment)
+    }
+
+    #[test]
+    fn postfix_evaluation_follows_precedence() {
+        init_logging();
+
+        let cases = [
+            ("1 + 2 * 3", 7),
+            ("(1 + 2) * 3", 9),
+            ("10 - 4 - 3", 3),
+            ("100 / 10 / 5", 2),
+            ("2 ** 3 ** 2", 512),
+            ("-2 ** 2", -4),
+            ("1 << 2 + 1", 8),
+            ("-x * 3 + y", -10),
+            ("(x * (y + 1) ** 2 >= 36) == 1", 1),
+            ("z = x += y * 2; z", 8),
+        ];
+        for (input, expected) in cases {
+            let value = evaluate_parsed(input, &[("x", 4), ("y", 2)]).unwrap();
+            assert_eq!(value, expected, "{input}");
+        }
+
+        let error = evaluate_parsed("1 + 2 / (x - 4)", &[("x", 4)]).unwrap_err();
+        assert_eq!(error.code().unwrap().to_string(), "E0021");
+        let error = evaluate_parsed("x + w", &[("x", 4)]).unwrap_err();
+        assert_eq!(error.to_string(), "Cannot find `w` in this scope");
+        let error = evaluate_parsed("f(x)", &[("x", 4)]).unwrap_err();
+        assert_eq!(error.to_string(), "`call/1` can't be evaluated");
+        assert_eq!(error.code().unwrap().to_string(), "E0028");
+    }
+
+    /// Compiles `input` to bytecode, or returns the message of the error which stopped it.
+    fn compile_bytecode(input: &str) -> Result<Program, String> {
+        let input = String::from(input);
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+        let (resolution, diagnostics) = resolve(&module);
+        assert!(diagnostics.is_empty());
+        let (typing, diagnostics) = check_with(&module, &resolution, &Type::DEFAULT_INTEGER);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let (constants, diagnostics) = fold(&module, &resolution, &typing);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        compile(
+            &module,
+            &resolution,
+            &typing,
+            &constants,
+            "test.src",
+            &input,
+        )
+        .map_err(|report| report.to_string())
+    }
+
+    /// Runs `program` with `recursion_limit`, returning its result, or the message, offset and code of the error
+    /// which stopped it.
+    fn run_bytecode(
+        program: &Program,
+        recursion_limit: usize,
+    ) -> Result<String, (String, usize, String)> {
+        Vm::new(program)
+            .with_recursion_limit(recursion_limit)
+            .run()
+            .map(|value| value.to_string())
+            .map_err(|report| {
+                let label = report.labels().unwrap().next().unwrap();
+                let code = report.code().unwrap().to_string();
+                (report.to_string(), label.offset(), code)
+            })
+    }
+
+    #[test]
+    fn bytecode_runs_like_the_interpreter() {
+        init_logging();
+
+        let fib = "fn fib(x: i64): i64 { if x < 3 { 1 } else { fib(x - 1) + fib(x - 2) } }";
+        let leak = "fn leak(): &i32 { var x: i32 = 1; &x }";
+        let programs = [
+            format!("{fib} fib(10)"),
+            format!("{fib} fn main(): bool {{ fib(12) == 144 }}"),
+            "var total = 0; var i = 1; \
+             while i <= 10 { if i > 5 { total += i * 2; } else { total -= 1; } i += 1; } \
+             var r = &total; *r = *r / 3; (total, 2 ** 10, -128 >> 2)"
+                .into(),
+            "fn swap(p: &i32, q: &i32) { var t = *p; *p = *q; *q = t; } \
+             var a: i32 = 3; var b: i32 = 4; swap(&a, &b); a -= 16; (a, b, !(a < b), !a)"
+                .into(),
+            "var zero = 0; var a = 10 / zero;".into(),
+            "fn double(x: u8): u8 { x * 2 } double(100) + double(200)".into(),
+            "var a: i32; a = a + 1;".into(),
+            // A reference outliving its call is caught, even once a later call has reused its slot
+            format!("{leak} fn g(p: &i32): i32 {{ var y: i32 = 42; *p }} g(leak())"),
+            format!("{leak} *leak()"),
+            format!("{leak} var p = leak(); *p = 2;"),
+        ];
+        for program in programs {
+            let compiled = compile_bytecode(&program).unwrap();
+            assert_eq!(
+                run_bytecode(&compiled, 100),
+                interpret(&program, 100),
+                "{program}"
+            );
+        }
+        let dangling = compile_bytecode(&format!("{leak} *leak()")).unwrap();
+        assert_eq!(run_bytecode(&dangling, 100).unwrap_err().2, "E0027");
+
+        let down = compile_bytecode(
+            "fn down(n: i64): i64 { if n == 0 { 0 } else { down(n - 1) } } down(10)",
+        )
+        .unwrap();
+        assert_eq!(run_bytecode(&down, 11), Ok("0".into()));
+        assert_eq!(
+            run_bytecode(&down, 10),
+            Err((
+                "Reached the recursion limit of 10 calls".into(),
+                46,
+                "E0024".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn bytecode_disassembles() {
+        init_logging();
+
+        let program = compile_bytecode("fn inc(x: u8): u8 { x + 1 } var a = inc(2);").unwrap();
+        assert_eq!(
+            program.to_string(),
+            "0 <module>: 0 params, 1 slot\n  \
+             0000  function 1\n  \
+             0001  int 2\n  \
+             0002  call 1\n  \
+             0003  store 0\n  \
+             0004  unit\n  \
+             0005  return\n\
+             \n\
+             1 inc: 1 param, 1 slot\n  \
+             0000  load 0\n  \
+             0001  int 1\n  \
+             0002  add u8\n  \
+             0003  return\n"
+        );
+    }
+
+    #[test]
+    fn constant_expressions_compile_to_their_value() {
+        init_logging();
+
+        let input = "const A: i32 = 2 * 100; var b = 1; A + 3 * 4 + b";
+
+        let program = compile_bytecode(input).unwrap();
+        assert_eq!(
+            program.to_string(),
+            "0 <module>: 0 params, 2 slots\n  \
+             0000  int 200\n  \
+             0001  store 0\n  \
+             0002  int 1\n  \
+             0003  store 1\n  \
+             0004  int 212\n  \
+             0005  load 1\n  \
+             0006  add i32\n  \
+             0007  return\n"
+        );
+        assert_eq!(run_bytecode(&program, 100), Ok("213".into()));
+
+        let module = lower_ir(input).unwrap().to_string();
+        assert!(module.contains("%4: i32 = const 212\n"), "{module}");
+        assert!(!module.contains("mul"), "{module}");
+    }
+
+    #[test]
+    fn bytecode_files_round_trip() {
+        init_logging();
+
+        let program = compile_bytecode(
+            "fn fib(x: i64): i64 { if x < 3 { 1 } else { fib(x - 1) + fib(x - 2) } } \
+             var big: u64 = 18446744073709551615; var neg: i8 = -128; (fib(15), big, neg, true)",
+        )
+        .unwrap();
+        let bytes = program.to_bytes();
+        assert!(bytes.starts_with(b"DOBC"));
+        let read = Program::from_bytes(&bytes).unwrap();
+        assert_eq!(read, program);
+        assert_eq!(
+            run_bytecode(&read, 100),
+            Ok("(610, 18446744073709551615, -128, true)".into())
+        );
+
+        let error = Program::from_bytes(b"#!/bin/sh").unwrap_err();
+        assert_eq!(error.to_string(), "Not a bytecode file");
+        assert_eq!(error.code().unwrap().to_string(), "E0034");
+
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        let error = Program::from_bytes(&newer).unwrap_err();
+        assert!(
+            error.to_string().contains("version 2 isn't supported"),
+            "{error}"
+        );
+        assert_eq!(error.code().unwrap().to_string(), "E0035");
+
+        let error = Program::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(error.to_string(), "The bytecode file is corrupt");
+        assert_eq!(error.code().unwrap().to_string(), "E0036");
+
+        // A jump past the end of the function is rejected rather than run
+        let mut broken = program.clone();
+        broken.functions[1].code[3] = Instruction::JumpIfFalse(1000);
+        let error = Program::from_bytes(&broken.to_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "The bytecode file is corrupt");
+    }
+
+    /// Generates LLVM IR for `input`, named `name`, or returns the error which stopped it.
+    fn generate_llvm(input: &str, name: &str) -> Result<String, miette::Report> {
+        let input = String::from(input);
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+        let (resolution, diagnostics) = resolve(&module);
+        assert!(diagnostics.is_empty());
+        let (typing, diagnostics) = check_with(&module, &resolution, &Type::DEFAULT_INTEGER);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        llvm::generate(&module, &resolution, &typing, name)
+    }
+
+    /// Examples which are only input for the parser's benchmarks, rather than programs which check, so have no golden
+    /// file.
+    const PARSER_ONLY_EXAMPLES: &[&str] = &[
+        "80_char_1000_lines",
+        "example",
+        "example_1000_lines",
+        "infix",
+        "infix_1000_lines",
+        "long_identifier",
+        "parse_bench",
+    ];
+
+    /// Each example in `examples`, with its golden file in `examples/llvm`, failing if one doesn't have a golden file
+    /// unless it's in [`PARSER_ONLY_EXAMPLES`].
+    fn llvm_golden_files() -> Vec<(std::path::PathBuf, std::path::PathBuf)> {
+        let examples = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let mut files: Vec<_> = std::fs::read_dir(&examples)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "src"))
+            .filter(|source| {
+                let stem = source.file_stem().unwrap().to_str().unwrap();
+                !PARSER_ONLY_EXAMPLES.contains(&stem)
+            })
+            .map(|source| {
+                let golden = examples
+                    .join("llvm")
+                    .join(source.file_stem().unwrap())
+                    .with_extension("ll");
+                assert!(
+                    golden.exists() || std::env::var_os("UPDATE_GOLDEN").is_some(),
+                    "{} has no golden file, so run the tests with `UPDATE_GOLDEN` set to write one",
+                    source.display()
+                );
+                (golden, source)
+            })
+            .collect();
+        files.sort();
+        assert!(!files.is_empty());
+        files
+    }
+
+    /// Set `UPDATE_GOLDEN` to write the generated output to the golden files instead of comparing against them.
+    #[test]
+    fn llvm_output_matches_golden_files() {
+        init_logging();
+
+        for (golden, source) in llvm_golden_files() {
+            let name = source.file_name().unwrap().to_str().unwrap();
+            let output = generate_llvm(&std::fs::read_to_string(&source).unwrap(), name).unwrap();
+            if std::env::var_os("UPDATE_GOLDEN").is_some() {
+                std::fs::write(&golden, output).unwrap();
+            } else {
+                assert_eq!(output, std::fs::read_to_string(&golden).unwrap(), "{name}");
+            }
+        }
+
+        // References which could outlive their call would read a stack slot another call reused, which `run` reports
+        let leak = "fn leak(): &i32 { var x: i32 = 1; &x }";
+        let error = generate_llvm(&format!("{leak} *leak()"), "test.src").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Returning references from functions can't be compiled to LLVM IR yet"
+        );
+        assert_eq!(error.code().unwrap().to_string(), "E0029");
+        for escape in [
+            "var g: &i32 = &(1); fn f() { var x: i32 = 1; g = &x; }",
+            "fn f(p: &&i32) { var x: i32 = 1; *p = &x; }",
+        ] {
+            let error = generate_llvm(escape, "test.src").unwrap_err();
+            assert!(
+                error
+                    .to_string()
+                    .starts_with("Storing references in globals"),
+                "{escape}: {error}"
+            );
+        }
+        generate_llvm(
+            "fn f(p: &i32) { var q = p; q = &(3); *p = *q; } var a: i32 = 1; f(&a); a",
+            "test.src",
+        )
+        .unwrap();
+
+        let error = generate_llvm("var s: string;", "test.src").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Values of type `string` can't be compiled to LLVM IR yet"
+        );
+        assert_eq!(error.code().unwrap().to_string(), "E0029");
+    }
+
+    /// Runs `command`, returning whether it succeeded, or `None` if it couldn't be started.
+    fn try_command(command: &mut std::process::Command) -> Option<bool> {
+        let output = command.output().ok()?;
+        Some(output.status.success())
+    }
+
+    /// Compiles the LLVM IR at `ll` to the executable `executable` with `clang`, or `llc` and `cc`, returning
+    /// whether either is installed.
+    fn compile_llvm(ll: &std::path::Path, executable: &std::path::Path) -> bool {
+        use std::process::Command;
+
+        // LLVM before version 15 only reads `ptr` when opaque pointers are turned on
+        let clang = [&[][..], &["-mllvm", "-opaque-pointers"]]
+            .into_iter()
+            .find_map(|flags| {
+                let compiled = try_command(
+                    Command::new("clang")
+                        .args(flags)
+                        .arg(ll)
+                        .arg("-o")
+                        .arg(executable),
+                );
+                compiled.filter(|&compiled| compiled)
+            });
+        if clang.is_some() {
+            return true;
+        }
+
+        let object = executable.with_extension("o");
+        let llc = [
+            &["-relocation-model=pic"][..],
+            &["-relocation-model=pic", "-opaque-pointers"],
+        ]
+        .into_iter()
+        .find_map(|flags| {
+            let compiled = try_command(
+                Command::new("llc")
+                    .args(flags)
+                    .args(["-filetype=obj", "-o"])
+                    .arg(&object)
+                    .arg(ll),
+            );
+            compiled.filter(|&compiled| compiled)
+        });
+        match llc {
+            Some(_) => {
+                let linked = try_command(Command::new("cc").arg(&object).arg("-o").arg(executable));
+                assert_eq!(linked, Some(true), "couldn't link {}", object.display());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Compiles each golden file to a native program, when `clang` or `llc` is installed, and checks it prints what
+    /// the interpreter does.
+    #[test]
+    fn llvm_output_runs_natively() {
+        init_logging();
+
+        let directory = std::env::temp_dir().join(format!("llvm-compiler-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        for (golden, source) in llvm_golden_files() {
+            let executable = directory.join(golden.file_stem().unwrap());
+            if !compile_llvm(&golden, &executable) {
+                eprintln!("Neither `clang` nor `llc` is installed, so LLVM IR can't be run");
+                break;
+            }
+
+            let output = std::process::Command::new(&executable).output().unwrap();
+            let expected = interpret(&std::fs::read_to_string(&source).unwrap(), 1000).unwrap();
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap(),
+                format!("{expected}\n")
+            );
+        }
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    const COUNT: &str = "\
+fn @count(%n: i64) -> i64 {
+entry:
+    %zero: i64 = const 0
+    jump loop
+loop:
+    %i: i64 = phi [entry: %zero], [body: %next]
+    %done: bool = ge %i, %n ; stops at `%n`
+    branch %done, exit, body
+body:
+    %one: i64 = const 1
+    %next: i64 = add %i, %one
+    jump loop
+exit:
+    return %i
+}
+";
+
+    #[test]
+    fn ir_text_round_trips() {
+        init_logging();
+
+        let module = ir::parse::parse(COUNT).unwrap();
+        ir::verify::verify(&module).unwrap();
+        let printed = module.to_string();
+        assert_eq!(
+            printed,
+            "\
+fn @count(%0: i64) -> i64 {
+b0:
+    %1: i64 = const 0
+    jump b1
+b1:
+    %2: i64 = phi [b0: %1], [b2: %5]
+    %3: bool = ge %2, %0
+    branch %3, b3, b2
+b2:
+    %4: i64 = const 1
+    %5: i64 = add %2, %4
+    jump b1
+b3:
+    return %2
+}
+"
+        );
+        assert_eq!(ir::parse::parse(&printed).unwrap(), module);
+
+        let text = "\
+global @total: u8
+global @\"odd name\": (bool, (i32,))
+
+fn @main() -> (u8, bool) {
+b0:
+    %0: &u8 = global @total
+    %1: u8 = const 255
+    store %0, %1
+    %2: &(bool, (i32,)) = alloca
+    %3: (bool, (i32,)) = load %2
+    %4: bool = extract %3, 0
+    %5: fn(&u8) -> u8 = function @read
+    %6: u8 = call %5(%0)
+    %7: (u8, bool) = tuple %6, %4
+    %8: i32 = const -7
+    %9: i32 = neg %8
+    %10: bool = not %4
+    %11: () = tuple
+    return %7
+}
+
+fn @read(%0: &u8) -> u8 {
+b0:
+    %1: u8 = load %0
+    return %1
+}
+";
+        let module = ir::parse::parse(text).unwrap();
+        ir::verify::verify(&module).unwrap();
+        assert_eq!(module.to_string(), text);
+    }
+
+    #[test]
+    fn ir_parser_reports_syntax_errors() {
+        init_logging();
+
+        let cases = [
+            ("fn @f() -> i64 {\nb0:\n    return %x\n}\n", "`%x` isn't defined"),
+            ("fn @f() -> i64 {\nb0:\n    jump b1\n}\n", "There's no block called `b1`"),
+            (
+                "fn @f() -> i64 {\nb0:\n    %0: i64 = const 1\n    %0: i64 = const 2\n    return %0\n}\n",
+                "`%0` is defined more than once",
+            ),
+            ("fn @f() -> int {\n}\n", "Unknown type `int`"),
+            ("fn @f() -> i64 {\nb0:\n    %0: i64 = frob\n}\n", "Unknown instruction `frob`"),
+            ("fn @f() -> i64 {\nb0:\n    return\n}\n", "Expected a value, found the end of the line"),
+            ("fn @f() -> i64 $", "Unexpected character `$`"),
+            ("fn @f(%0: i64) i64 {\n}\n", "Expected `->`, found `i64`"),
+        ];
+        for (text, expected) in cases {
+            let error = ir::parse::parse(text).unwrap_err();
+            assert_eq!(error.to_string(), expected, "{text}");
+            assert_eq!(error.code().unwrap().to_string(), "E0030");
+        }
+    }
+
+    #[test]
+    fn ir_verifier_reports_invalid_ir() {
+        init_logging();
+
+        let cases = [
+            (
+                "fn @f(%0: bool) -> i64 {\nb0:\n    branch %0, b1, b2\nb1:\n    %1: i64 = const 1\n    jump b2\n\
+                 b2:\n    return %1\n}\n",
+                "Invalid IR in `@f`: %1 is used in b2, but isn't defined on every path to it",
+            ),
+            (
+                "fn @f() -> i64 {\nb0:\n    %0: i64 = add %1, %1\n    %1: i64 = const 1\n    return %0\n}\n",
+                "Invalid IR in `@f`: %1 is used in b0 before it's defined",
+            ),
+            (
+                "fn @f(%0: i64, %1: i32) -> i64 {\nb0:\n    %2: i64 = add %0, %1\n    return %2\n}\n",
+                "Invalid IR in `@f`: %1 has type i32, but the instruction defining %2 needs i64",
+            ),
+            (
+                "fn @f(%0: i64) -> bool {\nb0:\n    return %0\n}\n",
+                "Invalid IR in `@f`: %0 has type i64, but `return` needs bool",
+            ),
+            (
+                "fn @f(%0: i64) -> i64 {\nb0:\n    %1: bool = lt %0, %0\n    branch %1, b1, b2\nb1:\n    jump b2\n\
+                 b2:\n    %2: i64 = phi [b1: %0]\n    return %2\n}\n",
+                "Invalid IR in `@f`: the phi defining %2 has no value from b0",
+            ),
+            (
+                "fn @f() -> i64 {\nb0:\n    %0: i64 = const 1\n}\n",
+                "Invalid IR in `@f`: b0 has no terminator",
+            ),
+            (
+                "fn @f() -> u8 {\nb0:\n    %0: u8 = const 256\n    return %0\n}\n",
+                "Invalid IR in `@f`: 256 isn't a constant of type u8",
+            ),
+            (
+                "fn @f() -> i64 {\nb0:\n    %0: fn() -> i64 = function @g\n    %1: i64 = call %0()\n    return %1\n}\n",
+                "Invalid IR in `@f`: there's no function called @g",
+            ),
+        ];
+        for (text, expected) in cases {
+            let module = ir::parse::parse(text).unwrap();
+            let error = ir::verify::verify(&module).unwrap_err();
+            assert_eq!(error.to_string(), expected, "{text}");
+            assert_eq!(error.code().unwrap().to_string(), "E0031");
+        }
+    }
+
+    #[test]
+    fn ir_builder_builds_verified_functions() {
+        init_logging();
+
+        let int = Type::Int {
+            signed: true,
+            bits: 32,
+        };
+        let mut builder = FunctionBuilder::new("max", &[int.clone(), int.clone()], int.clone());
+        let (left, right) = (builder.param(0), builder.param(1));
+        let then = builder.create_block();
+        let otherwise = builder.create_block();
+        let end = builder.create_block();
+
+        let greater = builder.compare(ir::Comparison::Greater, left, right);
+        builder.branch(greater, then, otherwise);
+        builder.switch_to(then);
+        builder.jump(end);
+        builder.switch_to(otherwise);
+        let slot = builder.alloca(int.clone());
+        builder.store(slot, right);
+        let loaded = builder.load(slot);
+        builder.jump(end);
+        builder.switch_to(end);
+        let result = builder.phi(int, &[(then, left), (otherwise, loaded)]);
+        builder.ret(result);
+
+        let module = ir::Module {
+            globals: Vec::new(),
+            functions: vec![builder.finish()],
+        };
+        ir::verify::verify(&module).unwrap();
+        assert_eq!(
+            module.to_string(),
+            "\
+fn @max(%0: i32, %1: i32) -> i32 {
+b0:
+    %2: bool = gt %0, %1
+    %3: &i32 = alloca
+    branch %2, b1, b2
+b1:
+    jump b3
+b2:
+    store %3, %1
+    %4: i32 = load %3
+    jump b3
+b3:
+    %5: i32 = phi [b1: %0], [b2: %4]
+    return %5
+}
+"
+        );
+    }
+
+    fn lower_ir(input: &str) -> Result<ir::Module, String> {
+        let input = String::from(input);
+        let tokens = lex(&input);
+        let parsed = parse(&tokens);
+        let module = build(&parsed);
+        let (resolution, diagnostics) = resolve(&module);
+        assert!(diagnostics.is_empty());
+        let (typing, diagnostics) = check_with(&module, &resolution, &Type::DEFAULT_INTEGER);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let (constants, diagnostics) = fold(&module, &resolution, &typing);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        ir::lower::lower(&module, &resolution, &typing, &constants)
+            .map_err(|report| report.to_string())
+    }
+
+    #[test]
+    fn lowered_ir_verifies_and_round_trips() {
+        init_logging();
+
+        let mut sources: Vec<String> = llvm_golden_files()
+            .into_iter()
+            .map(|(_, source)| std::fs::read_to_string(source).unwrap())
+            .collect();
+        sources.extend(
+            [
+                "var x = 1; x += 2; if x > 2 { (x, true) } else { (0, false) }",
+                "fn main() -> i64 { 42 } main",
+                "var pair = (1, 2); var r = &pair; *r",
+                "fn f(x: u8) -> u8 { var y: u8; y = x; while y < 10 { y += 1; } y } f(3)",
+            ]
+            .map(String::from),
+        );
+
+        for source in sources {
+            let module = lower_ir(&source).unwrap();
+            ir::verify::verify(&module).unwrap_or_else(|report| panic!("{report}\n{module}"));
+            let printed = module.to_string();
+            assert_eq!(ir::parse::parse(&printed).unwrap().to_string(), printed);
+        }
+
+        let module = lower_ir("fn main() -> i64 { 42 } main()").unwrap();
+        assert_eq!(
+            module.to_string(),
+            "\
+fn @main.1() -> i64 {
+b0:
+    %0: i64 = const 42
+    return %0
+}
+
+fn @main() -> i64 {
+b0:
+    %0: fn() -> i64 = function @main.1
+    %1: i64 = call %0()
+    %2: fn() -> i64 = function @main.1
+    %3: i64 = call %2()
+    return %3
+}
+"
+        );
+    }
 }
@@ -0,0 +1,1152 @@
+//! Generates LLVM IR as text from a checked module, written by `--emit=llvm`.
+//!
+//! Nothing here links against LLVM, so the compiler builds without it installed, and the `.ll` files it writes can be
+//! compiled by `clang` or `llc`. The module's statements become the body of `main`, which prints the program's result
+//! as `run` does, and each function declared in the module becomes a function of its own.
+//!
+//! Variables live in stack slots allocated when their function is entered, or in globals when they're declared at the
+//! top of the module, leaving LLVM's `mem2reg` pass to turn them into SSA values. Arithmetic which would be reported
+//! as an error by `run` traps instead, and there's no recursion limit beyond the size of the native stack.
+//!
+//! `run` reports a reference used after the call it points into has returned, but here it would read a stack slot
+//! which another call may have reused. So references can't be returned from functions, or stored in globals or
+//! through other references from inside of one, which are the only ways for them to outlive their call.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::ast::{
+    BinaryOp, Block, Expr, ExprKind, Function, Ident, If, ItemKind, Module, Stmt, StmtKind, UnaryOp,
+};
+use crate::bytecode::IntType;
+use crate::sema::check::Typing;
+use crate::sema::consteval::LiteralOutOfRange;
+use crate::sema::resolve::Resolution;
+use crate::sema::symbols::{SymbolId, SymbolKind};
+use crate::sema::types::Type;
+
+/// Something the checker accepts, but which has no LLVM IR to generate for it yet
+#[derive(Error, Diagnostic, Debug)]
+#[error("{what} can't be compiled to LLVM IR yet")]
+#[diagnostic(code(E0029))]
+pub struct Unsupported {
+    pub what: String,
+    #[label("used here")]
+    pub span: SourceSpan,
+}
+
+/// A value computed by the generated code.
+#[derive(Debug, Clone)]
+struct Value {
+    /// A register such as `%t3`, a global such as `@fib`, or a constant
+    text: String,
+    ty: Type,
+}
+
+impl Value {
+    fn unit() -> Self {
+        Value {
+            text: "zeroinitializer".into(),
+            ty: Type::unit(),
+        }
+    }
+}
+
+/// Generates an LLVM module running `module`, which was parsed from the file `name`.
+pub fn generate<'t>(
+    module: &Module<'t>,
+    resolution: &Resolution<'t>,
+    typing: &Typing,
+    name: &str,
+) -> Result<String, miette::Report> {
+    let mut generator = Generator {
+        resolution,
+        typing,
+        // `main` is the program's entry point, which a function declared as `main` can't replace
+        symbols: HashSet::from(["main".to_owned(), "printf".to_owned()]),
+        names: HashMap::new(),
+        functions: Vec::new(),
+        globals: HashMap::new(),
+        module_level: true,
+        header: String::new(),
+        strings: HashMap::new(),
+        powers: Vec::new(),
+        declarations: BTreeSet::new(),
+        body: Body::new(),
+    };
+    generator.declare_functions(&module.stmts);
+
+    let end = SourceSpan::from((0, 0));
+    let mut value = generator.stmts(&module.stmts, None)?;
+
+    // The program's result is the value `main` returns instead, if it's declared
+    let main = module.stmts.iter().find_map(|stmt| match &stmt.kind {
+        StmtKind::Item(item) => {
+            let ItemKind::Function(function) = &item.kind;
+            (function.name.name == "main" && function.params.is_empty()).then_some(function)
+        }
+        _ => None,
+    });
+    if let Some(id) = main.and_then(|main| resolution.declaration(main.name.span)) {
+        let ty = match typing.symbol(id) {
+            Some(Type::Function(_, return_type)) => (**return_type).clone(),
+            _ => Type::unit(),
+        };
+        let callee = Value {
+            text: generator.names[&id].clone(),
+            ty: Type::Function(Vec::new(), Box::new(ty.clone())),
+        };
+        value = generator.call(&callee, &[], ty, end)?;
+    }
+    generator.print(&value, end)?;
+    generator.emit("ret i32 0".into());
+    let main = generator.finish("define i32 @main()");
+
+    generator.module_level = false;
+    let mut definitions = Vec::new();
+    for function in generator.functions.clone() {
+        definitions.push(generator.function(function)?);
+    }
+    definitions.push(main);
+    for &ty in &generator.powers {
+        definitions.push(power(ty));
+    }
+
+    let mut output = format!("; ModuleID = '{name}'\nsource_filename = \"{name}\"\n");
+    if !generator.header.is_empty() {
+        output.push('\n');
+        output.push_str(&generator.header);
+    }
+    for definition in definitions {
+        output.push('\n');
+        output.push_str(&definition);
+    }
+    if !generator.declarations.is_empty() {
+        output.push('\n');
+        for declaration in &generator.declarations {
+            output.push_str(declaration);
+            output.push('\n');
+        }
+    }
+
+    Ok(output)
+}
+
+/// The function being generated.
+struct Body {
+    /// The stack slot of each variable and parameter
+    slots: HashMap<SymbolId, String>,
+    /// Every name given to a register or block, which must be unique within the function
+    names: HashSet<String>,
+    /// The entry block's stack allocations, which come before any other instruction
+    allocas: String,
+    code: String,
+    /// The label of the block being generated, which phi nodes name as where values come from
+    block: String,
+    values: usize,
+    /// Whether anything branches to the block which traps
+    traps: bool,
+}
+
+impl Body {
+    fn new() -> Self {
+        Body {
+            slots: HashMap::new(),
+            names: HashSet::from(["entry".to_owned(), "trap".to_owned()]),
+            allocas: String::new(),
+            code: String::new(),
+            block: "entry".into(),
+            values: 0,
+            traps: false,
+        }
+    }
+}
+
+struct Generator<'m, 'r, 't> {
+    resolution: &'r Resolution<'t>,
+    typing: &'r Typing,
+    /// Every global name, which must be unique within the module
+    symbols: HashSet<String>,
+    /// The global name of each function
+    names: HashMap<SymbolId, String>,
+    /// Every function declared in the module
+    functions: Vec<&'m Function<'t>>,
+    /// The global of each variable declared at the top of the module
+    globals: HashMap<SymbolId, String>,
+    /// Whether the module's statements are being generated, whose variables are globals
+    module_level: bool,
+    /// Definitions of globals and string constants
+    header: String,
+    /// The global holding each string constant
+    strings: HashMap<String, String>,
+    /// The integer types which `**` has been used with, each of which needs a function to compute it
+    powers: Vec<IntType>,
+    /// Intrinsics and library functions which are called
+    declarations: BTreeSet<String>,
+    body: Body,
+}
+
+impl<'m, 'r, 't> Generator<'m, 'r, 't> {
+    /// Names every function declared in `stmts`, including those nested inside of other statements.
+    fn declare_functions(&mut self, stmts: &'m [Stmt<'t>]) {
+        for stmt in stmts {
+            match &stmt.kind {
+                StmtKind::Item(item) => {
+                    let ItemKind::Function(function) = &item.kind;
+                    if let Some(id) = self.resolution.declaration(function.name.span) {
+                        let name = unique(&mut self.symbols, function.name.name);
+                        self.names.insert(id, identifier('@', &name));
+                        self.functions.push(function);
+                    }
+                    self.declare_functions(&function.body.stmts);
+                }
+                StmtKind::Block(block) | StmtKind::While { body: block, .. } => {
+                    self.declare_functions(&block.stmts)
+                }
+                StmtKind::If(if_stmt) => {
+                    self.declare_functions(&if_stmt.then_block.stmts);
+                    if let Some(else_branch) = &if_stmt.else_branch {
+                        self.declare_functions(std::slice::from_ref(else_branch));
+                    }
+                }
+                StmtKind::Variable { .. }
+                | StmtKind::Const { .. }
+                | StmtKind::Expr(_)
+                | StmtKind::Error => {}
+            }
+        }
+    }
+
+    fn function(&mut self, function: &Function<'t>) -> Result<String, miette::Report> {
+        let id = self.resolution.declaration(function.name.span);
+        let Some(Type::Function(params, return_type)) = id.and_then(|id| self.typing.symbol(id))
+        else {
+            unreachable!("`{}` wasn't given a type", function.name.name)
+        };
+        let return_type = (**return_type).clone();
+        if return_type.has_references() {
+            let span = function
+                .return_type
+                .as_ref()
+                .map_or(function.name.span, |ty| ty.span);
+            return Err(Unsupported {
+                what: "Returning references from functions".into(),
+                span,
+            }
+            .into());
+        }
+
+        self.body = Body::new();
+        let mut arguments = Vec::new();
+        for (param, ty) in function.params.iter().zip(params.clone()) {
+            let llvm_type = self.llvm_type(&ty, param.span)?;
+            let register = self.register(param.name.name);
+            arguments.push(format!("{llvm_type} {register}"));
+            if let Some(slot) = self.declare(param.name.span, &ty)? {
+                self.emit(format!("store {llvm_type} {register}, ptr {slot}"));
+            }
+        }
+
+        let value = self.block(&function.body, true)?;
+        let return_type = if return_type.is_unit() {
+            self.emit("ret void".into());
+            "void".to_owned()
+        } else {
+            let llvm_type = self.llvm_type(&return_type, function.body.span)?;
+            self.emit(format!("ret {llvm_type} {}", value.text));
+            llvm_type
+        };
+        let name = &self.names[&id.unwrap()];
+        Ok(self.finish(&format!(
+            "define internal {return_type} {name}({})",
+            arguments.join(", ")
+        )))
+    }
+
+    /// The definition of the function whose body has been generated, given its signature.
+    fn finish(&mut self, signature: &str) -> String {
+        let body = std::mem::replace(&mut self.body, Body::new());
+        let mut definition = format!("{signature} {{\nentry:\n{}{}", body.allocas, body.code);
+        if body.traps {
+            definition.push_str("trap:\n  call void @llvm.trap()\n  unreachable\n");
+            self.declarations.insert("declare void @llvm.trap()".into());
+        }
+        definition.push_str("}\n");
+        definition
+    }
+
+    fn emit(&mut self, instruction: String) {
+        self.body.code.push_str("  ");
+        self.body.code.push_str(&instruction);
+        self.body.code.push('\n');
+    }
+
+    /// Starts the block called `label`.
+    fn start(&mut self, label: &str) {
+        self.body.code.push_str(label);
+        self.body.code.push_str(":\n");
+        self.body.block = label.to_owned();
+    }
+
+    /// A register or label based on `name`, which isn't used yet in the function.
+    fn register(&mut self, name: &str) -> String {
+        identifier('%', &unique(&mut self.body.names, name))
+    }
+
+    /// A label based on `name`, without the `%` it's written with when branching to it.
+    fn label(&mut self, name: &str) -> String {
+        self.register(name)[1..].to_owned()
+    }
+
+    /// A new register to hold a value.
+    fn value(&mut self) -> String {
+        let name = format!("t{}", self.body.values);
+        self.body.values += 1;
+        self.register(&name)
+    }
+
+    /// The LLVM type values of `ty` are represented with.
+    fn llvm_type(&self, ty: &Type, span: SourceSpan) -> Result<String, miette::Report> {
+        let llvm_type = match ty {
+            Type::Int { bits, .. } => format!("i{bits}"),
+            Type::Bool => "i1".into(),
+            Type::Tuple(elements) if elements.is_empty() => "{}".into(),
+            Type::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| self.llvm_type(element, span))
+                    .collect::<Result<Vec<_>, _>>()?;
+                format!("{{ {} }}", elements.join(", "))
+            }
+            Type::Reference(_) | Type::Function(..) => "ptr".into(),
+            _ => {
+                return Err(Unsupported {
+                    what: format!("Values of type `{ty}`"),
+                    span,
+                }
+                .into())
+            }
+        };
+        Ok(llvm_type)
+    }
+
+    /// The type the checker gave `expr`.
+    fn ty(&self, expr: &Expr) -> Type {
+        self.typing.expr(expr).cloned().unwrap_or(Type::Error)
+    }
+
+    /// The integer type the checker gave `expr`.
+    fn int_type(&self, expr: &Expr) -> IntType {
+        // Floats can't be written yet, so arithmetic on anything other than an integer can never be reached
+        self.typing
+            .expr(expr)
+            .and_then(IntType::new)
+            .unwrap_or(IntType {
+                signed: true,
+                bits: 64,
+            })
+    }
+
+    /// The stack slot or global of the variable declared by the name at `span`, which is allocated if it hasn't been
+    /// yet.
+    fn declare(&mut self, span: SourceSpan, ty: &Type) -> Result<Option<String>, miette::Report> {
+        let Some(id) = self.resolution.declaration(span) else {
+            return Ok(None);
+        };
+        if let Some(slot) = self.body.slots.get(&id).or(self.globals.get(&id)) {
+            return Ok(Some(slot.clone()));
+        }
+
+        let llvm_type = self.llvm_type(ty, span)?;
+        let name = self
+            .resolution
+            .symbols
+            .name(self.resolution.symbols.symbol(id).name);
+        let slot = if self.module_level {
+            let global = identifier('@', &unique(&mut self.symbols, name));
+            self.header.push_str(&format!(
+                "{global} = internal global {llvm_type} zeroinitializer\n"
+            ));
+            self.globals.insert(id, global.clone());
+            global
+        } else {
+            let slot = self.alloca(&format!("{name}.addr"), &llvm_type);
+            self.body.slots.insert(id, slot.clone());
+            slot
+        };
+        Ok(Some(slot))
+    }
+
+    /// Allocates a stack slot for a value of `llvm_type`, returning a pointer to it.
+    fn alloca(&mut self, name: &str, llvm_type: &str) -> String {
+        let slot = self.register(name);
+        self.body
+            .allocas
+            .push_str(&format!("  {slot} = alloca {llvm_type}\n"));
+        slot
+    }
+
+    /// Generates a sequence of statements sharing a scope, returning the value of the last one, or of `tail`.
+    fn stmts(
+        &mut self,
+        stmts: &[Stmt<'t>],
+        tail: Option<&Expr<'t>>,
+    ) -> Result<Value, miette::Report> {
+        let Some((last, rest)) = stmts.split_last() else {
+            return match tail {
+                Some(tail) => self.expr(tail),
+                None => Ok(Value::unit()),
+            };
+        };
+
+        for stmt in rest {
+            self.stmt(stmt, false)?;
+        }
+        match tail {
+            Some(tail) => {
+                self.stmt(last, false)?;
+                self.expr(tail)
+            }
+            None => self.stmt(last, true),
+        }
+    }
+
+    /// Generates a block, returning its value if `keep` is set.
+    fn block(&mut self, block: &Block<'t>, keep: bool) -> Result<Value, miette::Report> {
+        let ends_with_if = matches!(
+            block.stmts.last(),
+            Some(Stmt {
+                kind: StmtKind::If(_),
+                ..
+            })
+        );
+
+        if keep && (block.tail.is_some() || ends_with_if) {
+            return self.stmts(&block.stmts, block.tail.as_deref());
+        }
+        for stmt in &block.stmts {
+            self.stmt(stmt, false)?;
+        }
+        if let Some(tail) = &block.tail {
+            self.expr(tail)?;
+        }
+        Ok(Value::unit())
+    }
+
+    /// Generates a statement, returning its value if `keep` is set.
+    fn stmt(&mut self, stmt: &Stmt<'t>, keep: bool) -> Result<Value, miette::Report> {
+        match &stmt.kind {
+            // Functions are generated separately
+            StmtKind::Item(_) | StmtKind::Error => {}
+            StmtKind::Variable { name, value, .. } => {
+                let ty = self
+                    .resolution
+                    .declaration(name.span)
+                    .and_then(|id| self.typing.symbol(id))
+                    .cloned()
+                    .unwrap_or(Type::Error);
+                let slot = self.declare(name.span, &ty)?;
+                let value = match value {
+                    Some(value) => self.expr(value)?,
+                    // A variable without a value reads as zero, rather than being reported as it is by `run`
+                    None => Value {
+                        text: "zeroinitializer".into(),
+                        ty,
+                    },
+                };
+                if let Some(slot) = slot {
+                    self.store(&value, &slot, stmt.span)?;
+                }
+            }
+            StmtKind::Const { name, value, .. } => {
+                let value = self.expr(value)?;
+                if let Some(slot) = self.declare(name.span, &value.ty)? {
+                    self.store(&value, &slot, stmt.span)?;
+                }
+            }
+            StmtKind::Expr(expr) => {
+                let value = self.expr(expr)?;
+                if keep {
+                    return Ok(value);
+                }
+            }
+            StmtKind::Block(block) => return self.block(block, keep),
+            StmtKind::If(if_stmt) => return self.if_stmt(if_stmt, keep),
+            StmtKind::While { condition, body } => {
+                let head = self.label("while.cond");
+                let then = self.label("while.body");
+                let end = self.label("while.end");
+
+                self.emit(format!("br label %{head}"));
+                self.start(&head);
+                let condition = self.expr(condition)?;
+                self.emit(format!(
+                    "br i1 {}, label %{then}, label %{end}",
+                    condition.text
+                ));
+                self.start(&then);
+                self.block(body, false)?;
+                self.emit(format!("br label %{head}"));
+                self.start(&end);
+            }
+        }
+        Ok(Value::unit())
+    }
+
+    /// Generates an `if`, joining the values of its branches with a phi node if `keep` is set.
+    fn if_stmt(&mut self, if_stmt: &If<'t>, keep: bool) -> Result<Value, miette::Report> {
+        let condition = self.expr(&if_stmt.condition)?;
+        let then = self.label("if.then");
+        let otherwise = if_stmt.else_branch.as_ref().map(|_| self.label("if.else"));
+        let end = self.label("if.end");
+
+        self.emit(format!(
+            "br i1 {}, label %{then}, label %{}",
+            condition.text,
+            otherwise.as_ref().unwrap_or(&end)
+        ));
+        self.start(&then);
+        let then_value = self.block(&if_stmt.then_block, keep)?;
+        let then_end = self.body.block.clone();
+        self.emit(format!("br label %{end}"));
+
+        let Some((else_branch, otherwise)) = if_stmt.else_branch.as_ref().zip(otherwise) else {
+            self.start(&end);
+            return Ok(Value::unit());
+        };
+        self.start(&otherwise);
+        let else_value = self.stmt(else_branch, keep)?;
+        let else_end = self.body.block.clone();
+        self.emit(format!("br label %{end}"));
+        self.start(&end);
+
+        if !keep || then_value.ty.is_unit() {
+            return Ok(Value::unit());
+        }
+        let llvm_type = self.llvm_type(&then_value.ty, if_stmt.then_block.span)?;
+        let result = self.value();
+        self.emit(format!(
+            "{result} = phi {llvm_type} [ {}, %{then_end} ], [ {}, %{else_end} ]",
+            then_value.text, else_value.text
+        ));
+        Ok(Value {
+            text: result,
+            ty: then_value.ty,
+        })
+    }
+
+    fn expr(&mut self, expr: &Expr<'t>) -> Result<Value, miette::Report> {
+        let ty = self.ty(expr);
+        let text = match &expr.kind {
+            ExprKind::Integer(literal) => self.literal(literal, false, expr)?,
+            ExprKind::Boolean(value) => value.to_string(),
+            ExprKind::Identifier(_) => return self.load(expr),
+            ExprKind::Unary(op, operand) => {
+                // `-128` is in range for an `i8`, even though `128` isn't
+                if let (UnaryOp::Negate, ExprKind::Integer(literal)) = (op, &operand.kind) {
+                    self.literal(literal, true, expr)?
+                } else {
+                    return self.unary(*op, operand, expr);
+                }
+            }
+            ExprKind::Binary(op, left, right) => return self.binary(*op, left, right, expr),
+            ExprKind::Call(callee, arguments) => {
+                let callee = self.expr(callee)?;
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.expr(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                return self.call(&callee, &arguments, ty, expr.span);
+            }
+            ExprKind::Tuple(elements) if elements.is_empty() => return Ok(Value::unit()),
+            ExprKind::Tuple(elements) => {
+                let llvm_type = self.llvm_type(&ty, expr.span)?;
+                let mut tuple = "poison".to_owned();
+                for (index, element) in elements.iter().enumerate() {
+                    let element = self.expr(element)?;
+                    let element_type = self.llvm_type(&element.ty, expr.span)?;
+                    let next = self.value();
+                    self.emit(format!(
+                        "{next} = insertvalue {llvm_type} {tuple}, {element_type} {}, {index}",
+                        element.text
+                    ));
+                    tuple = next;
+                }
+                tuple
+            }
+        };
+        Ok(Value { text, ty })
+    }
+
+    fn call(
+        &mut self,
+        callee: &Value,
+        arguments: &[Value],
+        ty: Type,
+        span: SourceSpan,
+    ) -> Result<Value, miette::Report> {
+        let arguments = arguments
+            .iter()
+            .map(|argument| {
+                Ok(format!(
+                    "{} {}",
+                    self.llvm_type(&argument.ty, span)?,
+                    argument.text
+                ))
+            })
+            .collect::<Result<Vec<_>, miette::Report>>()?
+            .join(", ");
+
+        if ty.is_unit() {
+            self.emit(format!("call void {}({arguments})", callee.text));
+            return Ok(Value::unit());
+        }
+        let llvm_type = self.llvm_type(&ty, span)?;
+        let result = self.value();
+        self.emit(format!(
+            "{result} = call {llvm_type} {}({arguments})",
+            callee.text
+        ));
+        Ok(Value { text: result, ty })
+    }
+
+    /// The value of the name `expr`.
+    fn load(&mut self, expr: &Expr<'t>) -> Result<Value, miette::Report> {
+        let ty = self.ty(expr);
+        let slot = self.variable(expr)?;
+        if let Some(name) = slot.strip_prefix("fn ") {
+            return Ok(Value {
+                text: name.to_owned(),
+                ty,
+            });
+        }
+
+        let llvm_type = self.llvm_type(&ty, expr.span)?;
+        let result = self.value();
+        self.emit(format!("{result} = load {llvm_type}, ptr {slot}"));
+        Ok(Value { text: result, ty })
+    }
+
+    /// The stack slot or global holding the variable `expr` names, or `fn` followed by the name of a function.
+    fn variable(&self, expr: &Expr<'t>) -> Result<String, miette::Report> {
+        let ExprKind::Identifier(Ident { name, .. }) = expr.kind else {
+            unreachable!("only names can be loaded")
+        };
+        let Some(id) = self.resolution.binding(expr) else {
+            unreachable!("`{name}` wasn't resolved, which should have been reported")
+        };
+
+        if self.resolution.symbols.symbol(id).kind == SymbolKind::Function {
+            return Ok(format!("fn {}", self.names[&id]));
+        }
+        let Some(slot) = self.body.slots.get(&id).or(self.globals.get(&id)) else {
+            unreachable!(
+                "`{name}` belongs to an enclosing function, which should have been reported"
+            )
+        };
+        Ok(slot.clone())
+    }
+
+    /// A pointer to what `expr` refers to, storing any value which isn't a variable in a stack slot of its own.
+    fn place(&mut self, expr: &Expr<'t>) -> Result<String, miette::Report> {
+        match &expr.kind {
+            ExprKind::Identifier(_) => {
+                let slot = self.variable(expr)?;
+                if !slot.starts_with("fn ") {
+                    return Ok(slot);
+                }
+            }
+            ExprKind::Unary(UnaryOp::Deref, operand) => return Ok(self.expr(operand)?.text),
+            _ => {}
+        }
+
+        let value = self.expr(expr)?;
+        let llvm_type = self.llvm_type(&value.ty, expr.span)?;
+        let slot = self.alloca("temporary", &llvm_type);
+        self.store(&value, &slot, expr.span)?;
+        Ok(slot)
+    }
+
+    fn store(&mut self, value: &Value, slot: &str, span: SourceSpan) -> Result<(), miette::Report> {
+        let llvm_type = self.llvm_type(&value.ty, span)?;
+        self.emit(format!("store {llvm_type} {}, ptr {slot}", value.text));
+        Ok(())
+    }
+
+    fn unary(
+        &mut self,
+        op: UnaryOp,
+        operand: &Expr<'t>,
+        expr: &Expr<'t>,
+    ) -> Result<Value, miette::Report> {
+        let ty = self.ty(expr);
+        let text = match op {
+            UnaryOp::Reference => self.place(operand)?,
+            // Already the value of the operand
+            UnaryOp::Plus => return self.expr(operand),
+            UnaryOp::Deref => {
+                let pointer = self.expr(operand)?;
+                let llvm_type = self.llvm_type(&ty, expr.span)?;
+                let result = self.value();
+                self.emit(format!("{result} = load {llvm_type}, ptr {}", pointer.text));
+                result
+            }
+            UnaryOp::Negate => {
+                let value = self.expr(operand)?;
+                self.with_overflow("sub", self.int_type(expr), "0", &value.text)
+            }
+            UnaryOp::Not => {
+                let value = self.expr(operand)?;
+                let (llvm_type, mask) = match ty {
+                    Type::Bool => ("i1".to_owned(), "true"),
+                    _ => (format!("i{}", self.int_type(expr).bits), "-1"),
+                };
+                let result = self.value();
+                self.emit(format!("{result} = xor {llvm_type} {}, {mask}", value.text));
+                result
+            }
+        };
+        Ok(Value { text, ty })
+    }
+
+    fn binary(
+        &mut self,
+        op: BinaryOp,
+        left: &Expr<'t>,
+        right: &Expr<'t>,
+        expr: &Expr<'t>,
+    ) -> Result<Value, miette::Report> {
+        let arithmetic = match op {
+            BinaryOp::AddAssign => Some(BinaryOp::Add),
+            BinaryOp::SubtractAssign => Some(BinaryOp::Subtract),
+            BinaryOp::MultiplyAssign => Some(BinaryOp::Multiply),
+            _ => None,
+        };
+        if op == BinaryOp::Assign || arithmetic.is_some() {
+            self.assign(left, right, arithmetic)?;
+            return Ok(Value::unit());
+        }
+
+        let ty = self.ty(expr);
+        let left_value = self.expr(left)?;
+        let right_value = self.expr(right)?;
+        let text = match op {
+            BinaryOp::Greater
+            | BinaryOp::Less
+            | BinaryOp::GreaterEqual
+            | BinaryOp::LessEqual
+            | BinaryOp::Equal
+            | BinaryOp::NotEqual => self.compare(op, &left_value, &right_value, expr.span)?,
+            _ => self.arithmetic(op, self.int_type(left), &left_value.text, &right_value.text),
+        };
+        Ok(Value { text, ty })
+    }
+
+    /// Assigns `value` to `target`, first applying `operator` to the current value of `target` and `value` for a
+    /// compound assignment.
+    fn assign(
+        &mut self,
+        target: &Expr<'t>,
+        value: &Expr<'t>,
+        operator: Option<BinaryOp>,
+    ) -> Result<(), miette::Report> {
+        let slot = self.place(target)?;
+        let mut value = self.expr(value)?;
+
+        // A reference stored anywhere but a variable of the function could outlive the call it points into
+        let local = matches!(target.kind, ExprKind::Identifier(_))
+            && self
+                .resolution
+                .binding(target)
+                .is_some_and(|id| self.body.slots.contains_key(&id));
+        if !self.module_level && !local && value.ty.has_references() {
+            return Err(Unsupported {
+                what: "Storing references in globals, or through other references, inside of a function".into(),
+                span: target.span,
+            }
+            .into());
+        }
+
+        if let Some(operator) = operator {
+            let ty = self.int_type(target);
+            let current = self.value();
+            self.emit(format!("{current} = load i{}, ptr {slot}", ty.bits));
+            value.text = self.arithmetic(operator, ty, &current, &value.text);
+        }
+        self.store(&value, &slot, target.span)
+    }
+
+    fn compare(
+        &mut self,
+        op: BinaryOp,
+        left: &Value,
+        right: &Value,
+        span: SourceSpan,
+    ) -> Result<String, miette::Report> {
+        if matches!(left.ty, Type::Tuple(_)) {
+            return Err(Unsupported {
+                what: "Comparing tuples".into(),
+                span,
+            }
+            .into());
+        }
+
+        let signed = matches!(left.ty, Type::Int { signed: true, .. });
+        let predicate = match (op, signed) {
+            (BinaryOp::Equal, _) => "eq",
+            (BinaryOp::NotEqual, _) => "ne",
+            (BinaryOp::Greater, true) => "sgt",
+            (BinaryOp::Greater, false) => "ugt",
+            (BinaryOp::Less, true) => "slt",
+            (BinaryOp::Less, false) => "ult",
+            (BinaryOp::GreaterEqual, true) => "sge",
+            (BinaryOp::GreaterEqual, false) => "uge",
+            (BinaryOp::LessEqual, true) => "sle",
+            _ => "ule",
+        };
+        let llvm_type = self.llvm_type(&left.ty, span)?;
+        let result = self.value();
+        self.emit(format!(
+            "{result} = icmp {predicate} {llvm_type} {}, {}",
+            left.text, right.text
+        ));
+        Ok(result)
+    }
+
+    /// Applies an arithmetic operator, trapping where `run` would report an error.
+    fn arithmetic(&mut self, op: BinaryOp, ty: IntType, left: &str, right: &str) -> String {
+        let llvm_type = format!("i{}", ty.bits);
+        let sign = if ty.signed { "s" } else { "u" };
+        match op {
+            BinaryOp::Add => self.with_overflow("add", ty, left, right),
+            BinaryOp::Subtract => self.with_overflow("sub", ty, left, right),
+            BinaryOp::Multiply => self.with_overflow("mul", ty, left, right),
+            BinaryOp::Divide => {
+                let zero = self.value();
+                self.emit(format!("{zero} = icmp eq {llvm_type} {right}, 0"));
+                self.trap_if(&zero);
+                // The only quotient which doesn't fit is the smallest signed value divided by -1
+                if ty.signed {
+                    let minimum = ty.ty().range().unwrap().start().to_string();
+                    let (smallest, negative, overflow) = (self.value(), self.value(), self.value());
+                    self.emit(format!(
+                        "{smallest} = icmp eq {llvm_type} {left}, {minimum}"
+                    ));
+                    self.emit(format!("{negative} = icmp eq {llvm_type} {right}, -1"));
+                    self.emit(format!("{overflow} = and i1 {smallest}, {negative}"));
+                    self.trap_if(&overflow);
+                }
+                let result = self.value();
+                self.emit(format!("{result} = {sign}div {llvm_type} {left}, {right}"));
+                result
+            }
+            BinaryOp::Power => {
+                if !self.powers.contains(&ty) {
+                    self.powers.push(ty);
+                    self.declare_overflow("mul", ty);
+                    self.declarations.insert("declare void @llvm.trap()".into());
+                }
+                let result = self.value();
+                self.emit(format!(
+                    "{result} = call {llvm_type} @pow.{}({llvm_type} {left}, {llvm_type} {right})",
+                    ty.ty()
+                ));
+                result
+            }
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+                // Compared unsigned, so that negative amounts are out of range too
+                let out_of_range = self.value();
+                self.emit(format!(
+                    "{out_of_range} = icmp uge {llvm_type} {right}, {}",
+                    ty.bits
+                ));
+                self.trap_if(&out_of_range);
+
+                let right_shift = if ty.signed { "ashr" } else { "lshr" };
+                let result = self.value();
+                if op == BinaryOp::ShiftRight {
+                    self.emit(format!(
+                        "{result} = {right_shift} {llvm_type} {left}, {right}"
+                    ));
+                    return result;
+                }
+
+                // Bits shifted out, including the sign bit of a signed value, are lost if shifting back doesn't
+                // give the same value
+                let (back, lost) = (self.value(), self.value());
+                self.emit(format!("{result} = shl {llvm_type} {left}, {right}"));
+                self.emit(format!(
+                    "{back} = {right_shift} {llvm_type} {result}, {right}"
+                ));
+                self.emit(format!("{lost} = icmp ne {llvm_type} {back}, {left}"));
+                self.trap_if(&lost);
+                result
+            }
+            _ => unreachable!("`{op:?}` isn't an arithmetic operator"),
+        }
+    }
+
+    /// Applies an LLVM arithmetic intrinsic which reports overflow, eg. `llvm.sadd.with.overflow.i64`, trapping if it
+    /// does.
+    fn with_overflow(&mut self, operation: &str, ty: IntType, left: &str, right: &str) -> String {
+        let llvm_type = format!("i{}", ty.bits);
+        let intrinsic = self.declare_overflow(operation, ty);
+        let (pair, result, overflow) = (self.value(), self.value(), self.value());
+        self.emit(format!(
+            "{pair} = call {{ {llvm_type}, i1 }} {intrinsic}({llvm_type} {left}, {llvm_type} {right})"
+        ));
+        self.emit(format!(
+            "{result} = extractvalue {{ {llvm_type}, i1 }} {pair}, 0"
+        ));
+        self.emit(format!(
+            "{overflow} = extractvalue {{ {llvm_type}, i1 }} {pair}, 1"
+        ));
+        self.trap_if(&overflow);
+        result
+    }
+
+    /// Declares the overflow reporting intrinsic for `operation` on `ty`, returning its name.
+    fn declare_overflow(&mut self, operation: &str, ty: IntType) -> String {
+        let llvm_type = format!("i{}", ty.bits);
+        let sign = if ty.signed { "s" } else { "u" };
+        let intrinsic = format!("@llvm.{sign}{operation}.with.overflow.{llvm_type}");
+        self.declarations.insert(format!(
+            "declare {{ {llvm_type}, i1 }} {intrinsic}({llvm_type}, {llvm_type})"
+        ));
+        intrinsic
+    }
+
+    /// Branches to the function's trap block if `condition` is true, continuing in a new block otherwise.
+    fn trap_if(&mut self, condition: &str) {
+        let next = self.label("ok");
+        self.emit(format!("br i1 {condition}, label %trap, label %{next}"));
+        self.body.traps = true;
+        self.start(&next);
+    }
+
+    /// An integer literal as a constant of the type the checker gave `expr`.
+    fn literal(
+        &mut self,
+        literal: &str,
+        negative: bool,
+        expr: &Expr<'t>,
+    ) -> Result<String, miette::Report> {
+        let ty = self.ty(expr);
+        let value = literal
+            .parse::<i128>()
+            .ok()
+            .map(|value| if negative { -value } else { value })
+            .filter(|&value| ty.fits(value));
+
+        match value {
+            Some(value) => Ok(value.to_string()),
+            None => Err(LiteralOutOfRange {
+                ty,
+                span: expr.span,
+            }
+            .into()),
+        }
+    }
+
+    /// Prints `value` followed by a newline, as `run` writes out a program's result.
+    fn print(&mut self, value: &Value, span: SourceSpan) -> Result<(), miette::Report> {
+        if value.ty.is_unit() {
+            return Ok(());
+        }
+
+        let mut format = String::new();
+        let mut arguments = Vec::new();
+        self.format(value, &mut format, &mut arguments, span)?;
+        format.push('\n');
+
+        let format = self.string(&format);
+        self.declarations
+            .insert("declare i32 @printf(ptr, ...)".into());
+        self.emit(format!(
+            "call i32 (ptr, ...) @printf(ptr {format}{})",
+            arguments
+                .iter()
+                .map(|argument| format!(", {argument}"))
+                .collect::<String>()
+        ));
+        Ok(())
+    }
+
+    /// Appends a `printf` format for `value` to `format`, and the arguments it needs to `arguments`.
+    fn format(
+        &mut self,
+        value: &Value,
+        format: &mut String,
+        arguments: &mut Vec<String>,
+        span: SourceSpan,
+    ) -> Result<(), miette::Report> {
+        match &value.ty {
+            Type::Int { signed, bits } => {
+                let mut text = value.text.clone();
+                if *bits < 64 {
+                    let extend = if *signed { "sext" } else { "zext" };
+                    text = self.value();
+                    self.emit(format!("{text} = {extend} i{bits} {} to i64", value.text));
+                }
+                format.push_str(if *signed { "%lld" } else { "%llu" });
+                arguments.push(format!("i64 {text}"));
+            }
+            Type::Bool => {
+                let (yes, no) = (self.string("true"), self.string("false"));
+                let text = self.value();
+                self.emit(format!(
+                    "{text} = select i1 {}, ptr {yes}, ptr {no}",
+                    value.text
+                ));
+                format.push_str("%s");
+                arguments.push(format!("ptr {text}"));
+            }
+            Type::Tuple(elements) => {
+                let llvm_type = self.llvm_type(&value.ty, span)?;
+                format.push('(');
+                for (index, ty) in elements.iter().enumerate() {
+                    if index > 0 {
+                        format.push_str(", ");
+                    }
+                    let element = self.value();
+                    self.emit(format!(
+                        "{element} = extractvalue {llvm_type} {}, {index}",
+                        value.text
+                    ));
+                    let element = Value {
+                        text: element,
+                        ty: ty.clone(),
+                    };
+                    self.format(&element, format, arguments, span)?;
+                }
+                if elements.len() == 1 {
+                    format.push(',');
+                }
+                format.push(')');
+            }
+            Type::Function(..) => format.push_str("<function>"),
+            Type::Reference(_) => format.push_str("<reference>"),
+            ty => {
+                return Err(Unsupported {
+                    what: format!("Values of type `{ty}`"),
+                    span,
+                }
+                .into())
+            }
+        }
+        Ok(())
+    }
+
+    /// The global holding `text` as a null terminated string constant.
+    fn string(&mut self, text: &str) -> String {
+        if let Some(global) = self.strings.get(text) {
+            return global.clone();
+        }
+
+        let global = identifier('@', &unique(&mut self.symbols, ".str"));
+        let mut escaped = String::new();
+        for byte in text.bytes() {
+            match byte {
+                b'"' | b'\\' => escaped.push_str(&format!("\\{byte:02X}")),
+                b' '..=b'~' => escaped.push(byte as char),
+                _ => escaped.push_str(&format!("\\{byte:02X}")),
+            }
+        }
+        self.header.push_str(&format!(
+            "{global} = private unnamed_addr constant [{} x i8] c\"{escaped}\\00\"\n",
+            text.len() + 1
+        ));
+        self.strings.insert(text.to_owned(), global.clone());
+        global
+    }
+}
+
+/// A function computing `base ** exponent` for `ty`, by squaring.
+///
+/// A negative exponent is the reciprocal of a positive power, which truncates towards zero like division, and the
+/// base is only squared when there's more of the exponent left, so squaring never overflows unless the result would.
+fn power(ty: IntType) -> String {
+    let t = format!("i{}", ty.bits);
+    let sign = if ty.signed { "s" } else { "u" };
+    let start = if ty.signed {
+        format!(
+            "  %negative = icmp slt {t} %exponent, 0
+  br i1 %negative, label %reciprocal, label %loop
+reciprocal:
+  %zero = icmp eq {t} %base, 0
+  br i1 %zero, label %trap, label %truncate
+truncate:
+  %one = icmp eq {t} %base, 1
+  %minus_one = icmp eq {t} %base, -1
+  %odd = trunc {t} %exponent to i1
+  %sign = select i1 %odd, {t} -1, {t} 1
+  %unit = select i1 %minus_one, {t} %sign, {t} 0
+  %inverse = select i1 %one, {t} 1, {t} %unit
+  ret {t} %inverse
+"
+        )
+    } else {
+        "  br label %loop\n".into()
+    };
+
+    format!(
+        "define internal {t} @pow.{ty}({t} %base, {t} %exponent) {{
+entry:
+{start}loop:
+  %result = phi {t} [ 1, %entry ], [ %result.shifted, %square ]
+  %factor = phi {t} [ %base, %entry ], [ %squared, %square ]
+  %remaining = phi {t} [ %exponent, %entry ], [ %halved, %square ]
+  %bit = trunc {t} %remaining to i1
+  br i1 %bit, label %multiply, label %shift
+multiply:
+  %product = call {{ {t}, i1 }} @llvm.{sign}mul.with.overflow.{t}({t} %result, {t} %factor)
+  %multiplied = extractvalue {{ {t}, i1 }} %product, 0
+  %multiply.overflow = extractvalue {{ {t}, i1 }} %product, 1
+  br i1 %multiply.overflow, label %trap, label %shift
+shift:
+  %result.shifted = phi {t} [ %result, %loop ], [ %multiplied, %multiply ]
+  %halved = lshr {t} %remaining, 1
+  %done = icmp eq {t} %halved, 0
+  br i1 %done, label %exit, label %square
+square:
+  %squares = call {{ {t}, i1 }} @llvm.{sign}mul.with.overflow.{t}({t} %factor, {t} %factor)
+  %squared = extractvalue {{ {t}, i1 }} %squares, 0
+  %square.overflow = extractvalue {{ {t}, i1 }} %squares, 1
+  br i1 %square.overflow, label %trap, label %loop
+exit:
+  ret {t} %result.shifted
+trap:
+  call void @llvm.trap()
+  unreachable
+}}
+",
+        ty = ty.ty()
+    )
+}
+
+/// `name` made unique among `names` by adding a number to it if it's already taken, which is then taken.
+pub(crate) fn unique(names: &mut HashSet<String>, name: &str) -> String {
+    let mut unique = name.to_owned();
+    let mut suffix = 0;
+    while names.contains(&unique) {
+        suffix += 1;
+        unique = format!("{name}.{suffix}");
+    }
+    names.insert(unique.clone());
+    unique
+}
+
+/// `name` with `sigil`, quoted unless it only has the characters LLVM allows in a name without quotes.
+fn identifier(sigil: char, name: &str) -> String {
+    let bare = name
+        .chars()
+        .all(|char| char.is_ascii_alphanumeric() || matches!(char, '_' | '.' | '$' | '-'))
+        && !name.starts_with(|char: char| char.is_ascii_digit());
+    if bare {
+        format!("{sigil}{name}")
+    } else {
+        format!("{sigil}\"{name}\"")
+    }
+}
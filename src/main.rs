@@ -1,36 +1,678 @@
-use std::fs::File;
-use std::io::BufReader;
-use std::io::Read;
+use std::fs::{self, File};
+use std::io::{self, BufReader, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::thread;
 
-use llvm_compiler::{lexer::AsciiLexer, token::Token};
+use clap::{Args, Parser as _, Subcommand, ValueEnum};
+use llvm_compiler::{
+    ast::{builder::Builder, format},
+    bytecode::{self, file::EXTENSION, vm::Vm, Program},
+    codes,
+    emitter::{Emitter, Format, SourceFile},
+    interpret::{Interpreter, DEFAULT_RECURSION_LIMIT},
+    ir::{lower::lower, verify::verify},
+    lexer::AsciiLexer,
+    llvm,
+    parse::parser::Parser,
+    parsed_to_str,
+    sema::{check::check_with, consteval::fold, resolve::resolve, types::Type},
+    suggestion::{self, suggestion},
+    timing::Timings,
+    token::Token,
+};
+use miette::{miette, IntoDiagnostic, MietteHandlerOpts};
 
 pub const READER_CAPACITY: usize = 100_000_000;
 
-fn main() -> miette::Result<()> {
+/// The stack size of the thread programs are run on, before adding [`STACK_PER_CALL`] for each call they can make.
+const STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// An overestimate of the stack the interpreter uses for each call a program makes.
+const STACK_PER_CALL: usize = 16 * 1024;
+
+/// The path which reads source from stdin.
+const STDIN: &str = "-";
+
+/// A compiler for an as yet unnamed language.
+///
+/// Exits with 0 on success, 1 when the source has errors, and 2 when the compiler was used incorrectly.
+#[derive(clap::Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// When to colour output
+    #[arg(long, value_enum, default_value_t = Color::Auto, global = true)]
+    color: Color,
+
+    /// How diagnostics are written: `human` and `short` to stderr, and `json` and `sarif` to stdout
+    #[arg(long, value_name = "FORMAT", default_value = "human", global = true)]
+    error_format: Format,
+
+    /// Reports the time spent in each pass of the compiler to stderr
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text",
+        global = true
+    )]
+    time_passes: Option<TimePasses>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Splits each file into tokens
+    Lex(Options),
+    /// Parses each file, reporting any syntax errors
+    Parse(Options),
+    /// Checks each file for errors, without producing any output
+    Check(Options),
+    /// Compiles each file
+    Build(BuildOptions),
+    /// Compiles and runs each file, or runs each `.dobc` file written by `build --output`
+    Run(RunOptions),
+    /// Rewrites each file in the canonical layout
+    Fmt(FmtOptions),
+    /// Explains an error code, eg. `E0001`
+    Explain { code: String },
+}
+
+#[derive(Args)]
+struct Options {
+    /// Source files, or `-` to read from stdin
+    #[arg(required = true)]
+    paths: Vec<String>,
+
+    /// What to write to stdout, defaulting to the output of the subcommand
+    #[arg(long, value_enum)]
+    emit: Option<Emit>,
+
+    /// Applies suggestions which are safe to apply, writing them back to each file
+    #[arg(long)]
+    fix: bool,
+
+    /// The type of integer literals whose type isn't constrained by how they're used
+    #[arg(long, value_name = "TYPE", default_value = "i32", value_parser = integer_type)]
+    default_integer: Type,
+}
+
+#[derive(Args)]
+struct BuildOptions {
+    #[command(flatten)]
+    options: Options,
+
+    /// Writes the file compiled to bytecode to PATH, which can be run later with `run PATH`
+    #[arg(short, long, value_name = "PATH")]
+    output: Option<String>,
+}
+
+#[derive(Args)]
+struct RunOptions {
+    #[command(flatten)]
+    options: Options,
+
+    /// How many calls can be in progress at once before a program is stopped
+    #[arg(long, value_name = "CALLS", default_value_t = DEFAULT_RECURSION_LIMIT)]
+    recursion_limit: usize,
+}
+
+#[derive(Args)]
+struct FmtOptions {
+    /// Source files, or `-` to format stdin to stdout
+    #[arg(required = true)]
+    paths: Vec<String>,
+
+    /// Reports files which aren't formatted, rather than rewriting them
+    #[arg(long)]
+    check: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Color {
+    /// Colour output written to a terminal, unless `NO_COLOR` is set
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TimePasses {
+    /// A table per file
+    Text,
+    /// A JSON object per file, on a single line
+    Json,
+}
+
+/// An intermediate representation which can be written out with `--emit`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Emit {
+    /// The tokens produced by the lexer
+    Tokens,
+    /// The parser's reverse Polish output
+    Postfix,
+    /// The syntax tree
+    Ast,
+    /// Each declaration with its inferred type
+    Types,
+    /// The bytecode, disassembled
+    Bytecode,
+    /// The mid-level IR
+    Ir,
+    /// LLVM IR
+    Llvm,
+}
+
+/// What's done with each file once it's been checked, besides writing out `--emit`.
+#[derive(Clone, Copy)]
+enum Goal<'o> {
+    Check,
+    /// Runs the program, writing out its result
+    Run {
+        recursion_limit: usize,
+        /// The stack size of the thread the interpreter runs on, which fits `recursion_limit` calls
+        stack_size: usize,
+    },
+    /// Compiles the program to bytecode, and writes it to the given path
+    Save(&'o str),
+}
+
+/// The result of running a subcommand.
+enum Outcome {
+    Success,
+    /// The source had errors, which have been reported
+    Errors,
+}
+
+fn main() -> ExitCode {
     pretty_env_logger::init();
 
-    let file = File::open("./examples/infix.src").unwrap();
-    let mut reader = BufReader::with_capacity(READER_CAPACITY, file);
+    let cli = Cli::parse();
+    let color = match cli.color {
+        Color::Always => true,
+        Color::Never => false,
+        Color::Auto => io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+    };
+    let _ = miette::set_hook(Box::new(move |_| {
+        Box::new(MietteHandlerOpts::new().color(color).build())
+    }));
 
-    let mut lexer = AsciiLexer::new();
+    match run(cli, color) {
+        Ok(Outcome::Success) => ExitCode::SUCCESS,
+        Ok(Outcome::Errors) => ExitCode::from(1),
+        Err(report) => {
+            eprintln!("{report:?}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(cli: Cli, color: bool) -> miette::Result<Outcome> {
+    let out: Box<dyn Write> = match cli.error_format {
+        Format::Human | Format::Short => Box::new(io::stderr()),
+        Format::Json | Format::Sarif => Box::new(io::stdout()),
+    };
+    let mut emitter = Emitter::new(cli.error_format, out).with_color(color);
+
+    let outcome = match cli.command {
+        Command::Explain { code } => explain(&code)?,
+        Command::Fmt(options) => fmt(&options, cli.time_passes, &mut emitter)?,
+        Command::Lex(options) => compile(
+            &options,
+            Some(Emit::Tokens),
+            Goal::Check,
+            cli.time_passes,
+            &mut emitter,
+        )?,
+        Command::Parse(options) => compile(
+            &options,
+            Some(Emit::Postfix),
+            Goal::Check,
+            cli.time_passes,
+            &mut emitter,
+        )?,
+        Command::Check(options) => {
+            compile(&options, None, Goal::Check, cli.time_passes, &mut emitter)?
+        }
+        Command::Build(build) => match &build.output {
+            Some(output) => {
+                if build.options.paths.len() > 1 {
+                    return Err(miette!(
+                        "`--output` can only be used when building a single file"
+                    ));
+                }
+                compile(
+                    &build.options,
+                    None,
+                    Goal::Save(output),
+                    cli.time_passes,
+                    &mut emitter,
+                )?
+            }
+            None => compile(
+                &build.options,
+                Some(Emit::Ast),
+                Goal::Check,
+                cli.time_passes,
+                &mut emitter,
+            )?,
+        },
+        Command::Run(run) => {
+            let stack_size = stack_size(run.recursion_limit).ok_or_else(|| {
+                miette!(
+                    "`--recursion-limit {}` is too large to fit on the stack",
+                    run.recursion_limit
+                )
+            })?;
+            compile(
+                &run.options,
+                None,
+                Goal::Run {
+                    recursion_limit: run.recursion_limit,
+                    stack_size,
+                },
+                cli.time_passes,
+                &mut emitter,
+            )?
+        }
+    };
+
+    emitter.finish().into_diagnostic()?;
+    Ok(outcome)
+}
+
+/// Prints the explanation of an error code, eg. `E0001`.
+fn explain(code: &str) -> miette::Result<Outcome> {
+    let entry = codes::lookup(code).ok_or_else(|| miette!("No explanation for `{code}`"))?;
+
+    println!("{} ({})\n", entry.code, entry.name);
+    print!("{}", entry.explanation);
+    Ok(Outcome::Success)
+}
+
+/// Reads the file at `path`, or stdin when `path` is `-`.
+fn read(path: &str) -> miette::Result<String> {
     let mut buf = String::new();
 
+    if path == STDIN {
+        io::stdin().read_to_string(&mut buf).into_diagnostic()?;
+        return Ok(buf);
+    }
+
+    let file = File::open(path)
+        .into_diagnostic()
+        .map_err(|error| error.wrap_err(format!("Couldn't read `{path}`")))?;
+    let mut reader = BufReader::with_capacity(READER_CAPACITY, file);
+
     // Read in as much as we can at once
-    let _ = reader.read_to_string(&mut buf).unwrap(); // TODO: There is some edge
-                                                      // case behaviour here when
-                                                      // a file is too long to
-                                                      // store in memory. This is
-                                                      // currently unhandled.
+    let _ = reader.read_to_string(&mut buf).into_diagnostic()?; // TODO: There is some edge
+                                                                // case behaviour here when
+                                                                // a file is too long to
+                                                                // store in memory. This is
+                                                                // currently unhandled.
+    Ok(buf)
+}
+
+/// Writes `timings` to stderr, when `--time-passes` was given.
+fn report_timings(timings: &Timings, format: Option<TimePasses>) {
+    match format {
+        Some(TimePasses::Text) => eprint!("{timings}"),
+        Some(TimePasses::Json) => eprintln!("{}", timings.to_json()),
+        None => {}
+    }
+}
+
+/// The stack size needed for the interpreter to make `recursion_limit` nested calls, unless it's too large to
+/// represent.
+///
+/// Each call recurses through the interpreter, so the stack has to be large enough to reach the limit.
+fn stack_size(recursion_limit: usize) -> Option<usize> {
+    recursion_limit
+        .checked_mul(STACK_PER_CALL)?
+        .checked_add(STACK_SIZE)
+}
+
+/// Parses the name of a built-in integer type, for `--default-integer`.
+fn integer_type(name: &str) -> Result<Type, String> {
+    Type::builtin(name)
+        .filter(Type::is_integer)
+        .ok_or_else(|| format!("`{name}` isn't an integer type"))
+}
+
+/// The name diagnostics use for the file at `path`.
+fn display_name(path: &str) -> &str {
+    if path == STDIN {
+        "<stdin>"
+    } else {
+        path
+    }
+}
+
+/// Runs each file through the compiler as far as is needed to write out `--emit`, or `default` when it isn't given,
+/// and to reach `goal`.
+///
+/// Without either, every file is checked without writing anything out. Programs are only run when nothing else is
+/// written out.
+fn compile<W: Write>(
+    options: &Options,
+    default: Option<Emit>,
+    goal: Goal,
+    time_passes: Option<TimePasses>,
+    emitter: &mut Emitter<W>,
+) -> miette::Result<Outcome> {
+    let write = options.emit.or(default);
+    let goal = match goal {
+        Goal::Run { .. } if write.is_some() => Goal::Check,
+        goal => goal,
+    };
+    let emit = write.unwrap_or(Emit::Ast);
+    if options.fix && options.paths.iter().any(|path| path == STDIN) {
+        return Err(miette!("`--fix` can't write fixes back to stdin"));
+    }
+
+    let mut outcome = Outcome::Success;
+    for path in &options.paths {
+        if let Goal::Run {
+            recursion_limit, ..
+        } = goal
+        {
+            if Path::new(path)
+                .extension()
+                .is_some_and(|extension| extension == EXTENSION)
+            {
+                if let Outcome::Errors = run_bytecode(path, recursion_limit, time_passes, emitter)?
+                {
+                    outcome = Outcome::Errors;
+                }
+                continue;
+            }
+        }
+
+        let mut buf = read(path)?;
+        let name = display_name(path);
+
+        if options.fix {
+            let mut timings = Timings::new(name, &buf);
+            let (_, diagnostics) = front_end(name, &buf, emit, Goal::Check, options, &mut timings);
+            let (fixed, applied) =
+                suggestion::apply(&buf, diagnostics.iter().filter_map(suggestion));
+
+            if applied > 0 {
+                fs::write(path, &fixed).into_diagnostic()?;
+                eprintln!("Applied {applied} fixes to {path}");
+                buf = fixed;
+            }
+        }
+
+        let mut timings = Timings::new(name, &buf);
+        let (output, diagnostics) = front_end(name, &buf, emit, goal, options, &mut timings);
+        report_timings(&timings, time_passes);
+
+        let file = SourceFile { name, source: &buf };
+        for report in &diagnostics {
+            emitter.emit(&file, report).into_diagnostic()?;
+        }
+
+        if !diagnostics.is_empty() {
+            outcome = Outcome::Errors;
+        } else if write.is_some() || matches!(goal, Goal::Run { .. }) {
+            print!("{output}");
+        }
+    }
+
+    Ok(outcome)
+}
 
-    let tokens: Vec<Token<'_>> = lexer.tokenize(&buf);
+/// Runs the lexer, parser, syntax tree builder and semantic analysis over `buf`, stopping once `emit` has been
+/// produced, and then works towards `goal` if the program has no errors.
+///
+/// Returns `emit` or the program's result as text, and every diagnostic reported along the way.
+fn front_end(
+    name: &str,
+    buf: &String,
+    emit: Emit,
+    goal: Goal,
+    options: &Options,
+    timings: &mut Timings,
+) -> (String, Vec<miette::Report>) {
+    let mut lexer = AsciiLexer::new();
+    let tokens: Vec<Token<'_>> = timings.time("lex", || lexer.tokenize(buf));
+    timings.tokens = tokens.iter().filter(|token| !token.is_trivia()).count();
+    if emit == Emit::Tokens {
+        let output = tokens.iter().map(|token| format!("{token:?}\n")).collect();
+        return (output, Vec::new());
+    }
+
+    let (parsed, diagnostics) = timings.time("parse", || {
+        Parser::new(0, &tokens)
+            .with_source(name, buf)
+            .parse_recovering()
+    });
+    if emit == Emit::Postfix {
+        return (format!("{}\n", parsed_to_str(&parsed)), diagnostics);
+    }
+    if !diagnostics.is_empty() {
+        return (String::new(), diagnostics);
+    }
+
+    let module = match timings.time("build", || Builder::new(&parsed).build()) {
+        Ok(module) => module,
+        Err(report) => return (String::new(), vec![report]),
+    };
+
+    let (resolution, mut diagnostics) = timings.time("resolve", || resolve(&module));
+    let (typing, type_errors) = timings.time("typeck", || {
+        check_with(&module, &resolution, &options.default_integer)
+    });
+    diagnostics.extend(type_errors);
+    let (constants, const_errors) =
+        timings.time("consteval", || fold(&module, &resolution, &typing));
+    diagnostics.extend(const_errors);
+
+    if emit == Emit::Types {
+        let file = SourceFile { name, source: buf };
+        let output = typing
+            .declarations(&resolution)
+            .into_iter()
+            .map(|(span, declaration)| {
+                let (line, column) = file.position(span.offset());
+                format!("{name}:{line}:{column}: {declaration}\n")
+            })
+            .collect();
+        return (output, diagnostics);
+    }
+    if emit == Emit::Ir {
+        if !diagnostics.is_empty() {
+            return (String::new(), diagnostics);
+        }
+
+        let output = timings.time("lower", || {
+            let module = lower(&module, &resolution, &typing, &constants)?;
+            verify(&module)?;
+            Ok::<_, miette::Report>(module.to_string())
+        });
+        return match output {
+            Ok(output) => (output, diagnostics),
+            Err(report) => (String::new(), vec![report]),
+        };
+    }
+    if emit == Emit::Llvm {
+        if !diagnostics.is_empty() {
+            return (String::new(), diagnostics);
+        }
+
+        let output = timings.time("codegen", || {
+            llvm::generate(&module, &resolution, &typing, name)
+        });
+        return match output {
+            Ok(output) => (output, diagnostics),
+            Err(report) => (String::new(), vec![report]),
+        };
+    }
+    if emit == Emit::Bytecode || matches!(goal, Goal::Save(_)) {
+        if !diagnostics.is_empty() {
+            return (String::new(), diagnostics);
+        }
+
+        let program = timings.time("codegen", || {
+            bytecode::compile::compile(&module, &resolution, &typing, &constants, name, buf)
+        });
+        let program = match program {
+            Ok(program) => program,
+            Err(report) => return (String::new(), vec![report]),
+        };
+        if let Goal::Save(path) = goal {
+            if let Err(error) = fs::write(path, program.to_bytes()) {
+                return (
+                    String::new(),
+                    vec![miette!("Couldn't write `{path}`: {error}")],
+                );
+            }
+        }
+        if emit == Emit::Bytecode {
+            return (program.to_string(), diagnostics);
+        }
+    }
+    if let Goal::Run {
+        recursion_limit,
+        stack_size,
+    } = goal
+    {
+        if !diagnostics.is_empty() {
+            return (String::new(), diagnostics);
+        }
+
+        let result = timings.time("run", || {
+            thread::scope(|scope| {
+                thread::Builder::new()
+                    .stack_size(stack_size)
+                    .spawn_scoped(scope, || {
+                        Interpreter::new(&resolution, &typing)
+                            .with_recursion_limit(recursion_limit)
+                            .run(&module)
+                    })
+                    .map_err(|error| {
+                        miette!(
+                            "Couldn't start a thread with a {stack_size} byte stack to run the \
+                             program: {error}"
+                        )
+                    })?
+                    .join()
+                    .map_err(|_| miette!("The interpreter panicked while running the program"))?
+            })
+        });
+        return match result {
+            Ok(value) if value.is_unit() => (String::new(), diagnostics),
+            Ok(value) => (format!("{value}\n"), diagnostics),
+            Err(report) => (String::new(), vec![report]),
+        };
+    }
+    (format!("{module}\n"), diagnostics)
+}
 
-    for token in tokens.iter() {
-        print!("{:?}", token);
+/// Runs a program compiled to bytecode by `build --output`, writing out its result.
+fn run_bytecode<W: Write>(
+    path: &str,
+    recursion_limit: usize,
+    time_passes: Option<TimePasses>,
+    emitter: &mut Emitter<W>,
+) -> miette::Result<Outcome> {
+    let bytes = fs::read(path)
+        .into_diagnostic()
+        .map_err(|error| error.wrap_err(format!("Couldn't read `{path}`")))?;
+    let program = match Program::from_bytes(&bytes) {
+        Ok(program) => program,
+        // The file isn't source, so there's nothing for the diagnostic to point into
+        Err(report) => {
+            let file = SourceFile {
+                name: path,
+                source: "",
+            };
+            emitter.emit(&file, &report).into_diagnostic()?;
+            return Ok(Outcome::Errors);
+        }
+    };
+
+    let mut timings = Timings::new(path, &program.source);
+    let result = timings.time("run", || {
+        Vm::new(&program)
+            .with_recursion_limit(recursion_limit)
+            .run()
+    });
+    report_timings(&timings, time_passes);
+
+    match result {
+        Ok(value) => {
+            if !value.is_unit() {
+                println!("{value}");
+            }
+            Ok(Outcome::Success)
+        }
+        Err(report) => {
+            let file = SourceFile {
+                name: &program.name,
+                source: &program.source,
+            };
+            emitter.emit(&file, &report).into_diagnostic()?;
+            Ok(Outcome::Errors)
+        }
     }
+}
 
-    println!("{}", tokens.len());
+/// Formats each file, or with `--check`, reports each file which isn't formatted.
+fn fmt<W: Write>(
+    options: &FmtOptions,
+    time_passes: Option<TimePasses>,
+    emitter: &mut Emitter<W>,
+) -> miette::Result<Outcome> {
+    let mut outcome = Outcome::Success;
+    for path in &options.paths {
+        let buf = read(path)?;
+        let name = display_name(path);
 
-    println!();
+        let mut timings = Timings::new(name, &buf);
+        let mut lexer = AsciiLexer::new();
+        let tokens: Vec<Token<'_>> = timings.time("lex", || lexer.tokenize(&buf));
+        timings.tokens = tokens.iter().filter(|token| !token.is_trivia()).count();
+        let (parsed, mut diagnostics) = timings.time("parse", || {
+            Parser::new(0, &tokens)
+                .with_source(name, &buf)
+                .parse_recovering()
+        });
+        let module = timings
+            .time("build", || Builder::new(&parsed).build())
+            .map_err(|report| diagnostics.push(report));
+
+        // Source with errors can't be formatted without losing the statements which failed to parse
+        let module = match module {
+            Ok(module) if diagnostics.is_empty() => module,
+            _ => {
+                let file = SourceFile { name, source: &buf };
+                for report in &diagnostics {
+                    emitter.emit(&file, report).into_diagnostic()?;
+                }
+                outcome = Outcome::Errors;
+                continue;
+            }
+        };
+
+        let formatted = timings.time("format", || format::format(&module));
+        report_timings(&timings, time_passes);
+        if options.check {
+            if formatted != buf {
+                eprintln!("{name} is not formatted");
+                outcome = Outcome::Errors;
+            }
+        } else if path == STDIN {
+            print!("{formatted}");
+        } else if formatted != buf {
+            fs::write(path, &formatted).into_diagnostic()?;
+        }
+    }
 
-    Ok(())
+    Ok(outcome)
 }
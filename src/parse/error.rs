@@ -1,11 +1,112 @@
-use miette::{Diagnostic, LabeledSpan, SourceSpan};
+use std::fmt::{self, Display, Formatter};
+use std::sync::Arc;
+
+use miette::{Diagnostic, LabeledSpan, NamedSource, SourceSpan};
 use thiserror::Error;
 
+/// The source a diagnostic was found in, shared between every diagnostic from the same file.
+pub type Source = NamedSource<Arc<str>>;
+
+/// A token which can't appear where it was found, eg. the `{` in `fn f {}`
 #[derive(Error, Diagnostic, Debug)]
-#[diagnostic()]
-#[error("Unexpected token {found:?}")]
+#[error("Unexpected {found}")]
+#[diagnostic(code(E0001))]
 pub struct UnexpectedToken {
-    pub found: Option<String>,
+    #[source_code]
+    pub src: Option<Source>,
+
+    pub found: Found,
+    pub expected: Expected,
+
+    #[label("{expected}")]
+    pub span: SourceSpan,
+    /// The construct being parsed when the token was found, eg. "while parsing this function signature"
+    #[label(collection)]
+    pub related: Option<LabeledSpan>,
+
+    #[help]
+    pub help: Option<String>,
+}
+
+impl UnexpectedToken {
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Found {
+    Token(String),
+    EndOfInput,
+}
+
+impl Display for Found {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Found::Token(token) => write!(f, "token `{}`", token),
+            Found::EndOfInput => write!(f, "end of input"),
+        }
+    }
+}
+
+/// Descriptions of the tokens which could have been parsed instead, eg. "`;`" or "an expression".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expected(pub Vec<&'static str>);
+
+impl Display for Expected {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.0.as_slice() {
+            [] => write!(f, "unexpected"),
+            [only] => write!(f, "expected {}", only),
+            [first, second] => write!(f, "expected {} or {}", first, second),
+            [rest @ .., last] => write!(f, "expected one of {} or {}", rest.join(", "), last),
+        }
+    }
+}
+
+/// A closing delimiter without an opening delimiter to close, eg. the `)` in `1 + 2);`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Unmatched closing delimiter `{found}`")]
+#[diagnostic(code(E0002))]
+pub struct UnmatchedDelimiter {
+    pub found: String,
+
+    #[label("unmatched `{found}`")]
+    pub closer: SourceSpan,
+    /// The innermost delimiter which is still open, if there is one
+    #[label("nearest open delimiter")]
+    pub opener: Option<SourceSpan>,
+
+    #[help]
+    pub help: Option<String>,
+}
+
+/// An opening delimiter which is never closed, eg. the `(` in `(1 + 2;`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Unclosed delimiter `{open}`")]
+#[diagnostic(code(E0003))]
+pub struct UnclosedDelimiter {
+    pub open: String,
+    pub close: String,
+
+    #[label("unclosed `{open}`")]
+    pub opener: SourceSpan,
+    /// Where the closing delimiter was expected, which is empty at the end of the input
+    #[label("expected `{close}` before this")]
+    pub expected: SourceSpan,
+}
+
+/// A closing delimiter of the wrong kind, eg. the `}` in `{ (1 + 2 }`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Mismatched closing delimiter `{found}`")]
+#[diagnostic(code(E0004))]
+pub struct MismatchedDelimiter {
+    pub found: String,
+    pub open: String,
 
-    pub unexpected_span: LabeledSpan,
+    #[label("did you mean to close `{open}` here?")]
+    pub closer: SourceSpan,
+    #[label("unclosed `{open}`")]
+    pub opener: SourceSpan,
 }
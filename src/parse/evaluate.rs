@@ -0,0 +1,184 @@
+//! Direct evaluation of the parser's reverse Polish output, without building a syntax tree.
+//!
+//! Only integer arithmetic is supported, which is enough to check that the parser orders operators by their
+//! precedence and associativity: if `1 + 2 * 3` is parsed correctly, it evaluates to `7`. Comparisons evaluate to `1`
+//! or `0`, and assignments store into the environment, evaluating to the value they assigned.
+
+use std::collections::HashMap;
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::ast::builder::malformed;
+use crate::interpret::{DivisionByZero, Overflow};
+use crate::sema::resolve::UndefinedName;
+use crate::sema::types::Type;
+use crate::token::Token;
+
+/// The values of the names an expression can use, which assignments write back to.
+pub type Environment<'t> = HashMap<&'t str, i64>;
+
+/// A token with no meaning in an arithmetic expression, eg. `fn`
+#[derive(Error, Diagnostic, Debug)]
+#[error("`{token}` can't be evaluated")]
+#[diagnostic(code(E0028))]
+pub struct Unsupported {
+    pub token: String,
+    #[label("only arithmetic can be evaluated")]
+    pub span: SourceSpan,
+}
+
+/// An operand waiting on the stack for its operator.
+#[derive(Debug, Clone, Copy)]
+enum Operand<'t> {
+    Value(i64),
+    /// A name, which is only looked up once it's known not to be assigned to
+    Name(&'t str, SourceSpan),
+}
+
+/// Evaluates each statement in `parsed`, returning the value of the last one.
+///
+/// Names are looked up in `environment`, and assigning to a name inserts it.
+pub fn evaluate<'t>(
+    parsed: &[Token<'t>],
+    environment: &mut Environment<'t>,
+) -> miette::Result<i64> {
+    let mut stack: Vec<Operand<'t>> = Vec::new();
+    let mut result = None;
+
+    for &token in parsed {
+        let span = token.span();
+        let operand = match token {
+            Token::NumericLiteral(inner) => {
+                let value = inner.slice.parse().map_err(|_| overflow(span))?;
+                Operand::Value(value)
+            }
+            Token::Identifier(inner) => Operand::Name(inner.slice, span),
+            // Brackets are kept in the output, but the order of the operators inside of them already groups them
+            Token::OpenBracket(_) | Token::CloseBracket(_) => continue,
+            Token::SemiColon(_) => {
+                let value = pop(&mut stack, token, environment)?;
+                if !stack.is_empty() {
+                    return Err(malformed(span, "one value per statement"));
+                }
+                result = Some(value);
+                continue;
+            }
+            Token::Negate(_) | Token::UnaryPlus(_) | Token::Not(_) => {
+                let value = pop(&mut stack, token, environment)?;
+                let value = match token {
+                    Token::Negate(_) => value.checked_neg().ok_or_else(|| overflow(span))?,
+                    Token::Not(_) => !value,
+                    _ => value,
+                };
+                Operand::Value(value)
+            }
+            Token::Equals(_)
+            | Token::PlusEquals(_)
+            | Token::MinusEquals(_)
+            | Token::TimesEquals(_) => {
+                let value = pop(&mut stack, token, environment)?;
+                let Some(Operand::Name(name, _)) = stack.pop() else {
+                    return Err(malformed(span, "a name to assign to"));
+                };
+                let value = match token {
+                    Token::Equals(_) => value,
+                    _ => {
+                        let current = lookup(name, span, environment)?;
+                        binary(token, current, value)?
+                    }
+                };
+                environment.insert(name, value);
+                Operand::Value(value)
+            }
+            _ if token.is_binary() && !matches!(token, Token::Comma(_)) => {
+                let right = pop(&mut stack, token, environment)?;
+                let left = pop(&mut stack, token, environment)?;
+                Operand::Value(binary(token, left, right)?)
+            }
+            _ => {
+                return Err(Unsupported {
+                    token: token.to_string(),
+                    span,
+                }
+                .into())
+            }
+        };
+        stack.push(operand);
+    }
+
+    // The last statement doesn't need a terminating `;`
+    if let Some(&last) = parsed.last() {
+        if !stack.is_empty() {
+            result = Some(pop(&mut stack, last, environment)?);
+        }
+    }
+    result.ok_or_else(|| malformed(SourceSpan::from((0, 0)), "an expression"))
+}
+
+/// Pops the value of the operand on top of the stack, which `token` is applied to.
+fn pop(stack: &mut Vec<Operand>, token: Token, environment: &Environment) -> miette::Result<i64> {
+    match stack.pop() {
+        Some(Operand::Value(value)) => Ok(value),
+        Some(Operand::Name(name, span)) => lookup(name, span, environment),
+        None => Err(malformed(token.span(), "an operand")),
+    }
+}
+
+fn lookup(name: &str, span: SourceSpan, environment: &Environment) -> miette::Result<i64> {
+    environment.get(name).copied().ok_or_else(|| {
+        UndefinedName {
+            name: name.to_owned(),
+            span,
+        }
+        .into()
+    })
+}
+
+/// Applies the binary operator `token`, where compound assignments apply the operator they're named after.
+fn binary(token: Token, left: i64, right: i64) -> miette::Result<i64> {
+    let span = token.span();
+    let result = match token {
+        Token::Plus(_) | Token::PlusEquals(_) => left.checked_add(right),
+        Token::Minus(_) | Token::MinusEquals(_) => left.checked_sub(right),
+        Token::Times(_) | Token::TimesEquals(_) => left.checked_mul(right),
+        Token::Divide(_) if right == 0 => return Err(DivisionByZero { span }.into()),
+        Token::Divide(_) => left.checked_div(right),
+        Token::Power(_) => u32::try_from(right)
+            .ok()
+            .and_then(|right| left.checked_pow(right)),
+        Token::ShiftLeft(_) => u32::try_from(right)
+            .ok()
+            .filter(|&right| right < i64::BITS)
+            .and_then(|right| i64::try_from(i128::from(left) << right).ok()),
+        Token::ShiftRight(_) => u32::try_from(right)
+            .ok()
+            .and_then(|right| left.checked_shr(right)),
+        Token::GreaterThan(_) => Some((left > right).into()),
+        Token::LessThan(_) => Some((left < right).into()),
+        Token::GreaterEquals(_) => Some((left >= right).into()),
+        Token::LessEquals(_) => Some((left <= right).into()),
+        Token::DoubleEquals(_) => Some((left == right).into()),
+        Token::NotEquals(_) => Some((left != right).into()),
+        _ => {
+            return Err(Unsupported {
+                token: token.to_string(),
+                span,
+            }
+            .into())
+        }
+    };
+
+    result.ok_or_else(|| overflow(span))
+}
+
+fn overflow(span: SourceSpan) -> miette::Report {
+    Overflow {
+        ty: Type::Int {
+            signed: true,
+            bits: 64,
+        },
+        span,
+    }
+    .into()
+}
@@ -1,24 +1,27 @@
+use crate::parse::error::{MismatchedDelimiter, UnclosedDelimiter, UnmatchedDelimiter};
 use crate::parse::parser::Parser;
 use crate::parsed_to_str;
 use crate::token::Token;
 use log::trace;
+use miette::{miette, LabeledSpan};
 
+/// Parses an expression into reverse Polish notation using a shunting yard.
+///
+/// Stops without consuming the token that ends the expression, which is one of `;`, `{`, `}`, a keyword beginning a
+/// statement, or a `,` outside of any brackets. An operand on a new line after a complete expression also ends it, so
+/// that a missing `;` can be reported by the statement.
 pub(super) fn expression<'p, 't>(parser: &'p mut Parser<'t>) -> miette::Result<()> {
     // Write from the stack until pattern is matched, discarding the matched variant
     macro_rules! write_until {
         (None) => {
             while let Some(popped) = parser.pop() {
-                match popped {
-                    _ => {
-                        parser.write(popped);
-                    }
-                }
+                parser.write(popped);
             }
         };
         ($($variant:path)|+) => {
             while let Some(popped) = parser.pop() {
                 match popped {
-                    $($variant(_))|* => {
+                    $($variant(..))|* => {
                         break;
                     }
                     _ => {
@@ -29,86 +32,295 @@ pub(super) fn expression<'p, 't>(parser: &'p mut Parser<'t>) -> miette::Result<(
         };
     }
 
-    // Get last precedence from the stack
-    macro_rules! last_precedence {
+    // Write from the stack until the innermost bracket, leaving it on the stack
+    macro_rules! write_until_bracket {
         () => {
-            parser.last().map_or(0, |token| token.precedence().1)
+            while let Some(top) = parser.last() {
+                if let Token::OpenBracket(_) | Token::Call(..) = top {
+                    break;
+                }
+                let popped = parser.pop().unwrap();
+                parser.write(popped);
+            }
         };
     }
 
-    // let mut last_token = Token::Unknown(Inner::new(0, ""));
-    let mut last_rp = 0; // Right precedence of the operator just to the left of our current position
+    // Whether the next token should begin an operand, rather than be an operator following one
+    let mut expect_operand = true;
 
     loop {
-        let token = parser.next_token();
+        let token = parser.peek_token();
 
-        if let None = token {
+        let Some(token) = token else {
             trace!("Found None");
+
+            if expect_operand {
+                return Err(expected_expression(parser, None));
+            }
+            if let Some(bracket) = innermost_bracket(parser) {
+                return Err(unclosed_bracket(parser, &bracket, None));
+            }
+            write_until!(None);
             return Ok(());
-        }
+        };
 
-        let token = token.unwrap();
         trace!("Found {}", &token);
         match token {
+            // An operand at the start of a new line ends the expression before it
+            Token::Identifier(_) | Token::NumericLiteral(_)
+                if !expect_operand
+                    && parser.newline_before_next()
+                    && innermost_bracket(parser).is_none() =>
+            {
+                trace!("End of expression, at a new line");
+
+                write_until!(None);
+                return Ok(());
+            }
+
             // Write idents/literals immediately
             Token::Identifier(_) | Token::NumericLiteral(_) => {
+                if !expect_operand {
+                    return Err(parser.unexpected(Some(&token), &["an operator"]).into());
+                }
+                parser.consume_token();
                 parser.write(token);
+                expect_operand = false;
             }
 
             // Handle end of expression
-            Token::SemiColon(_) => {
+            Token::SemiColon(_)
+            | Token::OpenCurly(_)
+            | Token::CloseCurly(_)
+            | Token::FunctionDeclaration(_)
+            | Token::VariableDeclaration(_)
+            | Token::Const(_)
+            | Token::If(_)
+            | Token::Else(_)
+            | Token::While(_) => {
                 trace!("End of expression");
 
+                if expect_operand {
+                    return Err(expected_expression(parser, Some(&token)));
+                }
+                if let Some(bracket) = innermost_bracket(parser) {
+                    return Err(unclosed_bracket(parser, &bracket, Some(&token)));
+                }
+                write_until!(None);
+                return Ok(());
+            }
+            Token::Comma(_) if innermost_bracket(parser).is_none() => {
+                trace!("End of expression");
+
+                if expect_operand {
+                    return Err(expected_expression(parser, Some(&token)));
+                }
                 write_until!(None);
-                parser.write(token);
                 return Ok(());
             }
 
-            // If the incoming token is an intial, write it and push it to the stack
-            Token::OpenBracket(_) | Token::OpenCurly(_) => {
-                let (_, rp) = token.precedence();
+            // An open bracket directly after an operand begins a call's argument list
+            Token::OpenBracket(inner) if !expect_operand => {
+                parser.consume_token();
+                parser.push(Token::Call(inner, 0));
+                expect_operand = true;
+            }
 
+            // If the incoming token is an initial, write it and push it to the stack
+            Token::OpenBracket(_) => {
+                parser.consume_token();
                 parser.write(token);
-
                 parser.push(token);
-                last_rp = rp;
             }
 
-            // If the incoming token is a terminal, pop and write the stack until you come across an initial token with small enough precedence
-            Token::CloseBracket(_) | Token::CloseCurly(_) => {
-                write_until!(Token::OpenBracket | Token::OpenCurly);
-
-                last_rp = last_precedence!();
+            // A comma directly inside a call's brackets ends an argument
+            Token::Comma(_) if matches!(innermost_bracket(parser), Some(Token::Call(..))) => {
+                if expect_operand {
+                    return Err(parser.unexpected(Some(&token), &["an argument"]).into());
+                }
+                parser.consume_token();
 
-                parser.write(token);
+                write_until_bracket!();
+                if let Some(Token::Call(_, arity)) = parser.last_mut() {
+                    *arity += 1;
+                }
+                expect_operand = true;
             }
 
-            _ => {
-                let (lp, rp) = token.precedence();
-                if lp < last_rp {
-                    // If the incoming operator has lower lp than the last rp on the stack, pop and write until this is no longer the case
-                    trace!("Popping until lp < popped_rp");
-                    while let Some(popped) = parser.pop() {
-                        let (_pop_lp, pop_rp) = popped.precedence();
-                        trace!("Popped {}, with ({}, {})", &popped, _pop_lp, pop_rp);
+            // If the incoming token is a terminal, pop and write the stack until you come across an initial token
+            Token::CloseBracket(inner) => {
+                if innermost_bracket(parser).is_none() {
+                    return Err(unmatched_bracket(parser, &token));
+                }
+                parser.consume_token();
+                write_until_bracket!();
 
-                        parser.write(popped);
-                        if lp < pop_rp {
-                            break;
+                match parser.pop() {
+                    Some(Token::Call(_, arity)) => {
+                        // A trailing comma, or no arguments at all, doesn't add an argument
+                        let arity = if expect_operand { arity } else { arity + 1 };
+                        parser.write(Token::Call(inner, arity));
+                    }
+                    _ => {
+                        if expect_operand {
+                            return Err(expected_expression(parser, Some(&token)));
                         }
+                        parser.write(token);
+                    }
+                }
+                expect_operand = false;
+            }
+
+            // `**` in operand position is two dereferences, so it's split to read the second `*` next
+            Token::Power(_) if expect_operand => {
+                let times = parser.split_token().unwrap();
+                parser.push(times.prefix().unwrap());
+            }
+
+            // Operators in operand position are prefix operators, which have nothing to their left to pop
+            _ if expect_operand => {
+                let Some(prefix) = token.prefix() else {
+                    return Err(expected_expression(parser, Some(&token)));
+                };
+                parser.consume_token();
+                parser.push(prefix);
+            }
+
+            _ if token.is_binary() => {
+                parser.consume_token();
+
+                // Pop and write every operator that binds tighter than the incoming one
+                let (lp, _) = token.precedence();
+                while let Some(top) = parser.last() {
+                    let (_top_lp, top_rp) = top.precedence();
+                    if top_rp <= lp {
+                        break;
+                    }
+
+                    trace!("Popped {}, with ({}, {})", top, _top_lp, top_rp);
+                    let popped = parser.pop().unwrap();
+                    parser.write(popped);
+                }
+
+                // Anything binding tighter has been popped, so a comparison still on the stack is being chained
+                if let Some(&previous) = parser.last().filter(|top| top.is_comparison()) {
+                    if token.is_comparison() {
+                        return Err(chained_comparison(&previous, &token));
                     }
                 }
-                // If the incoming operator has higher lp than the last rp on top of the stack, push
+
                 parser.push(token);
-                last_rp = rp;
+                expect_operand = true;
+            }
+
+            _ => {
+                return Err(parser.unexpected(Some(&token), &["an operator"]).into());
             }
         }
 
         trace!(
-            "LRP: {}, Stack: {}, Parsed: {}",
-            last_rp,
+            "Stack: {}, Parsed: {}",
             parsed_to_str(&parser.stack),
             parsed_to_str(&parser.parsed)
         );
     }
 }
+
+/// Finds the innermost grouping or call bracket which hasn't been closed yet.
+fn innermost_bracket<'t>(parser: &Parser<'t>) -> Option<Token<'t>> {
+    parser
+        .stack
+        .iter()
+        .rev()
+        .find(|token| matches!(token, Token::OpenBracket(_) | Token::Call(..)))
+        .copied()
+}
+
+/// Reports a `(` which is still open when the expression ends at `token`, or at the end of the input.
+///
+/// A `}` is most likely meant to close the `(`, so is reported as mismatched rather than as a missing `)`.
+fn unclosed_bracket(
+    parser: &Parser<'_>,
+    bracket: &Token<'_>,
+    token: Option<&Token<'_>>,
+) -> miette::Report {
+    // Calls hold their `(`, so the opener is always at the bracket's location
+    let opener = (bracket.loc(), 1).into();
+
+    match token {
+        Some(token @ Token::CloseCurly(_)) => MismatchedDelimiter {
+            found: token.as_str().to_owned(),
+            open: "(".into(),
+            closer: token.span(),
+            opener,
+        }
+        .into(),
+        _ => {
+            let expected = match token {
+                Some(token) => token.span(),
+                None => (parser.end_of_input(), 0).into(),
+            };
+
+            UnclosedDelimiter {
+                open: "(".into(),
+                close: ")".into(),
+                opener,
+                expected,
+            }
+            .into()
+        }
+    }
+}
+
+/// Reports a `)` without a `(` to close, pointing at the enclosing block if there is one.
+fn unmatched_bracket(parser: &Parser<'_>, token: &Token<'_>) -> miette::Report {
+    let opener = parser.blocks.last();
+
+    let help = match opener {
+        Some(_) => "blocks are closed by `}`, so remove this `)` or add a matching `(` before it",
+        None => "remove this `)`, or add a matching `(` before it",
+    };
+
+    UnmatchedDelimiter {
+        found: token.as_str().to_owned(),
+        closer: token.span(),
+        opener: opener.map(Token::span),
+        help: Some(help.into()),
+    }
+    .into()
+}
+
+fn chained_comparison(first: &Token<'_>, second: &Token<'_>) -> miette::Report {
+    let labels = vec![
+        LabeledSpan::new(
+            Some("first comparison".to_string()),
+            first.loc(),
+            first.as_str().len(),
+        ),
+        LabeledSpan::new(
+            Some("chained with this comparison".to_string()),
+            second.loc(),
+            second.as_str().len(),
+        ),
+    ];
+
+    // `a < b > c` is most likely an attempt at type arguments
+    let help = match (first, second) {
+        (Token::LessThan(_), Token::GreaterThan(_) | Token::ShiftRight(_)) => {
+            "type arguments can only be written in a type, after `:` or `->`"
+        }
+        _ => "add parentheses to choose which comparison happens first",
+    };
+
+    miette!(
+        code = "E0005",
+        labels = labels,
+        help = help,
+        "Comparison operators cannot be chained"
+    )
+}
+
+/// Reports a missing operand, either at `token` or at the end of the input.
+fn expected_expression<'t>(parser: &Parser<'t>, token: Option<&Token<'t>>) -> miette::Report {
+    parser.unexpected(token, &["an expression"]).into()
+}
@@ -1,3 +1,6 @@
-mod error;
+pub mod error;
+pub mod evaluate;
 mod expression;
 pub mod parser;
+pub mod tree;
+mod type_expression;
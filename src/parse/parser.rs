@@ -1,15 +1,30 @@
+use std::sync::Arc;
+
 use log::trace;
-use miette::{miette, Context, LabeledSpan};
+use miette::{LabeledSpan, NamedSource};
 
-use crate::{parse::expression::expression, token::Token};
+use crate::{
+    parse::{expression::expression, type_expression::type_expression},
+    suggestion::{Applicability, Suggested, Suggestion},
+    token::{Inner, Token},
+};
 
-use super::error::UnexpectedToken;
+use super::error::{
+    Expected, Found, Source, UnclosedDelimiter, UnexpectedToken, UnmatchedDelimiter,
+};
 
 pub struct Parser<'t> {
     index: usize,
     tokens: &'t [Token<'t>],
+    split: Option<Token<'t>>,
     pub(super) stack: Vec<Token<'t>>,
     pub(super) parsed: Vec<Token<'t>>,
+    /// The `{` of each block being parsed, innermost last
+    pub(super) blocks: Vec<Token<'t>>,
+    /// The construct being parsed and the offset it began at, innermost last, eg. `("function signature", 0)`
+    contexts: Vec<(&'static str, usize)>,
+    source: Option<Source>,
+    diagnostics: Vec<miette::Report>,
 }
 
 impl<'t> Parser<'t> {
@@ -17,16 +32,31 @@ impl<'t> Parser<'t> {
         Parser {
             index,
             tokens,
+            split: None,
             stack: Vec::new(), // with_capacity(tokens.len()),
             parsed: Vec::with_capacity(tokens.len()),
+            blocks: Vec::new(),
+            contexts: Vec::new(),
+            source: None,
+            diagnostics: Vec::new(),
         }
     }
 
+    /// Attaches the named source the tokens were lexed from to unexpected token diagnostics.
+    pub fn with_source(mut self, name: impl AsRef<str>, source: &str) -> Self {
+        self.source = Some(NamedSource::new(name, Arc::from(source)));
+        self
+    }
+
     pub(super) fn peek_token(&self) -> Option<Token<'t>> {
+        if self.split.is_some() {
+            return self.split;
+        }
+
         let mut index = self.index;
 
         loop {
-            if index >= self.tokens.len() - 1 {
+            if index >= self.tokens.len() {
                 return None;
             }
 
@@ -42,8 +72,12 @@ impl<'t> Parser<'t> {
     }
 
     pub(super) fn next_token(&mut self) -> Option<Token<'t>> {
+        if let Some(split) = self.split.take() {
+            return Some(split);
+        }
+
         loop {
-            if self.index > self.tokens.len() - 1 {
+            if self.index >= self.tokens.len() {
                 return None;
             }
 
@@ -72,6 +106,66 @@ impl<'t> Parser<'t> {
         // }
     }
 
+    /// Splits the next token after its first character, eg. so that `>>` can close two lists of type arguments.
+    ///
+    /// Returns the first character as its own token, leaving the rest to be read next.
+    pub(super) fn split_token(&mut self) -> Option<Token<'t>> {
+        let token = self.next_token()?;
+        let loc = token.loc();
+        let (first, rest) = token.inner().slice.split_at(1);
+
+        if !rest.is_empty() {
+            self.split = Some(Token::from(loc + 1, rest));
+        }
+        Some(Token::from(loc, first))
+    }
+
+    /// Returns the token after the next one.
+    pub(super) fn peek_second_token(&self) -> Option<Token<'t>> {
+        let mut tokens = self.tokens[self.index.min(self.tokens.len())..]
+            .iter()
+            .filter(|token| !matches!(token, Token::Space(_) | Token::Newline(_)));
+
+        if self.split.is_none() {
+            tokens.next();
+        }
+        tokens.next().copied()
+    }
+
+    /// Returns the last token read.
+    pub(super) fn previous_token(&self) -> Option<&Token<'t>> {
+        self.tokens[..self.index.min(self.tokens.len())]
+            .iter()
+            .rev()
+            .find(|token| !matches!(token, Token::Space(_) | Token::Newline(_)))
+    }
+
+    /// Whether the next token is on a later line than the last token read.
+    pub(super) fn newline_before_next(&self) -> bool {
+        self.tokens[self.index.min(self.tokens.len())..]
+            .iter()
+            .take_while(|token| matches!(token, Token::Space(_) | Token::Newline(_)))
+            .any(|token| matches!(token, Token::Newline(_)))
+    }
+
+    /// Returns the offset just past the last token read, where a missing token would be inserted.
+    fn previous_end(&self) -> usize {
+        self.previous_token()
+            .map_or(0, |token| token.loc() + token.as_str().len())
+    }
+
+    /// Records a diagnostic for a mistake which has been recovered from without skipping any input.
+    pub(super) fn report(&mut self, report: impl Into<miette::Report>) {
+        self.diagnostics.push(report.into());
+    }
+
+    /// Returns the offset just past the last token.
+    pub(super) fn end_of_input(&self) -> usize {
+        self.tokens
+            .last()
+            .map_or(0, |token| token.loc() + token.as_str().len())
+    }
+
     pub(super) fn push(&mut self, token: Token<'t>) {
         self.stack.push(token);
     }
@@ -84,102 +178,562 @@ impl<'t> Parser<'t> {
         self.stack.last()
     }
 
+    pub(super) fn last_mut(&mut self) -> Option<&mut Token<'t>> {
+        self.stack.last_mut()
+    }
+
     pub(super) fn write(&mut self, token: Token<'t>) {
         self.parsed.push(token);
     }
 
     /// Peeks at the next token and passes it into `predicate`.
     ///
-    /// When `predicate` returns `true`, this function returns `Ok` containing the peeked token. Otherwise, this function returns `Err`.
+    /// When `predicate` returns `true`, this function returns `Ok` containing the peeked token. Otherwise, this function
+    /// returns an [`UnexpectedToken`], where `expected` describes the tokens `predicate` accepts.
     pub(super) fn expect(
-        &mut self,
+        &self,
+        predicate: impl FnOnce(&Token<'t>) -> bool,
+        expected: &[&'static str],
+    ) -> miette::Result<Token<'t>> {
+        match self.peek_token() {
+            Some(token) if predicate(&token) => Ok(token),
+            token => Err(self.unexpected(token.as_ref(), expected).into()),
+        }
+    }
+
+    /// Like [`Parser::expect`], with `help` explaining how to fix the error.
+    pub(super) fn expect_with_help(
+        &self,
         predicate: impl FnOnce(&Token<'t>) -> bool,
-    ) -> Result<Token<'t>, UnexpectedToken> {
+        expected: &[&'static str],
+        help: &str,
+    ) -> miette::Result<Token<'t>> {
         match self.peek_token() {
             Some(token) if predicate(&token) => Ok(token),
-            Some(token) => {
-                let start = token.loc();
-                let length = token.as_str().len();
-
-                Err(UnexpectedToken {
-                    found: Some(token.as_str().to_owned()),
-                    unexpected_span: LabeledSpan::new(None, start, length),
-                })
+            token => Err(self
+                .unexpected(token.as_ref(), expected)
+                .with_help(help)
+                .into()),
+        }
+    }
+
+    /// Reports that `token`, or the end of the input when it's `None`, isn't one of `expected`.
+    pub(super) fn unexpected(
+        &self,
+        token: Option<&Token<'t>>,
+        expected: &[&'static str],
+    ) -> UnexpectedToken {
+        let (found, span) = match token {
+            Some(token) => (Found::Token(token.as_str().to_owned()), token.span()),
+            // Point just past the last token
+            None => (Found::EndOfInput, (self.previous_end(), 0).into()),
+        };
+        trace!("Unexpected {}, expected {:?}", found, expected);
+
+        let related = self.contexts.last().map(|&(context, start)| {
+            let end = self.previous_end().max(start + 1);
+            LabeledSpan::new(
+                Some(format!("while parsing this {context}")),
+                start,
+                end - start,
+            )
+        });
+
+        UnexpectedToken {
+            src: self.source.clone(),
+            found,
+            expected: Expected(expected.to_vec()),
+            span,
+            related,
+            help: None,
+        }
+    }
+
+    /// Begins parsing a construct named `context`, which unexpected tokens are reported as being part of.
+    fn enter(&mut self, context: &'static str, start: &Token<'t>) {
+        self.contexts.push((context, start.loc()));
+    }
+
+    fn exit(&mut self) {
+        self.contexts.pop();
+    }
+
+    /// Consumes and writes the `;` ending a statement.
+    ///
+    /// When the `;` is missing before the start of another statement, or at the end of the input, it's reported with
+    /// a suggestion, and one is written in its place.
+    fn semicolon(&mut self) -> miette::Result<()> {
+        let applicability = match self.peek_token() {
+            Some(semicolon @ Token::SemiColon(_)) => {
+                self.consume_token();
+                self.write(semicolon);
+                return Ok(());
             }
-            None => {
-                self.index -= 1;
-                let last_token = self.peek_token();
-
-                match last_token {
-                    Some(token) => {
-                        trace!("Erroring out, last_token: {:?}", token);
-                        let start = token.loc();
-                        // let length = if token.spaced() {
-                        //     token.as_str().len() + 1
-                        // } else {
-                        //     token.as_str().len()
-                        // };
-                        let length = token.as_str().len();
-
-                        Err(UnexpectedToken {
-                            found: None,
-                            unexpected_span: LabeledSpan::new(None, start + length, 0),
-                        })
-                    }
-                    None => Err(UnexpectedToken {
-                        found: None,
-                        unexpected_span: LabeledSpan::new(None, 0, 0),
-                    }),
+
+            // An operand after an operand was only ended by a newline, so could be a mistake within the statement
+            Some(Token::Identifier(_) | Token::NumericLiteral(_)) => Applicability::MaybeIncorrect,
+            Some(
+                Token::FunctionDeclaration(_)
+                | Token::VariableDeclaration(_)
+                | Token::Const(_)
+                | Token::If(_)
+                | Token::While(_)
+                | Token::OpenCurly(_),
+            )
+            | None => Applicability::MachineApplicable,
+
+            token => return Err(self.unexpected(token.as_ref(), &["`;`"]).into()),
+        };
+
+        let end = self.previous_end();
+        let labels = match self.peek_token() {
+            Some(token) => vec![LabeledSpan::new(
+                Some("expected `;` before this".into()),
+                token.loc(),
+                token.as_str().len(),
+            )],
+            None => vec![],
+        };
+        self.report(Suggested {
+            code: "E0006",
+            message: "Missing `;` at the end of a statement".into(),
+            labels,
+            suggestion: Suggestion::insert(end, ";", "add a `;` to end the statement")
+                .with_applicability(applicability),
+        });
+        self.write(Token::SemiColon(Inner::new(end, ";")));
+
+        Ok(())
+    }
+
+    /// Reports a missing `:` between `name` and its type, returning one to write in its place.
+    fn missing_colon(&mut self, name: &Token<'t>, context: &str) -> Token<'t> {
+        let end = name.loc() + name.as_str().len();
+        let labels = match self.peek_token() {
+            Some(token) => vec![LabeledSpan::new(
+                Some(format!("expected `:` before {context}")),
+                token.loc(),
+                token.as_str().len(),
+            )],
+            None => vec![],
+        };
+
+        self.report(Suggested {
+            code: "E0008",
+            message: format!("Missing `:` before {context}"),
+            labels,
+            suggestion: Suggestion::insert(end, ":", "types are written after a `:`"),
+        });
+        Token::Colon(Inner::new(end, ":"))
+    }
+
+    /// Parses every statement, returning the first error if there were any.
+    ///
+    /// The whole input is still parsed, recovering after each error as [`Parser::parse_recovering`] does, which returns
+    /// every error rather than only the first.
+    pub fn parse(self) -> miette::Result<Vec<Token<'t>>> {
+        let (parsed, diagnostics) = self.parse_recovering();
+
+        match diagnostics.into_iter().next() {
+            Some(report) => Err(report),
+            None => Ok(parsed),
+        }
+    }
+
+    /// Parses every statement, recovering from errors so that each one is reported.
+    ///
+    /// A statement which fails to parse is replaced by a [`Token::Error`] in the output, and the input is skipped up to
+    /// the next `;`, `}`, `fn` or `var`. Returns the output along with a diagnostic for each error, in source order.
+    pub fn parse_recovering(mut self) -> (Vec<Token<'t>>, Vec<miette::Report>) {
+        trace!("Start parsing");
+
+        while let Some(token) = self.peek_token() {
+            match token {
+                Token::CloseCurly(_) => {
+                    self.diagnostics.push(
+                        UnmatchedDelimiter {
+                            found: token.as_str().to_owned(),
+                            closer: token.span(),
+                            opener: None,
+                            help: Some("remove this `}`, or add a matching `{` before it".into()),
+                        }
+                        .into(),
+                    );
+                    self.consume_token();
+                }
+                _ => {
+                    recovering_statement(&mut self);
                 }
             }
         }
+
+        (self.parsed, self.diagnostics)
     }
 
-    /// Peeks at the next token and passes it into `predicate`.
+    /// Discards the output of a statement which failed to parse, and skips ahead to where the next statement can
+    /// begin.
     ///
-    /// When `predicate` returns `true`, this function returns `Ok` containing the peeked token. Otherwise, this function returns a `miette::Err` decorated with `msg`.
-    pub(super) fn expect_with_msg(
-        &mut self,
-        predicate: impl FnOnce(&Token<'t>) -> bool,
-        msg: impl FnOnce(&UnexpectedToken) -> String,
-    ) -> miette::Result<Token<'t>> {
-        let result = self.expect(predicate);
+    /// `start` is the first token of the statement, and `written` is the length of the output before it.
+    fn synchronise(&mut self, start: Token<'t>, written: usize, index: usize) {
+        self.stack.clear();
+        self.parsed.truncate(written);
+        self.parsed.push(Token::Error(*start.inner()));
+
+        // Always skip at least one token, so that a statement failing on its first token can't loop forever
+        if self.index == index && self.split.is_none() {
+            self.consume_token();
+        }
+
+        // Skip nested blocks entirely, so their `}` doesn't end the enclosing block, and treat the end of one as the
+        // end of the statement, eg. the body of a function with a malformed signature
+        let mut depth = 0usize;
+        while let Some(token) = self.peek_token() {
+            match token {
+                Token::OpenCurly(_) => depth += 1,
+                Token::CloseCurly(_) if depth > 0 => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.consume_token();
+                        break;
+                    }
+                }
+                Token::CloseCurly(_)
+                | Token::FunctionDeclaration(_)
+                | Token::VariableDeclaration(_)
+                | Token::Const(_)
+                    if depth == 0 =>
+                {
+                    break;
+                }
+                Token::SemiColon(_) if depth == 0 => {
+                    self.consume_token();
+                    self.parsed.push(token);
+                    break;
+                }
+                _ => {}
+            }
+            self.consume_token();
+        }
+
+        trace!("Synchronised at {:?}", self.peek_token());
+    }
+}
+
+/// Parses a statement, recording a diagnostic and synchronising if it fails.
+fn recovering_statement(parser: &mut Parser<'_>) {
+    let Some(start) = parser.peek_token() else {
+        return;
+    };
+    let written = parser.parsed.len();
+    let index = parser.index;
+    let contexts = parser.contexts.len();
+
+    if let Err(report) = statement(parser) {
+        parser.diagnostics.push(report);
+        parser.contexts.truncate(contexts);
+        parser.synchronise(start, written, index);
+    }
+}
 
-        match result {
-            Ok(token) => Ok(token),
-            Err(err) => {
-                let msg = msg(&err);
-                let span = err.unexpected_span.clone();
-                let description = err.to_string();
+/// Keywords from other languages which are likely meant to be `fn`.
+const FUNCTION_KEYWORDS: [&str; 4] = ["fun", "func", "function", "def"];
 
-                let labelled_span =
-                    LabeledSpan::new(Some(msg.to_string()), span.offset(), span.len());
+fn statement<'p, 't>(parser: &'p mut Parser<'t>) -> miette::Result<()> {
+    match parser.peek_token() {
+        Some(Token::FunctionDeclaration(_)) => {
+            let fn_token = parser.next_token().unwrap();
+            function_declaration(parser, fn_token)
+        }
+        Some(Token::VariableDeclaration(_) | Token::Const(_)) => variable_declaration(parser),
+        Some(Token::If(_)) => if_statement(parser),
+        Some(Token::While(_)) => while_statement(parser),
+        Some(Token::OpenCurly(_)) => block(parser),
+
+        // eg. `fun name(...)`, where two names in a row are never valid, so parse it as a function
+        Some(Token::Identifier(inner))
+            if FUNCTION_KEYWORDS.contains(&inner.slice)
+                && matches!(parser.peek_second_token(), Some(Token::Identifier(_))) =>
+        {
+            parser.consume_token();
+            parser.report(Suggested {
+                code: "E0007",
+                message: format!("Unknown keyword `{}`", inner.slice),
+                labels: vec![],
+                suggestion: Suggestion::replace(
+                    Token::Identifier(inner).span(),
+                    "fn",
+                    "functions are declared with `fn`",
+                ),
+            });
+
+            function_declaration(parser, Token::FunctionDeclaration(inner))
+        }
+
+        _ => {
+            expression(parser)?;
 
-                Err(miette!(labels = vec![labelled_span], "{description}"))
+            // The last expression in a block, or in the file, doesn't need a terminating `;`
+            match parser.peek_token() {
+                Some(Token::CloseCurly(_)) | None => Ok(()),
+                _ => parser.semicolon(),
             }
         }
     }
+}
 
-    pub fn parse<'p>(mut self) -> miette::Result<Vec<Token<'t>>> {
-        trace!("Start parsing");
+/// Parses `if condition { ... }`, followed by any number of `else if condition { ... }`, and an optional
+/// `else { ... }`.
+fn if_statement<'p, 't>(parser: &'p mut Parser<'t>) -> miette::Result<()> {
+    let if_token = parser.next_token().unwrap();
+    parser.write(if_token);
+
+    parser.enter("condition", &if_token);
+    condition(parser)?;
+    parser.exit();
+    block(parser)?;
+
+    let Some(else_token @ Token::Else(_)) = parser.peek_token() else {
+        return Ok(());
+    };
+    parser.consume_token();
+    parser.write(else_token);
+
+    match parser.peek_token() {
+        Some(Token::If(_)) => if_statement(parser),
+        _ => block(parser),
+    }
+}
 
-        let token = self.peek_token().wrap_err_with(|| "Expected token")?;
+fn while_statement<'p, 't>(parser: &'p mut Parser<'t>) -> miette::Result<()> {
+    let while_token = parser.next_token().unwrap();
+    parser.write(while_token);
+
+    parser.enter("condition", &while_token);
+    condition(parser)?;
+    parser.exit();
+    block(parser)
+}
 
-        match token {
-            _ => {
-                expression(&mut self)?;
+/// Parses the condition of an `if` or `while`.
+///
+/// An assignment is never a condition, so `if a = b` is reported, and parsed as the comparison `if a == b`.
+fn condition<'p, 't>(parser: &'p mut Parser<'t>) -> miette::Result<()> {
+    expression(parser)?;
+
+    // The operator applied last is written last, before any brackets around the whole condition
+    let operator = parser
+        .parsed
+        .iter()
+        .rposition(|token| !matches!(token, Token::CloseBracket(_)));
+    if let Some((operator, &Token::Equals(inner))) =
+        operator.map(|operator| (operator, &parser.parsed[operator]))
+    {
+        let equals = Token::Equals(inner);
+
+        parser.report(Suggested {
+            code: "E0009",
+            message: "Expected a condition, found an assignment".into(),
+            labels: vec![],
+            suggestion: Suggestion::replace(
+                equals.span(),
+                "==",
+                "use `==` to compare for equality",
+            ),
+        });
+        parser.parsed[operator] = Token::DoubleEquals(inner);
+    }
+
+    Ok(())
+}
+
+fn block<'p, 't>(parser: &'p mut Parser<'t>) -> miette::Result<()> {
+    let open_curly = parser.expect(|token| matches!(token, Token::OpenCurly(_)), &["`{`"])?;
+    parser.consume_token();
+    parser.write(open_curly);
+    parser.blocks.push(open_curly);
+
+    loop {
+        match parser.peek_token() {
+            Some(close_curly @ Token::CloseCurly(_)) => {
+                parser.consume_token();
+                parser.write(close_curly);
+                parser.blocks.pop();
+                return Ok(());
             }
-        };
+            Some(_) => {
+                recovering_statement(parser);
+            }
+            None => {
+                parser.blocks.pop();
+
+                let end = parser.end_of_input();
+                return Err(UnclosedDelimiter {
+                    open: open_curly.as_str().to_owned(),
+                    close: "}".into(),
+                    opener: open_curly.span(),
+                    expected: (end, 0).into(),
+                }
+                .into());
+            }
+        }
+    }
+}
+
+/// Parses a function, after its `fn` has been read.
+fn function_declaration<'t>(parser: &mut Parser<'t>, fn_token: Token<'t>) -> miette::Result<()> {
+    parser.write(fn_token);
+    parser.enter("function signature", &fn_token);
+
+    let identifier = parser.expect(
+        |token| matches!(token, Token::Identifier(_)),
+        &["a function name"],
+    )?;
+    parser.consume_token();
+    parser.write(identifier);
+
+    let open_bracket = parser.expect_with_help(
+        |token| matches!(token, Token::OpenBracket(_)),
+        &["`(`"],
+        "parameters are listed in brackets after the function name, eg. `fn f(a: A)`",
+    )?;
+    parser.consume_token();
+    parser.write(open_bracket);
+
+    // Parameters are written as a list joined by commas, eg. `a A : b B : ,`
+    let mut last_comma = None;
+    while !matches!(parser.peek_token(), Some(Token::CloseBracket(_))) {
+        parameter(parser)?;
+
+        if let Some(comma) = last_comma.take() {
+            parser.write(comma);
+        }
 
-        Ok(self.parsed)
+        let next = parser.expect(
+            |token| matches!(token, Token::Comma(_) | Token::CloseBracket(_)),
+            &["`,`", "`)`"],
+        )?;
+        if let Token::Comma(_) = next {
+            parser.consume_token();
+            last_comma = Some(next);
+        }
+    }
+
+    let close_bracket = parser.next_token().unwrap();
+    parser.write(close_bracket);
+
+    // Return type
+    match parser.peek_token() {
+        Some(separator @ (Token::Colon(_) | Token::Arrow(_))) => {
+            parser.consume_token();
+            type_expression(parser)?;
+            parser.write(separator);
+        }
+        Some(token) if starts_type(&token) => {
+            let colon = parser.missing_colon(&close_bracket, "the return type");
+            type_expression(parser)?;
+            parser.write(colon);
+        }
+        _ => {}
     }
+    parser.exit();
+
+    block(parser)
 }
 
-fn function_declaration<'p, 't>(parser: &'p mut Parser<'t>) -> miette::Result<()> {
-    let identifier = parser.expect_with_msg(
+fn parameter<'p, 't>(parser: &'p mut Parser<'t>) -> miette::Result<()> {
+    let identifier = parser.expect(
         |token| matches!(token, Token::Identifier(_)),
-        |err| format!("Expected function name, found {:?}", err.found),
-    );
+        &["a parameter name"],
+    )?;
+    parser.consume_token();
+    parser.write(identifier);
+
+    let colon = match parser.peek_token() {
+        Some(colon @ Token::Colon(_)) => {
+            parser.consume_token();
+            colon
+        }
+        Some(token) if starts_type(&token) => {
+            parser.missing_colon(&identifier, "the parameter type")
+        }
+        token => {
+            return Err(parser
+                .unexpected(token.as_ref(), &["`:`"])
+                .with_help("parameters are written with their type, eg. `a: A`")
+                .into())
+        }
+    };
+    type_expression(parser)?;
+    parser.write(colon);
+
+    Ok(())
+}
+
+/// Whether `token` can begin a type.
+fn starts_type(token: &Token<'_>) -> bool {
+    matches!(
+        token,
+        Token::Identifier(_)
+            | Token::Ampersand(_)
+            | Token::OpenBracket(_)
+            | Token::FunctionDeclaration(_)
+    )
+}
+
+/// Parses `var name: T = value;` where the type and value are optional, or `const name: T = value;` where only the
+/// type is.
+fn variable_declaration<'p, 't>(parser: &'p mut Parser<'t>) -> miette::Result<()> {
+    let var_token = parser.next_token().unwrap();
+    let constant = matches!(var_token, Token::Const(_));
+    parser.write(var_token);
+    let context = if constant {
+        "constant declaration"
+    } else {
+        "variable declaration"
+    };
+    parser.enter(context, &var_token);
+
+    let identifier = parser.expect(
+        |token| matches!(token, Token::Identifier(_)),
+        &[if constant {
+            "a constant name"
+        } else {
+            "a variable name"
+        }],
+    )?;
+    parser.consume_token();
+    parser.write(identifier);
+
+    match parser.peek_token() {
+        Some(colon @ Token::Colon(_)) => {
+            parser.consume_token();
+            type_expression(parser)?;
+            parser.write(colon);
+        }
+        // A `(` can't begin a type here, since `var a (b)` is more likely a missing `=`
+        Some(Token::Identifier(_) | Token::Ampersand(_)) => {
+            let colon = parser.missing_colon(&identifier, "the variable type");
+            type_expression(parser)?;
+            parser.write(colon);
+        }
+        _ => {}
+    }
+
+    if constant {
+        parser.expect_with_help(
+            |token| matches!(token, Token::Equals(_)),
+            &["`=`"],
+            "constants have to be given a value, eg. `const SIZE: i32 = 16;`",
+        )?;
+    }
+    if let Some(equals @ Token::Equals(_)) = parser.peek_token() {
+        parser.consume_token();
+        expression(parser)?;
+        parser.write(equals);
+    }
+
+    parser.semicolon()?;
+    parser.exit();
 
     Ok(())
 }
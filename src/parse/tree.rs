@@ -0,0 +1,280 @@
+use crate::token::Token;
+
+/// The parser's output as a flat tree, stored as parallel arrays in post-order.
+///
+/// Each node is the index of its entry in the arrays, with a kind, the index of the lexed token it was parsed from,
+/// the number of nodes in its subtree including itself, and its parent. Children always come before their parent, so
+/// the subtree of `node` is the range `node + 1 - subtree_size(node)..=node`, and the last child of `node` is
+/// `node - 1`.
+#[derive(Debug, Default, Clone)]
+pub struct ParseTree {
+    kinds: Vec<NodeKind>,
+    token_indices: Vec<u32>,
+    subtree_sizes: Vec<u32>,
+    /// [`NO_PARENT`] for the roots
+    parents: Vec<u32>,
+}
+
+const NO_PARENT: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum NodeKind {
+    Name,
+    Literal,
+    FunctionIntroducer,
+    VariableIntroducer,
+    IfIntroducer,
+    /// An `else`, which stays a sibling of the blocks around it
+    ElseIntroducer,
+    WhileIntroducer,
+    /// A `(`, whose group is closed by a [`NodeKind::Group`]
+    GroupStart,
+    Group,
+    /// A `{`, whose block is closed by a [`NodeKind::Block`] or [`NodeKind::FunctionDefinition`]
+    BlockStart,
+    Block,
+    /// The `}` ending a function's body, with the whole function as its subtree
+    FunctionDefinition,
+    /// The `}` ending the last block of an `if`, with every branch as its subtree
+    If,
+    /// The `}` ending the body of a `while`, with the whole loop as its subtree
+    While,
+    /// A `;`, with the statement it terminates as its subtree
+    Statement,
+    PrefixOperator,
+    InfixOperator,
+    Call,
+    TypeArguments,
+    TupleType,
+    FunctionType,
+    /// A statement which failed to parse
+    Error,
+}
+
+impl ParseTree {
+    /// Builds the tree from the lexed `tokens`, and the postfix output of parsing them.
+    pub fn new<'t>(tokens: &[Token<'t>], parsed: &[Token<'t>]) -> Self {
+        let mut tree = ParseTree {
+            kinds: Vec::with_capacity(parsed.len()),
+            token_indices: Vec::with_capacity(parsed.len()),
+            subtree_sizes: Vec::with_capacity(parsed.len()),
+            parents: Vec::with_capacity(parsed.len()),
+        };
+
+        // Nodes which don't have a parent yet
+        let mut roots: Vec<u32> = Vec::new();
+        // Positions in `roots` of unclosed `(` and `{`
+        let mut brackets: Vec<usize> = Vec::new();
+        // Positions in `roots` where the current statement began, one per unclosed block
+        let mut statements: Vec<usize> = vec![0];
+
+        for (node, token) in parsed.iter().enumerate() {
+            let node = node as u32;
+
+            let (kind, children) = match token {
+                Token::Identifier(_) => (NodeKind::Name, 0),
+                Token::NumericLiteral(_) => (NodeKind::Literal, 0),
+                Token::FunctionDeclaration(_) => (NodeKind::FunctionIntroducer, 0),
+                Token::VariableDeclaration(_) | Token::Const(_) => {
+                    (NodeKind::VariableIntroducer, 0)
+                }
+                Token::If(_) => (NodeKind::IfIntroducer, 0),
+                Token::Else(_) => (NodeKind::ElseIntroducer, 0),
+                Token::While(_) => (NodeKind::WhileIntroducer, 0),
+                Token::Error(_) => (NodeKind::Error, 0),
+                Token::OpenBracket(_) => {
+                    brackets.push(roots.len());
+                    (NodeKind::GroupStart, 0)
+                }
+                Token::OpenCurly(_) => {
+                    brackets.push(roots.len());
+                    statements.push(roots.len() + 1);
+                    (NodeKind::BlockStart, 0)
+                }
+
+                Token::CloseBracket(_) => {
+                    let start = brackets.pop().unwrap_or(0);
+                    (NodeKind::Group, roots.len() - start)
+                }
+                Token::CloseCurly(_) => {
+                    let start = brackets.pop().unwrap_or(0);
+                    statements.pop();
+
+                    // A block directly after a function's signature, or a condition, ends the statement
+                    let statement = *statements.last().unwrap_or(&0);
+                    let introducer = roots
+                        .get(statement)
+                        .map(|&root| tree.kinds[root as usize])
+                        .filter(|_| statement < start);
+                    let followed_by_else =
+                        matches!(parsed.get(node as usize + 1), Some(Token::Else(_)));
+
+                    match introducer {
+                        Some(NodeKind::FunctionIntroducer) => {
+                            (NodeKind::FunctionDefinition, roots.len() - statement)
+                        }
+                        Some(NodeKind::IfIntroducer) if !followed_by_else => {
+                            (NodeKind::If, roots.len() - statement)
+                        }
+                        Some(NodeKind::WhileIntroducer) => {
+                            (NodeKind::While, roots.len() - statement)
+                        }
+                        _ => (NodeKind::Block, roots.len() - start),
+                    }
+                }
+                Token::SemiColon(_) => {
+                    let statement = *statements.last().unwrap_or(&0);
+                    (NodeKind::Statement, roots.len().saturating_sub(statement))
+                }
+
+                Token::Negate(_)
+                | Token::UnaryPlus(_)
+                | Token::Not(_)
+                | Token::Deref(_)
+                | Token::Reference(_) => (NodeKind::PrefixOperator, 1),
+                Token::Call(_, arity) => (NodeKind::Call, *arity as usize + 1),
+                Token::Generic(_, arity) => (NodeKind::TypeArguments, *arity as usize + 1),
+                Token::Tuple(_, arity) => (NodeKind::TupleType, *arity as usize),
+                Token::FunctionType(_, arity) => (NodeKind::FunctionType, *arity as usize + 1),
+
+                _ => (NodeKind::InfixOperator, 2),
+            };
+
+            // Children are contiguous, so the subtree begins where the first child's subtree does
+            let children = children.min(roots.len());
+            let first = roots.len() - children;
+            let size = match roots.get(first) {
+                Some(&child) => node - tree.start(child as usize) as u32 + 1,
+                None => 1,
+            };
+            for &child in &roots[first..] {
+                tree.parents[child as usize] = node;
+            }
+            roots.truncate(first);
+            roots.push(node);
+
+            // Statements end at `;`, and at the end of a block
+            let ends_statement = match kind {
+                NodeKind::Statement
+                | NodeKind::FunctionDefinition
+                | NodeKind::If
+                | NodeKind::While => true,
+                // Blocks between the branches of an `if` are still part of it
+                NodeKind::Block => !matches!(parsed.get(node as usize + 1), Some(Token::Else(_))),
+                _ => false,
+            };
+            if ends_statement {
+                if let Some(statement) = statements.last_mut() {
+                    *statement = roots.len();
+                }
+            }
+
+            tree.kinds.push(kind);
+            tree.token_indices.push(token_index(tokens, token));
+            tree.subtree_sizes.push(size);
+            tree.parents.push(NO_PARENT);
+        }
+
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.kinds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+
+    pub fn kind(&self, node: usize) -> NodeKind {
+        self.kinds[node]
+    }
+
+    /// Returns the index of the lexed token `node` was parsed from.
+    pub fn token(&self, node: usize) -> usize {
+        self.token_indices[node] as usize
+    }
+
+    pub fn subtree_size(&self, node: usize) -> usize {
+        self.subtree_sizes[node] as usize
+    }
+
+    /// Returns the first node in the subtree of `node`.
+    fn start(&self, node: usize) -> usize {
+        node + 1 - self.subtree_size(node)
+    }
+
+    /// Iterates over the children of `node`, starting from the last.
+    pub fn children_rev(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.siblings_rev(self.start(node), node)
+    }
+
+    /// Returns the children of `node` in source order.
+    pub fn children(&self, node: usize) -> Vec<usize> {
+        let mut children: Vec<usize> = self.children_rev(node).collect();
+        children.reverse();
+        children
+    }
+
+    /// Iterates over the nodes without a parent, starting from the last.
+    pub fn roots_rev(&self) -> impl Iterator<Item = usize> + '_ {
+        self.siblings_rev(0, self.len())
+    }
+
+    /// Returns the nodes without a parent in source order.
+    pub fn roots(&self) -> Vec<usize> {
+        let mut roots: Vec<usize> = self.roots_rev().collect();
+        roots.reverse();
+        roots
+    }
+
+    /// Iterates over the subtrees in `start..end`, from the one ending just before `end`.
+    fn siblings_rev(&self, start: usize, end: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut next = end;
+        std::iter::from_fn(move || {
+            if next <= start {
+                return None;
+            }
+            let sibling = next - 1;
+            next = self.start(sibling);
+            Some(sibling)
+        })
+    }
+
+    pub fn parent(&self, node: usize) -> Option<usize> {
+        let parent = self.parents[node];
+        (parent != NO_PARENT).then_some(parent as usize)
+    }
+
+    /// Returns the sibling directly before `node`.
+    pub fn previous_sibling(&self, node: usize) -> Option<usize> {
+        let start = self.parent(node).map_or(0, |parent| self.start(parent));
+        let previous = self.start(node).checked_sub(1)?;
+
+        (previous >= start).then_some(previous)
+    }
+
+    /// Returns the sibling directly after `node`.
+    ///
+    /// Its subtree starts right after `node`, so it's found by climbing from `node + 1` to the ancestor sharing
+    /// `node`'s parent, which takes as many steps as that subtree is deep.
+    pub fn next_sibling(&self, node: usize) -> Option<usize> {
+        let parent = self.parent(node);
+        let mut sibling = node + 1;
+        if sibling >= parent.unwrap_or(self.len()) {
+            return None;
+        }
+        while self.parent(sibling) != parent {
+            sibling = self.parent(sibling)?;
+        }
+        Some(sibling)
+    }
+}
+
+/// Finds the index of the lexed token containing `token`, which may be a part of it, or have been made by the parser
+/// from it.
+fn token_index(tokens: &[Token<'_>], token: &Token<'_>) -> u32 {
+    let index = tokens.partition_point(|lexed| lexed.loc() <= token.loc());
+    index.saturating_sub(1) as u32
+}
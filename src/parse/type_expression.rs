@@ -0,0 +1,146 @@
+use crate::parse::parser::Parser;
+use crate::token::Token;
+use log::trace;
+
+/// Parses a type into reverse Polish notation by recursive descent.
+///
+/// Types have their own grammar, separate from expressions:
+///
+/// ```text
+/// type := '&' type
+///       | 'fn' '(' types ')' ('->' type)?
+///       | '(' types ')'
+///       | identifier ('<' types '>')?
+/// types := (type (',' type)* ','?)?
+/// ```
+///
+/// Type arguments are only parsed here, so `<` and `>` are always comparisons in expression position. A type can only
+/// appear after `:` or `->`, so there is no ambiguity between the two.
+pub(super) fn type_expression<'p, 't>(parser: &'p mut Parser<'t>) -> miette::Result<()> {
+    let token = parser.expect(
+        |token| {
+            matches!(
+                token,
+                Token::Identifier(_)
+                    | Token::Ampersand(_)
+                    | Token::OpenBracket(_)
+                    | Token::FunctionDeclaration(_)
+            )
+        },
+        &["a type"],
+    )?;
+    parser.consume_token();
+
+    trace!("Parsing type starting at {}", &token);
+    match token {
+        // References, eg. `&T`
+        Token::Ampersand(inner) => {
+            type_expression(parser)?;
+            parser.write(Token::Reference(inner));
+        }
+
+        // Tuples, eg. `(A, B)` or `()`, where `(A)` is just `A`
+        Token::OpenBracket(_) => {
+            let (count, trailing_comma) = type_list(parser, ")")?;
+            let close_bracket = close_bracket(parser)?;
+
+            if count != 1 || trailing_comma {
+                parser.write(Token::Tuple(*close_bracket.inner(), count));
+            }
+        }
+
+        // Function types, eg. `fn(A, B) -> C`, which return `()` when no return type is given
+        Token::FunctionDeclaration(inner) => {
+            parser.expect_with_help(
+                |token| matches!(token, Token::OpenBracket(_)),
+                &["`(`"],
+                "function types are written as `fn(A, B) -> C`",
+            )?;
+            parser.consume_token();
+
+            let (count, _) = type_list(parser, ")")?;
+            let close_bracket = close_bracket(parser)?;
+
+            if let Some(Token::Arrow(_)) = parser.peek_token() {
+                parser.consume_token();
+                type_expression(parser)?;
+            } else {
+                parser.write(Token::Tuple(*close_bracket.inner(), 0));
+            }
+
+            parser.write(Token::FunctionType(inner, count));
+        }
+
+        // Named types, with optional type arguments, eg. `Map<K, Vec<V>>`
+        _ => {
+            parser.write(token);
+
+            if let Some(Token::LessThan(_)) = parser.peek_token() {
+                parser.consume_token();
+
+                let (count, _) = type_list(parser, ">")?;
+                let close_angle_bracket = close_angle_bracket(parser)?;
+
+                parser.write(Token::Generic(*close_angle_bracket.inner(), count));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses comma separated types up to, but not including, a token starting with `close`.
+///
+/// Returns the number of types parsed, and whether the list ended with a trailing comma.
+fn type_list<'p, 't>(parser: &'p mut Parser<'t>, close: &str) -> miette::Result<(u32, bool)> {
+    let mut count = 0;
+    let mut trailing_comma = false;
+
+    while !parser
+        .peek_token()
+        .is_some_and(|token| token.as_str().starts_with(close))
+    {
+        type_expression(parser)?;
+        count += 1;
+        trailing_comma = false;
+
+        let expected = match close {
+            ">" => ["`,`", "`>`"],
+            _ => ["`,`", "`)`"],
+        };
+        let next = parser.expect(
+            |token| matches!(token, Token::Comma(_)) || token.as_str().starts_with(close),
+            &expected,
+        )?;
+        if let Token::Comma(_) = next {
+            parser.consume_token();
+            trailing_comma = true;
+        }
+    }
+
+    Ok((count, trailing_comma))
+}
+
+fn close_bracket<'t>(parser: &mut Parser<'t>) -> miette::Result<Token<'t>> {
+    let close_bracket = parser.expect(|token| matches!(token, Token::CloseBracket(_)), &["`)`"])?;
+    parser.consume_token();
+
+    Ok(close_bracket)
+}
+
+/// Consumes the `>` closing a list of type arguments.
+///
+/// Tokens starting with `>`, such as the `>>` in `Vec<Vec<T>>`, are split so the rest can close an outer list.
+fn close_angle_bracket<'t>(parser: &mut Parser<'t>) -> miette::Result<Token<'t>> {
+    parser.expect(
+        |token| {
+            matches!(
+                token,
+                Token::GreaterThan(_) | Token::ShiftRight(_) | Token::GreaterEquals(_)
+            )
+        },
+        &["`>`"],
+    )?;
+
+    Ok(parser.split_token().unwrap())
+}
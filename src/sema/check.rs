@@ -0,0 +1,931 @@
+//! Type checking, giving every expression and symbol a type and reporting where they don't agree.
+//!
+//! Functions have to be declared with the types of their parameters, but variables declared without a type have it
+//! inferred from how they're used, by unifying the types each use requires. An integer literal has whichever integer
+//! type it's used as, and the configured default when nothing constrains it, so `var x = 1 + 2;` is an `i32` by
+//! default.
+//!
+//! A block's value is its tail expression, or a trailing `if` with an `else`, as in
+//! `fn f(): i32 { if c { 1 } else { 2 } }`.
+
+use std::collections::HashMap;
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::ast::{
+    BinaryOp, Block, Expr, ExprKind, Function, Ident, If, ItemKind, Module, Stmt, StmtKind,
+    TypeExpr, TypeKind, UnaryOp,
+};
+use crate::sema::infer::{Conflict, Unifier};
+use crate::sema::resolve::{edit_distance, Resolution};
+use crate::sema::symbols::{Mutability, SymbolId, SymbolKind};
+use crate::sema::types::{Type, BUILTINS};
+use crate::suggestion::{Applicability, Suggested, Suggestion};
+
+/// The type of every symbol and expression in a module.
+#[derive(Debug, Clone, Default)]
+pub struct Typing {
+    pub symbols: HashMap<SymbolId, Type>,
+    /// Keyed by the offset and length of each expression's span
+    pub exprs: HashMap<(usize, usize), Type>,
+}
+
+impl Typing {
+    pub fn symbol(&self, id: SymbolId) -> Option<&Type> {
+        self.symbols.get(&id)
+    }
+
+    pub fn expr(&self, expr: &Expr) -> Option<&Type> {
+        self.exprs.get(&(expr.span.offset(), expr.span.len()))
+    }
+
+    /// Each declaration with its type, in the order they're written, eg. `variable x: i32`.
+    pub fn declarations(&self, resolution: &Resolution) -> Vec<(SourceSpan, String)> {
+        let symbols = &resolution.symbols;
+        let mut declarations: Vec<(SourceSpan, String)> = symbols
+            .symbols()
+            .iter()
+            .enumerate()
+            .filter_map(|(id, symbol)| {
+                let ty = self.symbol(id)?;
+                let name = symbols.name(symbol.name);
+                Some((
+                    symbol.span,
+                    format!("{} {name}: {ty}", symbol.kind.describe()),
+                ))
+            })
+            .collect();
+        declarations.sort_by_key(|(span, _)| span.offset());
+
+        declarations
+    }
+}
+
+/// A type annotation naming a type which doesn't exist
+#[derive(Error, Diagnostic, Debug)]
+#[error("Cannot find type `{name}`")]
+#[diagnostic(code(E0013))]
+pub struct UnknownType {
+    pub name: String,
+    #[label("not a known type")]
+    pub span: SourceSpan,
+}
+
+/// An expression whose type isn't the type it's expected to have, eg. `var a: bool = 1;`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Mismatched types")]
+#[diagnostic(code(E0014))]
+pub struct TypeMismatch {
+    pub expected: Type,
+    pub found: Type,
+    #[label(primary, "expected `{expected}`, found `{found}`")]
+    pub span: SourceSpan,
+    /// The annotation, parameter or return type the expectation comes from
+    #[label("expected because of this")]
+    pub because: Option<SourceSpan>,
+}
+
+/// A binary operator applied to operands it isn't defined for, eg. `true + 1`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Cannot apply `{op}` to `{left_type}` and `{right_type}`")]
+#[diagnostic(code(E0015))]
+pub struct InvalidOperands {
+    pub op: BinaryOp,
+    pub left_type: Type,
+    pub right_type: Type,
+    #[label("`{left_type}`")]
+    pub left: SourceSpan,
+    #[label("`{right_type}`")]
+    pub right: SourceSpan,
+}
+
+/// A unary operator applied to an operand it isn't defined for, eg. `-true`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Cannot apply `{op}` to `{operand_type}`")]
+#[diagnostic(code(E0015))]
+pub struct InvalidOperand {
+    pub op: &'static str,
+    pub operand_type: Type,
+    #[label("`{operand_type}`")]
+    pub operand: SourceSpan,
+}
+
+/// A call with more or fewer arguments than the function has parameters
+#[derive(Error, Diagnostic, Debug)]
+#[error("Expected {expected} argument{}, found {found}", if *.expected == 1 { "" } else { "s" })]
+#[diagnostic(code(E0016))]
+pub struct ArgumentCount {
+    pub expected: usize,
+    pub found: usize,
+    #[label(primary, "called with {found}")]
+    pub span: SourceSpan,
+    #[label("function declared here")]
+    pub declaration: Option<SourceSpan>,
+}
+
+/// A call to a value which isn't a function, eg. `var a = 1; a();`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Cannot call a value of type `{ty}`")]
+#[diagnostic(code(E0017))]
+pub struct NotCallable {
+    pub ty: Type,
+    #[label("not a function")]
+    pub span: SourceSpan,
+}
+
+/// An assignment to something other than a variable or a dereference, eg. `1 = a;`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Invalid left-hand side of `{op}`")]
+#[diagnostic(
+    code(E0032),
+    help("only names, and the values references point to, such as `*p`, can be assigned to")
+)]
+pub struct InvalidAssignee {
+    pub op: BinaryOp,
+    #[label("can't be assigned to")]
+    pub span: SourceSpan,
+}
+
+/// An assignment to a symbol which can't be changed once it's declared, eg. `const X = 1; X = 2;`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Cannot assign to the {kind} `{name}`")]
+#[diagnostic(
+    code(E0033),
+    help("declare a variable with `var` to hold a value which changes")
+)]
+pub struct ImmutableAssignment {
+    pub kind: &'static str,
+    pub name: String,
+    #[label(primary, "`{name}` can't be changed")]
+    pub span: SourceSpan,
+    #[label("`{name}` declared here")]
+    pub declaration: SourceSpan,
+}
+
+/// A reference to a symbol which can't be changed, which could otherwise be assigned through, eg. `const X = 1; &X`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Cannot take a reference to the {kind} `{name}`")]
+#[diagnostic(
+    code(E0033),
+    help("values can be assigned through a reference, so copy `{name}` into a variable with `var` first")
+)]
+pub struct ImmutableReference {
+    pub kind: &'static str,
+    pub name: String,
+    #[label(primary, "`{name}` can't be changed")]
+    pub span: SourceSpan,
+    #[label("`{name}` declared here")]
+    pub declaration: SourceSpan,
+}
+
+/// A variable whose type can't be inferred from its initialiser or uses, eg. `var a;` on its own
+#[derive(Error, Diagnostic, Debug)]
+#[error("Type annotations needed for `{name}`")]
+#[diagnostic(
+    code(E0018),
+    help("give `{name}` a type, eg. `var {name}: i32;`, or an initial value")
+)]
+pub struct AnnotationsNeeded {
+    pub name: String,
+    #[label("type of `{name}` unknown")]
+    pub span: SourceSpan,
+}
+
+/// Checks the types in `module`, using the names bound by `resolution`, with integer literals defaulting to `i32`.
+pub fn check<'t>(
+    module: &Module<'t>,
+    resolution: &Resolution<'t>,
+) -> (Typing, Vec<miette::Report>) {
+    check_with(module, resolution, &Type::DEFAULT_INTEGER)
+}
+
+/// Checks the types in `module`, giving integer literals which aren't constrained to any type `default_integer`.
+pub fn check_with<'t>(
+    module: &Module<'t>,
+    resolution: &Resolution<'t>,
+    default_integer: &Type,
+) -> (Typing, Vec<miette::Report>) {
+    let mut checker = Checker {
+        resolution,
+        unifier: Unifier::new(),
+        typing: Typing::default(),
+        inferred: Vec::new(),
+        deferred: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+
+    checker.stmts(&module.stmts, None, None, module_end(module));
+    checker.finish(default_integer);
+
+    (checker.typing, checker.diagnostics)
+}
+
+/// The type an expression is expected to have, and where the expectation comes from.
+#[derive(Clone, Copy)]
+struct Expected<'e> {
+    ty: &'e Type,
+    because: Option<SourceSpan>,
+}
+
+/// An operator applied to operands whose types weren't known yet, to be checked once they've been inferred.
+enum Deferred {
+    Unary {
+        op: UnaryOp,
+        operand: Type,
+        span: SourceSpan,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Type,
+        right: Type,
+        spans: (SourceSpan, SourceSpan),
+    },
+}
+
+struct Checker<'r, 't> {
+    resolution: &'r Resolution<'t>,
+    unifier: Unifier,
+    typing: Typing,
+    /// Variables declared without a type, which need one inferred
+    inferred: Vec<(SymbolId, &'t str, SourceSpan)>,
+    deferred: Vec<Deferred>,
+    diagnostics: Vec<miette::Report>,
+}
+
+impl<'r, 't> Checker<'r, 't> {
+    /// Unifies the type `expr` was found to have with the type it's expected to have, reporting a mismatch if they
+    /// can't be unified.
+    fn mismatch(&mut self, expected: Expected, found: &Type, span: SourceSpan) {
+        if self.unifier.unify(expected.ty, found).is_err() {
+            self.diagnostics.push(
+                TypeMismatch {
+                    expected: self.unifier.resolve(expected.ty),
+                    found: self.unifier.resolve(found),
+                    span,
+                    because: expected.because,
+                }
+                .into(),
+            );
+        }
+    }
+
+    /// Defaults the integer literals nothing constrained, checks the operators which were waiting for their operands'
+    /// types, and substitutes the inferred types into every symbol and expression.
+    fn finish(&mut self, default_integer: &Type) {
+        self.unifier.default_integers(default_integer);
+
+        for deferred in std::mem::take(&mut self.deferred) {
+            match deferred {
+                Deferred::Unary { op, operand, span } => {
+                    let operand = self.unifier.resolve(&operand);
+                    if !operand.has_vars() && unary_type(op, &operand).is_none() {
+                        self.invalid_operand(op, operand, span);
+                    }
+                }
+                Deferred::Binary {
+                    op,
+                    left,
+                    right,
+                    spans,
+                } => {
+                    let left = self.unifier.resolve(&left);
+                    let right = self.unifier.resolve(&right);
+                    if !left.has_vars() && !right.has_vars() && !operands_valid(op, &left, &right) {
+                        self.invalid_operands(op, Conflict { left, right }, spans);
+                    }
+                }
+            }
+        }
+
+        for &(id, name, span) in &self.inferred {
+            let ty = self.typing.symbol(id).map(|ty| self.unifier.resolve(ty));
+            if ty.is_some_and(|ty| ty.has_vars()) {
+                self.diagnostics.push(
+                    AnnotationsNeeded {
+                        name: name.to_owned(),
+                        span,
+                    }
+                    .into(),
+                );
+            }
+        }
+
+        // Anything still not inferred has already been reported
+        let unifier = &self.unifier;
+        for ty in self
+            .typing
+            .symbols
+            .values_mut()
+            .chain(self.typing.exprs.values_mut())
+        {
+            *ty = ground(&unifier.resolve(ty));
+        }
+    }
+
+    /// The type named by an annotation.
+    fn resolve_type(&mut self, ty: &TypeExpr<'t>) -> Type {
+        match &ty.kind {
+            TypeKind::Named(name) => match Type::builtin(name) {
+                Some(builtin) => builtin,
+                None => {
+                    self.unknown_type(name, ty.span);
+                    Type::Error
+                }
+            },
+            // There are no generic types yet
+            TypeKind::Generic(..) => {
+                self.unknown_type(&ty.to_string(), ty.span);
+                Type::Error
+            }
+            TypeKind::Tuple(elements) => Type::Tuple(
+                elements
+                    .iter()
+                    .map(|element| self.resolve_type(element))
+                    .collect(),
+            ),
+            TypeKind::Reference(inner) => Type::Reference(Box::new(self.resolve_type(inner))),
+            TypeKind::Function(params, return_type) => {
+                let params = params
+                    .iter()
+                    .map(|param| self.resolve_type(param))
+                    .collect();
+                Type::Function(params, Box::new(self.resolve_type(return_type)))
+            }
+        }
+    }
+
+    /// Reports `name` as an unknown type, suggesting the closest built-in type if there's one close enough.
+    fn unknown_type(&mut self, name: &str, span: SourceSpan) {
+        let threshold = (name.chars().count() / 3).max(1);
+        let similar = BUILTINS
+            .iter()
+            .map(|builtin| (edit_distance(name, builtin), *builtin))
+            .filter(|&(distance, builtin)| {
+                distance <= threshold && distance < name.len().max(builtin.len())
+            })
+            .min();
+
+        let report = match similar {
+            Some((_, builtin)) => Suggested {
+                code: "E0013",
+                message: format!("Cannot find type `{name}`"),
+                labels: Vec::new(),
+                suggestion: Suggestion::replace(
+                    span,
+                    builtin,
+                    format!("a built-in type with a similar name exists: `{builtin}`"),
+                )
+                .with_applicability(Applicability::MaybeIncorrect),
+            }
+            .into(),
+            None => UnknownType {
+                name: name.to_owned(),
+                span,
+            }
+            .into(),
+        };
+        self.diagnostics.push(report);
+    }
+
+    /// The type of the function declared by `function`, with parameters and return type in declaration order.
+    fn signature(&mut self, function: &Function<'t>) -> Type {
+        let params = function
+            .params
+            .iter()
+            .map(|param| self.resolve_type(&param.ty))
+            .collect();
+        let return_type = match &function.return_type {
+            Some(return_type) => self.resolve_type(return_type),
+            None => Type::unit(),
+        };
+
+        Type::Function(params, Box::new(return_type))
+    }
+
+    /// Checks a sequence of statements sharing a scope, returning the value of the sequence.
+    ///
+    /// `end` is the span of the end of the sequence, where a missing value is reported.
+    fn stmts(
+        &mut self,
+        stmts: &[Stmt<'t>],
+        tail: Option<&Expr<'t>>,
+        expected: Option<Expected>,
+        end: SourceSpan,
+    ) -> Type {
+        // Functions can be called before they're declared, so their types are needed first
+        for stmt in stmts {
+            if let StmtKind::Item(item) = &stmt.kind {
+                let ItemKind::Function(function) = &item.kind;
+                if let Some(id) = self.resolution.declaration(function.name.span) {
+                    let signature = self.signature(function);
+                    self.typing.symbols.insert(id, signature);
+                }
+            }
+        }
+
+        let (last, rest) = match (tail, stmts.split_last()) {
+            (
+                None,
+                Some((
+                    last @ Stmt {
+                        kind: StmtKind::If(_),
+                        ..
+                    },
+                    rest,
+                )),
+            ) => (Some(last), rest),
+            _ => (None, stmts),
+        };
+        for stmt in rest {
+            self.stmt(stmt);
+        }
+
+        if let Some(tail) = tail {
+            return match expected {
+                Some(expected) => self.expect(tail, expected),
+                None => self.expr(tail),
+            };
+        }
+        if let Some(Stmt {
+            kind: StmtKind::If(if_stmt),
+            span,
+        }) = last
+        {
+            return self.if_value(if_stmt, *span, expected);
+        }
+
+        let unit = Type::unit();
+        if let Some(expected) = expected {
+            self.mismatch(expected, &unit, end);
+        }
+        unit
+    }
+
+    fn stmt(&mut self, stmt: &Stmt<'t>) {
+        match &stmt.kind {
+            StmtKind::Item(item) => match &item.kind {
+                ItemKind::Function(function) => self.function(function),
+            },
+            StmtKind::Variable { name, ty, value } => {
+                self.variable(name, ty.as_ref(), value.as_ref());
+            }
+            StmtKind::Const { name, ty, value } => self.variable(name, ty.as_ref(), Some(value)),
+            StmtKind::Expr(expr) => {
+                self.expr(expr);
+            }
+            StmtKind::Block(block) => {
+                self.block(block, None);
+            }
+            StmtKind::If(if_stmt) => {
+                self.if_value(if_stmt, stmt.span, None);
+            }
+            StmtKind::While { condition, body } => {
+                self.condition(condition);
+                self.block(body, None);
+            }
+            StmtKind::Error => {}
+        }
+    }
+
+    /// Checks a variable or constant declaration, inferring its type from its value when it isn't given one.
+    fn variable(&mut self, name: &Ident<'t>, ty: Option<&TypeExpr<'t>>, value: Option<&Expr<'t>>) {
+        let ty = match ty {
+            Some(annotation) => {
+                let ty = self.resolve_type(annotation);
+                if let Some(value) = value {
+                    let expected = Expected {
+                        ty: &ty,
+                        because: Some(annotation.span),
+                    };
+                    self.expect(value, expected);
+                }
+                ty
+            }
+            None => match value {
+                Some(value) => self.expr(value),
+                None => self.unifier.fresh(),
+            },
+        };
+
+        if let Some(id) = self.resolution.declaration(name.span) {
+            if ty.has_vars() {
+                self.inferred.push((id, name.name, name.span));
+            }
+            self.typing.symbols.insert(id, ty);
+        }
+    }
+
+    fn function(&mut self, function: &Function<'t>) {
+        let id = self.resolution.declaration(function.name.span);
+        let signature = match id.and_then(|id| self.typing.symbol(id)) {
+            Some(signature) => signature.clone(),
+            // A duplicate function has no symbol, but its body is still checked
+            None => self.signature(function),
+        };
+        let Type::Function(params, return_type) = signature else {
+            unreachable!("functions have function types");
+        };
+
+        for (param, ty) in function.params.iter().zip(params) {
+            if let Some(id) = self.resolution.declaration(param.name.span) {
+                self.typing.symbols.insert(id, ty);
+            }
+        }
+
+        let expected = Expected {
+            ty: &return_type,
+            because: function.return_type.as_ref().map(|ty| ty.span),
+        };
+        self.block(&function.body, Some(expected));
+    }
+
+    fn block(&mut self, block: &Block<'t>, expected: Option<Expected>) -> Type {
+        // The closing `}`
+        let end = SourceSpan::from((block.span.offset() + block.span.len().max(1) - 1, 1));
+        self.stmts(&block.stmts, block.tail.as_deref(), expected, end)
+    }
+
+    /// Checks an `if`, returning its value when every branch has one.
+    fn if_value(&mut self, if_stmt: &If<'t>, span: SourceSpan, expected: Option<Expected>) -> Type {
+        self.condition(&if_stmt.condition);
+        let then_type = self.block(&if_stmt.then_block, expected);
+
+        let else_type = match if_stmt.else_branch.as_deref() {
+            Some(Stmt {
+                kind: StmtKind::Block(block),
+                ..
+            }) => self.block(block, expected),
+            Some(Stmt {
+                kind: StmtKind::If(else_if),
+                span,
+            }) => self.if_value(else_if, *span, expected),
+            Some(else_branch) => {
+                self.stmt(else_branch);
+                Type::unit()
+            }
+            None => {
+                let unit = Type::unit();
+                if let Some(expected) = expected {
+                    self.mismatch(expected, &unit, span);
+                }
+                unit
+            }
+        };
+
+        match expected {
+            Some(expected) => expected.ty.clone(),
+            // Without an expected type, the branches only give the `if` a value if they already agree
+            None if self.unifier.resolve(&then_type) == self.unifier.resolve(&else_type) => {
+                then_type
+            }
+            None => Type::unit(),
+        }
+    }
+
+    fn condition(&mut self, condition: &Expr<'t>) {
+        self.expect(
+            condition,
+            Expected {
+                ty: &Type::Bool,
+                because: None,
+            },
+        );
+    }
+
+    /// Checks `expr` against the type it's expected to have, reporting a mismatch if it has another type.
+    fn expect(&mut self, expr: &Expr<'t>, expected: Expected) -> Type {
+        let found = self.expr(expr);
+        self.mismatch(expected, &found, expr.span);
+        found
+    }
+
+    /// Infers the type of `expr`, which may still contain variables.
+    fn expr(&mut self, expr: &Expr<'t>) -> Type {
+        let ty = match &expr.kind {
+            ExprKind::Identifier(_) => self
+                .resolution
+                .binding(expr)
+                .and_then(|id| self.typing.symbol(id))
+                .cloned()
+                .unwrap_or(Type::Error),
+            ExprKind::Integer(_) => self.unifier.fresh_integer(),
+            ExprKind::Boolean(_) => Type::Bool,
+            ExprKind::Unary(op, operand) => self.unary(*op, operand),
+            ExprKind::Binary(op, left, right) => self.binary(*op, left, right),
+            ExprKind::Call(callee, arguments) => self.call(callee, arguments),
+            ExprKind::Tuple(elements) => {
+                Type::Tuple(elements.iter().map(|element| self.expr(element)).collect())
+            }
+        };
+
+        self.typing
+            .exprs
+            .insert((expr.span.offset(), expr.span.len()), ty.clone());
+        ty
+    }
+
+    fn invalid_operand(&mut self, op: UnaryOp, operand_type: Type, span: SourceSpan) {
+        self.diagnostics.push(
+            InvalidOperand {
+                op: op.symbol(),
+                operand_type,
+                operand: span,
+            }
+            .into(),
+        );
+    }
+
+    fn invalid_operands(&mut self, op: BinaryOp, types: Conflict, spans: (SourceSpan, SourceSpan)) {
+        self.diagnostics.push(
+            InvalidOperands {
+                op,
+                left_type: types.left,
+                right_type: types.right,
+                left: spans.0,
+                right: spans.1,
+            }
+            .into(),
+        );
+    }
+
+    fn unary(&mut self, op: UnaryOp, operand: &Expr<'t>) -> Type {
+        let operand_type = self.expr(operand);
+
+        match op {
+            UnaryOp::Reference => {
+                // Anything referenced can be assigned through the reference
+                if let Some((kind, name, declaration)) = self.immutable(operand) {
+                    self.diagnostics.push(
+                        ImmutableReference {
+                            kind,
+                            name,
+                            span: operand.span,
+                            declaration,
+                        }
+                        .into(),
+                    );
+                }
+                Type::Reference(Box::new(operand_type))
+            }
+            UnaryOp::Deref => {
+                let inner = self.unifier.fresh();
+                let reference = Type::Reference(Box::new(inner.clone()));
+                if self.unifier.unify(&operand_type, &reference).is_err() {
+                    let operand_type = self.unifier.resolve(&operand_type);
+                    self.invalid_operand(op, operand_type, operand.span);
+                    return Type::Error;
+                }
+                inner
+            }
+            _ => {
+                let resolved = self.unifier.resolve(&operand_type);
+                if matches!(resolved, Type::Var(_)) {
+                    self.deferred.push(Deferred::Unary {
+                        op,
+                        operand: operand_type.clone(),
+                        span: operand.span,
+                    });
+                    return operand_type;
+                }
+
+                unary_type(op, &resolved).unwrap_or_else(|| {
+                    self.invalid_operand(op, resolved, operand.span);
+                    Type::Error
+                })
+            }
+        }
+    }
+
+    fn binary(&mut self, op: BinaryOp, left: &Expr<'t>, right: &Expr<'t>) -> Type {
+        if matches!(
+            op,
+            BinaryOp::Assign
+                | BinaryOp::AddAssign
+                | BinaryOp::SubtractAssign
+                | BinaryOp::MultiplyAssign
+        ) {
+            self.assignee(op, left);
+        }
+
+        let left_type = self.expr(left);
+        let right_type = self.expr(right);
+        let spans = (left.span, right.span);
+
+        let ty = match op {
+            BinaryOp::Add
+            | BinaryOp::Subtract
+            | BinaryOp::Multiply
+            | BinaryOp::Divide
+            | BinaryOp::Power
+            | BinaryOp::ShiftLeft
+            | BinaryOp::ShiftRight => left_type.clone(),
+            BinaryOp::Greater
+            | BinaryOp::Less
+            | BinaryOp::GreaterEqual
+            | BinaryOp::LessEqual
+            | BinaryOp::Equal
+            | BinaryOp::NotEqual => Type::Bool,
+            BinaryOp::Assign
+            | BinaryOp::AddAssign
+            | BinaryOp::SubtractAssign
+            | BinaryOp::MultiplyAssign => Type::unit(),
+        };
+
+        // Besides shifts, the operands have to have the same type
+        if !matches!(op, BinaryOp::ShiftLeft | BinaryOp::ShiftRight) {
+            if op == BinaryOp::Assign {
+                let expected = Expected {
+                    ty: &left_type,
+                    because: None,
+                };
+                self.mismatch(expected, &right_type, right.span);
+                return ty;
+            }
+            if let Err(conflict) = self.unifier.unify(&left_type, &right_type) {
+                self.invalid_operands(op, conflict, spans);
+                return if ty == left_type { Type::Error } else { ty };
+            }
+        }
+
+        let left_resolved = self.unifier.resolve(&left_type);
+        let right_resolved = self.unifier.resolve(&right_type);
+        if matches!(left_resolved, Type::Var(_)) || matches!(right_resolved, Type::Var(_)) {
+            self.deferred.push(Deferred::Binary {
+                op,
+                left: left_type,
+                right: right_type,
+                spans,
+            });
+            return ty;
+        }
+
+        if !operands_valid(op, &left_resolved, &right_resolved) {
+            let conflict = Conflict {
+                left: left_resolved,
+                right: right_resolved,
+            };
+            self.invalid_operands(op, conflict, spans);
+            return if ty == left_type { Type::Error } else { ty };
+        }
+        ty
+    }
+
+    /// Reports the left-hand side of an assignment if it isn't a place which can be written to, or names a symbol
+    /// which can't be changed.
+    fn assignee(&mut self, op: BinaryOp, left: &Expr<'t>) {
+        match &left.kind {
+            ExprKind::Identifier(_) => {
+                if let Some((kind, name, declaration)) = self.immutable(left) {
+                    self.diagnostics.push(
+                        ImmutableAssignment {
+                            kind,
+                            name,
+                            span: left.span,
+                            declaration,
+                        }
+                        .into(),
+                    );
+                }
+            }
+            ExprKind::Unary(UnaryOp::Deref, _) => {}
+            _ => self.diagnostics.push(
+                InvalidAssignee {
+                    op,
+                    span: left.span,
+                }
+                .into(),
+            ),
+        }
+    }
+
+    /// The kind, name and declaration of the symbol named by `expr`, if it can't be changed.
+    fn immutable(&self, expr: &Expr<'t>) -> Option<(&'static str, String, SourceSpan)> {
+        let symbol = self.resolution.symbol(expr)?;
+        let name = self.resolution.symbols.name(symbol.name);
+        (symbol.mutability == Mutability::Immutable)
+            .then(|| (symbol.kind.describe(), name.to_owned(), symbol.span))
+    }
+
+    fn call(&mut self, callee: &Expr<'t>, arguments: &[Expr<'t>]) -> Type {
+        let callee_type = self.expr(callee);
+        let callee_type = self.unifier.resolve(&callee_type);
+        let (params, return_type) = match callee_type {
+            Type::Function(params, return_type) => (params, *return_type),
+            // A function whose type isn't known yet takes whichever arguments it's given
+            Type::Var(_) => {
+                let params: Vec<Type> = arguments.iter().map(|_| self.unifier.fresh()).collect();
+                let return_type = self.unifier.fresh();
+                let function = Type::Function(params.clone(), Box::new(return_type.clone()));
+                self.unifier
+                    .unify(&callee_type, &function)
+                    .expect("an unbound variable unifies with anything");
+                (params, return_type)
+            }
+            _ => {
+                for argument in arguments {
+                    self.expr(argument);
+                }
+                if !callee_type.is_error() {
+                    self.diagnostics.push(
+                        NotCallable {
+                            ty: callee_type,
+                            span: callee.span,
+                        }
+                        .into(),
+                    );
+                }
+                return Type::Error;
+            }
+        };
+
+        // Point at the parameters of a named function, to show where each expectation comes from
+        let symbol = self.resolution.symbol(callee);
+        let declared = symbol.and_then(|symbol| match (&symbol.kind, &symbol.ty) {
+            (
+                SymbolKind::Function,
+                Some(TypeExpr {
+                    kind: TypeKind::Function(params, _),
+                    ..
+                }),
+            ) => Some(params.iter().map(|param| param.span).collect::<Vec<_>>()),
+            _ => None,
+        });
+
+        if params.len() != arguments.len() {
+            self.diagnostics.push(
+                ArgumentCount {
+                    expected: params.len(),
+                    found: arguments.len(),
+                    span: callee.span,
+                    declaration: symbol.map(|symbol| symbol.span),
+                }
+                .into(),
+            );
+        }
+        for (index, argument) in arguments.iter().enumerate() {
+            match params.get(index) {
+                Some(param) => {
+                    let because = declared.as_ref().map(|spans| spans[index]);
+                    self.expect(argument, Expected { ty: param, because });
+                }
+                None => {
+                    self.expr(argument);
+                }
+            }
+        }
+
+        return_type
+    }
+}
+
+/// The type of applying `op` to an operand of type `operand`, if it's defined for that type.
+fn unary_type(op: UnaryOp, operand: &Type) -> Option<Type> {
+    let valid = match op {
+        UnaryOp::Negate => matches!(
+            operand,
+            Type::Int { signed: true, .. } | Type::Float { .. } | Type::Integer(_) | Type::Error
+        ),
+        UnaryOp::Plus => operand.is_numeric() || operand.is_error(),
+        UnaryOp::Not => operand.is_integer() || matches!(operand, Type::Bool | Type::Error),
+        UnaryOp::Deref | UnaryOp::Reference => true,
+    };
+
+    valid.then(|| operand.clone())
+}
+
+/// Whether `op` is defined for operands of these types, which have already been unified unless `op` is a shift.
+fn operands_valid(op: BinaryOp, left: &Type, right: &Type) -> bool {
+    if left.is_error() || right.is_error() {
+        return true;
+    }
+
+    match op {
+        BinaryOp::ShiftLeft | BinaryOp::ShiftRight => left.is_integer() && right.is_integer(),
+        BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Assign => true,
+        _ => left.is_numeric(),
+    }
+}
+
+/// `ty` with every variable which is still unbound replaced by an error.
+fn ground(ty: &Type) -> Type {
+    match ty {
+        Type::Var(_) | Type::Integer(_) => Type::Error,
+        Type::Tuple(elements) => Type::Tuple(elements.iter().map(ground).collect()),
+        Type::Reference(inner) => Type::Reference(Box::new(ground(inner))),
+        Type::Function(params, return_type) => Type::Function(
+            params.iter().map(ground).collect(),
+            Box::new(ground(return_type)),
+        ),
+        _ => ty.clone(),
+    }
+}
+
+/// The end of the last statement in `module`, which has no closing brace of its own.
+fn module_end(module: &Module) -> SourceSpan {
+    let end = module
+        .stmts
+        .last()
+        .map_or(0, |stmt| stmt.span.offset() + stmt.span.len());
+    SourceSpan::from((end, 0))
+}
@@ -0,0 +1,383 @@
+//! Compile time evaluation of constant expressions.
+//!
+//! An expression is constant when it's made only of literals, constants and operators, and its value is computed
+//! with the type the checker gave it, so that `200 + 100` overflows when it's a `u8` but not when it's an `i32`. Every
+//! constant sub-expression is folded, including those inside expressions which aren't constant themselves, like
+//! `2 * 3` in `a + 2 * 3`.
+//!
+//! [`Evaluator::evaluate`] can be used on its own wherever a value has to be known at compile time.
+
+use std::collections::HashMap;
+
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::ast::{BinaryOp, Block, Expr, ExprKind, If, ItemKind, Module, Stmt, StmtKind, UnaryOp};
+use crate::sema::check::Typing;
+use crate::sema::resolve::Resolution;
+use crate::sema::symbols::{SymbolId, SymbolKind};
+use crate::sema::types::Type;
+
+/// A value computed at compile time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    /// Wide enough to hold any value of any integer type
+    Int(i128),
+    Bool(bool),
+}
+
+/// The value of every constant and constant expression in a module.
+#[derive(Debug, Clone, Default)]
+pub struct Constants {
+    pub symbols: HashMap<SymbolId, Value>,
+    /// Keyed by the offset and length of each expression's span
+    pub exprs: HashMap<(usize, usize), Value>,
+}
+
+impl Constants {
+    pub fn symbol(&self, id: SymbolId) -> Option<Value> {
+        self.symbols.get(&id).copied()
+    }
+
+    pub fn expr(&self, expr: &Expr) -> Option<Value> {
+        self.exprs
+            .get(&(expr.span.offset(), expr.span.len()))
+            .copied()
+    }
+}
+
+/// An integer literal too large for its type, eg. `var a: u8 = 256;`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Literal out of range for `{ty}`")]
+#[diagnostic(code(E0026))]
+pub struct LiteralOutOfRange {
+    pub ty: Type,
+    #[label("doesn't fit in `{ty}`")]
+    pub span: SourceSpan,
+}
+
+/// Arithmetic on constants whose result doesn't fit in its type, eg. `200 + 100` as a `u8`
+#[derive(Error, Diagnostic, Debug)]
+#[error("Arithmetic overflow evaluating a constant expression")]
+#[diagnostic(code(E0019))]
+pub struct Overflow {
+    pub ty: Type,
+    #[label("overflows `{ty}`")]
+    pub span: SourceSpan,
+}
+
+/// A constant initialised with a value which can only be known at runtime
+#[derive(Error, Diagnostic, Debug)]
+#[error("The value of constant `{name}` isn't known at compile time")]
+#[diagnostic(
+    code(E0020),
+    help("constants can only use literals, other constants and operators")
+)]
+pub struct NotConstant {
+    pub name: String,
+    #[label("not a constant expression")]
+    pub span: SourceSpan,
+}
+
+/// Evaluates every constant and folds every constant expression in `module`.
+pub fn fold(
+    module: &Module,
+    resolution: &Resolution,
+    typing: &Typing,
+) -> (Constants, Vec<miette::Report>) {
+    let mut evaluator = Evaluator::new(resolution, typing);
+    evaluator.stmts(&module.stmts);
+    evaluator.finish()
+}
+
+pub struct Evaluator<'r, 't> {
+    resolution: &'r Resolution<'t>,
+    typing: &'r Typing,
+    constants: Constants,
+    diagnostics: Vec<miette::Report>,
+}
+
+impl<'r, 't> Evaluator<'r, 't> {
+    pub fn new(resolution: &'r Resolution<'t>, typing: &'r Typing) -> Self {
+        Evaluator {
+            resolution,
+            typing,
+            constants: Constants::default(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn finish(self) -> (Constants, Vec<miette::Report>) {
+        (self.constants, self.diagnostics)
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Item(item) => match &item.kind {
+                ItemKind::Function(function) => self.block(&function.body),
+            },
+            StmtKind::Variable { value, .. } => {
+                if let Some(value) = value {
+                    self.evaluate(value);
+                }
+            }
+            StmtKind::Const { name, value, .. } => {
+                let Some(id) = self.resolution.declaration(name.span) else {
+                    self.evaluate(value);
+                    return;
+                };
+
+                match self.evaluate(value) {
+                    Some(result) => {
+                        self.constants.symbols.insert(id, result);
+                    }
+                    None => {
+                        if let Some(span) = self.non_constant(value) {
+                            self.diagnostics.push(
+                                NotConstant {
+                                    name: name.name.to_owned(),
+                                    span,
+                                }
+                                .into(),
+                            );
+                        }
+                    }
+                }
+            }
+            StmtKind::Expr(expr) => {
+                self.evaluate(expr);
+            }
+            StmtKind::Block(block) => self.block(block),
+            StmtKind::If(if_stmt) => self.if_stmt(if_stmt),
+            StmtKind::While { condition, body } => {
+                self.evaluate(condition);
+                self.block(body);
+            }
+            StmtKind::Error => {}
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.stmts(&block.stmts);
+        if let Some(tail) = &block.tail {
+            self.evaluate(tail);
+        }
+    }
+
+    fn if_stmt(&mut self, if_stmt: &If) {
+        self.evaluate(&if_stmt.condition);
+        self.block(&if_stmt.then_block);
+        if let Some(else_branch) = &if_stmt.else_branch {
+            self.stmt(else_branch);
+        }
+    }
+
+    /// The value of `expr`, if it's a constant expression, reporting any overflow while evaluating it.
+    ///
+    /// Every constant sub-expression of `expr` is folded, even when `expr` isn't constant.
+    pub fn evaluate(&mut self, expr: &Expr) -> Option<Value> {
+        let value = self.value(expr);
+        if let Some(value) = value {
+            self.constants
+                .exprs
+                .insert((expr.span.offset(), expr.span.len()), value);
+        }
+        value
+    }
+
+    fn value(&mut self, expr: &Expr) -> Option<Value> {
+        let ty = self.typing.expr(expr).cloned().unwrap_or(Type::Error);
+
+        match &expr.kind {
+            ExprKind::Integer(literal) => self.literal(literal, false, &ty, expr.span),
+            ExprKind::Boolean(value) => Some(Value::Bool(*value)),
+            ExprKind::Identifier(_) => {
+                let id = self.resolution.binding(expr)?;
+                if self.resolution.symbols.symbol(id).kind != SymbolKind::Constant {
+                    return None;
+                }
+                self.constants.symbol(id)
+            }
+
+            ExprKind::Unary(op, operand) => {
+                // `-128` is in range for an `i8`, even though `128` isn't
+                if let (UnaryOp::Negate, ExprKind::Integer(literal)) = (op, &operand.kind) {
+                    return self.literal(literal, true, &ty, expr.span);
+                }
+
+                let operand = self.evaluate(operand)?;
+                let result = match (op, operand) {
+                    (UnaryOp::Negate, Value::Int(value)) => value.checked_neg(),
+                    (UnaryOp::Plus, Value::Int(value)) => Some(value),
+                    (UnaryOp::Not, Value::Bool(value)) => return Some(Value::Bool(!value)),
+                    // Flips every bit within the width of the type, which is `-x - 1` when signed
+                    (UnaryOp::Not, Value::Int(value)) => match ty {
+                        Type::Int { signed: false, .. } => {
+                            ty.range().map(|range| range.end() - value)
+                        }
+                        _ => Some(-value - 1),
+                    },
+                    _ => return None,
+                };
+                self.int(result, &ty, expr.span)
+            }
+            ExprKind::Binary(op, left, right) => {
+                // Both sides are folded, even if one isn't constant
+                let left = self.evaluate(left);
+                let right = self.evaluate(right);
+                self.binary(*op, left?, right?, &ty, expr.span)
+            }
+            ExprKind::Call(callee, arguments) => {
+                self.evaluate(callee);
+                for argument in arguments {
+                    self.evaluate(argument);
+                }
+                None
+            }
+            ExprKind::Tuple(elements) => {
+                for element in elements {
+                    self.evaluate(element);
+                }
+                None
+            }
+        }
+    }
+
+    fn binary(
+        &mut self,
+        op: BinaryOp,
+        left: Value,
+        right: Value,
+        ty: &Type,
+        span: SourceSpan,
+    ) -> Option<Value> {
+        let (Value::Int(l), Value::Int(r)) = (left, right) else {
+            return match op {
+                BinaryOp::Equal => Some(Value::Bool(left == right)),
+                BinaryOp::NotEqual => Some(Value::Bool(left != right)),
+                _ => None,
+            };
+        };
+
+        let result = match op {
+            BinaryOp::Add => l.checked_add(r),
+            BinaryOp::Subtract => l.checked_sub(r),
+            BinaryOp::Multiply => l.checked_mul(r),
+            // Dividing by zero is left to be reported at runtime
+            BinaryOp::Divide if r == 0 => return None,
+            BinaryOp::Divide => l.checked_div(r),
+            // A negative power of an integer isn't an integer, so is left to be reported at runtime
+            BinaryOp::Power if r < 0 => return None,
+            BinaryOp::Power => u32::try_from(r).ok().and_then(|r| l.checked_pow(r)),
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+                let bits = match ty {
+                    Type::Int { bits, .. } => i128::from(*bits),
+                    _ => return None,
+                };
+                if !(0..bits).contains(&r) {
+                    None
+                } else if op == BinaryOp::ShiftLeft {
+                    l.checked_mul(1 << r)
+                } else {
+                    Some(l >> r)
+                }
+            }
+            BinaryOp::Greater => return Some(Value::Bool(l > r)),
+            BinaryOp::Less => return Some(Value::Bool(l < r)),
+            BinaryOp::GreaterEqual => return Some(Value::Bool(l >= r)),
+            BinaryOp::LessEqual => return Some(Value::Bool(l <= r)),
+            BinaryOp::Equal => return Some(Value::Bool(l == r)),
+            BinaryOp::NotEqual => return Some(Value::Bool(l != r)),
+            // Assignments have an effect, so aren't constant
+            BinaryOp::Assign
+            | BinaryOp::AddAssign
+            | BinaryOp::SubtractAssign
+            | BinaryOp::MultiplyAssign => return None,
+        };
+
+        self.int(result, ty, span)
+    }
+
+    /// Parses an integer literal, reporting it if it doesn't fit in `ty`.
+    fn literal(
+        &mut self,
+        literal: &str,
+        negative: bool,
+        ty: &Type,
+        span: SourceSpan,
+    ) -> Option<Value> {
+        if !ty.is_integer() {
+            return None;
+        }
+
+        let value = literal
+            .parse::<i128>()
+            .ok()
+            .map(|value| if negative { -value } else { value });
+        match value {
+            Some(value) if ty.fits(value) => Some(Value::Int(value)),
+            _ => {
+                self.diagnostics.push(
+                    LiteralOutOfRange {
+                        ty: ty.clone(),
+                        span,
+                    }
+                    .into(),
+                );
+                None
+            }
+        }
+    }
+
+    /// The result of integer arithmetic, reporting an overflow if it's `None` or doesn't fit in `ty`.
+    fn int(&mut self, result: Option<i128>, ty: &Type, span: SourceSpan) -> Option<Value> {
+        if !ty.is_integer() {
+            return None;
+        }
+
+        match result {
+            Some(value) if ty.fits(value) => Some(Value::Int(value)),
+            _ => {
+                self.diagnostics.push(
+                    Overflow {
+                        ty: ty.clone(),
+                        span,
+                    }
+                    .into(),
+                );
+                None
+            }
+        }
+    }
+
+    /// The first part of `expr` which stops it being constant, unless that's an error which has been reported.
+    fn non_constant(&self, expr: &Expr) -> Option<SourceSpan> {
+        let children: Vec<&Expr> = match &expr.kind {
+            ExprKind::Integer(_) | ExprKind::Boolean(_) => return None,
+            ExprKind::Identifier(_) => {
+                let id = self.resolution.binding(expr)?;
+                let symbol = self.resolution.symbols.symbol(id);
+                return (symbol.kind != SymbolKind::Constant).then_some(expr.span);
+            }
+            ExprKind::Unary(_, operand) => vec![operand],
+            ExprKind::Binary(op, left, right) => match op {
+                BinaryOp::Assign
+                | BinaryOp::AddAssign
+                | BinaryOp::SubtractAssign
+                | BinaryOp::MultiplyAssign => return Some(expr.span),
+                _ => vec![left, right],
+            },
+            ExprKind::Call(..) | ExprKind::Tuple(_) => return Some(expr.span),
+        };
+
+        children
+            .into_iter()
+            .find_map(|child| self.non_constant(child))
+    }
+}
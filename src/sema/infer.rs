@@ -0,0 +1,147 @@
+//! Type inference by unification.
+//!
+//! Every type still to be inferred is a variable, which is bound to a type the first time it's unified with one.
+//! Variables given to integer literals can only be bound to integer types, and those left unbound once everything
+//! has been checked default to a configured integer type.
+
+use crate::sema::types::Type;
+
+/// Two types which can't be unified, as far as they were resolved when unification failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub left: Type,
+    pub right: Type,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Unifier {
+    /// The type each variable is bound to, indexed by variable
+    bindings: Vec<Option<Type>>,
+    /// The variables which can only be bound to integer types
+    integers: Vec<u32>,
+}
+
+impl Unifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A new variable, which can be bound to any type.
+    pub fn fresh(&mut self) -> Type {
+        Type::Var(self.next())
+    }
+
+    /// A new variable, which can only be bound to an integer type.
+    pub fn fresh_integer(&mut self) -> Type {
+        let var = self.next();
+        self.integers.push(var);
+        Type::Integer(var)
+    }
+
+    fn next(&mut self) -> u32 {
+        self.bindings.push(None);
+        (self.bindings.len() - 1) as u32
+    }
+
+    /// Substitutes every bound variable in `ty`, leaving only those which are still unbound.
+    pub fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(var) | Type::Integer(var) => match &self.bindings[*var as usize] {
+                Some(bound) => self.resolve(bound),
+                None => ty.clone(),
+            },
+            Type::Tuple(elements) => Type::Tuple(
+                elements
+                    .iter()
+                    .map(|element| self.resolve(element))
+                    .collect(),
+            ),
+            Type::Reference(inner) => Type::Reference(Box::new(self.resolve(inner))),
+            Type::Function(params, return_type) => Type::Function(
+                params.iter().map(|param| self.resolve(param)).collect(),
+                Box::new(self.resolve(return_type)),
+            ),
+            _ => ty.clone(),
+        }
+    }
+
+    /// Binds variables in `left` and `right` so that they're the same type.
+    ///
+    /// An error unifies with anything, so that it isn't reported again.
+    pub fn unify(&mut self, left: &Type, right: &Type) -> Result<(), Conflict> {
+        let left = self.resolve(left);
+        let right = self.resolve(right);
+
+        match (&left, &right) {
+            _ if left == right => Ok(()),
+            (Type::Var(var), other) | (other, Type::Var(var)) => self.bind(*var, other),
+            (Type::Integer(var), other @ (Type::Integer(_) | Type::Int { .. } | Type::Error))
+            | (other @ (Type::Int { .. } | Type::Error), Type::Integer(var)) => {
+                self.bind(*var, other)
+            }
+            (Type::Error, _) | (_, Type::Error) => Ok(()),
+            (Type::Tuple(left_elements), Type::Tuple(right_elements))
+                if left_elements.len() == right_elements.len() =>
+            {
+                for (l, r) in left_elements.iter().zip(right_elements) {
+                    self.unify(l, r).map_err(|_| self.conflict(&left, &right))?;
+                }
+                Ok(())
+            }
+            (Type::Reference(l), Type::Reference(r)) => {
+                self.unify(l, r).map_err(|_| self.conflict(&left, &right))
+            }
+            (
+                Type::Function(left_params, left_return),
+                Type::Function(right_params, right_return),
+            ) if left_params.len() == right_params.len() => {
+                for (l, r) in left_params.iter().zip(right_params) {
+                    self.unify(l, r).map_err(|_| self.conflict(&left, &right))?;
+                }
+                self.unify(left_return, right_return)
+                    .map_err(|_| self.conflict(&left, &right))
+            }
+            _ => Err(Conflict { left, right }),
+        }
+    }
+
+    fn conflict(&self, left: &Type, right: &Type) -> Conflict {
+        Conflict {
+            left: self.resolve(left),
+            right: self.resolve(right),
+        }
+    }
+
+    fn bind(&mut self, var: u32, ty: &Type) -> Result<(), Conflict> {
+        // A variable can't be bound to a type containing itself, which would be infinitely large
+        if self.occurs(var, ty) {
+            return Err(Conflict {
+                left: Type::Var(var),
+                right: ty.clone(),
+            });
+        }
+        self.bindings[var as usize] = Some(ty.clone());
+        Ok(())
+    }
+
+    fn occurs(&self, var: u32, ty: &Type) -> bool {
+        match ty {
+            Type::Var(other) | Type::Integer(other) => *other == var,
+            Type::Tuple(elements) => elements.iter().any(|element| self.occurs(var, element)),
+            Type::Reference(inner) => self.occurs(var, inner),
+            Type::Function(params, return_type) => {
+                params.iter().any(|param| self.occurs(var, param)) || self.occurs(var, return_type)
+            }
+            _ => false,
+        }
+    }
+
+    /// Binds every integer variable which is still unbound to `integer`.
+    pub fn default_integers(&mut self, integer: &Type) {
+        for &var in &self.integers {
+            if self.bindings[var as usize].is_none() {
+                self.bindings[var as usize] = Some(integer.clone());
+            }
+        }
+    }
+}
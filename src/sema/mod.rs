@@ -0,0 +1,8 @@
+//! Semantic analysis of the syntax tree, checking the meaning of a program once it's known to be well formed.
+
+pub mod check;
+pub mod consteval;
+pub mod infer;
+pub mod resolve;
+pub mod symbols;
+pub mod types;
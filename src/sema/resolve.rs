@@ -0,0 +1,394 @@
+//! Name resolution, binding each use of a name to the declaration it refers to.
+//!
+//! Functions and blocks each introduce a scope, nested inside of the scope they're written in. Variables are visible
+//! from the statement after their declaration, and functions from anywhere in the scope they're declared in, so they
+//! can be called before they're declared, and call themselves.
+//!
+//! A function declared inside of another can call the functions it can see, and use the variables declared at the top
+//! of the module, but not the variables, parameters or constants of the function it's declared in.
+
+use std::collections::HashMap;
+
+use miette::{Diagnostic, LabeledSpan, SourceSpan};
+use thiserror::Error;
+
+use crate::ast::{
+    Block, Expr, ExprKind, Function, If, ItemKind, Module, Stmt, StmtKind, TypeExpr, TypeKind,
+};
+use crate::sema::symbols::{
+    DuplicateDeclaration, Mutability, ScopeId, Symbol, SymbolId, SymbolKind, SymbolTable,
+};
+use crate::suggestion::{Applicability, Suggested, Suggestion};
+
+/// Every symbol in a module, and the symbol each use of a name refers to.
+#[derive(Debug, Clone, Default)]
+pub struct Resolution<'t> {
+    pub symbols: SymbolTable<'t>,
+    /// Keyed by the offset of each name used, which is that of its identifier rather than of the expression, as
+    /// brackets around a name widen the expression's span
+    pub bindings: HashMap<usize, SymbolId>,
+    /// The symbol each declaration introduced, keyed by the offset of the declared name
+    pub declarations: HashMap<usize, SymbolId>,
+}
+
+impl<'t> Resolution<'t> {
+    /// The symbol `expr` refers to, if it's a name which was resolved.
+    pub fn symbol(&self, expr: &Expr) -> Option<&Symbol<'t>> {
+        self.binding(expr).map(|id| self.symbols.symbol(id))
+    }
+
+    pub fn binding(&self, expr: &Expr) -> Option<SymbolId> {
+        match expr.kind {
+            ExprKind::Identifier(ident) => self.bindings.get(&ident.span.offset()).copied(),
+            _ => None,
+        }
+    }
+
+    /// The symbol declared by the name at `span`, unless it was a duplicate declaration.
+    pub fn declaration(&self, span: SourceSpan) -> Option<SymbolId> {
+        self.declarations.get(&span.offset()).copied()
+    }
+}
+
+/// A name which isn't declared in any scope it's used in, and has no similar name to suggest instead
+#[derive(Error, Diagnostic, Debug)]
+#[error("Cannot find `{name}` in this scope")]
+#[diagnostic(code(E0011))]
+pub struct UndefinedName {
+    pub name: String,
+    #[label("not found in this scope")]
+    pub span: SourceSpan,
+}
+
+/// A function using a variable of the function it's declared in, which it has no way to reach once compiled
+#[derive(Error, Diagnostic, Debug)]
+#[error("Functions can't use `{name}` from the function they're declared in")]
+#[diagnostic(
+    code(E0025),
+    help("pass `{name}` to the function as an argument instead")
+)]
+pub struct CapturedVariable {
+    pub name: String,
+    #[label(primary, "used in a nested function")]
+    pub span: SourceSpan,
+    #[label("declared in an enclosing function")]
+    pub declaration: SourceSpan,
+}
+
+/// Resolves every name used in `module`, returning a diagnostic for each name which isn't declared, or which belongs
+/// to an enclosing function.
+pub fn resolve<'t>(module: &Module<'t>) -> (Resolution<'t>, Vec<miette::Report>) {
+    let mut resolver = Resolver {
+        resolution: Resolution::default(),
+        diagnostics: Vec::new(),
+        function: None,
+        functions: vec![None],
+    };
+
+    resolver.stmts(&module.stmts, None);
+
+    (resolver.resolution, resolver.diagnostics)
+}
+
+struct Resolver<'t> {
+    resolution: Resolution<'t>,
+    diagnostics: Vec<miette::Report>,
+    /// The scope of the innermost function being resolved, or `None` at the top of the module
+    function: Option<ScopeId>,
+    /// The function each scope is in, indexed by scope
+    functions: Vec<Option<ScopeId>>,
+}
+
+impl<'t> Resolver<'t> {
+    /// Runs `inner` in a new scope, nested inside of the current scope.
+    fn scoped(&mut self, inner: impl FnOnce(&mut Self)) {
+        self.resolution.symbols.enter();
+        self.functions.push(self.function);
+        inner(self);
+        self.resolution.symbols.exit();
+    }
+
+    fn declare(
+        &mut self,
+        name: &str,
+        kind: SymbolKind,
+        span: SourceSpan,
+        mutability: Mutability,
+        ty: Option<TypeExpr<'t>>,
+    ) {
+        let symbols = &mut self.resolution.symbols;
+        match symbols.declare(name, kind, span, mutability, ty) {
+            Ok(id) => {
+                self.resolution.declarations.insert(span.offset(), id);
+            }
+            Err(first) => self.diagnostics.push(
+                DuplicateDeclaration {
+                    kind: kind.describe(),
+                    name: name.to_owned(),
+                    first: symbols.symbol(first).span,
+                    second: span,
+                }
+                .into(),
+            ),
+        }
+    }
+
+    /// Resolves a sequence of statements sharing the current scope, followed by an optional tail expression.
+    fn stmts(&mut self, stmts: &[Stmt<'t>], tail: Option<&Expr<'t>>) {
+        // Functions can be used anywhere in their scope
+        for stmt in stmts {
+            if let StmtKind::Item(item) = &stmt.kind {
+                let ItemKind::Function(function) = &item.kind;
+                self.declare(
+                    function.name.name,
+                    SymbolKind::Function,
+                    function.name.span,
+                    Mutability::Immutable,
+                    Some(signature(function, item.span)),
+                );
+            }
+        }
+
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+        if let Some(tail) = tail {
+            self.expr(tail);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt<'t>) {
+        match &stmt.kind {
+            StmtKind::Item(item) => match &item.kind {
+                ItemKind::Function(function) => self.function(function),
+            },
+            StmtKind::Variable { name, ty, value } => {
+                // The initialiser can't refer to the variable it initialises
+                if let Some(value) = value {
+                    self.expr(value);
+                }
+                self.declare(
+                    name.name,
+                    SymbolKind::Variable,
+                    name.span,
+                    Mutability::Mutable,
+                    ty.clone(),
+                );
+            }
+            StmtKind::Const { name, ty, value } => {
+                self.expr(value);
+                self.declare(
+                    name.name,
+                    SymbolKind::Constant,
+                    name.span,
+                    Mutability::Immutable,
+                    ty.clone(),
+                );
+            }
+            StmtKind::Expr(expr) => self.expr(expr),
+            StmtKind::Block(block) => self.block(block),
+            StmtKind::If(if_stmt) => self.if_stmt(if_stmt),
+            StmtKind::While { condition, body } => {
+                self.expr(condition);
+                self.block(body);
+            }
+            StmtKind::Error => {}
+        }
+    }
+
+    fn function(&mut self, function: &Function<'t>) {
+        let enclosing = self.function;
+        self.scoped(|resolver| {
+            let scope = resolver.resolution.symbols.current_scope();
+            resolver.function = Some(scope);
+            resolver.functions[scope] = Some(scope);
+
+            for param in &function.params {
+                resolver.declare(
+                    param.name.name,
+                    SymbolKind::Parameter,
+                    param.name.span,
+                    Mutability::Immutable,
+                    Some(param.ty.clone()),
+                );
+            }
+            resolver.block(&function.body);
+        });
+        self.function = enclosing;
+    }
+
+    fn if_stmt(&mut self, if_stmt: &If<'t>) {
+        self.expr(&if_stmt.condition);
+        self.block(&if_stmt.then_block);
+        if let Some(else_branch) = &if_stmt.else_branch {
+            self.stmt(else_branch);
+        }
+    }
+
+    fn block(&mut self, block: &Block<'t>) {
+        self.scoped(|resolver| resolver.stmts(&block.stmts, block.tail.as_deref()));
+    }
+
+    fn expr(&mut self, expr: &Expr<'t>) {
+        match &expr.kind {
+            ExprKind::Identifier(ident) => match self.resolution.symbols.lookup(ident.name) {
+                Some(id) => {
+                    self.resolution.bindings.insert(ident.span.offset(), id);
+                    self.captured(id, ident.span);
+                }
+                None => self.undefined(ident.name, ident.span),
+            },
+            ExprKind::Integer(_) | ExprKind::Boolean(_) => {}
+            ExprKind::Unary(_, operand) => self.expr(operand),
+            ExprKind::Binary(_, left, right) => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ExprKind::Call(callee, arguments) => {
+                self.expr(callee);
+                for argument in arguments {
+                    self.expr(argument);
+                }
+            }
+            ExprKind::Tuple(elements) => {
+                for element in elements {
+                    self.expr(element);
+                }
+            }
+        }
+    }
+
+    /// Reports the use at `span` of the symbol `id`, if it isn't a function and belongs to a function enclosing the
+    /// current one.
+    fn captured(&mut self, id: SymbolId, span: SourceSpan) {
+        let symbol = self.resolution.symbols.symbol(id);
+        let function = self.functions[symbol.scope];
+        if symbol.kind == SymbolKind::Function || function.is_none() || function == self.function {
+            return;
+        }
+
+        self.diagnostics.push(
+            CapturedVariable {
+                name: self.resolution.symbols.name(symbol.name).to_owned(),
+                span,
+                declaration: symbol.span,
+            }
+            .into(),
+        );
+    }
+
+    /// Reports `name` as undefined, suggesting the most similar name in scope if there's one close enough.
+    fn undefined(&mut self, name: &str, span: SourceSpan) {
+        let Some(similar) = self.similar(name) else {
+            self.diagnostics.push(
+                UndefinedName {
+                    name: name.to_owned(),
+                    span,
+                }
+                .into(),
+            );
+            return;
+        };
+
+        let symbols = &self.resolution.symbols;
+        let symbol = symbols.symbol(similar);
+        let similar = symbols.name(symbol.name);
+        let kind = symbol.kind.describe();
+        self.diagnostics.push(
+            Suggested {
+                code: "E0011",
+                message: format!("Cannot find `{name}` in this scope"),
+                labels: vec![LabeledSpan::new_with_span(
+                    Some(format!("similarly named {kind} `{similar}` declared here")),
+                    symbol.span,
+                )],
+                suggestion: Suggestion::replace(
+                    span,
+                    similar,
+                    format!("a {kind} with a similar name exists: `{similar}`"),
+                )
+                .with_applicability(Applicability::MaybeIncorrect),
+            }
+            .into(),
+        );
+    }
+
+    /// The visible name closest to `name`, if it's within a third of its length in edits.
+    ///
+    /// Ties are broken in favour of the earliest declaration.
+    fn similar(&self, name: &str) -> Option<SymbolId> {
+        let threshold = (name.chars().count() / 3).max(1);
+        let symbols = &self.resolution.symbols;
+
+        let mut best: Option<(usize, SymbolId)> = None;
+        for id in symbols.visible() {
+            let candidate = symbols.name(symbols.symbol(id).name);
+
+            // Replacing every character isn't a similar name, however short the names are
+            let distance = edit_distance(name, candidate);
+            if distance >= name.chars().count().max(candidate.chars().count()) {
+                continue;
+            }
+
+            let closer = match best {
+                Some((best_distance, best_id)) => (distance, id) < (best_distance, best_id),
+                None => true,
+            };
+            if distance <= threshold && closer {
+                best = Some((distance, id));
+            }
+        }
+
+        best.map(|(_, id)| id)
+    }
+}
+
+/// The type of `function`, as it would be written in a type annotation.
+fn signature<'t>(function: &Function<'t>, span: SourceSpan) -> TypeExpr<'t> {
+    let params = function
+        .params
+        .iter()
+        .map(|param| param.ty.clone())
+        .collect();
+    let return_type = function.return_type.clone().unwrap_or(TypeExpr {
+        kind: TypeKind::Tuple(Vec::new()),
+        span,
+    });
+
+    TypeExpr {
+        kind: TypeKind::Function(params, Box::new(return_type)),
+        span,
+    }
+}
+
+/// The edit distance between `a` and `b`, counting the insertions, deletions and substitutions of characters, and
+/// swaps of adjacent characters, needed to turn one into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // `distances[i][j]` is the distance between the first `i` characters of `a` and the first `j` of `b`
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = substitution
+                .min(distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
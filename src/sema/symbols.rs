@@ -0,0 +1,219 @@
+//! The symbol table, recording every declaration in a module along with the scope it was declared in.
+//!
+//! Names are interned, so that looking a name up compares integers rather than strings, and each scope only stores
+//! the symbols declared directly inside of it. A symbol declared in an inner scope shadows any symbol of the same name
+//! in its enclosing scopes.
+
+use std::collections::HashMap;
+
+use miette::{Diagnostic, SourceSpan};
+use string_interner::{DefaultBackend, DefaultSymbol, StringInterner};
+use thiserror::Error;
+
+use crate::ast::TypeExpr;
+
+/// An interned name.
+pub type Name = DefaultSymbol;
+
+/// Indexes [`SymbolTable::symbols`].
+pub type SymbolId = usize;
+
+/// Indexes the scopes of a [`SymbolTable`], where the root scope is `0`.
+pub type ScopeId = usize;
+
+/// What a name was declared as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Variable,
+    Constant,
+    Parameter,
+    Function,
+    Type,
+}
+
+impl SymbolKind {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            SymbolKind::Variable => "variable",
+            SymbolKind::Constant => "constant",
+            SymbolKind::Parameter => "parameter",
+            SymbolKind::Function => "function",
+            SymbolKind::Type => "type",
+        }
+    }
+}
+
+/// Whether a symbol can be assigned to, or referenced, which only variables can be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutability {
+    Mutable,
+    Immutable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol<'t> {
+    pub name: Name,
+    pub kind: SymbolKind,
+    /// The span of the name in its declaration
+    pub span: SourceSpan,
+    pub mutability: Mutability,
+    /// The type the symbol was declared with, if it was given one
+    pub ty: Option<TypeExpr<'t>>,
+    pub scope: ScopeId,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    parent: Option<ScopeId>,
+    symbols: HashMap<Name, SymbolId>,
+}
+
+/// A function or parameter declared twice in the same scope, eg. `fn f(a: i32, a: i32)`
+#[derive(Error, Diagnostic, Debug)]
+#[error("The {kind} `{name}` is declared more than once")]
+#[diagnostic(code(E0012))]
+pub struct DuplicateDeclaration {
+    pub kind: &'static str,
+    pub name: String,
+    #[label("`{name}` first declared here")]
+    pub first: SourceSpan,
+    #[label(primary, "`{name}` declared again here")]
+    pub second: SourceSpan,
+}
+
+/// Every symbol in a module, and the tree of scopes they were declared in.
+#[derive(Debug, Clone)]
+pub struct SymbolTable<'t> {
+    interner: StringInterner<DefaultBackend<Name>>,
+    symbols: Vec<Symbol<'t>>,
+    scopes: Vec<Scope>,
+    /// The scope new symbols are declared in
+    current: ScopeId,
+}
+
+impl Default for SymbolTable<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'t> SymbolTable<'t> {
+    /// Creates a table containing only the root scope.
+    pub fn new() -> Self {
+        SymbolTable {
+            interner: StringInterner::new(),
+            symbols: Vec::new(),
+            scopes: vec![Scope::default()],
+            current: 0,
+        }
+    }
+
+    pub fn intern(&mut self, name: &str) -> Name {
+        self.interner.get_or_intern(name)
+    }
+
+    /// The string `name` was interned from.
+    pub fn name(&self, name: Name) -> &str {
+        self.interner
+            .resolve(name)
+            .expect("name interned by another table")
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &Symbol<'t> {
+        &self.symbols[id]
+    }
+
+    pub fn symbols(&self) -> &[Symbol<'t>] {
+        &self.symbols
+    }
+
+    pub fn current_scope(&self) -> ScopeId {
+        self.current
+    }
+
+    /// Enters a new scope nested inside of the current scope.
+    pub fn enter(&mut self) -> ScopeId {
+        let scope = self.scopes.len();
+        self.scopes.push(Scope {
+            parent: Some(self.current),
+            symbols: HashMap::new(),
+        });
+        self.current = scope;
+        scope
+    }
+
+    /// Returns to the scope enclosing the current scope.
+    pub fn exit(&mut self) {
+        self.current = self.scopes[self.current]
+            .parent
+            .expect("exited the root scope");
+    }
+
+    /// Declares a symbol in the current scope, shadowing any symbol of the same name.
+    ///
+    /// Functions and parameters can't be declared twice in the same scope, and return the symbol they would have
+    /// shadowed as an `Err` instead.
+    pub fn declare(
+        &mut self,
+        name: &str,
+        kind: SymbolKind,
+        span: SourceSpan,
+        mutability: Mutability,
+        ty: Option<TypeExpr<'t>>,
+    ) -> Result<SymbolId, SymbolId> {
+        let name = self.intern(name);
+        let scope = &mut self.scopes[self.current];
+
+        if let Some(&previous) = scope.symbols.get(&name) {
+            let exclusive = matches!(kind, SymbolKind::Function | SymbolKind::Parameter);
+            if exclusive && self.symbols[previous].kind == kind {
+                return Err(previous);
+            }
+        }
+
+        let id = self.symbols.len();
+        scope.symbols.insert(name, id);
+        self.symbols.push(Symbol {
+            name,
+            kind,
+            span,
+            mutability,
+            ty,
+            scope: self.current,
+        });
+        Ok(id)
+    }
+
+    /// Finds the symbol `name` refers to from the current scope.
+    pub fn lookup(&self, name: &str) -> Option<SymbolId> {
+        let name = self.interner.get(name)?;
+        self.lookup_name(name)
+    }
+
+    pub fn lookup_name(&self, name: Name) -> Option<SymbolId> {
+        let mut scope = Some(self.current);
+        while let Some(id) = scope {
+            if let Some(&symbol) = self.scopes[id].symbols.get(&name) {
+                return Some(symbol);
+            }
+            scope = self.scopes[id].parent;
+        }
+        None
+    }
+
+    /// Every symbol which can be referred to from the current scope, excluding those which are shadowed.
+    pub fn visible(&self) -> impl Iterator<Item = SymbolId> + '_ {
+        let mut scopes = Vec::new();
+        let mut scope = Some(self.current);
+        while let Some(id) = scope {
+            scopes.push(id);
+            scope = self.scopes[id].parent;
+        }
+
+        scopes
+            .into_iter()
+            .flat_map(|scope| self.scopes[scope].symbols.iter())
+            .filter(|&(&name, &id)| self.lookup_name(name) == Some(id))
+            .map(|(_, &id)| id)
+    }
+}
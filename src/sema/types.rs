@@ -0,0 +1,160 @@
+//! The types values can have, and the built-in types which can be named in annotations.
+
+use std::fmt::{self, Display, Formatter};
+use std::ops::RangeInclusive;
+
+/// The names of the built-in types, as they're written in annotations.
+pub const BUILTINS: &[&str] = &[
+    "i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64", "f32", "f64", "bool", "string",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    /// A fixed width integer, eg. `i32` or `u8`
+    Int {
+        signed: bool,
+        bits: u8,
+    },
+    /// `f32` or `f64`
+    Float {
+        bits: u8,
+    },
+    Bool,
+    String,
+    /// The unit type `()` is the empty tuple
+    Tuple(Vec<Type>),
+    Reference(Box<Type>),
+    Function(Vec<Type>, Box<Type>),
+    /// A type still to be inferred, indexing the variables of a [`Unifier`](crate::sema::infer::Unifier)
+    Var(u32),
+    /// A type still to be inferred which can only be an integer type, given to integer literals
+    Integer(u32),
+    /// The type of an expression which already has an error, which is compatible with every type so that one mistake
+    /// isn't reported again by every expression using it
+    Error,
+}
+
+impl Type {
+    /// The type given to integer literals with nothing else to decide their type.
+    pub const DEFAULT_INTEGER: Type = Type::Int {
+        signed: true,
+        bits: 32,
+    };
+
+    pub fn unit() -> Self {
+        Type::Tuple(Vec::new())
+    }
+
+    /// The built-in type called `name`.
+    pub fn builtin(name: &str) -> Option<Self> {
+        let ty = match name {
+            "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" => Type::Int {
+                signed: name.starts_with('i'),
+                bits: name[1..].parse().unwrap(),
+            },
+            "f32" => Type::Float { bits: 32 },
+            "f64" => Type::Float { bits: 64 },
+            "bool" => Type::Bool,
+            "string" => Type::String,
+            _ => return None,
+        };
+
+        Some(ty)
+    }
+
+    pub fn is_unit(&self) -> bool {
+        matches!(self, Type::Tuple(elements) if elements.is_empty())
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Type::Int { .. } | Type::Integer(_))
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Type::Int { .. } | Type::Float { .. } | Type::Integer(_)
+        )
+    }
+
+    /// Whether any part of this type is still to be inferred.
+    pub fn has_vars(&self) -> bool {
+        match self {
+            Type::Var(_) | Type::Integer(_) => true,
+            Type::Tuple(elements) => elements.iter().any(Type::has_vars),
+            Type::Reference(inner) => inner.has_vars(),
+            Type::Function(params, return_type) => {
+                params.iter().any(Type::has_vars) || return_type.has_vars()
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether a value of this type holds a reference, directly or in a tuple.
+    pub fn has_references(&self) -> bool {
+        match self {
+            Type::Reference(_) => true,
+            Type::Tuple(elements) => elements.iter().any(Type::has_references),
+            _ => false,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Type::Error)
+    }
+
+    /// The smallest and largest values of an integer type.
+    pub fn range(&self) -> Option<RangeInclusive<i128>> {
+        match *self {
+            Type::Int { signed: true, bits } => Some(-(1 << (bits - 1))..=(1 << (bits - 1)) - 1),
+            Type::Int {
+                signed: false,
+                bits,
+            } => Some(0..=(1 << bits) - 1),
+            _ => None,
+        }
+    }
+
+    /// Whether `value` is in the range of this type, which is never the case unless it's an integer type.
+    pub fn fits(&self, value: i128) -> bool {
+        self.range().is_some_and(|range| range.contains(&value))
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Type::Int { signed, bits } => write!(f, "{}{}", if *signed { "i" } else { "u" }, bits),
+            Type::Float { bits } => write!(f, "f{}", bits),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::Tuple(elements) => {
+                write!(f, "(")?;
+                write_separated(f, elements)?;
+                if elements.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Type::Reference(inner) => write!(f, "&{}", inner),
+            Type::Function(params, return_type) => {
+                write!(f, "fn(")?;
+                write_separated(f, params)?;
+                write!(f, ") -> {}", return_type)
+            }
+            Type::Var(_) => write!(f, "_"),
+            Type::Integer(_) => write!(f, "{{integer}}"),
+            Type::Error => write!(f, "{{error}}"),
+        }
+    }
+}
+
+fn write_separated(f: &mut Formatter, types: &[Type]) -> fmt::Result {
+    for (index, ty) in types.iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", ty)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+//! Suggested fixes attached to diagnostics, which can be shown to the user or applied to the source.
+
+use std::fmt::{self, Display, Formatter};
+
+use miette::{Diagnostic, LabeledSpan, SourceSpan};
+use thiserror::Error;
+
+/// How confident a [`Suggestion`] is that it fixes the mistake it was made for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Applicability {
+    /// The suggestion is what the user meant, and can be applied without being reviewed
+    MachineApplicable,
+    /// The suggestion is likely what the user meant, but should be reviewed before being applied
+    MaybeIncorrect,
+}
+
+/// Replaces the source in `span` with `replacement`, which inserts when `span` is empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    /// Describes the change, eg. "add a `;` to end the statement"
+    pub message: String,
+    pub span: SourceSpan,
+    pub replacement: String,
+    pub applicability: Applicability,
+}
+
+impl Suggestion {
+    pub fn insert(offset: usize, text: &str, message: impl Into<String>) -> Self {
+        Suggestion {
+            message: message.into(),
+            span: SourceSpan::from((offset, 0)),
+            replacement: text.to_owned(),
+            applicability: Applicability::MachineApplicable,
+        }
+    }
+
+    pub fn replace(span: SourceSpan, text: &str, message: impl Into<String>) -> Self {
+        Suggestion {
+            message: message.into(),
+            span,
+            replacement: text.to_owned(),
+            applicability: Applicability::MachineApplicable,
+        }
+    }
+
+    pub fn with_applicability(mut self, applicability: Applicability) -> Self {
+        self.applicability = applicability;
+        self
+    }
+
+    /// The label shown at the span being changed, which is where the diagnostic is reported.
+    fn label(&self) -> LabeledSpan {
+        let text = if self.span.is_empty() {
+            format!("insert `{}`", self.replacement)
+        } else {
+            format!("replace with `{}`", self.replacement)
+        };
+
+        LabeledSpan::new_primary_with_span(Some(text), self.span)
+    }
+}
+
+/// A diagnostic with a [`Suggestion`] for fixing it.
+#[derive(Error, Debug)]
+pub struct Suggested {
+    /// The code registered in [`crate::codes`]
+    pub code: &'static str,
+    pub message: String,
+    pub labels: Vec<LabeledSpan>,
+    pub suggestion: Suggestion,
+}
+
+impl Display for Suggested {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Diagnostic for Suggested {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.code))
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let labels = self.labels.iter().cloned();
+        Some(Box::new(labels.chain(Some(self.suggestion.label()))))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(&self.suggestion.message))
+    }
+}
+
+/// Returns the suggestion attached to `report`, if it has one.
+pub fn suggestion(report: &miette::Report) -> Option<&Suggestion> {
+    report
+        .downcast_ref::<Suggested>()
+        .map(|suggested| &suggested.suggestion)
+}
+
+/// Applies every [`Applicability::MachineApplicable`] suggestion to `source`.
+///
+/// Suggestions are applied in source order, and any overlapping an earlier suggestion are skipped. Returns the fixed
+/// source, and the number of suggestions applied.
+pub fn apply<'s>(
+    source: &str,
+    suggestions: impl IntoIterator<Item = &'s Suggestion>,
+) -> (String, usize) {
+    let mut suggestions: Vec<&Suggestion> = suggestions
+        .into_iter()
+        .filter(|suggestion| suggestion.applicability == Applicability::MachineApplicable)
+        .collect();
+    suggestions.sort_by_key(|suggestion| suggestion.span.offset());
+
+    let mut fixed = String::with_capacity(source.len());
+    let mut applied = 0;
+    let mut end = 0;
+    for suggestion in suggestions {
+        let start = suggestion.span.offset();
+        if start < end || start + suggestion.span.len() > source.len() {
+            continue;
+        }
+
+        fixed.push_str(&source[end..start]);
+        fixed.push_str(&suggestion.replacement);
+        end = start + suggestion.span.len();
+        applied += 1;
+    }
+    fixed.push_str(&source[end..]);
+
+    (fixed, applied)
+}
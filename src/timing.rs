@@ -0,0 +1,130 @@
+//! Wall time spent in each pass of the compiler, reported by `--time-passes`.
+//!
+//! Times are given per line and per token so they can be compared against the budgets in the README, of 100 ns/line
+//! for lexing and parsing, and 1 µs/line for semantic analysis.
+
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+/// How long a single pass took.
+#[derive(Debug, Clone, Copy)]
+pub struct Pass {
+    pub name: &'static str,
+    pub duration: Duration,
+}
+
+/// The passes run over a single file, in the order they were run.
+#[derive(Debug, Clone)]
+pub struct Timings {
+    pub file: String,
+    pub lines: usize,
+    /// The tokens other than whitespace, set once the file has been lexed
+    pub tokens: usize,
+    pub passes: Vec<Pass>,
+}
+
+impl Timings {
+    pub fn new(file: &str, source: &str) -> Self {
+        Timings {
+            file: file.to_owned(),
+            lines: source.lines().count(),
+            tokens: 0,
+            passes: Vec::new(),
+        }
+    }
+
+    /// Runs `pass`, recording how long it took under `name`.
+    pub fn time<T>(&mut self, name: &'static str, pass: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = pass();
+        self.passes.push(Pass {
+            name,
+            duration: start.elapsed(),
+        });
+        result
+    }
+
+    pub fn total(&self) -> Duration {
+        self.passes.iter().map(|pass| pass.duration).sum()
+    }
+
+    pub fn nanos_per_line(&self, duration: Duration) -> f64 {
+        duration.as_nanos() as f64 / self.lines.max(1) as f64
+    }
+
+    pub fn tokens_per_second(&self, duration: Duration) -> f64 {
+        self.tokens as f64 / duration.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+
+    fn pass_json(&self, name: &str, duration: Duration) -> Value {
+        json!({
+            "name": name,
+            "nanos": duration.as_nanos() as u64,
+            "ns_per_line": self.nanos_per_line(duration),
+            "tokens_per_sec": self.tokens_per_second(duration),
+        })
+    }
+
+    /// The timings as a single JSON object, with a `total` after the passes.
+    pub fn to_json(&self) -> Value {
+        let mut passes: Vec<Value> = self
+            .passes
+            .iter()
+            .map(|pass| self.pass_json(pass.name, pass.duration))
+            .collect();
+        passes.push(self.pass_json("total", self.total()));
+
+        json!({
+            "file": self.file,
+            "lines": self.lines,
+            "tokens": self.tokens,
+            "passes": passes,
+        })
+    }
+
+    /// The width of the column of pass names, which fits the longest name.
+    fn name_width(&self) -> usize {
+        self.passes
+            .iter()
+            .map(|pass| pass.name.len())
+            .chain(["pass".len(), "total".len()])
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn write_row(
+        &self,
+        f: &mut Formatter,
+        name: &str,
+        duration: Duration,
+        width: usize,
+    ) -> fmt::Result {
+        writeln!(
+            f,
+            "{name:<width$} {:>12} {:>10} {:>10} {:>12.1} {:>14.0}",
+            format!("{duration:.3?}"),
+            self.lines,
+            self.tokens,
+            self.nanos_per_line(duration),
+            self.tokens_per_second(duration),
+        )
+    }
+}
+
+impl Display for Timings {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "time passes for {}", self.file)?;
+        let width = self.name_width();
+        writeln!(
+            f,
+            "{:<width$} {:>12} {:>10} {:>10} {:>12} {:>14}",
+            "pass", "time", "lines", "tokens", "ns/line", "tokens/sec"
+        )?;
+        for pass in &self.passes {
+            self.write_row(f, pass.name, pass.duration, width)?;
+        }
+        self.write_row(f, "total", self.total(), width)
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt::{self, Formatter};
 
+use miette::SourceSpan;
+
 #[derive(Debug, Clone, Copy, token_macro::Token)]
 pub enum Token<'buffer> {
     // --- Operators ---
@@ -16,13 +18,65 @@ pub enum Token<'buffer> {
     #[word = "*"]
     Times(Inner<'buffer>),
 
+    #[operator]
+    #[word = "/"]
+    Divide(Inner<'buffer>),
+
+    #[operator]
+    #[word = "**"]
+    Power(Inner<'buffer>),
+
+    // Bitwise Operators
+    #[operator]
+    #[word = "<<"]
+    ShiftLeft(Inner<'buffer>),
+    #[operator]
+    #[word = ">>"]
+    ShiftRight(Inner<'buffer>),
+
     // Comparison Operators
     #[operator]
     #[word = ">"]
     GreaterThan(Inner<'buffer>),
     #[operator]
+    #[word = "<"]
+    LessThan(Inner<'buffer>),
+    #[operator]
+    #[word = ">="]
+    GreaterEquals(Inner<'buffer>),
+    #[operator]
+    #[word = "<="]
+    LessEquals(Inner<'buffer>),
+    #[operator]
+    #[word = "=="]
+    DoubleEquals(Inner<'buffer>),
+    #[operator]
+    #[word = "!="]
+    NotEquals(Inner<'buffer>),
+
+    // Assignment Operators
+    #[operator]
     #[word = "="]
     Equals(Inner<'buffer>),
+    #[operator]
+    #[word = "+="]
+    PlusEquals(Inner<'buffer>),
+    #[operator]
+    #[word = "-="]
+    MinusEquals(Inner<'buffer>),
+    #[operator]
+    #[word = "*="]
+    TimesEquals(Inner<'buffer>),
+
+    // Logical Operators
+    #[operator]
+    #[word = "!"]
+    Not(Inner<'buffer>),
+
+    // Reference Operators
+    #[operator]
+    #[word = "&"]
+    Ampersand(Inner<'buffer>),
 
     // Misc Operators
     #[operator]
@@ -42,6 +96,18 @@ pub enum Token<'buffer> {
     #[initial]
     #[word = "var"]
     VariableDeclaration(Inner<'buffer>),
+    #[initial]
+    #[word = "const"]
+    Const(Inner<'buffer>),
+    #[initial]
+    #[word = "if"]
+    If(Inner<'buffer>),
+    #[initial]
+    #[word = "else"]
+    Else(Inner<'buffer>),
+    #[initial]
+    #[word = "while"]
+    While(Inner<'buffer>),
     #[terminal]
     #[word = ";"]
     SemiColon(Inner<'buffer>),
@@ -69,6 +135,24 @@ pub enum Token<'buffer> {
     NumericLiteral(Inner<'buffer>),
     Identifier(Inner<'buffer>),
     Unknown(Inner<'buffer>),
+
+    // --- Parser generated ---
+    // Prefix operators, written by the parser in place of the operator they were parsed from
+    Negate(Inner<'buffer>),
+    UnaryPlus(Inner<'buffer>),
+    Deref(Inner<'buffer>),
+    Reference(Inner<'buffer>),
+
+    // Written by the parser in place of a call's argument list, holding the `)` and the argument count
+    Call(Inner<'buffer>, u32),
+
+    // Types, holding their closing bracket, or `fn` for function types, and the number of types they contain
+    Generic(Inner<'buffer>, u32),
+    Tuple(Inner<'buffer>, u32),
+    FunctionType(Inner<'buffer>, u32),
+
+    // Written by the parser in place of a statement it couldn't parse, holding the token the statement began at
+    Error(Inner<'buffer>),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -89,38 +173,110 @@ impl<'buffer> Inner<'buffer> {
 }
 
 impl<'a> Token<'a> {
+    /// Returns the left and right binding power of an operator.
+    ///
+    /// Left associative operators bind more tightly to their right, and right associative operators to their left.
     // TODO: Build precedence by transitively closing a graph of order relations
     pub fn precedence(&self) -> (u8, u8) {
         match self {
             // Operators
-            Token::Plus(_) | Token::Minus(_) => (3, 4),
-            Token::Times(_) => (5, 6),
+            Token::Comma(_) => (1, 2),
+
+            // Right associative, so that `a = b = c` assigns `c` to `b` first
+            Token::Equals(_)
+            | Token::PlusEquals(_)
+            | Token::MinusEquals(_)
+            | Token::TimesEquals(_) => (4, 3),
+
+            // Non-associative, see `Token::is_comparison`
+            Token::GreaterThan(_)
+            | Token::LessThan(_)
+            | Token::GreaterEquals(_)
+            | Token::LessEquals(_)
+            | Token::DoubleEquals(_)
+            | Token::NotEquals(_) => (6, 6),
+
+            Token::ShiftLeft(_) | Token::ShiftRight(_) => (7, 8),
+            Token::Plus(_) | Token::Minus(_) => (9, 10),
+            Token::Times(_) | Token::Divide(_) => (11, 12),
+
+            // Prefix operators only bind to their right
+            Token::Negate(_)
+            | Token::UnaryPlus(_)
+            | Token::Not(_)
+            | Token::Deref(_)
+            | Token::Reference(_) => (0, 13),
 
-            Token::Equals(_) | Token::OpenCurly(_) => (3, 4),
-            Token::Colon(_) => (5, 6),
+            // Binds tighter than a prefix operator on its left, so `-a ** b` negates `a ** b`
+            Token::Power(_) => (15, 14),
 
             _ => (0, 0),
         }
     }
 
+    /// Whether this token is a comparison operator, which can't be chained with another comparison.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Token::GreaterThan(_)
+                | Token::LessThan(_)
+                | Token::GreaterEquals(_)
+                | Token::LessEquals(_)
+                | Token::DoubleEquals(_)
+                | Token::NotEquals(_)
+        )
+    }
+
+    /// Returns the prefix form of this token, if it can be used as a prefix operator.
+    pub fn prefix(&self) -> Option<Token<'a>> {
+        match *self {
+            Token::Minus(inner) => Some(Token::Negate(inner)),
+            Token::Plus(inner) => Some(Token::UnaryPlus(inner)),
+            Token::Times(inner) => Some(Token::Deref(inner)),
+            Token::Ampersand(inner) => Some(Token::Reference(inner)),
+            Token::Not(inner) => Some(Token::Not(inner)),
+            _ => None,
+        }
+    }
+
+    /// Whether this token is whitespace, which separates other tokens but has no meaning of its own.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Token::Space(_) | Token::Newline(_))
+    }
+
+    /// Whether this token can be used as a binary operator.
+    pub fn is_binary(&self) -> bool {
+        self.is_operator()
+            && !matches!(
+                self,
+                Token::Not(_) | Token::Ampersand(_) | Token::Colon(_) | Token::Arrow(_)
+            )
+    }
+
+    #[allow(dead_code)]
     pub(crate) fn terminates<'buffer>(&self, initiator: Token<'buffer>) -> bool {
-        let is_terminal = match self {
+        match self {
             Token::CloseBracket(_) => matches!(initiator, Token::OpenBracket(_)),
             Token::SemiColon(_) => matches!(
                 initiator,
-                Token::FunctionDeclaration(_) | Token::VariableDeclaration(_)
+                Token::FunctionDeclaration(_) | Token::VariableDeclaration(_) | Token::Const(_)
             ),
             _ => false,
-        };
-
-        return is_terminal;
+        }
     }
 
+    #[allow(dead_code)]
     pub(crate) fn initiates<'buffer>(&self, terminator: Token<'buffer>) -> bool {
         terminator.terminates(*self)
     }
 }
 
+impl<'a> Token<'a> {
+    pub fn span(&self) -> SourceSpan {
+        SourceSpan::from((self.loc(), self.as_str().len()))
+    }
+}
+
 impl<'buffer> Default for Token<'buffer> {
     fn default() -> Self {
         Self::Unknown(Inner {
@@ -132,6 +288,19 @@ impl<'buffer> Default for Token<'buffer> {
 
 impl<'t> fmt::Display for Token<'t> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Token::Call(_, arity) => return write!(f, "call/{}", arity),
+            Token::Generic(_, arity) => return write!(f, "generic/{}", arity),
+            Token::Tuple(_, arity) => return write!(f, "tuple/{}", arity),
+            Token::FunctionType(_, arity) => return write!(f, "fn/{}", arity),
+            Token::Negate(_) => return write!(f, "neg"),
+            Token::UnaryPlus(_) => return write!(f, "pos"),
+            Token::Deref(_) => return write!(f, "deref"),
+            Token::Reference(_) => return write!(f, "ref"),
+            Token::Error(_) => return write!(f, "error"),
+            _ => {}
+        }
+
         let inner = self.inner();
         // let spacing = if inner.spaced { " " } else { "" };
         let slice = inner.slice.to_string();
@@ -0,0 +1,182 @@
+//! Runs the built compiler as a user would, checking its output and exit codes.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Runs the compiler with `args`, writing `stdin` to it.
+fn compiler(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_llvm-compiler"))
+        .args(args)
+        .env_remove("NO_COLOR")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // The compiler may exit without reading its input, eg. on a usage error, closing the pipe before it's written
+    let written = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    if let Err(error) = written {
+        assert_eq!(error.kind(), std::io::ErrorKind::BrokenPipe, "{error}");
+    }
+    child.wait_with_output().unwrap()
+}
+
+/// Writes `source` to a file named `name` in a directory of its own, returning its path.
+fn source_file(name: &str, source: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("llvm-compiler-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join(name);
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn runs_a_file() {
+    let path = source_file("runs_a_file.src", "var a = 1 + 2;\na * 3\n");
+
+    let output = compiler(&["run", path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "9\n");
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn reports_diagnostics() {
+    let path = source_file("reports_diagnostics.src", "var a = (1;\n");
+
+    let output = compiler(&["check", "--color=never", path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+
+    let stderr = stderr(&output);
+    assert!(stderr.contains("E0003"), "{stderr}");
+    assert!(stderr.contains("Unclosed delimiter `(`"), "{stderr}");
+    assert!(stderr.contains("reports_diagnostics.src:1:9"), "{stderr}");
+}
+
+#[test]
+fn rejects_bad_usage() {
+    // Unknown flags and subcommands are rejected by the argument parser
+    let output = compiler(&["run", "--bogus", "-"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("--bogus"));
+
+    let output = compiler(&["compile", "-"], "");
+    assert_eq!(output.status.code(), Some(2));
+
+    // As are files which can't be read
+    let missing = std::env::temp_dir().join("llvm-compiler-cli-missing.src");
+    let output = compiler(&["check", missing.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("Couldn't read"));
+}
+
+#[test]
+fn limits_recursion() {
+    let source = "fn f(n: i32): i32 { if n == 0 { 0 } else { f(n - 1) + 1 } }\nf(50)\n";
+
+    let output = compiler(&["run", "--recursion-limit", "100", "-"], source);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "50\n");
+
+    let output = compiler(
+        &["run", "--recursion-limit", "10", "--color=never", "-"],
+        source,
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("E0024"), "{}", stderr(&output));
+
+    // A limit whose stack size can't be represented is a usage error, rather than an overflow
+    let output = compiler(
+        &["run", "--recursion-limit", &usize::MAX.to_string(), "-"],
+        source,
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("too large"), "{}", stderr(&output));
+}
+
+#[test]
+fn reports_bad_bytecode_files() {
+    let path = source_file("reports_bad_bytecode_files.dobc", "var a = 1;\n");
+
+    let output = compiler(&["run", "--color=never", path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("E0034"), "{}", stderr(&output));
+
+    // As diagnostics, they're written in the format asked for
+    let output = compiler(
+        &["run", "--error-format", "json", path.to_str().unwrap()],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        stdout(&output).contains(r#""code":"E0034""#),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn reads_stdin() {
+    let output = compiler(&["run", "-"], "2 * (3 + 4)\n");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "14\n");
+
+    let output = compiler(&["check", "--color=never", "-"], "var a = (1;\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("<stdin>:1:9"));
+}
+
+#[test]
+fn emits_each_representation() {
+    let source = "var a = 1 + 2;\na * 3\n";
+    let expected = [
+        ("tokens", "VariableDeclaration("),
+        ("postfix", "var a 1 2 + = ; a 3 *"),
+        ("ast", "(var a (+ 1 2))"),
+        ("types", "variable a: i32"),
+        ("bytecode", "mul i32"),
+        ("ir", "fn @main() -> i32 {"),
+        ("llvm", "source_filename = \"<stdin>\""),
+    ];
+
+    for (emit, fragment) in expected {
+        let output = compiler(&["check", "--emit", emit, "-"], source);
+        assert_eq!(output.status.code(), Some(0), "{emit}: {}", stderr(&output));
+        assert!(
+            stdout(&output).contains(fragment),
+            "{emit}: {}",
+            stdout(&output)
+        );
+    }
+
+    let output = compiler(&["check", "--emit", "assembly", "-"], source);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn colours_diagnostics_on_request() {
+    let source = "var a = (1;\n";
+    let escape = "\u{1b}[";
+
+    let output = compiler(&["check", "--color=always", "-"], source);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains(escape));
+
+    // Output to a pipe isn't coloured unless asked for
+    for args in [&["check", "--color=never", "-"][..], &["check", "-"]] {
+        let output = compiler(args, source);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(!stderr(&output).contains(escape), "{args:?}");
+    }
+}
@@ -26,6 +26,7 @@ pub fn token_derive(_tokens: TokenStream) -> TokenStream {
     }
 
     let mut variant_idents = vec![];
+    let mut word_idents = vec![];
     let mut words = vec![];
     let mut operators = vec![];
     let mut initials = vec![];
@@ -50,6 +51,7 @@ pub fn token_derive(_tokens: TokenStream) -> TokenStream {
 
                         if let Expr::Lit(literal) = meta.value {
                             if let Lit::Str(str_literal) = literal.lit {
+                                word_idents.push(variant.ident.clone());
                                 words.push(str_literal.value());
                             } else {
                                 abort!(literal.span(), "Expected str literal");
@@ -80,7 +82,7 @@ pub fn token_derive(_tokens: TokenStream) -> TokenStream {
             impl<#(#lifetimes),*> #enum_ident<#(#lifetimes),*> {
                 pub fn from(loc: usize, slice: & #lifetime str) -> Self {
                     match slice {
-                        #(#words => Self::#variant_idents(Inner::new(loc, slice))),*,
+                        #(#words => Self::#word_idents(Inner::new(loc, slice))),*,
                         _ => Self::Unknown(Inner::new(loc, slice)),
                     }
                 }
@@ -92,13 +94,13 @@ pub fn token_derive(_tokens: TokenStream) -> TokenStream {
             impl<#(#lifetimes),*> #enum_ident<#(#lifetimes),*> {
                 pub fn inner_mut(&mut self) -> &mut Inner<#(#lifetimes),*> {
                     match self {
-                        #(Self::#variant_idents(inner) => inner),*
+                        #(Self::#variant_idents(inner, ..) => inner),*
                     }
                 }
 
                 pub fn inner(&self) -> &Inner<#(#lifetimes),*> {
                     match self {
-                        #(Self::#variant_idents(inner) => inner),*
+                        #(Self::#variant_idents(inner, ..) => inner),*
                     }
                 }
 
@@ -110,31 +112,31 @@ pub fn token_derive(_tokens: TokenStream) -> TokenStream {
 
                 pub fn loc(&self) -> usize {
                     match self {
-                        #(Self::#variant_idents(inner) => inner.loc),*
+                        #(Self::#variant_idents(inner, ..) => inner.loc),*
                     }
                 }
 
                 pub fn as_str(&self) -> &str {
                     match self {
-                        #(Self::#variant_idents(inner) => inner.slice),*
+                        #(Self::#variant_idents(inner, ..) => inner.slice),*
                     }
                 }
 
                 pub fn is_operator(&self) -> bool {
                     match self {
-                        #(Self::#operators(_) => true,)*
+                        #(Self::#operators(..) => true,)*
                         _ => false,
                     }
                 }
                 pub fn is_initial(&self) -> bool {
                     match self {
-                        #(Self::#initials(_) => true,)*
+                        #(Self::#initials(..) => true,)*
                         _ => false,
                     }
                 }
                 pub fn is_terminal(&self) -> bool {
                     match self {
-                        #(Self::#terminals(_) => true,)*
+                        #(Self::#terminals(..) => true,)*
                         _ => false,
                     }
                 }